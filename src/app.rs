@@ -30,8 +30,76 @@ pub fn build() -> App<'static, 'static> {
                 .default_value("auto")
                 .multiple(true)
                 .number_of_values(1)
+                .overrides_with("no-color")
                 .help("When to use terminal colours"),
         )
+        .arg(
+            Arg::with_name("no-color")
+                .long("no-color")
+                .multiple(true)
+                .overrides_with("color")
+                .help("Do not use terminal colours, like --color never"),
+        )
+        .arg(
+            Arg::with_name("paging")
+                .long("paging")
+                .possible_value("always")
+                .possible_value("auto")
+                .possible_value("never")
+                .default_value("never")
+                .multiple(true)
+                .number_of_values(1)
+                .help("When to page the listing with $PAGER, auto paging the ones taller than the terminal"),
+        )
+        .arg(
+            Arg::with_name("width")
+                .long("width")
+                .short("w")
+                .value_name("columns")
+                .multiple(true)
+                .number_of_values(1)
+                .help("Fit the grid in this many columns instead of the width of the terminal"),
+        )
+        .arg(
+            Arg::with_name("truncate-names")
+                .long("truncate-names")
+                .value_name("columns")
+                .multiple(true)
+                .number_of_values(1)
+                .help("Cut the names wider than this many columns in the grid, or than a third of its width with auto, keeping their extension"),
+        )
+        .arg(
+            Arg::with_name("owner-width")
+                .long("owner-width")
+                .value_name("columns")
+                .multiple(true)
+                .number_of_values(1)
+                .help("Truncate the user and group names longer than this many columns"),
+        )
+        .arg(
+            Arg::with_name("full-path")
+                .long("full-path")
+                .multiple(true)
+                .help("Display the paths of the entries from the listed operands instead of their names"),
+        )
+        .arg(
+            Arg::with_name("absolute")
+                .long("absolute")
+                .multiple(true)
+                .help("Display the absolute paths of the entries instead of their names"),
+        )
+        .arg(
+            Arg::with_name("summary")
+                .long("summary")
+                .multiple(true)
+                .help("Print the numbers of directories and files, and their size, after the listing"),
+        )
+        .arg(
+            Arg::with_name("watch")
+                .long("watch")
+                .multiple(true)
+                .help("Refresh the listing each time the listed entries change"),
+        )
         .arg(
             Arg::with_name("icon")
                 .long("icon")
@@ -53,6 +121,17 @@ pub fn build() -> App<'static, 'static> {
                 .number_of_values(1)
                 .help("Whether to use fancy or unicode icons"),
         )
+        .arg(
+            Arg::with_name("icon-fallback")
+                .long("icon-fallback")
+                .possible_value("always")
+                .possible_value("auto")
+                .possible_value("never")
+                .default_value("auto")
+                .multiple(true)
+                .number_of_values(1)
+                .help("When to show the unicode icons instead of the fancy ones, auto doing so on the terminals without Nerd Fonts like the Linux console"),
+        )
         .arg(
             Arg::with_name("prefix-indent")
                 .long("prefix-indent")
@@ -71,15 +150,67 @@ pub fn build() -> App<'static, 'static> {
                 .short("l")
                 .long("long")
                 .multiple(true)
+                .overrides_with("oneline")
                 .help("Display extended file metadata as a table"),
         )
+        .arg(
+            Arg::with_name("format")
+                .long("format")
+                .possible_value("csv")
+                .possible_value("tsv")
+                .possible_value("porcelain")
+                .multiple(true)
+                .number_of_values(1)
+                .help("Print one row of machine-readable fields per entry instead of the listing, porcelain padding them to fixed widths"),
+        )
+        .arg(
+            Arg::with_name("header")
+                .long("header")
+                .multiple(true)
+                .help("Display a header line naming the columns of the long view"),
+        )
+        .arg(
+            Arg::with_name("preview")
+                .long("preview")
+                .value_name("lines")
+                .multiple(true)
+                .number_of_values(1)
+                .help("Display the first lines of the text files in the long and tree views"),
+        )
+        .arg(
+            Arg::with_name("extended")
+                .short("@")
+                .long("extended")
+                .multiple(true)
+                .help("Display the extended attributes of each entry in the long view"),
+        )
         .arg(
             Arg::with_name("oneline")
                 .short("1")
                 .long("oneline")
                 .multiple(true)
+                .overrides_with("long")
                 .help("Display one entry per line"),
         )
+        .arg(
+            Arg::with_name("quoting-style")
+                .long("quoting-style")
+                .possible_value("literal")
+                .possible_value("shell")
+                .possible_value("c")
+                .default_value("literal")
+                .multiple(true)
+                .number_of_values(1)
+                .help("How to quote the file names holding special or control characters"),
+        )
+        .arg(
+            Arg::with_name("zero")
+                .short("0")
+                .long("zero")
+                .multiple(true)
+                .conflicts_with("tree")
+                .help("End each entry with a NUL character instead of a newline, for xargs -0"),
+        )
         .arg(
             Arg::with_name("recursive")
                 .short("R")
@@ -88,10 +219,18 @@ pub fn build() -> App<'static, 'static> {
                 .conflicts_with("tree")
                 .help("Recurse into directories"),
         )
+        .arg(
+            Arg::with_name("flat-recursive")
+                .long("flat-recursive")
+                .multiple(true)
+                .conflicts_with("tree")
+                .help("Recurse into directories and list all their entries as one list of paths"),
+        )
         .arg(
             Arg::with_name("human_readable")
                 .short("h")
                 .long("human-readable")
+                .multiple(true)
                 .help("For ls compatibility purposes ONLY, currently set by default"),
         )
         .arg(
@@ -112,13 +251,69 @@ pub fn build() -> App<'static, 'static> {
             Arg::with_name("directory-only")
                 .short("d")
                 .long("directory-only")
-                .conflicts_with("all")
-                .conflicts_with("almost-all")
-                .conflicts_with("depth")
-                .conflicts_with("recursive")
-                .conflicts_with("tree")
+                .multiple(true)
                 .help("Display directories themselves, and not their contents"),
         )
+        .arg(
+            Arg::with_name("permission")
+                .long("permission")
+                .possible_value("rwx")
+                .possible_value("octal")
+                .possible_value("disable")
+                .default_value("rwx")
+                .multiple(true)
+                .number_of_values(1)
+                .help("How to display permissions"),
+        )
+        .arg(
+            Arg::with_name("total")
+                .long("total")
+                .possible_value("blocks")
+                .possible_value("size")
+                .possible_value("none")
+                .default_value("none")
+                .multiple(true)
+                .number_of_values(1)
+                .help("Print a total line before the long listing of each directory"),
+        )
+        .arg(
+            Arg::with_name("mime")
+                .long("mime")
+                .possible_value("extension")
+                .possible_value("deep")
+                .default_value("extension")
+                .multiple(true)
+                .number_of_values(1)
+                .help("How the mime block infers the types: from the extensions, or also from the content"),
+        )
+        .arg(
+            Arg::with_name("executable")
+                .long("executable")
+                .possible_value("access")
+                .possible_value("mode")
+                .default_value("access")
+                .multiple(true)
+                .number_of_values(1)
+                .help("Color the files as executable when the user may run them, or when any execute bit is set"),
+        )
+        .arg(
+            Arg::with_name("checksum")
+                .long("checksum")
+                .possible_value("sha256")
+                .possible_value("md5")
+                .multiple(true)
+                .number_of_values(1)
+                .help("Display the checksums of the files, computed with the given algorithm"),
+        )
+        .arg(
+            Arg::with_name("checksum-max-size")
+                .long("checksum-max-size")
+                .value_name("size")
+                .default_value("100M")
+                .multiple(true)
+                .number_of_values(1)
+                .help("Do not compute the checksums of the files above this size"),
+        )
         .arg(
             Arg::with_name("size")
                 .long("size")
@@ -130,42 +325,141 @@ pub fn build() -> App<'static, 'static> {
                 .number_of_values(1)
                 .help("How to display size"),
         )
+        .arg(
+            Arg::with_name("size-unit")
+                .long("size-unit")
+                .possible_value("B")
+                .possible_value("K")
+                .possible_value("M")
+                .possible_value("G")
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("unit")
+                .help("Display all the sizes in this unit"),
+        )
         .arg(
             Arg::with_name("total-size")
                 .long("total-size")
                 .multiple(true)
                 .help("Display the total size of directories"),
         )
+        .arg(
+            Arg::with_name("dir-size")
+                .long("dir-size")
+                .possible_value("inode")
+                .possible_value("count")
+                .possible_value("total")
+                .default_value("inode")
+                .multiple(true)
+                .number_of_values(1)
+                .help("What the size of directories is: their own, their entry count, or the total of their content"),
+        )
+        .arg(
+            Arg::with_name("quiet")
+                .long("quiet")
+                .multiple(true)
+                .help("Don't report the entries below the operands which cannot be accessed"),
+        )
+        .arg(
+            Arg::with_name("dereference")
+                .short("L")
+                .long("dereference")
+                .multiple(true)
+                .help("Display the targets of the symlinks instead of the links themselves"),
+        )
+        .arg(
+            Arg::with_name("dereference-command-line")
+                .short("H")
+                .long("dereference-command-line")
+                .multiple(true)
+                .help("Follow the symlinks given on the command line, but not the ones listed"),
+        )
+        .arg(
+            Arg::with_name("one-file-system")
+                .long("one-file-system")
+                .multiple(true)
+                .help("Don't descend into the directories on other filesystems"),
+        )
+        .arg(
+            Arg::with_name("archives")
+                .long("archives")
+                .multiple(true)
+                .help("In the tree view, list the entries at the top of the zip and tar archives too"),
+        )
         .arg(
             Arg::with_name("date")
                 .long("date")
                 .possible_value("date")
                 .possible_value("relative")
+                .possible_value("utc")
                 .default_value("date")
                 .multiple(true)
                 .number_of_values(1)
                 .help("How to display date"),
         )
+        .arg(
+            Arg::with_name("time")
+                .long("time")
+                .possible_value("modified")
+                .possible_value("created")
+                .default_value("modified")
+                .multiple(true)
+                .number_of_values(1)
+                .help("Which time to display and sort by: the modification or the creation"),
+        )
         .arg(
             Arg::with_name("timesort")
                 .short("t")
                 .long("timesort")
                 .multiple(true)
-                .help("Sort by time modified"),
+                .overrides_with_all(&["sizesort", "no-sort", "sort"])
+                .help("Sort by time, the one of --time"),
         )
         .arg(
             Arg::with_name("sizesort")
                 .short("S")
                 .long("sizesort")
                 .multiple(true)
+                .overrides_with_all(&["timesort", "no-sort", "sort"])
                 .help("Sort by size"),
         )
+        .arg(
+            Arg::with_name("no-sort")
+                .short("U")
+                .long("no-sort")
+                .multiple(true)
+                .overrides_with_all(&["timesort", "sizesort", "sort"])
+                .help("Don't sort, list the entries in directory order"),
+        )
+        .arg(
+            Arg::with_name("sort")
+                .long("sort")
+                .value_name("keys")
+                .multiple(true)
+                .number_of_values(1)
+                .overrides_with_all(&["timesort", "sizesort", "no-sort"])
+                .help("Sort by these comma separated keys, of name, size, time, extension, inode, links and none, the next ones ordering the entries equal by the first"),
+        )
+        .arg(
+            Arg::with_name("ignore-case")
+                .long("ignore-case")
+                .multiple(true)
+                .overrides_with("locale-sort")
+                .help("Sort the names regardless of their case, the capitalized ones first"),
+        )
+        .arg(
+            Arg::with_name("locale-sort")
+                .long("locale-sort")
+                .multiple(true)
+                .overrides_with("ignore-case")
+                .help("Sort the names following the collation rules of the locale"),
+        )
         .arg(
             Arg::with_name("reverse")
                 .short("r")
                 .long("reverse")
                 .multiple(true)
-                .help("Reverse the order of the sort"),
+                .help("Reverse the order of the sort, keeping the groups of --group-dirs and --dotfiles"),
         )
         .arg(
             Arg::with_name("group-dirs")
@@ -178,27 +472,109 @@ pub fn build() -> App<'static, 'static> {
                 .number_of_values(1)
                 .help("Sort the directories then the files"),
         )
+        .arg(
+            Arg::with_name("dotfiles")
+                .long("dotfiles")
+                .possible_value("first")
+                .possible_value("last")
+                .possible_value("mixed")
+                .default_value("mixed")
+                .multiple(true)
+                .number_of_values(1)
+                .help("Group the hidden files at the top or the bottom of the listing"),
+        )
         .arg(
             Arg::with_name("blocks")
                 .long("blocks")
                 .multiple(true)
                 .number_of_values(1)
                 .require_delimiter(true)
-                .possible_values(&["permission", "user", "group", "size", "date", "name"])
+                .possible_values(&[
+                    "permission",
+                    "user",
+                    "group",
+                    "context",
+                    "device",
+                    "filesystem",
+                    "flags",
+                    "size",
+                    "count",
+                    "mime",
+                    "checksum",
+                    "custom",
+                    "date",
+                    "name",
+                ])
                 .default_value("permission,user,group,size,date,name")
                 .help("Specify the blocks that will be displayed and in what order"),
         )
+        .arg(
+            Arg::with_name("no-user")
+                .long("no-user")
+                .multiple(true)
+                .help("Do not display the user block in the long view"),
+        )
+        .arg(
+            Arg::with_name("no-group")
+                .long("no-group")
+                .multiple(true)
+                .help("Do not display the group block in the long view"),
+        )
+        .arg(
+            Arg::with_name("context")
+                .short("Z")
+                .long("context")
+                .multiple(true)
+                .help("Display the security context of each entry in the long view"),
+        )
         .arg(
             Arg::with_name("classic")
                 .long("classic")
                 .help("Enable classic mode (no colors or icons)"),
         )
+        .arg(
+            Arg::with_name("preview-pane")
+                .long("preview-pane")
+                .multiple(true)
+                .help("List the names one per line with icons and colors, cut to --width, for the previewers of the file managers"),
+        )
+        .arg(
+            Arg::with_name("ascii")
+                .long("ascii")
+                .multiple(true)
+                .help("Draw the arrows and the tree in ASCII, without icons"),
+        )
         .arg(
             Arg::with_name("no-symlink")
                 .long("no-symlink")
                 .multiple(true)
                 .help("Do not display symlink target"),
         )
+        .arg(
+            Arg::with_name("symlink-target")
+                .long("symlink-target")
+                .possible_value("stored")
+                .possible_value("absolute")
+                .possible_value("relative")
+                .default_value("stored")
+                .multiple(true)
+                .number_of_values(1)
+                .help("Display the symlink targets as stored, as absolute paths, or relative to the current directory"),
+        )
+        .arg(
+            Arg::with_name("follow-chain")
+                .long("follow-chain")
+                .multiple(true)
+                .help("Display the whole chain of the symlinks pointing to symlinks"),
+        )
+        .arg(
+            Arg::with_name("chain-depth")
+                .long("chain-depth")
+                .value_name("num")
+                .multiple(true)
+                .number_of_values(1)
+                .help("Follow at most this many targets of the chains of --follow-chain [default: 8]"),
+        )
         .arg(
             Arg::with_name("ignore-glob")
                 .short("I")
@@ -209,4 +585,169 @@ pub fn build() -> App<'static, 'static> {
                 .default_value("")
                 .help("Do not display files/directories with names matching the glob pattern(s)"),
         )
+        .arg(
+            Arg::with_name("no-default-ignore")
+                .long("no-default-ignore")
+                .multiple(true)
+                .help("List the entries like .git, node_modules and target, which --tree and --recursive ignore by default"),
+        )
+        .arg(
+            Arg::with_name("only")
+                .long("only")
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("pattern")
+                .help("Only display the files/directories with names matching the glob pattern(s)"),
+        )
+        .arg(
+            Arg::with_name("size-filter")
+                .long("size-filter")
+                .multiple(true)
+                .number_of_values(1)
+                .allow_hyphen_values(true)
+                .value_name("threshold")
+                .help("Only display the files above (+) or below (-) the size threshold, like +10M or -4K"),
+        )
+        .arg(
+            Arg::with_name("audit")
+                .long("audit")
+                .multiple(true)
+                .help("Flag the world-writable and setuid entries, and the ones not owned by the owner of their directory"),
+        )
+        .arg(
+            Arg::with_name("type")
+                .long("type")
+                .possible_value("f")
+                .possible_value("d")
+                .possible_value("l")
+                .possible_value("socket")
+                .possible_value("pipe")
+                .possible_value("block")
+                .possible_value("char")
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("type")
+                .help("Only display the entries of this file type, files (f), directories (d), symlinks (l) or special files"),
+        )
+        .arg(
+            Arg::with_name("newer-than")
+                .long("newer-than")
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("date")
+                .help("Only display the entries modified after the date, or since the duration, like 2d"),
+        )
+        .arg(
+            Arg::with_name("older-than")
+                .long("older-than")
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("date")
+                .help("Only display the entries modified before the date, or the duration ago, like 2d"),
+        )
+        .arg(
+            Arg::with_name("config-file")
+                .long("config-file")
+                .value_name("path")
+                .help("Read the configuration from this file instead of the default location"),
+        )
+        .arg(
+            Arg::with_name("stdin")
+                .long("stdin")
+                .help("Read the entries to list from the standard input, one per line"),
+        )
+        .arg(
+            Arg::with_name("generate-completions")
+                .long("generate-completions")
+                .possible_values(&clap::Shell::variants())
+                .value_name("shell")
+                .hidden(true)
+                .help("Print the completion script for the given shell"),
+        )
+}
+
+// The environment variables standing for a single flag.
+const ENV_FLAGS: &[(&str, &str)] = &[
+    ("LSD_COLOR", "--color"),
+    ("LSD_ICONS", "--icon"),
+    ("LSD_DATE_FORMAT", "--date"),
+];
+
+/// The arguments set by the environment: the flags of `LSD_OPTIONS`, then the
+/// ones of the variables like `LSD_COLOR`. They go before the arguments of the
+/// command line, so that the latter win.
+pub fn env_args<F>(var: F) -> Vec<String>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let mut args = match var("LSD_OPTIONS") {
+        Some(options) => split_options(&options),
+        None => Vec::new(),
+    };
+
+    for (name, flag) in ENV_FLAGS {
+        match var(name) {
+            Some(ref value) if !value.is_empty() => args.push(format!("{}={}", flag, value)),
+            _ => {}
+        }
+    }
+
+    args
+}
+
+/// Split the options on the whitespaces, except inside the single or double
+/// quotes.
+fn split_options(options: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut current: Option<String> = None;
+    let mut quote = None;
+
+    for c in options.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => current.get_or_insert_with(String::new).push(c),
+            (None, '\'') | (None, '"') => {
+                quote = Some(c);
+                current.get_or_insert_with(String::new);
+            }
+            (None, c) if c.is_whitespace() => args.extend(current.take()),
+            (None, c) => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+    args.extend(current);
+
+    args
+}
+
+#[cfg(test)]
+mod test {
+    use super::{env_args, split_options};
+
+    #[test]
+    fn test_split_options() {
+        assert_eq!(
+            vec!["-l", "--blocks", "size,name"],
+            split_options(" -l  --blocks size,name ")
+        );
+        assert_eq!(
+            vec!["--ignore-glob", "*.o files", ""],
+            split_options("--ignore-glob '*.o files' \"\"")
+        );
+        assert!(split_options("").is_empty());
+    }
+
+    #[test]
+    fn test_env_args() {
+        let var = |name: &str| match name {
+            "LSD_OPTIONS" => Some(String::from("-l --color never")),
+            "LSD_COLOR" => Some(String::from("always")),
+            "LSD_ICONS" => Some(String::new()),
+            _ => None,
+        };
+
+        assert_eq!(
+            vec!["-l", "--color", "never", "--color=always"],
+            env_args(var)
+        );
+    }
 }
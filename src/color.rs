@@ -1,6 +1,10 @@
+use crate::config_file::ThemeConfig;
+use crate::flags::DisplayRule;
 use ansi_term::{ANSIString, Colour, Style};
 use lscolors::{Indicator, LsColors};
 use std::collections::HashMap;
+use std::path::Path;
+use time::Duration;
 
 #[allow(dead_code)]
 #[derive(Hash, Debug, Eq, PartialEq, Clone)]
@@ -20,6 +24,14 @@ pub enum Elem {
     CharDevice,
     Socket,
     Special,
+    /// A file given Linux capabilities, like with `setcap`.
+    Capabilities,
+    Setuid,
+    Setgid,
+    /// The directories with the sticky bit, or writable by everyone.
+    Sticky,
+    OtherWritable,
+    StickyOtherWritable,
 
     /// Permissions
     Read,
@@ -27,6 +39,8 @@ pub enum Elem {
     Exec,
     ExecSticky,
     NoAccess,
+    Octal,
+    Acl,
 
     /// Last Time Modified
     DayOld,
@@ -36,25 +50,85 @@ pub enum Elem {
     /// User / Group Name
     User,
     Group,
+    /// The user running the command, and its groups.
+    CurrentUser,
+    CurrentGroup,
+
+    /// Security Context
+    Context,
+
+    /// Filesystem Device
+    Device,
+    Filesystem,
+
+    /// BSD File Flags
+    FileFlags,
 
     /// File Size
     NonFile,
     FileLarge,
     FileMedium,
     FileSmall,
+
+    /// Directory Entry Count
+    EntryCount,
+
+    /// MIME Type
+    ContentType,
+
+    /// File Checksum
+    Checksum,
+
+    /// File Content Preview
+    Preview,
+
+    /// Custom Block Output
+    Custom,
+
+    /// The entries flagged by `--audit`
+    Audit,
+
+    /// The names of the entries meeting a rule of the config file, by the
+    /// index of the rule.
+    Rule(usize),
 }
 
 impl Elem {
     pub fn has_suid(&self) -> bool {
+        matches!(self, Elem::Dir { uid: true } | Elem::File { uid: true, .. })
+    }
+
+    /// The background of the elements standing out, like in `ls`.
+    fn background(&self) -> Option<Colour> {
         match self {
-            Elem::Dir { uid: true } | Elem::File { uid: true, .. } => true,
-            _ => false,
+            Elem::Dir { uid: true }
+            | Elem::File { uid: true, .. }
+            | Elem::Setuid
+            | Elem::Capabilities
+            | Elem::Audit => Some(Colour::Fixed(124)), // Red3
+            Elem::Setgid => Some(Colour::Fixed(178)), // Gold3
+            Elem::OtherWritable | Elem::StickyOtherWritable => Some(Colour::Fixed(28)), // Green4
+            Elem::Sticky => Some(Colour::Fixed(25)),  // DeepSkyBlue4
+            _ => None,
         }
     }
 }
 
 pub type ColoredString<'a> = ANSIString<'a>;
 
+/// The style of an element set by the theme, over its default colors.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ElemStyle {
+    /// Leave the element in the colors of the terminal, like `none`. The
+    /// attributes still apply.
+    pub no_color: bool,
+    pub foreground: Option<Colour>,
+    pub background: Option<Colour>,
+    pub bold: bool,
+    pub dimmed: bool,
+    pub underline: bool,
+}
+
 #[allow(dead_code)]
 #[derive(Debug, Copy, Clone)]
 pub enum Theme {
@@ -63,9 +137,44 @@ pub enum Theme {
     NoLscolors,
 }
 
+/// The sizes from which the files are colored as medium and large.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SizeThresholds {
+    pub medium: u64,
+    pub large: u64,
+}
+
+impl Default for SizeThresholds {
+    fn default() -> Self {
+        Self {
+            medium: 1024 * 1024,
+            large: 1024 * 1024 * 1024,
+        }
+    }
+}
+
+/// The ages under which the dates are colored as an hour old and a day old.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct DateThresholds {
+    pub hour_old: Duration,
+    pub day_old: Duration,
+}
+
+impl Default for DateThresholds {
+    fn default() -> Self {
+        Self {
+            hour_old: Duration::hours(1),
+            day_old: Duration::days(1),
+        }
+    }
+}
+
 pub struct Colors {
     colors: Option<HashMap<Elem, Colour>>,
+    styles: HashMap<Elem, ElemStyle>,
     lscolors: Option<LsColors>,
+    size_thresholds: SizeThresholds,
+    date_thresholds: DateThresholds,
 }
 
 impl Colors {
@@ -81,33 +190,108 @@ impl Colors {
             Theme::NoLscolors => None,
         };
 
-        Self { colors, lscolors }
+        Self {
+            colors,
+            styles: HashMap::new(),
+            lscolors,
+            size_thresholds: SizeThresholds::default(),
+            date_thresholds: DateThresholds::default(),
+        }
+    }
+
+    /// Apply the theme of the configuration file over the default one.
+    pub fn with_config(mut self, config: &ThemeConfig) -> Self {
+        if let Some(ref mut colors) = self.colors {
+            for (elem, colour) in &config.colors {
+                colors.insert(elem.clone(), *colour);
+            }
+            self.styles = config.styles.clone();
+        }
+        self.size_thresholds = config.size_thresholds;
+        self.date_thresholds = config.date_thresholds;
+
+        self
+    }
+
+    /// Style the names of the entries meeting the `rules` which set a style.
+    pub fn with_rules(mut self, rules: &[DisplayRule]) -> Self {
+        for (idx, rule) in rules.iter().enumerate() {
+            if let Some(style) = rule.style {
+                self.styles.insert(Elem::Rule(idx), style);
+            }
+        }
+
+        self
+    }
+
+    /// The element used to color a date, according to its age.
+    pub fn date_elem(&self, age: Duration) -> Elem {
+        if age < self.date_thresholds.hour_old {
+            Elem::HourOld
+        } else if age < self.date_thresholds.day_old {
+            Elem::DayOld
+        } else {
+            Elem::Older
+        }
+    }
+
+    /// The element used to color a file size, according to its magnitude.
+    pub fn size_elem(&self, bytes: u64) -> Elem {
+        if bytes >= self.size_thresholds.large {
+            Elem::FileLarge
+        } else if bytes >= self.size_thresholds.medium {
+            Elem::FileMedium
+        } else {
+            Elem::FileSmall
+        }
     }
 
     pub fn colorize<'a>(&self, input: String, elem: &Elem) -> ColoredString<'a> {
         self.style(elem).paint(input)
     }
 
+    /// Colors the name of the entry at `path`, of the type `kind`, with the
+    /// `LS_COLORS`, falling back on the style of `elem`.
     pub fn colorize_using_path<'a>(
         &self,
         input: String,
-        path: &str,
+        path: &Path,
+        kind: &Elem,
         elem: &Elem,
     ) -> ColoredString<'a> {
-        let style_from_path = self.style_from_path(path);
+        let style_from_path = self.style_from_path(path, kind);
         match style_from_path {
             Some(style_from_path) => style_from_path.paint(input),
             None => self.colorize(input, elem),
         }
     }
 
-    fn style_from_path(&self, path: &str) -> Option<Style> {
-        match &self.lscolors {
-            Some(lscolors) => lscolors
-                .style_for_path(path)
-                .map(lscolors::Style::to_ansi_term_style),
-            None => None,
-        }
+    // The type is already known, only the links are read again for their
+    // target, and the plain files are matched by their names.
+    fn style_from_path(&self, path: &Path, kind: &Elem) -> Option<Style> {
+        let lscolors = self.lscolors.as_ref()?;
+        let indicator = match kind {
+            Elem::SymLink => {
+                return lscolors
+                    .style_for_path(path)
+                    .map(lscolors::Style::to_ansi_term_style)
+            }
+            Elem::File { exec: true, .. } => Indicator::ExecutableFile,
+            Elem::Dir { .. } => Indicator::Directory,
+            Elem::Pipe => Indicator::FIFO,
+            Elem::Socket => Indicator::Socket,
+            Elem::BlockDevice => Indicator::BlockDevice,
+            Elem::CharDevice => Indicator::CharacterDevice,
+            _ => {
+                return lscolors
+                    .style_for_path_with_metadata(path, None)
+                    .map(lscolors::Style::to_ansi_term_style)
+            }
+        };
+
+        lscolors
+            .style_for_indicator(indicator)
+            .map(lscolors::Style::to_ansi_term_style)
     }
 
     fn style(&self, elem: &Elem) -> Style {
@@ -126,16 +310,33 @@ impl Colors {
     }
 
     fn style_default(&self, elem: &Elem) -> Style {
-        if let Some(ref colors) = self.colors {
-            let style_fg = Style::default().fg(colors[elem]);
-            if elem.has_suid() {
-                style_fg.on(Colour::Fixed(124)) // Red3
-            } else {
-                style_fg
+        let colors = match self.colors {
+            Some(ref colors) => colors,
+            None => return Style::default(),
+        };
+        let elem_style = self.styles.get(elem).cloned().unwrap_or_default();
+
+        let mut style = Style::default();
+        if !elem_style.no_color {
+            // The rules have no color of their own in the theme.
+            if let Some(foreground) = elem_style.foreground.or_else(|| colors.get(elem).cloned()) {
+                style = style.fg(foreground);
             }
-        } else {
-            Style::default()
+            if let Some(background) = elem_style.background.or_else(|| elem.background()) {
+                style = style.on(background);
+            }
+        }
+        if elem_style.bold {
+            style = style.bold();
         }
+        if elem_style.dimmed {
+            style = style.dimmed();
+        }
+        if elem_style.underline {
+            style = style.underline();
+        }
+
+        style
     }
 
     fn get_indicator_from_elem(&self, elem: &Elem) -> Option<Indicator> {
@@ -158,6 +359,12 @@ impl Colors {
             Elem::BlockDevice => Some("bd"),
             Elem::CharDevice => Some("cd"),
             Elem::BrokenSymLink => Some("or"),
+            Elem::Capabilities => Some("ca"),
+            Elem::Setuid => Some("su"),
+            Elem::Setgid => Some("sg"),
+            Elem::Sticky => Some("st"),
+            Elem::OtherWritable => Some("ow"),
+            Elem::StickyOtherWritable => Some("tw"),
             _ => None,
         };
 
@@ -175,6 +382,18 @@ impl Colors {
         // User / Group
         m.insert(Elem::User, Colour::Fixed(230)); // Cornsilk1
         m.insert(Elem::Group, Colour::Fixed(187)); // LightYellow3
+        m.insert(Elem::CurrentUser, Colour::Fixed(220)); // Gold1
+        m.insert(Elem::CurrentGroup, Colour::Fixed(180)); // Tan
+
+        // Security Context
+        m.insert(Elem::Context, Colour::Fixed(109)); // LightSkyBlue4
+
+        // Filesystem Device
+        m.insert(Elem::Device, Colour::Fixed(144)); // NavajoWhite3
+        m.insert(Elem::Filesystem, Colour::Fixed(108)); // DarkSeaGreen
+
+        // BSD File Flags
+        m.insert(Elem::FileFlags, Colour::Fixed(173)); // LightSalmon3
 
         // Permissions
         m.insert(Elem::Read, Colour::Green);
@@ -182,6 +401,8 @@ impl Colors {
         m.insert(Elem::Exec, Colour::Red);
         m.insert(Elem::ExecSticky, Colour::Purple);
         m.insert(Elem::NoAccess, Colour::Fixed(245)); // Grey
+        m.insert(Elem::Octal, Colour::Fixed(6)); // Teal
+        m.insert(Elem::Acl, Colour::Fixed(6)); // Teal
 
         // File Types
         m.insert(
@@ -221,6 +442,12 @@ impl Colors {
         m.insert(Elem::CharDevice, Colour::Fixed(172)); // Orange3
         m.insert(Elem::Socket, Colour::Fixed(44)); // DarkTurquoise
         m.insert(Elem::Special, Colour::Fixed(44)); // DarkTurquoise
+        m.insert(Elem::Capabilities, Colour::Black);
+        m.insert(Elem::Setuid, Colour::White);
+        m.insert(Elem::Setgid, Colour::Black);
+        m.insert(Elem::Sticky, Colour::White);
+        m.insert(Elem::OtherWritable, Colour::Fixed(33)); // DodgerBlue1
+        m.insert(Elem::StickyOtherWritable, Colour::Black);
 
         // Last Time Modified
         m.insert(Elem::HourOld, Colour::Fixed(40)); // Green3
@@ -233,6 +460,24 @@ impl Colors {
         m.insert(Elem::FileMedium, Colour::Fixed(216)); // LightSalmon1
         m.insert(Elem::FileLarge, Colour::Fixed(172)); // Orange3
 
+        // Directory Entry Count
+        m.insert(Elem::EntryCount, Colour::Fixed(229)); // Wheat1
+
+        // MIME Type
+        m.insert(Elem::ContentType, Colour::Fixed(109)); // LightSkyBlue4
+
+        // File Checksum
+        m.insert(Elem::Checksum, Colour::Fixed(103)); // LightSlateGrey
+
+        // File Content Preview
+        m.insert(Elem::Preview, Colour::Fixed(245)); // Grey54
+
+        // Custom Block Output
+        m.insert(Elem::Custom, Colour::Fixed(152)); // LightCyan3
+
+        // Audit Findings
+        m.insert(Elem::Audit, Colour::Fixed(230)); // Cornsilk1
+
         m
     }
 }
@@ -0,0 +1,1153 @@
+use crate::color::{DateThresholds, Elem, ElemStyle, SizeThresholds};
+use crate::flags::{
+    AuditRule, Block, CustomBlock, DateFilter, DateFlag, DirSizeFlag, DisplayRule, SizeFilter,
+    SortFlag, Symbols, TypeFilter, WhenFlag,
+};
+use crate::meta::{Date, Size};
+use ansi_term::Colour;
+use globset::{Glob, GlobMatcher};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// The content of the configuration file, once validated.
+#[derive(Clone, Debug, Default)]
+pub struct Config {
+    pub theme: ThemeConfig,
+    pub icons: IconsConfig,
+    /// The default of `--date`.
+    pub date: Option<DateFlag>,
+    /// The locale of the dates, empty for the one of `LC_TIME`.
+    pub date_locale: Option<String>,
+    /// The minimum width of the date column.
+    pub date_width: Option<usize>,
+    /// The default of `--dir-size`.
+    pub dir_size: Option<DirSizeFlag>,
+    /// The default of `--owner-width`.
+    pub owner_width: Option<usize>,
+    pub custom_blocks: Vec<CustomBlock>,
+    /// How many commands of the custom blocks run at once.
+    pub custom_block_jobs: Option<usize>,
+    /// Whether the directories listed may override the flags with a
+    /// `.lsd.yaml` file.
+    pub directory_config: bool,
+    /// The patterns ignored by `--tree` and `--recursive`, instead of the
+    /// default ones.
+    pub default_ignores: Option<Vec<String>>,
+    /// The rules changing how the entries meeting them show.
+    pub rules: Vec<DisplayRule>,
+}
+
+/// The name of the files overriding the flags of their directory.
+pub const DIRECTORY_CONFIG_NAME: &str = ".lsd.yaml";
+
+/// The flags a directory overrides for its own listing, through its
+/// `.lsd.yaml` file.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DirectoryConfig {
+    pub sort: Option<SortFlag>,
+    pub reverse: Option<bool>,
+    pub blocks: Option<Vec<Block>>,
+    /// The patterns ignored on top of the ones of `--ignore-glob`.
+    pub ignore_globs: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ThemeConfig {
+    /// The colors overriding the default ones.
+    pub colors: HashMap<Elem, Colour>,
+    /// The styles of the elements given attributes or no color at all.
+    pub styles: HashMap<Elem, ElemStyle>,
+    pub size_thresholds: SizeThresholds,
+    pub date_thresholds: DateThresholds,
+    pub symbols: Symbols,
+    /// The rules of `--audit`, all of them by default.
+    pub audit_rules: Option<Vec<AuditRule>>,
+}
+
+/// The icons extending the default tables, indexed by file name, by
+/// directory name, by extension and by glob pattern.
+#[derive(Clone, Debug, Default)]
+pub struct IconsConfig {
+    pub by_name: HashMap<String, String>,
+    pub by_dir_name: HashMap<String, String>,
+    pub by_extension: HashMap<String, String>,
+    pub by_glob: Vec<(GlobMatcher, String)>,
+    /// The string between the icons and the names.
+    pub separator: Option<String>,
+    /// The default of `--icon-fallback`.
+    pub fallback: Option<WhenFlag>,
+}
+
+// The content of the configuration file, as written by the user.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+struct RawConfig {
+    theme: RawTheme,
+    icons: RawIcons,
+    date: Option<String>,
+    date_locale: Option<String>,
+    date_width: Option<usize>,
+    dir_size: Option<String>,
+    owner_width: Option<usize>,
+    custom_blocks: Vec<RawCustomBlock>,
+    custom_block_jobs: Option<usize>,
+    directory_config: bool,
+    default_ignores: Option<Vec<String>>,
+    // A list rather than a map, as the first matching rule wins.
+    rules: Vec<RawRule>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+struct RawDirectoryConfig {
+    sort: Option<String>,
+    reverse: Option<bool>,
+    blocks: Option<Vec<String>>,
+    ignore_globs: Vec<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+struct RawTheme {
+    colors: HashMap<String, RawColorStyle>,
+    size_thresholds: RawSizeThresholds,
+    date_thresholds: RawDateThresholds,
+    symbols: RawSymbols,
+    audit_rules: Option<Vec<String>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+struct RawIcons {
+    by_name: HashMap<String, String>,
+    by_dir_name: HashMap<String, String>,
+    by_extension: HashMap<String, String>,
+    // A list rather than a map, as the first matching pattern wins.
+    by_glob: Vec<RawGlobIcon>,
+    separator: Option<String>,
+    fallback: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawGlobIcon {
+    pattern: String,
+    icon: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawCustomBlock {
+    name: String,
+    command: Vec<String>,
+    timeout: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+struct RawRule {
+    name: Option<String>,
+    #[serde(rename = "type")]
+    file_type: Option<String>,
+    size: Option<String>,
+    older_than: Option<String>,
+    newer_than: Option<String>,
+    color: Option<RawColorStyle>,
+    icon: Option<String>,
+    hide: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum RawColor {
+    Fixed(u8),
+    Named(String),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum RawColorStyle {
+    Color(RawColor),
+    Style(RawStyle),
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawStyle {
+    color: Option<RawColor>,
+    background: Option<RawColor>,
+    bold: bool,
+    dim: bool,
+    underline: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum RawSize {
+    Bytes(u64),
+    Human(String),
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct RawSizeThresholds {
+    medium: Option<RawSize>,
+    large: Option<RawSize>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+struct RawDateThresholds {
+    hour_old: Option<String>,
+    day_old: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+struct RawSymbols {
+    symlink_arrow: Option<String>,
+    tree_edge: Option<String>,
+    tree_line: Option<String>,
+    tree_corner: Option<String>,
+    ellipsis: Option<String>,
+    symlink_loop: Option<String>,
+}
+
+impl Config {
+    /// Read the configuration file at `path`, or at the default location when
+    /// no path is given. Only a missing file at the default location is not
+    /// an error.
+    pub fn load(path: Option<&Path>) -> Result<Self, String> {
+        let path = match path {
+            Some(path) => path.to_path_buf(),
+            None => match Self::default_path() {
+                Some(path) if path.is_file() => path,
+                _ => return Ok(Self::default()),
+            },
+        };
+
+        let content = fs::read_to_string(&path)
+            .map_err(|err| format!("cannot read the config file '{}': {}", path.display(), err))?;
+
+        Self::from_yaml(&content)
+            .map_err(|err| format!("invalid config file '{}': {}", path.display(), err))
+    }
+
+    /// `$XDG_CONFIG_HOME/lsd/config.yaml`, defaulting to
+    /// `~/.config/lsd/config.yaml`.
+    #[cfg(not(windows))]
+    pub fn default_path() -> Option<PathBuf> {
+        let config_dir = match env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+        };
+
+        Some(config_dir.join("lsd").join("config.yaml"))
+    }
+
+    /// `%APPDATA%\lsd\config.yaml`.
+    #[cfg(windows)]
+    pub fn default_path() -> Option<PathBuf> {
+        let config_dir = PathBuf::from(env::var_os("APPDATA")?);
+
+        Some(config_dir.join("lsd").join("config.yaml"))
+    }
+
+    pub fn from_yaml(content: &str) -> Result<Self, String> {
+        // An empty file is a valid, if useless, configuration.
+        if content.trim().is_empty() {
+            return Ok(Self::default());
+        }
+
+        let raw: RawConfig = serde_yaml::from_str(content).map_err(|err| err.to_string())?;
+
+        Ok(Self {
+            theme: ThemeConfig::from_raw(raw.theme)?,
+            icons: IconsConfig::from_raw(raw.icons)?,
+            date: match raw.date {
+                Some(date) => Some(parse_date(&date)?),
+                None => None,
+            },
+            date_locale: match raw.date_locale {
+                Some(ref locale) if locale == "auto" => Some(String::new()),
+                Some(ref locale) if locale.is_empty() || locale.contains('\0') => {
+                    return Err(format!("invalid date locale '{}'", locale))
+                }
+                locale => locale,
+            },
+            date_width: raw.date_width,
+            dir_size: match raw.dir_size {
+                Some(dir_size) => Some(parse_dir_size(&dir_size)?),
+                None => None,
+            },
+            owner_width: match raw.owner_width {
+                Some(0) => return Err(String::from("invalid owner width '0'")),
+                owner_width => owner_width,
+            },
+            custom_blocks: raw
+                .custom_blocks
+                .into_iter()
+                .map(parse_custom_block)
+                .collect::<Result<_, _>>()?,
+            custom_block_jobs: match raw.custom_block_jobs {
+                Some(0) => return Err(String::from("invalid custom block jobs '0'")),
+                jobs => jobs,
+            },
+            directory_config: raw.directory_config,
+            default_ignores: match raw.default_ignores {
+                Some(patterns) => Some(parse_ignore_globs(patterns)?),
+                None => None,
+            },
+            rules: raw
+                .rules
+                .into_iter()
+                .map(parse_rule)
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+impl DirectoryConfig {
+    /// Read the `.lsd.yaml` file of the directory at `dir`, if it has one.
+    pub fn load(dir: &Path) -> Result<Option<Self>, String> {
+        let path = dir.join(DIRECTORY_CONFIG_NAME);
+        if !path.is_file() {
+            return Ok(None);
+        }
+
+        let content = fs::read_to_string(&path)
+            .map_err(|err| format!("cannot read the config file '{}': {}", path.display(), err))?;
+
+        Self::from_yaml(&content)
+            .map(Some)
+            .map_err(|err| format!("invalid config file '{}': {}", path.display(), err))
+    }
+
+    pub fn from_yaml(content: &str) -> Result<Self, String> {
+        if content.trim().is_empty() {
+            return Ok(Self::default());
+        }
+
+        let raw: RawDirectoryConfig =
+            serde_yaml::from_str(content).map_err(|err| err.to_string())?;
+
+        Ok(Self {
+            sort: match raw.sort {
+                Some(sort) => Some(parse_sort(&sort)?),
+                None => None,
+            },
+            reverse: raw.reverse,
+            blocks: match raw.blocks {
+                Some(blocks) => Some(
+                    blocks
+                        .iter()
+                        .map(|block| parse_block(block))
+                        .collect::<Result<_, _>>()?,
+                ),
+                None => None,
+            },
+            ignore_globs: parse_ignore_globs(raw.ignore_globs)?,
+        })
+    }
+}
+
+impl ThemeConfig {
+    fn from_raw(raw: RawTheme) -> Result<Self, String> {
+        let mut colors = HashMap::new();
+        let mut styles = HashMap::new();
+        // The names of whole blocks go first, for the ones of their elements to
+        // override them.
+        let mut entries: Vec<(String, RawColorStyle)> = raw.colors.into_iter().collect();
+        entries.sort_by_key(|(name, _)| block_elems(name).is_none());
+        for (name, value) in entries {
+            let elems = elems_from_name(&name)
+                .ok_or_else(|| format!("unknown color element '{}'", name))?;
+            // A plain color is the one of the theme, without any style.
+            let style = match value {
+                RawColorStyle::Color(ref color) if !is_no_color(color) => {
+                    let colour = parse_colour(color)
+                        .ok_or_else(|| format!("invalid color for '{}': {:?}", name, color))?;
+                    for elem in elems {
+                        styles.remove(&elem);
+                        colors.insert(elem, colour);
+                    }
+                    continue;
+                }
+                value => parse_style(&name, &value)?,
+            };
+            for elem in elems {
+                styles.insert(elem, style);
+            }
+        }
+
+        let mut size_thresholds = SizeThresholds::default();
+        if let Some(medium) = raw.size_thresholds.medium {
+            size_thresholds.medium = parse_size(&medium)?;
+        }
+        if let Some(large) = raw.size_thresholds.large {
+            size_thresholds.large = parse_size(&large)?;
+        }
+        if size_thresholds.medium > size_thresholds.large {
+            return Err(String::from(
+                "the medium size threshold is above the large one",
+            ));
+        }
+
+        let mut date_thresholds = DateThresholds::default();
+        if let Some(hour_old) = raw.date_thresholds.hour_old {
+            date_thresholds.hour_old = parse_age(&hour_old)?;
+        }
+        if let Some(day_old) = raw.date_thresholds.day_old {
+            date_thresholds.day_old = parse_age(&day_old)?;
+        }
+        if date_thresholds.hour_old > date_thresholds.day_old {
+            return Err(String::from(
+                "the hour-old date threshold is above the day-old one",
+            ));
+        }
+
+        let mut symbols = Symbols::default();
+        if let Some(symlink_arrow) = raw.symbols.symlink_arrow {
+            symbols.symlink_arrow = symlink_arrow;
+        }
+        if let Some(tree_edge) = raw.symbols.tree_edge {
+            symbols.tree_edge = tree_edge;
+        }
+        if let Some(tree_line) = raw.symbols.tree_line {
+            symbols.tree_line = tree_line;
+        }
+        if let Some(tree_corner) = raw.symbols.tree_corner {
+            symbols.tree_corner = tree_corner;
+        }
+        if let Some(ellipsis) = raw.symbols.ellipsis {
+            symbols.ellipsis = ellipsis;
+        }
+        if let Some(symlink_loop) = raw.symbols.symlink_loop {
+            symbols.symlink_loop = symlink_loop;
+        }
+
+        let audit_rules = match raw.audit_rules {
+            Some(rules) => Some(
+                rules
+                    .iter()
+                    .map(|rule| parse_audit_rule(rule))
+                    .collect::<Result<Vec<AuditRule>, String>>()?,
+            ),
+            None => None,
+        };
+
+        Ok(Self {
+            colors,
+            styles,
+            size_thresholds,
+            date_thresholds,
+            symbols,
+            audit_rules,
+        })
+    }
+}
+
+impl IconsConfig {
+    fn from_raw(raw: RawIcons) -> Result<Self, String> {
+        let mut by_glob = Vec::with_capacity(raw.by_glob.len());
+        for glob_icon in raw.by_glob {
+            let glob = Glob::new(&glob_icon.pattern)
+                .map_err(|err| format!("invalid icon pattern '{}': {}", glob_icon.pattern, err))?;
+            by_glob.push((glob.compile_matcher(), glob_icon.icon));
+        }
+
+        Ok(Self {
+            by_name: raw.by_name,
+            by_dir_name: raw.by_dir_name,
+            by_extension: raw.by_extension,
+            by_glob,
+            separator: raw.separator,
+            fallback: match raw.fallback {
+                Some(fallback) => Some(parse_when(&fallback)?),
+                None => None,
+            },
+        })
+    }
+}
+
+fn parse_size(size: &RawSize) -> Result<u64, String> {
+    match size {
+        RawSize::Bytes(bytes) => Ok(*bytes),
+        RawSize::Human(human) => Size::parse(human)
+            .map(|size| size.get_bytes())
+            .ok_or_else(|| format!("invalid size threshold '{}'", human)),
+    }
+}
+
+fn parse_date(date: &str) -> Result<DateFlag, String> {
+    match date {
+        "date" | "relative" | "utc" => Ok(DateFlag::from(date)),
+        _ => Err(format!("invalid date format '{}'", date)),
+    }
+}
+
+fn parse_dir_size(dir_size: &str) -> Result<DirSizeFlag, String> {
+    match dir_size {
+        "inode" | "count" | "total" => Ok(DirSizeFlag::from(dir_size)),
+        _ => Err(format!("invalid directory size '{}'", dir_size)),
+    }
+}
+
+fn parse_ignore_globs(patterns: Vec<String>) -> Result<Vec<String>, String> {
+    for pattern in &patterns {
+        Glob::new(pattern)
+            .map_err(|err| format!("invalid ignore pattern '{}': {}", pattern, err))?;
+    }
+
+    Ok(patterns)
+}
+
+fn parse_when(when: &str) -> Result<WhenFlag, String> {
+    match when {
+        "always" | "auto" | "never" => Ok(WhenFlag::from(when)),
+        _ => Err(format!("invalid icon fallback '{}'", when)),
+    }
+}
+
+fn parse_sort(sort: &str) -> Result<SortFlag, String> {
+    SortFlag::from_key(sort).ok_or_else(|| format!("invalid sort '{}'", sort))
+}
+
+fn parse_audit_rule(rule: &str) -> Result<AuditRule, String> {
+    match rule {
+        "world-writable" | "setuid" | "foreign-owner" => Ok(AuditRule::from(rule)),
+        _ => Err(format!("invalid audit rule '{}'", rule)),
+    }
+}
+
+fn parse_block(block: &str) -> Result<Block, String> {
+    match block {
+        "permission" | "user" | "group" | "context" | "device" | "filesystem" | "flags"
+        | "size" | "count" | "mime" | "checksum" | "custom" | "date" | "name" => {
+            Ok(Block::from(block))
+        }
+        _ => Err(format!("invalid block '{}'", block)),
+    }
+}
+
+fn parse_custom_block(raw: RawCustomBlock) -> Result<CustomBlock, String> {
+    if raw.command.is_empty() {
+        return Err(format!("empty command for the custom block '{}'", raw.name));
+    }
+
+    // The commands are given a second by default, as they run for each entry.
+    let timeout = match raw.timeout {
+        Some(timeout) => Date::parse_duration(&timeout)
+            .and_then(|duration| duration.to_std().ok())
+            .ok_or_else(|| format!("invalid custom block timeout '{}'", timeout))?,
+        None => Duration::from_secs(1),
+    };
+
+    Ok(CustomBlock {
+        name: raw.name,
+        command: raw.command,
+        timeout,
+    })
+}
+
+fn parse_rule(raw: RawRule) -> Result<DisplayRule, String> {
+    let name = match raw.name {
+        Some(pattern) => Some(
+            Glob::new(&pattern)
+                .map_err(|err| format!("invalid rule pattern '{}': {}", pattern, err))?
+                .compile_matcher(),
+        ),
+        None => None,
+    };
+    let file_type = match raw.file_type {
+        Some(file_type) => match file_type.as_str() {
+            "f" | "d" | "l" | "socket" | "pipe" | "block" | "char" => {
+                Some(TypeFilter::from(file_type.as_str()))
+            }
+            _ => return Err(format!("invalid rule type '{}'", file_type)),
+        },
+        None => None,
+    };
+    let size = match raw.size {
+        Some(size) => {
+            Some(SizeFilter::parse(&size).map_err(|_| format!("invalid rule size '{}'", size))?)
+        }
+        None => None,
+    };
+
+    // The ages are from now, like the ones of `--newer` and `--older`.
+    let age = |age: &str| {
+        Date::parse_duration(age)
+            .map(|duration| Date::new(time::now() - duration))
+            .ok_or_else(|| format!("invalid rule age '{}'", age))
+    };
+    let mut dates = Vec::new();
+    if let Some(older_than) = raw.older_than {
+        dates.push(DateFilter::OlderThan(age(&older_than)?));
+    }
+    if let Some(newer_than) = raw.newer_than {
+        dates.push(DateFilter::NewerThan(age(&newer_than)?));
+    }
+
+    Ok(DisplayRule {
+        name,
+        file_type,
+        size,
+        dates,
+        style: match raw.color {
+            Some(color) => Some(parse_style("rules", &color)?),
+            None => None,
+        },
+        icon: raw.icon,
+        hide: raw.hide,
+    })
+}
+
+/// The style of the theme element `name`, or of a rule, given either a
+/// color, `none` or the attributes.
+fn parse_style(name: &str, value: &RawColorStyle) -> Result<ElemStyle, String> {
+    let colour = |color: &RawColor| {
+        parse_colour(color).ok_or_else(|| format!("invalid color for '{}': {:?}", name, color))
+    };
+    let optional_colour = |color: &Option<RawColor>| match color {
+        Some(color) if !is_no_color(color) => colour(color).map(Some),
+        _ => Ok(None),
+    };
+
+    match value {
+        RawColorStyle::Color(color) if is_no_color(color) => Ok(ElemStyle {
+            no_color: true,
+            ..ElemStyle::default()
+        }),
+        RawColorStyle::Color(color) => Ok(ElemStyle {
+            foreground: Some(colour(color)?),
+            ..ElemStyle::default()
+        }),
+        RawColorStyle::Style(raw) => Ok(ElemStyle {
+            no_color: raw.color.as_ref().map(is_no_color) == Some(true),
+            foreground: optional_colour(&raw.color)?,
+            background: optional_colour(&raw.background)?,
+            bold: raw.bold,
+            dimmed: raw.dim,
+            underline: raw.underline,
+        }),
+    }
+}
+
+fn parse_age(age: &str) -> Result<time::Duration, String> {
+    Date::parse_duration(age).ok_or_else(|| format!("invalid date threshold '{}'", age))
+}
+
+/// Whether `color` is `none`, leaving the element uncolored.
+fn is_no_color(color: &RawColor) -> bool {
+    match color {
+        RawColor::Named(name) => name.eq_ignore_ascii_case("none"),
+        RawColor::Fixed(_) => false,
+    }
+}
+
+/// A color is either a number of the 256 colors palette, a name of the 8
+/// basic colors, or an RGB hexadecimal code like `#ff8700`.
+fn parse_colour(color: &RawColor) -> Option<Colour> {
+    let name = match color {
+        RawColor::Fixed(number) => return Some(Colour::Fixed(*number)),
+        RawColor::Named(name) => name.to_lowercase(),
+    };
+
+    if name.starts_with('#') && name.len() == 7 {
+        let component = |idx| u8::from_str_radix(&name[idx..idx + 2], 16).ok();
+        return Some(Colour::RGB(component(1)?, component(3)?, component(5)?));
+    }
+
+    match name.as_str() {
+        "black" => Some(Colour::Black),
+        "red" => Some(Colour::Red),
+        "green" => Some(Colour::Green),
+        "yellow" => Some(Colour::Yellow),
+        "blue" => Some(Colour::Blue),
+        "purple" | "magenta" => Some(Colour::Purple),
+        "cyan" => Some(Colour::Cyan),
+        "white" => Some(Colour::White),
+        _ => None,
+    }
+}
+
+fn elems_from_name(name: &str) -> Option<Vec<Elem>> {
+    let elem = match name {
+        "file" => {
+            return Some(vec![
+                Elem::File {
+                    exec: false,
+                    uid: false,
+                },
+                Elem::File {
+                    exec: false,
+                    uid: true,
+                },
+            ])
+        }
+        "executable-file" => {
+            return Some(vec![
+                Elem::File {
+                    exec: true,
+                    uid: false,
+                },
+                Elem::File {
+                    exec: true,
+                    uid: true,
+                },
+            ])
+        }
+        "dir" => return Some(vec![Elem::Dir { uid: false }, Elem::Dir { uid: true }]),
+        "symlink" => Elem::SymLink,
+        "broken-symlink" => Elem::BrokenSymLink,
+        "pipe" => Elem::Pipe,
+        "block-device" => Elem::BlockDevice,
+        "char-device" => Elem::CharDevice,
+        "socket" => Elem::Socket,
+        "special" => Elem::Special,
+        "capabilities" => Elem::Capabilities,
+        "setuid" => Elem::Setuid,
+        "setgid" => Elem::Setgid,
+        "sticky" => Elem::Sticky,
+        "other-writable" => Elem::OtherWritable,
+        "sticky-other-writable" => Elem::StickyOtherWritable,
+        "read" => Elem::Read,
+        "write" => Elem::Write,
+        "exec" => Elem::Exec,
+        "exec-sticky" => Elem::ExecSticky,
+        "no-access" => Elem::NoAccess,
+        "octal" => Elem::Octal,
+        "acl" => Elem::Acl,
+        "hour-old" => Elem::HourOld,
+        "day-old" => Elem::DayOld,
+        "older" => Elem::Older,
+        "user" => Elem::User,
+        "group" => Elem::Group,
+        "current-user" => Elem::CurrentUser,
+        "current-group" => Elem::CurrentGroup,
+        "context" => Elem::Context,
+        "device" => Elem::Device,
+        "filesystem" => Elem::Filesystem,
+        "file-flags" => Elem::FileFlags,
+        "non-file" => Elem::NonFile,
+        "file-small" => Elem::FileSmall,
+        "file-medium" => Elem::FileMedium,
+        "file-large" => Elem::FileLarge,
+        "entry-count" => Elem::EntryCount,
+        "mime" => Elem::ContentType,
+        "checksum" => Elem::Checksum,
+        "preview" => Elem::Preview,
+        "audit" => Elem::Audit,
+        _ => return block_elems(name),
+    };
+
+    Some(vec![elem])
+}
+
+/// The elements of the block named `name`, styled at once.
+fn block_elems(name: &str) -> Option<Vec<Elem>> {
+    match name {
+        "permission" => Some(vec![
+            Elem::Read,
+            Elem::Write,
+            Elem::Exec,
+            Elem::ExecSticky,
+            Elem::NoAccess,
+            Elem::Octal,
+            Elem::Acl,
+        ]),
+        "size" => Some(vec![
+            Elem::NonFile,
+            Elem::FileSmall,
+            Elem::FileMedium,
+            Elem::FileLarge,
+        ]),
+        "date" => Some(vec![Elem::HourOld, Elem::DayOld, Elem::Older]),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Config;
+    use crate::color::{DateThresholds, Elem, SizeThresholds};
+    use crate::flags::WhenFlag;
+    use ansi_term::{Colour, Style};
+
+    #[test]
+    fn test_empty_config() {
+        let config = Config::from_yaml("").unwrap();
+
+        assert!(config.theme.colors.is_empty());
+        assert_eq!(SizeThresholds::default(), config.theme.size_thresholds);
+    }
+
+    #[test]
+    fn test_date_locale_config() {
+        let config = Config::from_yaml("date-locale: auto\ndate-width: 30\n").unwrap();
+        assert_eq!(Some(String::new()), config.date_locale);
+        assert_eq!(Some(30), config.date_width);
+
+        let config = Config::from_yaml("date-locale: fr_FR.UTF-8\n").unwrap();
+        assert_eq!(Some(String::from("fr_FR.UTF-8")), config.date_locale);
+
+        assert!(Config::from_yaml("date-locale: \"\"\n").is_err());
+    }
+
+    #[test]
+    fn test_theme_config() {
+        let config = Config::from_yaml(
+            r##"
+theme:
+  colors:
+    file-large: red
+    file-medium: 214
+    user: "#ff8700"
+  size-thresholds:
+    medium: 10M
+    large: 2147483648
+"##,
+        )
+        .unwrap();
+
+        assert_eq!(
+            Some(&Colour::Red),
+            config.theme.colors.get(&Elem::FileLarge)
+        );
+        assert_eq!(
+            Some(&Colour::Fixed(214)),
+            config.theme.colors.get(&Elem::FileMedium)
+        );
+        assert_eq!(
+            Some(&Colour::RGB(255, 135, 0)),
+            config.theme.colors.get(&Elem::User)
+        );
+        assert_eq!(
+            SizeThresholds {
+                medium: 10 * 1024 * 1024,
+                large: 2 * 1024 * 1024 * 1024,
+            },
+            config.theme.size_thresholds
+        );
+    }
+
+    #[test]
+    fn test_style_config() {
+        use crate::color::{Colors, ElemStyle, Theme};
+
+        let config = Config::from_yaml(
+            r##"
+theme:
+  colors:
+    user:
+      color: 230
+      background: black
+      bold: true
+      underline: true
+    group: none
+    date:
+      color: none
+      dim: true
+    hour-old: red
+"##,
+        )
+        .unwrap();
+
+        assert_eq!(
+            Some(&ElemStyle {
+                foreground: Some(Colour::Fixed(230)),
+                background: Some(Colour::Black),
+                bold: true,
+                underline: true,
+                ..ElemStyle::default()
+            }),
+            config.theme.styles.get(&Elem::User)
+        );
+        assert!(config.theme.styles[&Elem::Group].no_color);
+        assert!(config.theme.styles[&Elem::Older].no_color);
+        // The names of the elements win over the one of their block.
+        assert_eq!(Some(&Colour::Red), config.theme.colors.get(&Elem::HourOld));
+
+        let colors = Colors::new(Theme::NoLscolors).with_config(&config.theme);
+        assert_eq!(
+            Colour::Fixed(230)
+                .on(Colour::Black)
+                .bold()
+                .underline()
+                .paint("root"),
+            colors.colorize(String::from("root"), &Elem::User)
+        );
+        assert_eq!(
+            "root",
+            colors
+                .colorize(String::from("root"), &Elem::Group)
+                .to_string()
+        );
+        assert_eq!(
+            Style::default().dimmed().paint("May 1"),
+            colors.colorize(String::from("May 1"), &Elem::Older)
+        );
+        assert_eq!(
+            Colour::Red.paint("now"),
+            colors.colorize(String::from("now"), &Elem::HourOld)
+        );
+
+        assert!(Config::from_yaml("theme:\n  colors:\n    user:\n      italic: true\n").is_err());
+        assert!(Config::from_yaml("theme:\n  colors:\n    user:\n      color: rainbow\n").is_err());
+    }
+
+    #[test]
+    fn test_date_thresholds_config() {
+        let config =
+            Config::from_yaml("theme:\n  date-thresholds:\n    hour-old: 10m\n    day-old: 1w\n")
+                .unwrap();
+
+        assert_eq!(
+            DateThresholds {
+                hour_old: time::Duration::minutes(10),
+                day_old: time::Duration::weeks(1),
+            },
+            config.theme.date_thresholds
+        );
+        assert!(Config::from_yaml("theme:\n  date-thresholds:\n    day-old: soon\n").is_err());
+        assert!(Config::from_yaml(
+            "theme:\n  date-thresholds:\n    hour-old: 99999999999999999d\n"
+        )
+        .unwrap_err()
+        .contains("invalid date threshold"));
+    }
+
+    #[test]
+    fn test_date_config() {
+        use crate::flags::DateFlag;
+
+        assert_eq!(
+            Some(DateFlag::Utc),
+            Config::from_yaml("date: utc").unwrap().date
+        );
+        assert!(Config::from_yaml("date: iso").is_err());
+    }
+
+    #[test]
+    fn test_dir_size_config() {
+        use crate::flags::DirSizeFlag;
+
+        assert_eq!(
+            Some(DirSizeFlag::Count),
+            Config::from_yaml("dir-size: count").unwrap().dir_size
+        );
+        assert!(Config::from_yaml("dir-size: items").is_err());
+    }
+
+    #[test]
+    fn test_custom_blocks_config() {
+        use crate::flags::CustomBlock;
+        use std::time::Duration;
+
+        let config = Config::from_yaml(
+            "custom-blocks:\n  - name: Age\n    command: [git, log, -1, \"--format=%ar\", \"{}\"]\n    timeout: 2s\ncustom-block-jobs: 2\n",
+        )
+        .unwrap();
+        assert_eq!(
+            vec![CustomBlock {
+                name: String::from("Age"),
+                command: vec!["git", "log", "-1", "--format=%ar", "{}"]
+                    .into_iter()
+                    .map(String::from)
+                    .collect(),
+                timeout: Duration::from_secs(2),
+            }],
+            config.custom_blocks
+        );
+        assert_eq!(Some(2), config.custom_block_jobs);
+
+        let config = Config::from_yaml(
+            "custom-blocks:\n  - name: Age\n    command: [date]\n    timeout: 100ms\n",
+        )
+        .unwrap();
+        assert_eq!(Duration::from_millis(100), config.custom_blocks[0].timeout);
+
+        assert!(Config::from_yaml("custom-blocks:\n  - name: Age\n    command: []\n").is_err());
+        assert!(Config::from_yaml(
+            "custom-blocks:\n  - name: Age\n    command: [date]\n    timeout: soon\n"
+        )
+        .is_err());
+        assert!(Config::from_yaml(
+            "custom-blocks:\n  - name: Age\n    command: [date]\n    timeout: 9999999999999y\n"
+        )
+        .unwrap_err()
+        .contains("invalid custom block timeout"));
+        assert!(Config::from_yaml("custom-block-jobs: 0").is_err());
+    }
+
+    #[test]
+    fn test_rules_config() {
+        use crate::color::ElemStyle;
+        use crate::flags::{SizeFilter, TypeFilter};
+
+        let config = Config::from_yaml(
+            "rules:\n  - name: \"*.log\"\n    type: f\n    size: +100M\n    color: {color: red, bold: true}\n    icon: L\n  - older-than: 1y\n    color: none\n  - name: \"*.pyc\"\n    hide: true\n",
+        )
+        .unwrap();
+
+        assert_eq!(3, config.rules.len());
+        let rule = &config.rules[0];
+        assert!(rule.name.as_ref().unwrap().is_match("big.log"));
+        assert_eq!(Some(TypeFilter::File), rule.file_type);
+        assert_eq!(Some(SizeFilter::Above(100 * 1024 * 1024)), rule.size);
+        assert_eq!(
+            Some(ElemStyle {
+                foreground: Some(Colour::Red),
+                bold: true,
+                ..ElemStyle::default()
+            }),
+            rule.style
+        );
+        assert_eq!(Some(String::from("L")), rule.icon);
+        assert!(!rule.hide);
+
+        assert_eq!(1, config.rules[1].dates.len());
+        assert!(config.rules[1].style.unwrap().no_color);
+        assert!(config.rules[2].hide);
+        assert_eq!(None, config.rules[2].style);
+
+        assert!(Config::from_yaml("rules:\n  - type: file\n").is_err());
+        assert!(Config::from_yaml("rules:\n  - size: 100M\n").is_err());
+        assert!(Config::from_yaml("rules:\n  - older-than: soon\n").is_err());
+        assert!(Config::from_yaml("rules:\n  - color: nocolor\n").is_err());
+        assert!(Config::from_yaml("rules:\n  - hidden: true\n").is_err());
+    }
+
+    #[test]
+    fn test_directory_config() {
+        use super::DirectoryConfig;
+        use crate::flags::{Block, SortFlag};
+
+        let config = DirectoryConfig::from_yaml(
+            "sort: size\nreverse: true\nblocks: [size, name]\nignore-globs: [target]\n",
+        )
+        .unwrap();
+        assert_eq!(
+            DirectoryConfig {
+                sort: Some(SortFlag::Size),
+                reverse: Some(true),
+                blocks: Some(vec![Block::Size, Block::Name]),
+                ignore_globs: vec![String::from("target")],
+            },
+            config
+        );
+
+        assert!(DirectoryConfig::from_yaml("sort: random").is_err());
+        assert!(DirectoryConfig::from_yaml("blocks: [inode]").is_err());
+        assert!(DirectoryConfig::from_yaml("ignore-globs: [\"[\"]").is_err());
+        assert!(!Config::from_yaml("").unwrap().directory_config);
+        assert!(
+            Config::from_yaml("directory-config: true")
+                .unwrap()
+                .directory_config
+        );
+    }
+
+    #[test]
+    fn test_owner_width_config() {
+        assert_eq!(
+            Some(12),
+            Config::from_yaml("owner-width: 12").unwrap().owner_width
+        );
+        assert!(Config::from_yaml("owner-width: 0").is_err());
+        assert!(Config::from_yaml("owner-width: wide").is_err());
+    }
+
+    #[test]
+    fn test_icons_config() {
+        let config = Config::from_yaml(
+            "icons:\n  by-name:\n    Justfile: \"j\"\n  by-dir-name:\n    src: \"s\"\n  by-extension:\n    nix: \"n\"\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            Some(&String::from("j")),
+            config.icons.by_name.get("Justfile")
+        );
+        assert_eq!(
+            Some(&String::from("s")),
+            config.icons.by_dir_name.get("src")
+        );
+        assert_eq!(
+            Some(&String::from("n")),
+            config.icons.by_extension.get("nix")
+        );
+    }
+
+    #[test]
+    fn test_icons_glob_config() {
+        let config = Config::from_yaml(
+            r#"
+icons:
+  by-glob:
+    - pattern: "*.min.*"
+      icon: "m"
+    - pattern: "*.test.*"
+      icon: "t"
+"#,
+        )
+        .unwrap();
+
+        let globs = &config.icons.by_glob;
+        assert_eq!(2, globs.len());
+        assert!(globs[0].0.is_match("app.min.css"));
+        assert_eq!("m", globs[0].1);
+        assert_eq!("t", globs[1].1);
+        assert!(
+            Config::from_yaml("icons:\n  by-glob:\n    - pattern: \"[\"\n      icon: x\n").is_err()
+        );
+    }
+
+    #[test]
+    fn test_symbols_config() {
+        let config = Config::from_yaml(
+            "theme:\n  symbols:\n    symlink-arrow: \"->\"\n    tree-line: \"|  \"\nicons:\n  separator: \" \"\n",
+        )
+        .unwrap();
+
+        assert_eq!("->", config.theme.symbols.symlink_arrow);
+        assert_eq!("|  ", config.theme.symbols.tree_line);
+        assert_eq!("\u{2514}\u{2500}\u{2500}", config.theme.symbols.tree_corner);
+        assert_eq!(Some(String::from(" ")), config.icons.separator);
+    }
+
+    #[test]
+    fn test_default_ignores_config() {
+        let config = Config::from_yaml("default-ignores: [vendor, \"*.egg-info\"]\n").unwrap();
+
+        assert_eq!(
+            Some(vec![String::from("vendor"), String::from("*.egg-info")]),
+            config.default_ignores
+        );
+        assert_eq!(None, Config::from_yaml("").unwrap().default_ignores);
+        assert!(Config::from_yaml("default-ignores: [\"[\"]\n").is_err());
+    }
+
+    #[test]
+    fn test_icon_fallback_config() {
+        let config = Config::from_yaml("icons:\n  fallback: always\n").unwrap();
+
+        assert_eq!(Some(WhenFlag::Always), config.icons.fallback);
+        assert!(Config::from_yaml("icons:\n  fallback: sometimes\n").is_err());
+    }
+
+    #[test]
+    fn test_invalid_theme_config() {
+        assert!(Config::from_yaml("theme:\n  colors:\n    nothing: red\n").is_err());
+        assert!(Config::from_yaml("theme:\n  colors:\n    user: rainbow\n").is_err());
+        assert!(Config::from_yaml("theme:\n  size-thresholds:\n    medium: lots\n").is_err());
+        assert!(Config::from_yaml("theme:\n  audit-rules: [sticky]\n").is_err());
+        assert!(
+            Config::from_yaml("theme:\n  size-thresholds:\n    medium: 2G\n    large: 1G\n")
+                .is_err()
+        );
+    }
+}
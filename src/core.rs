@@ -1,28 +1,42 @@
 use crate::color::{self, Colors};
+use crate::config_file::Config;
 use crate::display;
-use crate::flags::{Display, Flags, IconTheme, Layout, WhenFlag};
+use crate::flags::{
+    Block, DirOrderFlag, DirSizeFlag, Display, DisplayRule, DotFilesFlag, Flags, FormatFlag,
+    IconTheme, Layout, PathFlag, SortFlag, WhenFlag,
+};
 use crate::icon::{self, Icons};
-use crate::meta::Meta;
+use crate::interrupt;
+use crate::meta::{AccessError, Audit, Details, FileType, Meta};
+use crate::pager::Output;
+use crate::progress::Progress;
 use crate::sort;
-use std::path::PathBuf;
-use std::{fs, io};
+use crate::watch::Changes;
+use crate::ExitCode;
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{self, Component, Path, PathBuf};
+use std::process;
+use std::slice;
 
+#[cfg(not(target_os = "windows"))]
 use super::libc;
 #[cfg(not(target_os = "windows"))]
 use std::os::unix::io::AsRawFd;
-
-#[cfg(target_os = "windows")]
-use terminal_size::terminal_size;
+use terminal_size::{terminal_size, Height};
 
 pub struct Core {
     flags: Flags,
     icons: Icons,
     //display: Display,
     colors: Colors,
+    /// When to page the listing, `Auto` only when the output is a tty.
+    paging: WhenFlag,
 }
 
 impl Core {
-    pub fn new(flags: Flags) -> Self {
+    pub fn new(mut flags: Flags, config: &Config) -> Self {
         // Check through libc if stdout is a tty. Unix specific so not on windows.
         // Determine color output availability (and initialize color output (for Windows 10))
         #[cfg(not(target_os = "windows"))]
@@ -37,18 +51,44 @@ impl Core {
         #[cfg(target_os = "windows")]
         let console_color_ok = ansi_term::enable_ansi_support().is_ok();
 
+        // Read `TZ` before rendering the dates in the local time.
+        time::tzset();
+
+        #[cfg(not(target_os = "windows"))]
+        {
+            use crate::flags::CollationFlag;
+
+            if flags.collation == CollationFlag::Locale {
+                // Use the collation rules of the user locale in `strcoll`.
+                unsafe { libc::setlocale(libc::LC_COLLATE, b"\0".as_ptr() as *const libc::c_char) };
+            }
+
+            // Name the days and the months in the language of the locale.
+            if let Some(locale) = &flags.date_locale {
+                if let Ok(locale) = std::ffi::CString::new(locale.as_str()) {
+                    unsafe { libc::setlocale(libc::LC_TIME, locale.as_ptr()) };
+                }
+            }
+        }
+
         let mut inner_flags = flags.clone();
 
         let color_theme = match (tty_available && console_color_ok, flags.color) {
             (_, WhenFlag::Never) | (false, WhenFlag::Auto) => color::Theme::NoColor,
             _ => color::Theme::Default,
         };
+        // Nothing depends on the colors any more once they are off.
+        if let color::Theme::NoColor = color_theme {
+            flags.color = WhenFlag::Never;
+        }
 
         let icon_theme = match (tty_available, flags.icon, flags.icon_theme) {
             (_, WhenFlag::Never, _) | (false, WhenFlag::Auto, _) => icon::Theme::NoIcon,
             (_, _, IconTheme::Fancy) => icon::Theme::Fancy,
             (_, _, IconTheme::Unicode) => icon::Theme::Unicode,
         };
+        let term = env::var("TERM").unwrap_or_default();
+        let icon_theme = icon_theme.with_fallback(flags.icon_fallback, &term);
 
         if !tty_available {
             // The output is not a tty, this means the command is piped. (ex: lsd -l | less)
@@ -58,39 +98,110 @@ impl Core {
             inner_flags.layout = Layout::OneLine { long: false };
         };
 
+        let paging = match (tty_available, flags.paging) {
+            (false, WhenFlag::Auto) => WhenFlag::Never,
+            (_, paging) => paging,
+        };
+
+        let colors = Colors::new(color_theme)
+            .with_config(&config.theme)
+            .with_rules(&flags.display_rules);
+
         Self {
             flags,
+            paging,
             //display: Display::new(inner_flags),
-            colors: Colors::new(color_theme),
-            icons: Icons::new(icon_theme),
+            colors,
+            icons: Icons::new(icon_theme).with_config(&config.icons),
         }
     }
 
-    pub fn run(self, paths: Vec<PathBuf>) {
-        let mut meta_list = self.fetch(paths);
+    /// List `paths` on the standard output, then report the inaccessible
+    /// entries on the standard error, only the operands with `--quiet`.
+    pub fn run(&self, paths: Vec<PathBuf>) -> ExitCode {
+        let paths: Vec<PathBuf> = match self.flags.full_path {
+            // The entries get their paths from the ones of the operands.
+            PathFlag::Absolute => paths.iter().map(|path| absolute_path(path)).collect(),
+            _ => paths,
+        };
+        // Like `ls -R`, the recursion names every directory listed.
+        let with_headers = paths.len() > 1 || self.flags.recursive;
+        let operands = paths.clone();
+        let mut errors = Vec::new();
+
+        let mut exit_code = if self.can_stream() {
+            let mut out = self.output(None);
+            let exit_code = self.stream(paths, with_headers, &mut out, &mut errors);
+            out.finish();
+
+            exit_code
+        } else {
+            let (mut meta_list, exit_code) = self.fetch(paths, &mut errors);
+
+            self.sort(&mut meta_list);
+            let listing = self.display(meta_list, with_headers);
+            let mut out = self.output(Some(&listing));
+            // The pager may be quit before reading the whole listing.
+            out.write_all(&listing).ok();
+            out.finish();
 
-        self.sort(&mut meta_list);
-        self.display(meta_list)
+            exit_code
+        };
+
+        // The entries found below the operands are the only ones hidden.
+        if self.flags.quiet {
+            errors.retain(|err| operands.iter().any(|operand| err.path() == operand));
+        }
+        // Keep the report after the listing when both go to a terminal.
+        io::stdout().flush().ok();
+        eprint!("{}", AccessError::summary(&errors));
+        if interrupt::is_interrupted() {
+            eprintln!("interrupted, the listing is incomplete");
+            exit_code.set_if_greater(ExitCode::Interrupted);
+        }
+
+        exit_code
     }
 
-    fn fetch(&self, paths: Vec<PathBuf>) -> Vec<Meta> {
-        let mut meta_list = Vec::with_capacity(paths.len());
-        let depth = match self.flags.layout {
+    /// Clear the screen and list `paths` again each time the listed entries
+    /// change, until interrupted.
+    pub fn watch(&self, paths: Vec<PathBuf>) -> ! {
+        let mut changes = Changes::watch(&paths, self.depth());
+
+        loop {
+            print!("\x1b[2J\x1b[H");
+            self.run(paths.clone());
+
+            if !changes.wait() {
+                process::exit(ExitCode::Interrupted as i32);
+            }
+        }
+    }
+
+    /// How deep the directory operands are listed.
+    fn depth(&self) -> usize {
+        match self.flags.layout {
             Layout::Tree { .. } => self.flags.recursion_depth,
             _ if self.flags.recursive => self.flags.recursion_depth,
+            _ if self.flags.display == Display::DisplayDirectoryItself => 0,
             _ => 1,
-        };
+        }
+    }
+
+    fn fetch(&self, paths: Vec<PathBuf>, errors: &mut Vec<AccessError>) -> (Vec<Meta>, ExitCode) {
+        let mut meta_list = Vec::with_capacity(paths.len());
+        let mut exit_code = ExitCode::OK;
+        let depth = self.depth();
 
         for path in paths {
-            if let Err(err) = fs::canonicalize(&path) {
-                eprintln!("cannot access '{}': {}", path.display(), err);
-                continue;
+            // The operands left are not listed at all once interrupted.
+            if interrupt::is_interrupted() {
+                break;
             }
-
-            let mut meta = match Meta::from_path(&path) {
-                Ok(meta) => meta,
-                Err(err) => {
-                    eprintln!("cannot access '{}': {}", path.display(), err);
+            let mut meta = match self.fetch_operand(&path, errors) {
+                Some(meta) => meta,
+                None => {
+                    exit_code.set_if_greater(ExitCode::MajorIssue);
                     continue;
                 }
             };
@@ -100,46 +211,421 @@ impl Core {
                     meta_list.push(meta);
                 }
                 _ => {
-                    match meta.recurse_into(depth, self.flags.display, &self.flags.ignore_globs) {
-                        Ok(content) => {
-                            meta.content = content;
-                            meta_list.push(meta);
-                        }
-                        Err(err) => {
-                            eprintln!("cannot access '{}': {}", path.display(), err);
-                            continue;
-                        }
-                    };
+                    let nb_errors = errors.len();
+                    meta.apply_directory_config(&self.flags);
+                    meta.content =
+                        meta.recurse_into(depth, meta.content_flags(&self.flags), errors);
+                    // Like `ls`, an operand that can't be read is a major
+                    // issue, the directories below it only minor ones.
+                    let new_errors = &errors[nb_errors..];
+                    if new_errors.iter().any(|err| err.path() == meta.path) {
+                        exit_code.set_if_greater(ExitCode::MajorIssue);
+                    } else if !new_errors.is_empty() {
+                        exit_code.set_if_greater(ExitCode::MinorIssue);
+                    }
+
+                    meta_list.push(meta);
                 }
             };
         }
-        if self.flags.total_size {
+        if self.flags.dir_size == DirSizeFlag::Total {
+            let nb_errors = errors.len();
+            let mut progress = Progress::on_stderr();
             for meta in &mut meta_list.iter_mut() {
-                meta.calculate_total_size();
+                meta.calculate_total_size(self.flags.one_file_system, errors, &mut progress);
+            }
+            progress.finish();
+            if errors.len() > nb_errors {
+                exit_code.set_if_greater(ExitCode::MinorIssue);
+            }
+        }
+
+        if !self.flags.only_globs.is_empty()
+            || !self.flags.size_filters.is_empty()
+            || !self.flags.type_filters.is_empty()
+            || !self.flags.date_filters.is_empty()
+            || !self.flags.display_rules.is_empty()
+        {
+            for meta in &mut meta_list {
+                meta.retain_content(&|meta| self.is_wanted(meta));
+            }
+        }
+        for meta in &mut meta_list {
+            let flags = meta.content_flags(&self.flags).clone();
+            calculate_blocks(meta, &flags);
+        }
+
+        (meta_list, exit_code)
+    }
+
+    fn fetch_operand(&self, path: &PathBuf, errors: &mut Vec<AccessError>) -> Option<Meta> {
+        if let Err(err) = fs::canonicalize(path) {
+            errors.push(AccessError::new(path, err));
+            return None;
+        }
+
+        let details = Details::for_flags(&self.flags);
+        // Like `ls`, the trailing separator of `link/` leads into the directory.
+        let dereference = self.flags.dereference
+            || self.flags.dereference_command_line
+            || has_trailing_separator(path);
+        match Meta::from_path_with(path, dereference, details) {
+            Ok(meta) => Some(meta),
+            Err(err) => {
+                errors.push(AccessError::new(path, err));
+                None
             }
         }
+    }
+
+    /// Where to write the `listing`, through the pager with `--paging always`,
+    /// or with `auto` when the listing doesn't fit the terminal.
+    fn output(&self, listing: Option<&[u8]>) -> Output {
+        let paging = match (self.paging, listing) {
+            (WhenFlag::Always, _) => true,
+            (WhenFlag::Auto, Some(listing)) => match terminal_size() {
+                Some((_, Height(height))) => {
+                    listing.iter().filter(|byte| **byte == b'\n').count() >= height as usize
+                }
+                None => false,
+            },
+            _ => false,
+        };
+
+        if paging {
+            Output::pager()
+        } else {
+            Output::stdout()
+        }
+    }
 
-        meta_list
+    /// Whether the entries can be printed as they are read: nothing orders
+    /// them, aligns them or needs the content of their directories. The
+    /// height of a streamed listing isn't known beforehand, so it can't be
+    /// paged only when needed.
+    fn can_stream(&self) -> bool {
+        self.flags.layout == (Layout::OneLine { long: false })
+            && self.flags.format == FormatFlag::Default
+            && self.flags.sort_by == SortFlag::None
+            && self.flags.directory_order == DirOrderFlag::None
+            && self.flags.dotfiles_order == DotFilesFlag::Mixed
+            && !self.flags.recursive
+            && self.flags.dir_size != DirSizeFlag::Total
+            && self.flags.display != Display::DisplayDirectoryItself
+            && self.paging != WhenFlag::Auto
+            && !self.flags.summary
+            && !self.flags.directory_config
+            && self.flags.audit_rules.is_empty()
+            && self.flags.display_rules.is_empty()
+    }
+
+    /// List the operands like `display_operands`, but print the content of the
+    /// directories one entry at a time, so that huge directories neither need
+    /// to fit in memory nor delay the output.
+    fn stream(
+        &self,
+        paths: Vec<PathBuf>,
+        with_headers: bool,
+        out: &mut Output,
+        errors: &mut Vec<AccessError>,
+    ) -> ExitCode {
+        let mut exit_code = ExitCode::OK;
+
+        let mut metas = Vec::with_capacity(paths.len());
+        for path in paths {
+            match self.fetch_operand(&path, errors) {
+                Some(meta) => metas.push(meta),
+                None => exit_code.set_if_greater(ExitCode::MajorIssue),
+            }
+        }
+        let (dirs, files) = self.partition_operands(metas);
+
+        let mut is_empty = files.is_empty();
+        if !files.is_empty()
+            && out
+                .write_all(&self.display_entries(files, &self.flags))
+                .is_err()
+        {
+            return exit_code;
+        }
+
+        for dir in dirs {
+            if interrupt::is_interrupted() {
+                break;
+            }
+            if with_headers {
+                let mut header = Vec::new();
+                if !is_empty {
+                    header.push(display::entry_terminator(&self.flags) as u8);
+                }
+                header.extend(display::directory_header(&dir.path, &self.flags));
+                if out.write_all(&header).is_err() {
+                    return exit_code;
+                }
+                is_empty = false;
+            }
+
+            let dots = match self.flags.display {
+                Display::DisplayAll => dir.dot_entries(&self.flags),
+                _ => Ok(Vec::new()),
+            };
+            let entries = dots.and_then(|dots| Ok((dots, dir.read_entries(&self.flags)?)));
+            let (dots, entries) = match entries {
+                Ok(entries) => entries,
+                // Like in `fetch`, an operand that can't be read is a major
+                // issue.
+                Err(err) => {
+                    errors.push(err);
+                    exit_code.set_if_greater(ExitCode::MajorIssue);
+                    continue;
+                }
+            };
+
+            for entry in dots.into_iter().map(Ok).chain(entries) {
+                if interrupt::is_interrupted() {
+                    break;
+                }
+                let meta = match entry {
+                    Ok(meta) => meta,
+                    Err(err) => {
+                        errors.push(err);
+                        exit_code.set_if_greater(ExitCode::MinorIssue);
+                        continue;
+                    }
+                };
+
+                if self.is_wanted(&meta) {
+                    if out
+                        .write_all(&self.display_entries(vec![meta], &self.flags))
+                        .is_err()
+                    {
+                        return exit_code;
+                    }
+                    is_empty = false;
+                }
+            }
+        }
+
+        exit_code
+    }
+
+    /// Whether the entry passes the filters given by the user.
+    fn is_wanted(&self, meta: &Meta) -> bool {
+        let total_size = self.flags.dir_size == DirSizeFlag::Total;
+        if let Some(idx) = DisplayRule::first_match(&self.flags.display_rules, meta, total_size) {
+            if self.flags.display_rules[idx].hide {
+                return false;
+            }
+        }
+
+        if !self.flags.only_globs.is_empty() && !self.flags.only_globs.is_match(&meta.name.name) {
+            return false;
+        }
+
+        if !self.flags.size_filters.is_empty() {
+            // The size of the directories only means something when it is the
+            // total size of their content.
+            if let (FileType::Directory { .. }, false) = (meta.file_type, total_size) {
+                return false;
+            }
+
+            let bytes = meta.size.get_bytes();
+            if !self
+                .flags
+                .size_filters
+                .iter()
+                .all(|filter| filter.matches(bytes))
+            {
+                return false;
+            }
+        }
+
+        if !self.flags.type_filters.is_empty()
+            && !self
+                .flags
+                .type_filters
+                .iter()
+                .any(|filter| filter.matches(meta.file_type))
+        {
+            return false;
+        }
+
+        self.flags
+            .date_filters
+            .iter()
+            .all(|filter| filter.matches(&meta.date))
     }
 
     fn sort(&self, metas: &mut Vec<Meta>) {
-        metas.sort_unstable_by(|a, b| sort::by_meta(a, b, &self.flags));
+        sort_with(metas, &self.flags);
+    }
+
+    fn display(&self, metas: Vec<Meta>, with_headers: bool) -> Vec<u8> {
+        match (self.flags.format, self.flags.layout) {
+            (FormatFlag::Csv, _) | (FormatFlag::Tsv, _) => display::delimited(metas, &self.flags),
+            (FormatFlag::Porcelain, _) => display::porcelain(metas, &self.flags),
+            (_, Layout::Tree { .. }) => {
+                display::tree(metas, &self.flags, &self.colors, &self.icons).into_bytes()
+            }
+            _ if self.flags.flat_recursive => self.display_flat(metas),
+            _ => self.display_operands(metas, with_headers),
+        }
+    }
+
+    /// List the file operands and every entry found in the directory ones in
+    /// a single list, like `find`. The directories kept only for holding the
+    /// entries passing the filters are left out.
+    fn display_flat(&self, metas: Vec<Meta>) -> Vec<u8> {
+        let (dirs, mut entries) = self.partition_operands(metas);
+
+        for dir in dirs {
+            for meta in dir.content.unwrap_or_default() {
+                self.flatten(meta, &mut entries);
+            }
+        }
+
+        self.display_entries(entries, &self.flags)
+    }
+
+    fn flatten(&self, mut meta: Meta, entries: &mut Vec<Meta>) {
+        let content = meta.content.take();
+        if self.is_wanted(&meta) {
+            entries.push(meta);
+        }
+
+        for meta in content.unwrap_or_default() {
+            self.flatten(meta, entries);
+        }
+    }
+
+    /// List the file operands first, then the content of each directory
+    /// operand, under a `path:` header when several operands were given or
+    /// with the recursion.
+    fn display_operands(&self, metas: Vec<Meta>, with_headers: bool) -> Vec<u8> {
+        let (dirs, files) = self.partition_operands(metas);
+
+        let mut output = Vec::new();
+        if !files.is_empty() {
+            output.extend(self.display_entries(files, &self.flags));
+        }
 
-        for meta in metas {
-            if let Some(ref mut content) = meta.content {
-                self.sort(content);
+        for mut dir in dirs {
+            if with_headers {
+                if !output.is_empty() {
+                    output.push(display::entry_terminator(&self.flags) as u8);
+                }
+                output.extend(display::directory_header(&dir.path, &self.flags));
+            }
+
+            if let Some(content) = dir.content.take() {
+                let flags = dir.content_flags(&self.flags);
+                output.extend(display::total(&content, flags, &self.colors).into_bytes());
+                output.extend(self.display_entries(content, flags));
             }
         }
+
+        output
     }
 
-    fn display(&self, metas: Vec<Meta>) {
-        let output = match self.flags.layout {
-            Layout::OneLine { .. } => {
-                display::one_line(metas, &self.flags, &self.colors, &self.icons)
+    /// Split the directories to list the content of from the other operands.
+    fn partition_operands(&self, metas: Vec<Meta>) -> (Vec<Meta>, Vec<Meta>) {
+        metas.into_iter().partition(|meta| match meta.file_type {
+            FileType::Directory { .. } => self.flags.display != Display::DisplayDirectoryItself,
+            _ => false,
+        })
+    }
+
+    fn display_entries(&self, metas: Vec<Meta>, flags: &Flags) -> Vec<u8> {
+        let output = match flags.layout {
+            // The names of `--zero` are for the tools, which want them as they are.
+            Layout::OneLine { long: false } if flags.zero => {
+                return display::raw_one_line(metas, flags)
+            }
+            Layout::OneLine { long: true } if flags.zero => {
+                return display::raw_long(metas, flags, &self.colors, &self.icons)
             }
-            Layout::Tree { .. } => display::tree(metas, &self.flags, &self.colors, &self.icons),
-            Layout::Grid => display::grid(metas, &self.flags, &self.colors, &self.icons),
+            Layout::Grid => display::grid(metas, flags, &self.colors, &self.icons),
+            _ => display::one_line(metas, flags, &self.colors, &self.icons),
         };
-        print!("{}", output);
+
+        output.into_bytes()
+    }
+}
+
+fn sort_with(metas: &mut Vec<Meta>, flags: &Flags) {
+    // Unlike an unstable sort, this keeps the reading order of the entries
+    // `--no-sort` doesn't move.
+    metas.sort_by(|a, b| sort::by_meta(a, b, flags));
+
+    // The content of the directories is sorted by their own flags.
+    for meta in metas {
+        if let Some(mut content) = meta.content.take() {
+            sort_with(&mut content, meta.content_flags(flags));
+            meta.content = Some(content);
+        }
+    }
+}
+
+/// Gather the details of `meta` and its content which only some `flags.blocks`
+/// display.
+fn calculate_blocks(meta: &mut Meta, flags: &Flags) {
+    if !flags.audit_rules.is_empty() {
+        meta.calculate_audit(&flags.audit_rules, Audit::parent_uid(&meta.path));
+    }
+    // The sizes of the directories may be their entry counts.
+    if flags.blocks.contains(&Block::Count)
+        || (flags.dir_size == DirSizeFlag::Count && flags.blocks.contains(&Block::Size))
+    {
+        meta.calculate_entry_count();
+    }
+    if !flags.display_rules.is_empty() {
+        meta.calculate_display_rules(&flags.display_rules, flags.dir_size == DirSizeFlag::Total);
     }
+    if flags.blocks.contains(&Block::Mime) {
+        meta.calculate_content_type(flags.mime);
+    }
+    if flags.blocks.contains(&Block::Checksum) {
+        Meta::calculate_checksums(
+            slice::from_mut(meta),
+            flags.checksum,
+            flags.checksum_max_size,
+        );
+    }
+    // The commands are run for the long views only, which show them.
+    let long = match flags.layout {
+        Layout::OneLine { long } | Layout::Tree { long } => long,
+        Layout::Grid => false,
+    };
+    if long && flags.blocks.contains(&Block::Custom) {
+        Meta::calculate_custom_blocks(
+            slice::from_mut(meta),
+            &flags.custom_blocks,
+            flags.custom_block_jobs,
+        );
+    }
+}
+
+/// The `path` from the root, without the `.` components, but with the symlinks
+/// and the trailing separator left as they are.
+fn absolute_path(path: &Path) -> PathBuf {
+    let absolute = match env::current_dir() {
+        Ok(current_dir) if path.is_relative() => current_dir.join(path),
+        _ => path.to_path_buf(),
+    };
+
+    let absolute: PathBuf = absolute
+        .components()
+        .filter(|component| *component != Component::CurDir)
+        .collect();
+    if has_trailing_separator(path) {
+        // Joining the empty path adds the separator back.
+        absolute.join("")
+    } else {
+        absolute
+    }
+}
+
+/// Whether `path` ends with a separator, like `link/`.
+fn has_trailing_separator(path: &Path) -> bool {
+    path.to_string_lossy().ends_with(path::is_separator)
 }
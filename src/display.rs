@@ -1,42 +1,290 @@
-use crate::color::Colors;
-use crate::flags::{Block, Display, Flags, Layout};
+use crate::color::{ColoredString, Colors};
+use crate::flags::{
+    Block, DirSizeFlag, Display, Flags, FormatFlag, Layout, PathFlag, SizeFlag, TimeFlag, TotalFlag,
+};
 use crate::icon::Icons;
-use crate::meta::{FileType, Meta, Size};
+use crate::meta::{CustomValue, FileType, Meta, Preview, Size};
 use ansi_term::{ANSIString, ANSIStrings};
+use std::env;
+use std::ffi::OsStr;
+use std::path::Path;
+use std::rc::Rc;
 use term_grid::{Cell, Direction, Filling, Grid, GridOptions};
 use terminal_size::terminal_size;
-use unicode_width::UnicodeWidthStr;
-
-const EDGE: &str = "\u{251c}\u{2500}\u{2500}"; // "├──"
-const LINE: &str = "\u{2502}  "; // "├  "
-const CORNER: &str = "\u{2514}\u{2500}\u{2500}"; // "└──"
-const BLANK: &str = "   ";
-
-#[derive(Debug, Copy, Clone)]
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+const DELIMITED_FIELDS: &[&str] = &[
+    "path",
+    "type",
+    "size_bytes",
+    "time_epoch",
+    "uid",
+    "gid",
+    "mode_octal",
+    "symlink_target",
+];
+
+#[derive(Debug, Clone)]
 struct PaddingRules {
+    access_control: usize,
     user: usize,
     group: usize,
-    size: (usize, usize),
+    context: usize,
+    device: usize,
+    filesystem: usize,
+    flags: usize,
+    size: (usize, usize, usize),
+    entry_count: usize,
+    content_type: usize,
+    checksum: usize,
     date: usize,
     name: usize,
     name_with_symlink: usize,
+    custom: Vec<usize>,
 }
 
+/// Render the entries one per line, with the blocks of the long format when
+/// `flags.layout` asks for it.
 pub fn one_line(metas: Vec<Meta>, flags: &Flags, colors: &Colors, icons: &Icons) -> String {
-    inner_display_one_line(metas, &flags, colors, icons, 0)
+    String::from_utf8_lossy(&inner_display_one_line(metas, flags, colors, icons)).into_owned()
+}
+
+/// Render the long one-line listing of `--zero`, with the names as stored by
+/// the system, even when they aren't valid UTF-8.
+pub fn raw_long(metas: Vec<Meta>, flags: &Flags, colors: &Colors, icons: &Icons) -> Vec<u8> {
+    inner_display_one_line(metas, flags, colors, icons)
+}
+
+/// The `path:` line heading the content of a directory. With `--zero`, the
+/// path is written as stored by the system and the line ends like the entries.
+pub fn directory_header(path: &Path, flags: &Flags) -> Vec<u8> {
+    let mut output = if flags.zero {
+        raw_bytes(path.as_os_str())
+    } else {
+        path.to_string_lossy().to_string().into_bytes()
+    };
+    output.push(b':');
+    output.push(entry_terminator(flags) as u8);
+
+    output
+}
+
+/// Render the `total` line heading the long listing of a directory content,
+/// or nothing when it is not requested.
+pub fn total(metas: &[Meta], flags: &Flags, colors: &Colors) -> String {
+    if flags.layout != (Layout::OneLine { long: true }) {
+        return String::new();
+    }
+
+    match flags.total {
+        TotalFlag::None => String::new(),
+        TotalFlag::Blocks => {
+            let blocks: u64 = metas.iter().map(|meta| meta.size.get_blocks()).sum();
+            // The blocks are counted in kilobytes, rounded up.
+            format!("total {}\n", blocks.div_ceil(2))
+        }
+        TotalFlag::Size => {
+            let size = Size::new(metas.iter().map(|meta| meta.size.get_bytes()).sum());
+            format!(
+                "total {}\n",
+                size.render(colors, size.lengths(flags), flags)
+            )
+        }
+    }
+}
+
+/// The `--summary` footer of the listed entries, counting the directories and
+/// the other entries, down to the content of the directories with `recursive`.
+pub fn summary(metas: &[Meta], flags: &Flags, colors: &Colors, recursive: bool) -> String {
+    if !flags.summary {
+        return String::new();
+    }
+
+    let mut counts = SummaryCounts::default();
+    counts.add(metas, flags, recursive);
+    counts.render(flags, colors)
 }
 
+#[derive(Default)]
+struct SummaryCounts {
+    dirs: u64,
+    files: u64,
+    bytes: u64,
+}
+
+impl SummaryCounts {
+    /// Only the files count in the size, unless the directories have their
+    /// total size and are not recursed into.
+    fn add(&mut self, metas: &[Meta], flags: &Flags, recursive: bool) {
+        for meta in metas {
+            if let FileType::Directory { .. } = meta.file_type {
+                self.dirs += 1;
+                if flags.dir_size == DirSizeFlag::Total && !recursive {
+                    self.bytes += meta.size.get_bytes();
+                }
+            } else {
+                self.files += 1;
+                self.bytes += meta.size.get_bytes();
+            }
+
+            if let (Some(content), true) = (&meta.content, recursive) {
+                self.add(content, flags, recursive);
+            }
+        }
+    }
+
+    fn render(&self, flags: &Flags, colors: &Colors) -> String {
+        let size = Size::new(self.bytes);
+
+        format!(
+            "{} {}, {} {}, {} total\n",
+            self.dirs,
+            if self.dirs == 1 {
+                "directory"
+            } else {
+                "directories"
+            },
+            self.files,
+            if self.files == 1 { "file" } else { "files" },
+            size.render(colors, size.lengths(flags), flags)
+        )
+    }
+}
+
+/// Render the entries in a grid fitting the width given by `--width`, or the
+/// terminal one, or `$COLUMNS` when the output is not a terminal.
 pub fn grid(metas: Vec<Meta>, flags: &Flags, colors: &Colors, icons: &Icons) -> String {
-    let term_width = match terminal_size() {
-        Some((w, _)) => Some(w.0 as usize),
-        None => None,
+    let term_width = output_width(flags);
+
+    inner_display_grid(metas, flags, colors, icons, term_width)
+}
+
+/// The width given by `--width`, or the terminal one, or `$COLUMNS` when the
+/// output is not a terminal.
+fn output_width(flags: &Flags) -> Option<usize> {
+    match (flags.width, terminal_size()) {
+        (Some(width), _) => Some(width),
+        (None, Some((w, _))) => Some(w.0 as usize),
+        (None, None) => env::var("COLUMNS")
+            .ok()
+            .and_then(|columns| columns.trim().parse().ok())
+            .filter(|columns| *columns > 0),
+    }
+}
+
+/// Render one row of fields per entry, separated by commas or tabs depending
+/// on `flags.format`, after a row naming the fields. Like the listing, the
+/// directory operands are replaced by their content. The paths are written as
+/// stored by the system, even when they aren't valid UTF-8.
+pub fn delimited(metas: Vec<Meta>, flags: &Flags) -> Vec<u8> {
+    let separator = match flags.format {
+        FormatFlag::Tsv => b'\t',
+        _ => b',',
     };
 
-    inner_display_grid(metas, &flags, colors, icons, 0, term_width)
+    let terminator = entry_terminator(flags) as u8;
+
+    // The date is the one picked by `--time`.
+    let time_field = match flags.time {
+        TimeFlag::Modified => "mtime_epoch",
+        TimeFlag::Created => "btime_epoch",
+    };
+    let mut output = DELIMITED_FIELDS
+        .iter()
+        .map(|field| {
+            if *field == "time_epoch" {
+                time_field
+            } else {
+                field
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(&(separator as char).to_string())
+        .into_bytes();
+    output.push(terminator);
+
+    push_operand_rows(&mut output, metas, flags, &|output, meta| {
+        push_delimited_row(output, meta, separator, terminator)
+    });
+
+    output
 }
 
+/// Render the fields of the long view as `--format porcelain`, one row per
+/// entry without header, colors nor icons: the type, the octal mode, the
+/// number of links, the uid, the gid, the size in bytes and the date picked by
+/// `--time` in UTC, padded to the widest they can be, then the path until the end
+/// of the row. The missing fields are written as `-`.
+///
+/// The scripts rely on this format, which must never change.
+pub fn porcelain(metas: Vec<Meta>, flags: &Flags) -> Vec<u8> {
+    let terminator = entry_terminator(flags) as u8;
+    let mut output = Vec::new();
+
+    push_operand_rows(&mut output, metas, flags, &|output, meta| {
+        output.extend(porcelain_fields(meta).into_bytes());
+        output.extend(raw_bytes(meta.path.as_os_str()));
+        output.push(terminator);
+    });
+
+    output
+}
+
+/// The fixed width fields of `meta` before its path, a space after each.
+fn porcelain_fields(meta: &Meta) -> String {
+    let field = |value: Option<String>| value.unwrap_or_else(|| String::from("-"));
+
+    // The widths are the ones of the longest type name, of the `u64` and
+    // `u32` values, and of the dates.
+    format!(
+        "{:<12} {} {:>20} {:>10} {:>10} {:>20} {:<20} ",
+        meta.file_type.type_name(),
+        meta.permissions.octal(),
+        field(meta.inode.links().map(|links| links.to_string())),
+        field(meta.owner.uid().map(|uid| uid.to_string())),
+        field(meta.owner.gid().map(|gid| gid.to_string())),
+        meta.size.get_bytes(),
+        field(meta.date.iso_string()),
+    )
+}
+
+/// Push the rows of `metas`, the directory operands being replaced by their
+/// content like in the listing.
+fn push_operand_rows<F>(output: &mut Vec<u8>, metas: Vec<Meta>, flags: &Flags, push_row: &F)
+where
+    F: Fn(&mut Vec<u8>, &Meta),
+{
+    for meta in metas {
+        match meta.content {
+            Some(content) if flags.display != Display::DisplayDirectoryItself => {
+                push_rows(output, content, push_row)
+            }
+            _ => push_row(output, &meta),
+        }
+    }
+}
+
+/// Render the entries and their content as a tree.
 pub fn tree(metas: Vec<Meta>, flags: &Flags, colors: &Colors, icons: &Icons) -> String {
-    inner_display_tree(metas, &flags, colors, icons, 0, "")
+    let mut footer = String::new();
+    if flags.summary {
+        // Like `tree`, the directory operands themselves are not counted.
+        let mut counts = SummaryCounts::default();
+        for meta in &metas {
+            match &meta.content {
+                Some(content) => counts.add(content, flags, true),
+                None => counts.add(std::slice::from_ref(meta), flags, true),
+            }
+        }
+        footer = counts.render(flags, colors);
+    }
+
+    let output = match flags.layout {
+        Layout::Tree { long: true } => inner_display_long_tree(metas, flags, colors, icons),
+        _ => inner_display_tree(metas, flags, colors, icons, 0, "", None),
+    };
+
+    output + &footer
 }
 
 fn inner_display_one_line(
@@ -44,60 +292,87 @@ fn inner_display_one_line(
     flags: &Flags,
     colors: &Colors,
     icons: &Icons,
-    depth: usize,
-) -> String {
-    let mut output = String::new();
+) -> Vec<u8> {
+    let mut output = Vec::new();
 
     let mut padding_rules = None;
     if let Layout::OneLine { long: true } = flags.layout {
         // Defining the padding rules is costly and so shouldn't be done several
         // times. That's why it's done outside the loop.
-        padding_rules = Some(PaddingRules {
-            user: detect_user_length(&metas),
-            group: detect_group_length(&metas),
-            size: detect_size_lengths(&metas, &flags),
-            date: detect_date_length(&metas, &flags),
-            name: detect_name_length(&metas, &icons, &flags),
-            name_with_symlink: detect_name_with_symlink_length(&metas, &icons, &flags),
-        })
-    }
+        let mut rules = PaddingRules {
+            access_control: detect_access_control_length(&metas),
+            user: detect_user_length(&metas, flags),
+            group: detect_group_length(&metas, flags),
+            context: detect_context_length(&metas),
+            device: detect_device_length(&metas),
+            filesystem: detect_filesystem_length(&metas),
+            flags: detect_flags_length(&metas),
+            size: detect_size_lengths(&metas, flags),
+            entry_count: detect_entry_count_length(&metas),
+            content_type: detect_content_type_length(&metas),
+            checksum: detect_checksum_length(&metas),
+            date: detect_date_length(&metas, flags),
+            name: detect_name_length(&metas, icons, flags),
+            name_with_symlink: detect_name_with_symlink_length(&metas, icons, flags),
+            custom: detect_custom_lengths(&metas, flags),
+        };
+
+        if flags.header && !metas.is_empty() {
+            if flags.prefix_indent {
+                output.extend_from_slice(b"    ");
+            }
+            output.extend(get_header_output(&metas[0], flags, &mut rules).into_bytes());
+            output.push(b'\n');
+        }
 
-    // The first iteration (depth == 0) corresponds to the inputs given by the
-    // user. We defer displaying directories given by the user unless we've been
-    // asked to display the directory itself (rather than its contents).
-    let skip_dirs = (depth == 0) && (flags.display != Display::DisplayDirectoryItself);
+        padding_rules = Some(rules);
+    }
+    // The lines of a preview pane never wrap.
+    let pane_width = if flags.preview_pane {
+        output_width(flags)
+    } else {
+        None
+    };
 
-    // print the files first.
     for meta in &metas {
-        // Maybe skip showing the directory meta now; show its contents later.
-        if let (true, FileType::Directory { .. }) = (skip_dirs, meta.file_type) {
-            continue;
+        if flags.prefix_indent {
+            output.extend_from_slice(b"    ");
+        }
+
+        match (flags.layout, &padding_rules) {
+            (Layout::OneLine { long: true }, Some(rules)) if flags.zero => {
+                output.extend(get_raw_long_output(meta, colors, icons, flags, rules))
+            }
+            (Layout::OneLine { long: true }, Some(rules)) => {
+                output.extend(get_long_output(meta, colors, icons, flags, rules).into_bytes())
+            }
+            _ => {
+                output.extend(get_short_output(meta, colors, icons, flags, pane_width).into_bytes())
+            }
         }
 
-		if flags.prefix_indent {
-			output.push_str("    ");
-		}
+        output.push(entry_terminator(flags) as u8);
 
         if let Layout::OneLine { long: true } = flags.layout {
-            output += &get_long_output(&meta, &colors, &icons, &flags, padding_rules.unwrap());
-        } else {
-            output += &get_short_output(&meta, &colors, &icons, &flags);
+            let prefix = if flags.prefix_indent { "    " } else { "" };
+            if flags.extended {
+                output.extend(get_xattrs_output(meta, prefix).into_bytes());
+            }
+            output.extend(get_preview_output(meta, colors, flags, prefix).into_bytes());
+            output.extend(meta.audit.render(colors, prefix).into_bytes());
         }
-
-        output.push('\n');
     }
-
-    let should_display_folder_path = should_display_folder_path(depth, &metas);
+    output.extend(summary(&metas, flags, colors, false).into_bytes());
 
     // print the folder content
-    for meta in metas {
-        if meta.content.is_some() {
-            if should_display_folder_path {
-                output += &display_folder_path(&meta);
-            }
-
-            output +=
-                &inner_display_one_line(meta.content.unwrap(), &flags, colors, icons, depth + 1);
+    for mut meta in metas {
+        if let Some(content) = meta.content.take() {
+            output.push(entry_terminator(flags) as u8);
+            output.extend(directory_header(&meta.path, flags));
+
+            let flags = meta.content_flags(flags);
+            output.extend(total(&content, flags, colors).into_bytes());
+            output.extend(inner_display_one_line(content, flags, colors, icons));
         }
     }
 
@@ -109,7 +384,6 @@ fn inner_display_grid(
     flags: &Flags,
     colors: &Colors,
     icons: &Icons,
-    depth: usize,
     term_width: Option<usize>,
 ) -> String {
     let mut output = String::new();
@@ -119,19 +393,16 @@ fn inner_display_grid(
         direction: Direction::TopToBottom,
     });
 
-    // The first iteration (depth == 0) corresponds to the inputs given by the
-    // user. We defer displaying directories given by the user unless we've been
-    // asked to display the directory itself (rather than its contents).
-    let skip_dirs = (depth == 0) && (flags.display != Display::DisplayDirectoryItself);
-
-    // print the files first.
+    let name_width = flags
+        .truncate_names
+        .and_then(|truncate| truncate.name_width(term_width));
     for meta in &metas {
-        // Maybe skip showing the directory meta now; show its contents later.
-        if let (true, FileType::Directory { .. }) = (skip_dirs, meta.file_type) {
-            continue;
-        }
-
-        let line_output = get_short_output(&meta, &colors, &icons, &flags);
+        let strings: &[ANSIString] = &[
+            meta.name
+                .render_within(&meta.path, colors, icons, flags, name_width),
+            meta.indicator.render(flags),
+        ];
+        let line_output = ANSIStrings(strings).to_string();
         grid.add(Cell {
             width: get_visible_width(&line_output),
             contents: line_output,
@@ -150,22 +421,18 @@ fn inner_display_grid(
     } else {
         output += &grid.fit_into_columns(1).to_string();
     }
-
-    let should_display_folder_path = should_display_folder_path(depth, &metas);
+    output += &summary(&metas, flags, colors, false);
 
     // print the folder content
-    for meta in metas {
-        if meta.content.is_some() {
-            if should_display_folder_path {
-                output += &display_folder_path(&meta);
-            }
+    for mut meta in metas {
+        if let Some(content) = meta.content.take() {
+            output += &display_folder_path(&meta);
 
             output += &inner_display_grid(
-                meta.content.unwrap(),
-                &flags,
+                content,
+                meta.content_flags(flags),
                 colors,
                 icons,
-                depth + 1,
                 term_width,
             );
         }
@@ -181,63 +448,64 @@ fn inner_display_tree(
     icons: &Icons,
     depth: usize,
     prefix: &str,
+    parent_size: Option<u64>,
 ) -> String {
     let mut output = String::new();
     let last_idx = metas.len();
+    let size_lengths = detect_size_lengths(&metas, flags);
 
-    let mut padding_rules = None;
-    if let Layout::Tree { long: true } = flags.layout {
-        // Defining the padding rules is costly and so shouldn't be done several
-        // times. That's why it's done outside the loop.
-        padding_rules = Some(PaddingRules {
-            user: detect_user_length(&metas),
-            group: detect_group_length(&metas),
-            size: detect_size_lengths(&metas, flags),
-            date: detect_date_length(&metas, flags),
-            name: detect_name_length(&metas, &icons, &flags),
-            name_with_symlink: detect_name_with_symlink_length(&metas, &icons, &flags),
-        })
-    }
-
-    for (idx, meta) in metas.into_iter().enumerate() {
+    for (idx, mut meta) in metas.into_iter().enumerate() {
         let is_last_folder_elem = idx + 1 != last_idx;
 
         if depth > 0 {
             output += prefix;
 
             if is_last_folder_elem {
-                output += EDGE;
+                output += &flags.symbols.tree_edge;
             } else {
-                output += CORNER;
+                output += &flags.symbols.tree_corner;
             }
             output += " ";
         }
 
-        if let Layout::Tree { long: true } = flags.layout {
-            output += &get_long_output(&meta, &colors, &icons, &flags, padding_rules.unwrap());
-        } else {
-            output += &get_short_output(&meta, &colors, &icons, &flags);
+        if flags.is_disk_usage() {
+            // The operands have no parent to take a share of.
+            match parent_size {
+                Some(parent_size) => {
+                    output += &meta.size.render_share(colors, parent_size).to_string()
+                }
+                None => output += "    ",
+            }
+            output += " ";
+
+            let size = meta.size.render(colors, size_lengths, flags);
+            output += &size.to_string();
+            output += " ";
         }
+        output += &get_short_output(&meta, colors, icons, flags, None);
         output += "\n";
 
-        if meta.content.is_some() {
-            let mut new_prefix = String::from(prefix);
+        let mut new_prefix = String::from(prefix);
 
-            if depth > 0 {
-                if is_last_folder_elem {
-                    new_prefix += LINE;
-                } else {
-                    new_prefix += BLANK;
-                }
+        if depth > 0 {
+            if is_last_folder_elem {
+                new_prefix += &flags.symbols.tree_line;
+            } else {
+                new_prefix += &" ".repeat(flags.symbols.tree_line.width());
             }
+        }
 
+        output += &get_preview_output(&meta, colors, flags, &new_prefix);
+
+        if let Some(content) = meta.content.take() {
             output += &inner_display_tree(
-                meta.content.unwrap(),
-                &flags,
+                content,
+                meta.content_flags(flags),
                 colors,
                 icons,
                 depth + 1,
                 &new_prefix,
+                Some(meta.size.get_bytes()),
             );
         }
     }
@@ -245,20 +513,319 @@ fn inner_display_tree(
     output
 }
 
-fn should_display_folder_path(depth: usize, metas: &[Meta]) -> bool {
-    if depth > 0 {
-        true
-    } else {
-        let folder_number = metas
+/// The branches of the tree leading to an entry of the long tree.
+struct TreeBranch {
+    /// The branches on the line of the entry.
+    branch: String,
+    /// The branches on the lines under it, like the ones of its content.
+    prefix: String,
+    parent_size: Option<u64>,
+    /// The flags of the blocks of the entry, the ones of its parent directory.
+    block_flags: Rc<Flags>,
+}
+
+/// Render the long tree: the branches and the names on the left, then the
+/// other blocks aligned in columns over the whole tree.
+fn inner_display_long_tree(
+    metas: Vec<Meta>,
+    flags: &Flags,
+    colors: &Colors,
+    icons: &Icons,
+) -> String {
+    let mut rows = Vec::new();
+    push_tree_rows(
+        metas,
+        &Rc::new(tree_block_flags(flags)),
+        0,
+        "",
+        None,
+        &mut rows,
+    );
+    let (metas, branches): (Vec<Meta>, Vec<TreeBranch>) = rows.into_iter().unzip();
+
+    // Defining the padding rules is costly and so shouldn't be done several
+    // times. That's why it's done outside the loop.
+    let padding_rules = PaddingRules {
+        access_control: detect_access_control_length(&metas),
+        user: detect_user_length(&metas, flags),
+        group: detect_group_length(&metas, flags),
+        context: detect_context_length(&metas),
+        device: detect_device_length(&metas),
+        filesystem: detect_filesystem_length(&metas),
+        flags: detect_flags_length(&metas),
+        size: detect_size_lengths(&metas, flags),
+        entry_count: detect_entry_count_length(&metas),
+        content_type: detect_content_type_length(&metas),
+        checksum: detect_checksum_length(&metas),
+        date: detect_date_length(&metas, flags),
+        name: detect_name_length(&metas, icons, flags),
+        name_with_symlink: detect_name_with_symlink_length(&metas, icons, flags),
+        custom: detect_custom_lengths(&metas, flags),
+    };
+    let cells: Vec<(String, usize)> = metas
+        .iter()
+        .zip(&branches)
+        .map(|(meta, branch)| get_tree_cell(meta, branch, colors, icons, flags))
+        .collect();
+    let tree_width = cells.iter().map(|(_, width)| *width).max().unwrap_or(0);
+
+    let mut output = String::new();
+    for ((meta, branch), (cell, width)) in metas.iter().zip(&branches).zip(cells) {
+        output += &cell;
+        if !branch.block_flags.blocks.is_empty() {
+            output += &" ".repeat(tree_width - width + 1);
+            output += &get_long_output(meta, colors, icons, &branch.block_flags, &padding_rules);
+        }
+        output += "\n";
+
+        if flags.extended {
+            output += &get_xattrs_output(meta, &branch.prefix);
+        }
+        output += &get_preview_output(meta, colors, flags, &branch.prefix);
+        output += &meta.audit.render(colors, &branch.prefix);
+    }
+
+    output
+}
+
+/// The flags of the blocks of the long tree, whose names are in the tree
+/// column.
+fn tree_block_flags(flags: &Flags) -> Flags {
+    Flags {
+        blocks: flags
+            .blocks
             .iter()
-            .filter(|x| match x.file_type {
-                FileType::Directory { .. } => true,
-                _ => false,
-            })
-            .count();
+            .filter(|block| **block != Block::Name)
+            .cloned()
+            .collect(),
+        ..flags.clone()
+    }
+}
 
-        folder_number > 1 || folder_number < metas.len()
+/// Flatten the tree of `metas` into `rows`, each entry followed by its content,
+/// which the `.lsd.yaml` of its directory may give other blocks.
+fn push_tree_rows(
+    metas: Vec<Meta>,
+    flags: &Rc<Flags>,
+    depth: usize,
+    prefix: &str,
+    parent_size: Option<u64>,
+    rows: &mut Vec<(Meta, TreeBranch)>,
+) {
+    let last_idx = metas.len();
+
+    for (idx, mut meta) in metas.into_iter().enumerate() {
+        let is_last_folder_elem = idx + 1 != last_idx;
+
+        let mut branch = String::new();
+        let mut new_prefix = String::from(prefix);
+        if depth > 0 {
+            branch += prefix;
+            if is_last_folder_elem {
+                branch += &flags.symbols.tree_edge;
+                new_prefix += &flags.symbols.tree_line;
+            } else {
+                branch += &flags.symbols.tree_corner;
+                new_prefix += &" ".repeat(flags.symbols.tree_line.width());
+            }
+            branch += " ";
+        }
+
+        let content = meta.content.take();
+        let content_flags = match meta.directory_flags.take() {
+            Some(directory_flags) => Rc::new(tree_block_flags(&directory_flags)),
+            None => Rc::clone(flags),
+        };
+        let size = meta.size.get_bytes();
+        rows.push((
+            meta,
+            TreeBranch {
+                branch,
+                prefix: new_prefix.clone(),
+                parent_size,
+                block_flags: Rc::clone(flags),
+            },
+        ));
+
+        if let Some(content) = content {
+            push_tree_rows(
+                content,
+                &content_flags,
+                depth + 1,
+                &new_prefix,
+                Some(size),
+                rows,
+            );
+        }
+    }
+}
+
+/// The branches and the name of the entry, with its share of the parent size
+/// and its symlink target when they are displayed, and their width.
+fn get_tree_cell(
+    meta: &Meta,
+    branch: &TreeBranch,
+    colors: &Colors,
+    icons: &Icons,
+    flags: &Flags,
+) -> (String, usize) {
+    let mut strings: Vec<ANSIString> = vec![ANSIString::from(branch.branch.clone())];
+    let mut width = branch.branch.width();
+
+    if flags.is_disk_usage() {
+        // The operands have no parent to take a share of.
+        match branch.parent_size {
+            Some(parent_size) => strings.push(meta.size.render_share(colors, parent_size)),
+            None => strings.push(ANSIString::from("    ")),
+        }
+        strings.push(ANSIString::from(" "));
+        width += 5;
+    }
+
+    strings.push(meta.name.render(&meta.path, colors, icons, flags));
+    strings.push(meta.indicator.render(flags));
+    width += meta.name.name_string(&meta.path, icons, flags).width() + meta.indicator.len(flags);
+
+    if !flags.no_symlink {
+        if let Some(target) = meta.symlink.symlink_string(flags) {
+            strings.push(meta.symlink.render(colors, flags));
+            width += symlink_arrow_width(flags) + target.width();
+        }
+    }
+
+    (ANSIStrings(&strings).to_string(), width)
+}
+
+/// The end of the entries, the NUL of `--zero` or the end of the line.
+pub fn entry_terminator(flags: &Flags) -> char {
+    if flags.zero {
+        '\0'
+    } else {
+        '\n'
+    }
+}
+
+/// Render the short one-line listing of `--zero` with the names as stored by
+/// the system, even when they aren't valid UTF-8, for the tools reading them
+/// back. Being meant for them, it has neither colors nor icons.
+pub fn raw_one_line(metas: Vec<Meta>, flags: &Flags) -> Vec<u8> {
+    let mut output = Vec::new();
+
+    for meta in &metas {
+        output.extend(raw_bytes(raw_name(meta, flags)));
+        output.extend(meta.indicator.render(flags).to_string().into_bytes());
+        output.push(entry_terminator(flags) as u8);
     }
+
+    for mut meta in metas {
+        if let Some(content) = meta.content.take() {
+            output.push(entry_terminator(flags) as u8);
+            output.extend(directory_header(&meta.path, flags));
+            output.extend(raw_one_line(content, meta.content_flags(flags)));
+        }
+    }
+
+    output
+}
+
+/// The name of `meta` as given to the tools, its whole path with `--full-path`.
+fn raw_name<'a>(meta: &'a Meta, flags: &Flags) -> &'a OsStr {
+    // The paths are already absolute with `PathFlag::Absolute`.
+    match flags.full_path {
+        PathFlag::None => meta.name.raw_name(),
+        PathFlag::Relative | PathFlag::Absolute => meta.path.as_os_str(),
+    }
+}
+
+/// The bytes of `name` as stored by the system. The names of Windows are
+/// UTF-16, so their unpaired surrogates are replaced there.
+#[cfg(unix)]
+fn raw_bytes(name: &OsStr) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+
+    name.as_bytes().to_vec()
+}
+
+#[cfg(not(unix))]
+fn raw_bytes(name: &OsStr) -> Vec<u8> {
+    name.to_string_lossy().to_string().into_bytes()
+}
+
+fn push_rows<F>(output: &mut Vec<u8>, metas: Vec<Meta>, push_row: &F)
+where
+    F: Fn(&mut Vec<u8>, &Meta),
+{
+    for meta in metas {
+        push_row(output, &meta);
+
+        if let Some(content) = meta.content {
+            push_rows(output, content, push_row);
+        }
+    }
+}
+
+fn push_delimited_row(output: &mut Vec<u8>, meta: &Meta, separator: u8, terminator: u8) {
+    let id = |id: Option<u32>| id.map(|id| id.to_string()).unwrap_or_default();
+    let fields = [
+        raw_bytes(meta.path.as_os_str()),
+        meta.file_type.type_name().to_string().into_bytes(),
+        meta.size.get_bytes().to_string().into_bytes(),
+        meta.date
+            .epoch()
+            .map(|epoch| epoch.to_string())
+            .unwrap_or_default()
+            .into_bytes(),
+        id(meta.owner.uid()).into_bytes(),
+        id(meta.owner.gid()).into_bytes(),
+        meta.permissions.octal().into_bytes(),
+        meta.symlink
+            .target()
+            .map(|target| raw_bytes(target.as_os_str()))
+            .unwrap_or_default(),
+    ];
+
+    let fields: Vec<Vec<u8>> = fields
+        .iter()
+        .map(|field| escape_field(field, separator))
+        .collect();
+    output.extend(fields.join(&separator));
+    output.push(terminator);
+}
+
+/// Quote the CSV fields holding a separator, a quote or a line break. As TSV
+/// has no quoting, the tabs and line breaks are escaped with a backslash.
+fn escape_field(field: &[u8], separator: u8) -> Vec<u8> {
+    let mut res = Vec::with_capacity(field.len());
+
+    if separator == b'\t' {
+        for byte in field {
+            match byte {
+                b'\\' => res.extend_from_slice(b"\\\\"),
+                b'\t' => res.extend_from_slice(b"\\t"),
+                b'\n' => res.extend_from_slice(b"\\n"),
+                b'\r' => res.extend_from_slice(b"\\r"),
+                _ => res.push(*byte),
+            }
+        }
+        return res;
+    }
+
+    if !field
+        .iter()
+        .any(|byte| [separator, b'"', b'\n', b'\r'].contains(byte))
+    {
+        return field.to_vec();
+    }
+
+    res.push(b'"');
+    for byte in field {
+        if *byte == b'"' {
+            res.push(b'"');
+        }
+        res.push(*byte);
+    }
+    res.push(b'"');
+    res
 }
 
 fn display_folder_path(meta: &Meta) -> String {
@@ -270,10 +837,23 @@ fn display_folder_path(meta: &Meta) -> String {
     output
 }
 
-fn get_short_output(meta: &Meta, colors: &Colors, icons: &Icons, flags: &Flags) -> String {
+/// The name and the indicator of `meta`, the name being cut so that they fit
+/// in `max_width` columns.
+fn get_short_output(
+    meta: &Meta,
+    colors: &Colors,
+    icons: &Icons,
+    flags: &Flags,
+    max_width: Option<usize>,
+) -> String {
+    let name_width = max_width.map(|width| {
+        let decorations = icons.get(&meta.name).width() + meta.indicator.len(flags);
+        width.saturating_sub(decorations).max(1)
+    });
     let strings: &[ANSIString] = &[
-        meta.name.render(colors, icons),
-        meta.indicator.render(&flags),
+        meta.name
+            .render_within(&meta.path, colors, icons, flags, name_width),
+        meta.indicator.render(flags),
     ];
 
     ANSIStrings(strings).to_string()
@@ -284,147 +864,483 @@ fn get_long_output(
     colors: &Colors,
     icons: &Icons,
     flags: &Flags,
-    padding_rules: PaddingRules,
+    padding_rules: &PaddingRules,
 ) -> String {
     let mut strings: Vec<ANSIString> = Vec::new();
     for block in flags.blocks.iter() {
-        match block {
+        strings.extend(get_block_output(
+            meta,
+            block,
+            colors,
+            icons,
+            flags,
+            padding_rules,
+        ));
+        strings.push(ANSIString::from(" "));
+    }
+
+    strings.pop(); // remove the last space
+
+    ANSIStrings(&strings).to_string()
+}
+
+/// Render the long row of `--zero`, with the name as stored by the system and
+/// neither colored nor with its icon.
+fn get_raw_long_output(
+    meta: &Meta,
+    colors: &Colors,
+    icons: &Icons,
+    flags: &Flags,
+    padding_rules: &PaddingRules,
+) -> Vec<u8> {
+    let mut output = Vec::new();
+    for (idx, block) in flags.blocks.iter().enumerate() {
+        if idx > 0 {
+            output.push(b' ');
+        }
+        if *block != Block::Name {
+            let strings = get_block_output(meta, block, colors, icons, flags, padding_rules);
+            output.extend(ANSIStrings(&strings).to_string().into_bytes());
+            continue;
+        }
+
+        output.extend(raw_bytes(raw_name(meta, flags)));
+        output.extend(meta.indicator.render(flags).to_string().into_bytes());
+        if !flags.no_symlink {
+            output.extend(meta.symlink.render(colors, flags).to_string().into_bytes());
+        }
+        // The names are all without their icon, so they stay aligned.
+        let padding = get_name_padding(meta, icons, flags, padding_rules);
+        output.extend(" ".repeat(padding).into_bytes());
+    }
+
+    output
+}
+
+fn get_block_output<'a>(
+    meta: &'a Meta,
+    block: &Block,
+    colors: &'a Colors,
+    icons: &Icons,
+    flags: &Flags,
+    padding_rules: &PaddingRules,
+) -> Vec<ANSIString<'a>> {
+    let mut strings: Vec<ANSIString> = Vec::new();
+    match block {
+        Block::Permission => {
+            strings.push(meta.file_type.render(colors));
+            strings.push(meta.permissions.render(colors, flags));
+            strings.push(
+                meta.access_control
+                    .render(colors, padding_rules.access_control),
+            );
+        }
+        Block::User => strings.push(meta.owner.render_user(colors, padding_rules.user, flags)),
+        Block::Group => strings.push(meta.owner.render_group(colors, padding_rules.group, flags)),
+        Block::Context => strings.push(meta.security_context.render(colors, padding_rules.context)),
+        Block::Device => strings.push(meta.device.render(colors, padding_rules.device)),
+        Block::Filesystem => strings.push(meta.filesystem.render(colors, padding_rules.filesystem)),
+        Block::Flags => strings.push(meta.file_flags.render(colors, padding_rules.flags)),
+        Block::Size => strings.push(render_size(meta, colors, padding_rules.size, flags)),
+        Block::Count => strings.push(meta.entry_count.render(colors, padding_rules.entry_count)),
+        Block::Mime => strings.push(meta.content_type.render(colors, padding_rules.content_type)),
+        Block::Checksum => strings.push(meta.checksum.render(colors, padding_rules.checksum)),
+        Block::Custom => {
+            for (idx, width) in padding_rules.custom.iter().enumerate() {
+                if idx > 0 {
+                    strings.push(ANSIString::from(" "));
+                }
+                match meta.custom.get(idx) {
+                    Some(value) => strings.push(value.render(colors, *width)),
+                    None => strings.push(CustomValue::default().render(colors, *width)),
+                }
+            }
+        }
+        Block::Date => strings.push(meta.date.render(colors, padding_rules.date, flags)),
+        Block::Name => {
+            strings.push(meta.name.render(&meta.path, colors, icons, flags));
+            strings.push(meta.indicator.render(flags));
+            if !flags.no_symlink {
+                strings.push(meta.symlink.render(colors, flags));
+            }
+            strings.push(ANSIString::from(" ".repeat(get_name_padding(
+                meta,
+                icons,
+                flags,
+                padding_rules,
+            ))));
+        }
+    };
+
+    strings
+}
+
+/// The spaces after the name of `meta`, its symlink target and its indicator,
+/// so that the next block lines up.
+fn get_name_padding(
+    meta: &Meta,
+    icons: &Icons,
+    flags: &Flags,
+    padding_rules: &PaddingRules,
+) -> usize {
+    let width = meta.name.name_string(&meta.path, icons, flags).width() + meta.indicator.len(flags);
+    if flags.no_symlink {
+        return padding_rules.name - width;
+    }
+
+    match meta.symlink.symlink_string(flags) {
+        Some(target) => {
+            padding_rules.name_with_symlink - symlink_arrow_width(flags) - target.width() - width
+        }
+        None => padding_rules.name_with_symlink - width,
+    }
+}
+
+/// Render the line naming the blocks of the long format. The columns narrower
+/// than their name are widened, so that the entries stay aligned with it.
+fn get_header_output(first: &Meta, flags: &Flags, padding_rules: &mut PaddingRules) -> String {
+    let mut output = String::new();
+
+    // The names are aligned like the values of their column.
+    for block in flags.blocks.iter() {
+        let (name, width, right_aligned) = match block {
             Block::Permission => {
-                strings.push(meta.file_type.render(colors));
-                strings.push(meta.permissions.render(colors));
+                let name = "Permissions";
+                // The file type character comes before the permissions.
+                let width = 1 + first.permissions.len(flags) + padding_rules.access_control;
+                if name.len() > width {
+                    padding_rules.access_control += name.len() - width;
+                }
+                (name, width, false)
             }
-            Block::User => strings.push(meta.owner.render_user(colors, padding_rules.user)),
-            Block::Group => strings.push(meta.owner.render_group(colors, padding_rules.group)),
-            Block::Size => strings.push(meta.size.render(
-                colors,
-                padding_rules.size.0,
-                padding_rules.size.1,
-                &flags,
-            )),
-            Block::Date => strings.push(meta.date.render(colors, padding_rules.date, &flags)),
-            Block::Name => {
-                if flags.no_symlink {
-                    strings.push(meta.name.render(colors, icons));
-                    strings.push(meta.indicator.render(&flags));
-                    strings.push(ANSIString::from(" ".to_string().repeat(
-                        padding_rules.name
-                            - meta.indicator.len(&flags)
-                            - meta.name.name_string(icons).len(),
-                    )))
+            Block::User => {
+                padding_rules.user = padding_rules.user.max("User".len());
+                ("User", padding_rules.user, true)
+            }
+            Block::Group => {
+                padding_rules.group = padding_rules.group.max("Group".len());
+                ("Group", padding_rules.group, true)
+            }
+            Block::Context => {
+                padding_rules.context = padding_rules.context.max("Context".len());
+                ("Context", padding_rules.context, false)
+            }
+            Block::Device => {
+                padding_rules.device = padding_rules.device.max("Device".len());
+                ("Device", padding_rules.device, false)
+            }
+            Block::Filesystem => {
+                padding_rules.filesystem = padding_rules.filesystem.max("Filesystem".len());
+                ("Filesystem", padding_rules.filesystem, false)
+            }
+            Block::Flags => {
+                padding_rules.flags = padding_rules.flags.max("Flags".len());
+                ("Flags", padding_rules.flags, false)
+            }
+            Block::Size => {
+                let name = "Size";
+                let separator = if flags.size == SizeFlag::Default {
+                    1
                 } else {
-                    match meta.symlink.symlink_string() {
-                        Some(s) => {
-                            strings.push(meta.name.render(colors, icons));
-                            strings.push(meta.indicator.render(&flags));
-                            strings.push(meta.symlink.render(colors));
-                            strings.push(ANSIString::from(" ".to_string().repeat(
-                                padding_rules.name_with_symlink
-                                    - 3 //  3 = ( arrow + 2 spaces) for symlink;
-                                    - meta.name.name_string(icons).len()
-                                    - meta.indicator.len(&flags)
-                                    - s.len(),
-                            )))
-                        }
-                        None => {
-                            strings.push(meta.name.render(colors, icons));
-                            strings.push(meta.indicator.render(&flags));
-                            strings.push(meta.symlink.render(colors));
-                            strings.push(ANSIString::from(" ".to_string().repeat(
-                                padding_rules.name_with_symlink
-                                    - meta.name.name_string(icons).len()
-                                    - meta.indicator.len(&flags),
-                            )))
-                        }
-                    }
+                    0
+                };
+                let width =
+                    padding_rules.size.0 + padding_rules.size.1 + separator + padding_rules.size.2;
+                if name.len() > width {
+                    padding_rules.size.0 += name.len() - width;
                 }
+                (name, width, true)
+            }
+            Block::Count => {
+                padding_rules.entry_count = padding_rules.entry_count.max("Count".len());
+                ("Count", padding_rules.entry_count, true)
+            }
+            Block::Mime => {
+                padding_rules.content_type = padding_rules.content_type.max("Mime".len());
+                ("Mime", padding_rules.content_type, false)
+            }
+            Block::Checksum => {
+                padding_rules.checksum = padding_rules.checksum.max("Checksum".len());
+                ("Checksum", padding_rules.checksum, false)
+            }
+            Block::Custom => {
+                // One name for each of the custom blocks.
+                for (block, width) in flags.custom_blocks.iter().zip(&mut padding_rules.custom) {
+                    *width = (*width).max(block.name.width());
+                    output += &block.name;
+                    output += &" ".repeat(*width - block.name.width());
+                    output.push(' ');
+                }
+                continue;
+            }
+            Block::Date => {
+                padding_rules.date = padding_rules.date.max("Date".len());
+                ("Date", padding_rules.date, false)
             }
+            Block::Name => ("Name", 0, false),
         };
-        strings.push(ANSIString::from(" "));
+
+        let padding = " ".repeat(width.saturating_sub(name.len()));
+        if right_aligned {
+            output += &padding;
+            output += name;
+        } else {
+            output += name;
+            output += &padding;
+        }
+        output.push(' ');
     }
 
-    strings.pop(); // remove the last space
+    output.trim_end().to_string()
+}
 
-    ANSIStrings(&strings).to_string()
+fn get_xattrs_output(meta: &Meta, prefix: &str) -> String {
+    let mut output = String::new();
+
+    for xattr in meta.access_control.xattrs() {
+        output += prefix;
+        output += "    ";
+        output += &xattr.name;
+        output += ": ";
+        output += &xattr.value_string();
+        output.push('\n');
+    }
+
+    output
 }
 
+fn get_preview_output(meta: &Meta, colors: &Colors, flags: &Flags, prefix: &str) -> String {
+    let preview = match (flags.preview, meta.file_type) {
+        (Some(lines), FileType::File { .. }) => Preview::for_file(&meta.path, lines),
+        _ => None,
+    };
+
+    match preview {
+        Some(preview) => preview.render(colors, prefix),
+        None => String::new(),
+    }
+}
+
+/// The number of columns taken by `input` in the terminal: the wide characters
+/// like the CJK ones count twice, while the escape sequences of the colors
+/// don't count at all.
 fn get_visible_width(input: &str) -> usize {
-    let mut nb_invisible_char = 0;
+    let mut width = 0;
+    let mut in_escape_sequence = false;
+
+    for c in input.chars() {
+        if c == '\u{1b}' {
+            in_escape_sequence = true;
+        } else if in_escape_sequence {
+            // The sequences end with a letter, like the `m` of `\u{1b}[38;5;33m`.
+            in_escape_sequence = !c.is_ascii_alphabetic();
+        } else {
+            width += c.width().unwrap_or(0);
+        }
+    }
+
+    width
+}
 
-    // If the input has color, do not compute the length contributed by the color to the actual length
-    if input.starts_with("\u{1b}[") {
-        let m_pos = input.find('m');
-        if let Some(len) = m_pos {
-            nb_invisible_char = len + 3 // 1 (index -> length) + 2 ( compensate for color reset chars )
+fn detect_access_control_length(metas: &[Meta]) -> usize {
+    let mut max: usize = 0;
+
+    for meta in metas {
+        let len = meta.access_control.method_char().len();
+        if len > max {
+            max = len;
         }
     }
 
-    UnicodeWidthStr::width(input) - nb_invisible_char
+    max
 }
 
-fn detect_user_length(metas: &[Meta]) -> usize {
+fn detect_user_length(metas: &[Meta], flags: &Flags) -> usize {
     let mut max: usize = 0;
 
     for meta in metas {
-        let user = meta.owner.user();
-        if user.len() > max {
-            max = user.len();
+        let user = meta.owner.user_string(flags);
+        if user.width() > max {
+            max = user.width();
         }
     }
 
     max
 }
 
-fn detect_group_length(metas: &[Meta]) -> usize {
+fn detect_group_length(metas: &[Meta], flags: &Flags) -> usize {
     let mut max: usize = 0;
 
     for meta in metas {
-        let group = meta.owner.group();
-        if group.len() > max {
-            max = group.len();
+        let group = meta.owner.group_string(flags);
+        if group.width() > max {
+            max = group.width();
         }
     }
 
     max
 }
 
-fn detect_date_length(metas: &[Meta], flags: &Flags) -> usize {
-    let mut max_value_length: usize = 0;
+fn detect_context_length(metas: &[Meta]) -> usize {
+    let mut max: usize = 0;
 
     for meta in metas {
-        if meta.date.date_string(&flags).len() > max_value_length {
-            max_value_length = meta.date.date_string(&flags).len();
+        let context = meta.security_context.context_string();
+        if context.len() > max {
+            max = context.len();
         }
     }
 
-    max_value_length
+    max
 }
 
-fn detect_size_lengths(metas: &[Meta], flags: &Flags) -> (usize, usize) {
-    let mut max_value_length: usize = 0;
-    let mut max_unit_size: usize = 0;
+fn detect_device_length(metas: &[Meta]) -> usize {
+    let mut max: usize = 0;
+
+    for meta in metas {
+        let device = meta.device.device_string();
+        if device.len() > max {
+            max = device.len();
+        }
+    }
+
+    max
+}
+
+fn detect_filesystem_length(metas: &[Meta]) -> usize {
+    metas
+        .iter()
+        .map(|meta| meta.filesystem.filesystem_string().chars().count())
+        .max()
+        .unwrap_or(0)
+}
+
+fn detect_flags_length(metas: &[Meta]) -> usize {
+    let mut max: usize = 0;
+
+    for meta in metas {
+        let flags = meta.file_flags.flags_string();
+        if flags.len() > max {
+            max = flags.len();
+        }
+    }
+
+    max
+}
+
+fn detect_custom_lengths(metas: &[Meta], flags: &Flags) -> Vec<usize> {
+    let mut lengths = vec![1; flags.custom_blocks.len()];
+
+    for meta in metas {
+        for (length, value) in lengths.iter_mut().zip(&meta.custom) {
+            *length = (*length).max(value.value_string().width());
+        }
+    }
+
+    lengths
+}
+
+fn detect_entry_count_length(metas: &[Meta]) -> usize {
+    let mut max: usize = 0;
 
     for meta in metas {
-        let unit = meta.size.get_unit(flags);
-        let value_len = meta.size.render_value(&unit).len();
-        let unit_len = Size::render_unit(&unit, &flags).len();
-        
-        if value_len > max_value_length {
-            max_value_length = value_len;
+        let count = meta.entry_count.value_string();
+        if count.len() > max {
+            max = count.len();
         }
+    }
+
+    max
+}
+
+fn detect_content_type_length(metas: &[Meta]) -> usize {
+    let mut max: usize = 0;
 
-        if unit_len > max_unit_size {
-            max_unit_size = unit_len;
+    for meta in metas {
+        let mime = meta.content_type.value_string();
+        if mime.len() > max {
+            max = mime.len();
         }
     }
 
-    (max_value_length, max_unit_size)
+    max
+}
+
+fn detect_checksum_length(metas: &[Meta]) -> usize {
+    let mut max: usize = 0;
+
+    for meta in metas {
+        let checksum = meta.checksum.value_string();
+        if checksum.len() > max {
+            max = checksum.len();
+        }
+    }
+
+    max
+}
+
+fn detect_date_length(metas: &[Meta], flags: &Flags) -> usize {
+    let mut max_value_length = flags.date_width.unwrap_or(0);
+
+    for meta in metas {
+        let length = meta.date.date_string(flags).width();
+        if length > max_value_length {
+            max_value_length = length;
+        }
+    }
+
+    max_value_length
+}
+
+fn detect_size_lengths(metas: &[Meta], flags: &Flags) -> (usize, usize, usize) {
+    let mut lengths = (0, 0, 0);
+
+    for meta in metas {
+        let (integer, fraction, unit) = size_lengths(meta, flags);
+        lengths.0 = lengths.0.max(integer);
+        lengths.1 = lengths.1.max(fraction);
+        lengths.2 = lengths.2.max(unit);
+    }
+
+    lengths
+}
+
+// With `--dir-size count`, the directories show their entry count in the
+// sub-columns of the sizes.
+fn shows_entry_count(meta: &Meta, flags: &Flags) -> bool {
+    match meta.file_type {
+        FileType::Directory { .. } => flags.dir_size == DirSizeFlag::Count,
+        _ => false,
+    }
+}
+
+fn size_lengths(meta: &Meta, flags: &Flags) -> (usize, usize, usize) {
+    if shows_entry_count(meta, flags) {
+        meta.entry_count.size_lengths(flags)
+    } else {
+        meta.size.lengths(flags)
+    }
+}
+
+fn render_size<'a>(
+    meta: &'a Meta,
+    colors: &Colors,
+    alignment: (usize, usize, usize),
+    flags: &Flags,
+) -> ColoredString<'a> {
+    if shows_entry_count(meta, flags) {
+        meta.entry_count.render_as_size(colors, alignment, flags)
+    } else {
+        meta.size.render(colors, alignment, flags)
+    }
 }
 
 fn detect_name_length(metas: &[Meta], icons: &Icons, flags: &Flags) -> usize {
     let mut max_value_length: usize = 0;
 
     for meta in metas {
-        let len = meta.name.name_string(&icons).len() + meta.indicator.len(&flags);
+        let len =
+            meta.name.name_string(&meta.path, icons, flags).width() + meta.indicator.len(flags);
         if len > max_value_length {
             max_value_length = len;
         }
@@ -433,13 +1349,19 @@ fn detect_name_length(metas: &[Meta], icons: &Icons, flags: &Flags) -> usize {
     max_value_length
 }
 
+/// The width of the arrow between a symlink and its target, with its spaces.
+fn symlink_arrow_width(flags: &Flags) -> usize {
+    flags.symbols.symlink_arrow.width() + 2
+}
+
 fn detect_name_with_symlink_length(metas: &[Meta], icons: &Icons, flags: &Flags) -> usize {
     let mut max_value_length: usize = 0;
 
     for meta in metas {
-        let mut len = meta.name.name_string(&icons).len() + meta.indicator.len(&flags);
-        if let Some(syml) = meta.symlink.symlink_string() {
-            len += syml.len() + 3  // 3 = ( arrow + 2 spaces) for symlink;
+        let mut len =
+            meta.name.name_string(&meta.path, icons, flags).width() + meta.indicator.len(flags);
+        if let Some(syml) = meta.symlink.symlink_string(flags) {
+            len += syml.width() + symlink_arrow_width(flags);
         }
         if len > max_value_length {
             max_value_length = len;
@@ -456,7 +1378,7 @@ mod tests {
     use crate::color::Colors;
     use crate::icon;
     use crate::icon::Icons;
-    use crate::meta::{FileType, Name};
+    use crate::meta::{Date, FileType, Name};
     use std::path::Path;
 
     #[test]
@@ -472,15 +1394,17 @@ mod tests {
         ] {
             let path = Path::new(s);
             let name = Name::new(
-                &path,
+                path,
                 FileType::File {
                     exec: false,
                     uid: false,
                 },
             );
             let output = name.render(
+                path,
                 &Colors::new(color::Theme::NoColor),
                 &Icons::new(icon::Theme::NoIcon),
+                &Flags::default(),
             );
 
             assert_eq!(get_visible_width(&output), *l);
@@ -502,7 +1426,7 @@ mod tests {
         ] {
             let path = Path::new(s);
             let name = Name::new(
-                &path,
+                path,
                 FileType::File {
                     exec: false,
                     uid: false,
@@ -510,8 +1434,10 @@ mod tests {
             );
             let output = name
                 .render(
+                    path,
                     &Colors::new(color::Theme::NoColor),
                     &Icons::new(icon::Theme::Fancy),
+                    &Flags::default(),
                 )
                 .to_string();
 
@@ -533,7 +1459,7 @@ mod tests {
         ] {
             let path = Path::new(s);
             let name = Name::new(
-                &path,
+                path,
                 FileType::File {
                     exec: false,
                     uid: false,
@@ -541,14 +1467,16 @@ mod tests {
             );
             let output = name
                 .render(
+                    path,
                     &Colors::new(color::Theme::NoLscolors),
                     &Icons::new(icon::Theme::NoIcon),
+                    &Flags::default(),
                 )
                 .to_string();
 
             // check if the color is present.
-            assert_eq!(true, output.starts_with("\u{1b}[38;5;"));
-            assert_eq!(true, output.ends_with("[0m"));
+            assert!(output.starts_with("\u{1b}[38;5;"));
+            assert!(output.ends_with("[0m"));
 
             assert_eq!(get_visible_width(&output), *l);
         }
@@ -568,7 +1496,7 @@ mod tests {
         ] {
             let path = Path::new(s);
             let name = Name::new(
-                &path,
+                path,
                 FileType::File {
                     exec: false,
                     uid: false,
@@ -576,16 +1504,172 @@ mod tests {
             );
             let output = name
                 .render(
+                    path,
                     &Colors::new(color::Theme::NoColor),
                     &Icons::new(icon::Theme::NoIcon),
+                    &Flags::default(),
                 )
                 .to_string();
 
             // check if the color is present.
-            assert_eq!(false, output.starts_with("\u{1b}[38;5;"));
-            assert_eq!(false, output.ends_with("[0m"));
+            assert!(!output.starts_with("\u{1b}[38;5;"));
+            assert!(!output.ends_with("[0m"));
 
             assert_eq!(get_visible_width(&output), *l);
         }
     }
+
+    #[test]
+    fn test_display_total_size() {
+        use crate::flags::{Flags, Layout, TotalFlag};
+        use crate::meta::Meta;
+        use std::fs;
+        use tempfile::tempdir;
+
+        let tmp_dir = tempdir().expect("failed to create temp dir");
+        let file_path = tmp_dir.path().join("file.txt");
+        fs::write(&file_path, vec![0u8; 2048]).expect("failed to write file");
+        let metas = vec![Meta::from_path(&file_path).unwrap()];
+        let colors = Colors::new(color::Theme::NoColor);
+
+        let mut flags = Flags {
+            total: TotalFlag::Size,
+            ..Flags::default()
+        };
+        assert_eq!("", total(&metas, &flags, &colors));
+
+        flags.layout = Layout::OneLine { long: true };
+        assert_eq!("total 2.0 KB\n", total(&metas, &flags, &colors));
+
+        flags.total = TotalFlag::None;
+        assert_eq!("", total(&metas, &flags, &colors));
+    }
+
+    #[test]
+    fn test_display_summary() {
+        use crate::flags::Flags;
+        use crate::meta::Meta;
+        use std::fs;
+        use tempfile::tempdir;
+
+        let tmp_dir = tempdir().expect("failed to create temp dir");
+        fs::write(tmp_dir.path().join("one"), vec![0u8; 2048]).expect("failed to write file");
+        fs::create_dir(tmp_dir.path().join("dir")).expect("failed to create dir");
+        let mut metas = Vec::new();
+        for name in &["one", "dir"] {
+            metas.push(Meta::from_path(&tmp_dir.path().join(name)).unwrap());
+        }
+        let colors = Colors::new(color::Theme::NoColor);
+
+        let mut flags = Flags::default();
+        assert_eq!("", summary(&metas, &flags, &colors, false));
+
+        flags.summary = true;
+        assert_eq!(
+            "1 directory, 1 file, 2.0 KB total\n",
+            summary(&metas, &flags, &colors, false)
+        );
+    }
+
+    #[test]
+    fn test_display_header_widens_the_columns() {
+        use crate::flags::{Block, Flags, Layout, PermissionFlag, SizeFlag};
+        use crate::meta::Meta;
+        use std::fs::File;
+        use tempfile::tempdir;
+
+        let tmp_dir = tempdir().expect("failed to create temp dir");
+        let file_path = tmp_dir.path().join("file.txt");
+        File::create(&file_path).expect("failed to create file");
+        let metas = vec![Meta::from_path(&file_path).unwrap()];
+        let colors = Colors::new(color::Theme::NoColor);
+        let icons = Icons::new(icon::Theme::NoIcon);
+
+        let flags = Flags {
+            layout: Layout::OneLine { long: true },
+            header: true,
+            permission: PermissionFlag::Octal,
+            size: SizeFlag::Bytes,
+            blocks: vec![Block::Permission, Block::Size, Block::Name],
+            ..Flags::default()
+        };
+
+        let output = one_line(metas, &flags, &colors, &icons);
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!("Permissions Size Name", lines[0]);
+        // The permissions themselves depend on the umask.
+        assert_eq!("    0 file.txt", &lines[1][11..]);
+    }
+
+    #[test]
+    fn test_porcelain_fields() {
+        use tempfile::tempdir;
+
+        let tmp_dir = tempdir().expect("failed to create temp dir");
+        let file_path = tmp_dir.path().join("file.txt");
+        std::fs::write(&file_path, "data").expect("failed to write file");
+        let mut meta = Meta::from_path(&file_path).expect("failed to get meta");
+        meta.date = Date::new(time::at_utc(time::Timespec::new(1_577_934_245, 0)));
+
+        let fields = porcelain_fields(&meta);
+        assert_eq!(103, fields.len());
+        assert!(fields.starts_with("file         0"));
+        assert!(fields.ends_with(&format!("{:>20} 2020-01-02T03:04:05Z ", 4)));
+    }
+
+    #[test]
+    fn test_escape_delimited_field() {
+        assert_eq!(b"plain".to_vec(), escape_field(b"plain", b','));
+        assert_eq!(b"\"a,b\"".to_vec(), escape_field(b"a,b", b','));
+        assert_eq!(
+            b"\"say \"\"hi\"\"\"".to_vec(),
+            escape_field(b"say \"hi\"", b',')
+        );
+        assert_eq!(b"a,b".to_vec(), escape_field(b"a,b", b'\t'));
+        assert_eq!(b"a\\tb\\nc".to_vec(), escape_field(b"a\tb\nc", b'\t'));
+    }
+
+    #[test]
+    fn test_display_get_visible_width_with_several_colors() {
+        let output = format!(
+            "{}{}",
+            ansi_term::Colour::Fixed(33).paint("日本語"),
+            ansi_term::Colour::Fixed(184).paint("/")
+        );
+
+        assert_eq!(7, get_visible_width(&output));
+    }
+
+    #[test]
+    fn test_display_long_aligns_wide_names() {
+        use crate::flags::{Block, Flags, Layout, SizeFlag};
+        use crate::meta::Meta;
+        use std::fs::File;
+        use tempfile::tempdir;
+
+        let tmp_dir = tempdir().expect("failed to create temp dir");
+        let mut metas = Vec::new();
+        for name in &["日本語", "abcdefgh"] {
+            let file_path = tmp_dir.path().join(name);
+            File::create(&file_path).expect("failed to create file");
+            metas.push(Meta::from_path(&file_path).unwrap());
+        }
+        let flags = Flags {
+            layout: Layout::OneLine { long: true },
+            size: SizeFlag::Bytes,
+            blocks: vec![Block::Name, Block::Size],
+            ..Flags::default()
+        };
+
+        let output = one_line(
+            metas,
+            &flags,
+            &Colors::new(color::Theme::NoColor),
+            &Icons::new(icon::Theme::Fancy),
+        );
+
+        let widths: Vec<usize> = output.lines().map(UnicodeWidthStr::width).collect();
+        assert_eq!(2, widths.len());
+        assert_eq!(widths[0], widths[1]);
+    }
 }
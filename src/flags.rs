@@ -1,5 +1,9 @@
+use crate::color::ElemStyle;
+use crate::config_file::{Config, DirectoryConfig};
+use crate::meta::{Date, FileType, Meta, Size};
 use clap::{ArgMatches, Error, ErrorKind};
-use globset::{Glob, GlobSet, GlobSetBuilder};
+use globset::{Glob, GlobMatcher, GlobSet, GlobSetBuilder};
+use std::time::Duration;
 
 #[derive(Clone, Debug)]
 pub struct Flags {
@@ -7,20 +11,102 @@ pub struct Flags {
     pub layout: Layout,
     pub display_indicators: bool,
     pub recursive: bool,
+    /// List the entries found by `recursive` in one list, with their paths.
+    pub flat_recursive: bool,
     pub sort_by: SortFlag,
+    /// The keys ordering the entries equal by `sort_by`, one after the other.
+    pub sort_then_by: Vec<SortFlag>,
     pub sort_order: SortOrder,
+    pub collation: CollationFlag,
     pub directory_order: DirOrderFlag,
+    pub dotfiles_order: DotFilesFlag,
     pub size: SizeFlag,
+    /// The unit of all the sizes, in place of the most readable one.
+    pub size_unit: Option<SizeUnitFlag>,
+    pub permission: PermissionFlag,
+    pub total: TotalFlag,
+    pub mime: MimeFlag,
+    pub executable: ExecFlag,
+    pub checksum: ChecksumFlag,
+    /// The files bigger than this many bytes are not hashed.
+    pub checksum_max_size: u64,
+    pub format: FormatFlag,
+    pub quoting: QuotingFlag,
     pub date: DateFlag,
+    /// The time displayed, and sorted and filtered by.
+    pub time: TimeFlag,
     pub color: WhenFlag,
+    pub paging: WhenFlag,
+    /// Refresh the listing on changes, until interrupted.
+    pub watch: bool,
     pub prefix_indent: bool,
     pub icon: WhenFlag,
     pub icon_theme: IconTheme,
+    /// When the fancy icons give way to the unicode ones.
+    pub icon_fallback: WhenFlag,
     pub recursion_depth: usize,
     pub blocks: Vec<Block>,
     pub no_symlink: bool,
-    pub total_size: bool,
+    pub symlink_target: SymlinkTargetFlag,
+    /// How many targets of the symlinks pointing to symlinks are followed,
+    /// when their chain is displayed.
+    pub follow_chain: Option<usize>,
+    /// What the size of the directories is.
+    pub dir_size: DirSizeFlag,
+    /// Don't read the directories residing on other filesystems.
+    pub one_file_system: bool,
+    /// List the content of the archives like the one of the directories.
+    pub archives: bool,
+    /// Describe the targets of the symlinks instead of the links themselves.
+    pub dereference: bool,
+    /// Follow the symlink operands only.
+    pub dereference_command_line: bool,
+    /// Don't report the entries which cannot be accessed.
+    pub quiet: bool,
     pub ignore_globs: GlobSet,
+    /// The patterns of `ignore_globs`, which the directory configs extend.
+    pub ignore_patterns: Vec<String>,
+    /// When not empty, only the entries matching these globs are displayed.
+    pub only_globs: GlobSet,
+    pub size_filters: Vec<SizeFilter>,
+    /// When not empty, only the entries of these file types are displayed.
+    pub type_filters: Vec<TypeFilter>,
+    pub date_filters: Vec<DateFilter>,
+    pub extended: bool,
+    /// The number of lines of the text files to display under them.
+    pub preview: Option<usize>,
+    pub header: bool,
+    /// Count the listed entries after the listing.
+    pub summary: bool,
+    pub full_path: PathFlag,
+    /// The width of the grid, in place of the terminal one.
+    pub width: Option<usize>,
+    /// How the names too wide for the grid are cut.
+    pub truncate_names: Option<TruncateFlag>,
+    /// The width the user and group names are truncated to.
+    pub owner_width: Option<usize>,
+    /// The locale naming the days and the months of the dates, the one of
+    /// `LC_TIME` when empty. Without it they are named in English.
+    pub date_locale: Option<String>,
+    /// The minimum width of the date column, so that it lines up from a
+    /// listing to the other.
+    pub date_width: Option<usize>,
+    /// End the entries with a NUL character instead of a newline.
+    pub zero: bool,
+    /// Cut the names to the width, for the preview panes of the file managers.
+    pub preview_pane: bool,
+    pub symbols: Symbols,
+    /// The blocks of the config file filled by commands, shown by the
+    /// `custom` block.
+    pub custom_blocks: Vec<CustomBlock>,
+    /// How many of their commands run at once.
+    pub custom_block_jobs: usize,
+    /// Read the `.lsd.yaml` files of the listed directories.
+    pub directory_config: bool,
+    /// The rules of `--audit` flagging the risky entries, none without it.
+    pub audit_rules: Vec<AuditRule>,
+    /// The rules of the config file changing how the entries show.
+    pub display_rules: Vec<DisplayRule>,
 }
 
 impl Flags {
@@ -28,47 +114,99 @@ impl Flags {
         let classic_mode = matches.is_present("classic");
         let color_inputs: Vec<&str> = matches.values_of("color").unwrap().collect();
         let icon_inputs: Vec<&str> = matches.values_of("icon").unwrap().collect();
+        let paging_inputs: Vec<&str> = matches.values_of("paging").unwrap().collect();
         let icon_theme_inputs: Vec<&str> = matches.values_of("icon-theme").unwrap().collect();
+        let icon_fallback_inputs: Vec<&str> = matches.values_of("icon-fallback").unwrap().collect();
         let size_inputs: Vec<&str> = matches.values_of("size").unwrap().collect();
+        let permission_inputs: Vec<&str> = matches.values_of("permission").unwrap().collect();
+        let total_inputs: Vec<&str> = matches.values_of("total").unwrap().collect();
+        let mime_inputs: Vec<&str> = matches.values_of("mime").unwrap().collect();
+        let executable_inputs: Vec<&str> = matches.values_of("executable").unwrap().collect();
+        let quoting_inputs: Vec<&str> = matches.values_of("quoting-style").unwrap().collect();
         let date_inputs: Vec<&str> = matches.values_of("date").unwrap().collect();
+        let time_inputs: Vec<&str> = matches.values_of("time").unwrap().collect();
         let dir_order_inputs: Vec<&str> = matches.values_of("group-dirs").unwrap().collect();
+        let symlink_target_inputs: Vec<&str> =
+            matches.values_of("symlink-target").unwrap().collect();
+        let dotfiles_inputs: Vec<&str> = matches.values_of("dotfiles").unwrap().collect();
         let blocks_inputs: Vec<&str> = matches.values_of("blocks").unwrap().collect();
         let ignore_globs_inputs: Vec<&str> = matches.values_of("ignore-glob").unwrap().collect();
 
-        let display = if matches.is_present("all") {
+        // Like `ls`, listing the directories themselves makes `--all`,
+        // `--recursive`, `--tree` and `--depth` moot instead of being an error.
+        let display = if matches.is_present("directory-only") {
+            Display::DisplayDirectoryItself
+        } else if matches.is_present("all") {
             Display::DisplayAll
         } else if matches.is_present("almost-all") {
             Display::DisplayAlmostAll
-        } else if matches.is_present("directory-only") {
-            Display::DisplayDirectoryItself
         } else {
             Display::DisplayOnlyVisible
         };
 
-        let sort_by = if matches.is_present("timesort") {
+        let mut sort_keys = match matches.values_of("sort").and_then(|mut s| s.next_back()) {
+            Some(keys) => {
+                let mut sort_keys = Vec::new();
+                for key in keys.split(',') {
+                    sort_keys.push(match SortFlag::from_key(key.trim()) {
+                        Some(key) => key,
+                        None => {
+                            return Err(Error::with_description(
+                                "The argument '--sort' requires keys of name, size, time, extension, inode, links and none",
+                                ErrorKind::ValueValidation,
+                            ));
+                        }
+                    });
+                }
+                sort_keys
+            }
+            None => Vec::new(),
+        };
+        let sort_then_by = if sort_keys.is_empty() {
+            Vec::new()
+        } else {
+            sort_keys.split_off(1)
+        };
+
+        let sort_by = if let Some(key) = sort_keys.first() {
+            *key
+        } else if matches.is_present("timesort") {
             SortFlag::Time
         } else if matches.is_present("sizesort") {
             SortFlag::Size
+        } else if matches.is_present("no-sort") {
+            SortFlag::None
         } else {
             SortFlag::Name
         };
+        let collation = if matches.is_present("locale-sort") {
+            CollationFlag::Locale
+        } else if matches.is_present("ignore-case") {
+            CollationFlag::IgnoreCase
+        } else {
+            CollationFlag::Default
+        };
         let sort_order = if matches.is_present("reverse") {
             SortOrder::Reverse
         } else {
             SortOrder::Default
         };
-        let layout = if matches.is_present("tree") {
+        let preview_pane = matches.is_present("preview-pane");
+        let layout = if preview_pane {
+            Layout::OneLine { long: false }
+        } else if matches.is_present("tree") {
             Layout::Tree {
                 long: matches.is_present("long"),
             }
         } else if matches.is_present("long") {
             Layout::OneLine { long: true }
-        } else if matches.is_present("oneline") {
+        } else if matches.is_present("oneline") || matches.is_present("zero") {
             Layout::OneLine { long: false }
         } else {
             Layout::Grid
         };
-        let recursive = matches.is_present("recursive");
+        let flat_recursive = matches.is_present("flat-recursive");
+        let recursive = matches.is_present("recursive") || flat_recursive;
         let recursion_depth = match matches.value_of("depth") {
             Some(str)
                 if recursive
@@ -93,27 +231,182 @@ impl Flags {
                     ErrorKind::MissingRequiredArgument,
                 ));
             }
-            None => usize::max_value(),
+            None => usize::MAX,
         };
-        let no_symlink = matches.is_present("no-symlink");
-        let total_size = matches.is_present("total-size");
-
-        let mut ignore_globs_builder = GlobSetBuilder::new();
-        for pattern in ignore_globs_inputs {
-            let glob = match Glob::new(pattern) {
-                Ok(g) => g,
-                Err(e) => {
-                    return Err(Error::with_description(&e.to_string(), ErrorKind::ValueValidation));
+        let mut blocks: Vec<Block> = blocks_inputs.into_iter().map(Block::from).collect();
+        if matches.is_present("no-user") {
+            blocks.retain(|block| *block != Block::User);
+        }
+        if matches.is_present("no-group") {
+            blocks.retain(|block| *block != Block::Group);
+        }
+
+        // Like `ls -Z`, the context is displayed right after the owner.
+        if matches.is_present("context") && !blocks.contains(&Block::Context) {
+            let position = blocks
+                .iter()
+                .rposition(|block| *block == Block::User || *block == Block::Group)
+                .map(|idx| idx + 1)
+                .or_else(|| blocks.iter().position(|block| *block == Block::Name))
+                .unwrap_or(blocks.len());
+            blocks.insert(position, Block::Context);
+        }
+
+        // Like the context, the checksum goes before the name.
+        let checksum = match matches
+            .values_of("checksum")
+            .and_then(|mut c| c.next_back())
+        {
+            Some(checksum) => {
+                if !blocks.contains(&Block::Checksum) {
+                    let position = blocks
+                        .iter()
+                        .position(|block| *block == Block::Name)
+                        .unwrap_or(blocks.len());
+                    blocks.insert(position, Block::Checksum);
                 }
-            };
-            ignore_globs_builder.add(glob);
+                ChecksumFlag::from(checksum)
+            }
+            None => ChecksumFlag::Sha256,
+        };
+        let max_size_inputs: Vec<&str> = matches.values_of("checksum-max-size").unwrap().collect();
+        let max_size = max_size_inputs[max_size_inputs.len() - 1];
+        let checksum_max_size = match Size::parse(max_size) {
+            Some(size) => size.get_bytes(),
+            None => {
+                return Err(Error::with_description(
+                    &format!(
+                        "invalid size '{}', expected something like 100M or 4K",
+                        max_size
+                    ),
+                    ErrorKind::ValueValidation,
+                ));
+            }
+        };
+
+        // Take only the last value
+        let permission = PermissionFlag::from(permission_inputs[permission_inputs.len() - 1]);
+        if permission == PermissionFlag::Disable {
+            blocks.retain(|block| *block != Block::Permission);
         }
 
-        let ignore_globs = match ignore_globs_builder.build() {
-            Ok(globs) => globs,
-            Err(e) => {
-                return Err(Error::with_description(&e.to_string(), ErrorKind::ValueValidation));
+        let no_symlink = matches.is_present("no-symlink");
+        let follow_chain = match matches
+            .values_of("chain-depth")
+            .and_then(|mut d| d.next_back())
+        {
+            Some(_) if !matches.is_present("follow-chain") => {
+                return Err(Error::with_description(
+                    "The argument '--chain-depth' requires '--follow-chain'",
+                    ErrorKind::MissingRequiredArgument,
+                ));
+            }
+            Some(depth) => match depth.parse::<usize>() {
+                Ok(depth) if depth > 0 => Some(depth),
+                _ => {
+                    return Err(Error::with_description(
+                        "The argument '--chain-depth' requires a valid positive number",
+                        ErrorKind::ValueValidation,
+                    ));
+                }
+            },
+            None if matches.is_present("follow-chain") => Some(DEFAULT_CHAIN_DEPTH),
+            None => None,
+        };
+        // `--total-size` is the short for `--dir-size total`.
+        let dir_size = if matches.is_present("total-size") {
+            DirSizeFlag::Total
+        } else {
+            let dir_size_inputs: Vec<&str> = matches.values_of("dir-size").unwrap().collect();
+            DirSizeFlag::from(dir_size_inputs[dir_size_inputs.len() - 1])
+        };
+
+        let ignore_patterns = ignore_globs_inputs.iter().map(|p| p.to_string()).collect();
+        let ignore_globs = build_glob_set(ignore_globs_inputs)?;
+        let only_globs = match matches.values_of("only") {
+            Some(patterns) => build_glob_set(patterns.collect())?,
+            None => GlobSet::empty(),
+        };
+        let size_filters = match matches.values_of("size-filter") {
+            Some(filters) => filters
+                .map(SizeFilter::parse)
+                .collect::<Result<Vec<SizeFilter>, Error>>()?,
+            None => Vec::new(),
+        };
+        let type_filters = match matches.values_of("type") {
+            Some(types) => types.map(TypeFilter::from).collect(),
+            None => Vec::new(),
+        };
+
+        let mut date_filters = Vec::new();
+        if let Some(dates) = matches.values_of("newer-than") {
+            for date in dates {
+                date_filters.push(DateFilter::NewerThan(parse_date_limit(date)?));
+            }
+        }
+        if let Some(dates) = matches.values_of("older-than") {
+            for date in dates {
+                date_filters.push(DateFilter::OlderThan(parse_date_limit(date)?));
             }
+        }
+
+        let preview = match matches.values_of("preview").and_then(|mut p| p.next_back()) {
+            Some(lines) => match lines.parse::<usize>() {
+                Ok(lines) => Some(lines),
+                Err(_) => {
+                    return Err(Error::with_description(
+                        "The argument '--preview' requires a valid positive number",
+                        ErrorKind::ValueValidation,
+                    ));
+                }
+            },
+            None => None,
+        };
+
+        let width = match matches.values_of("width").and_then(|mut w| w.next_back()) {
+            Some(columns) => match columns.parse::<usize>() {
+                Ok(columns) if columns > 0 => Some(columns),
+                _ => {
+                    return Err(Error::with_description(
+                        "The argument '--width' requires a valid positive number",
+                        ErrorKind::ValueValidation,
+                    ));
+                }
+            },
+            None => None,
+        };
+
+        let truncate_names = match matches
+            .values_of("truncate-names")
+            .and_then(|mut t| t.next_back())
+        {
+            Some("auto") => Some(TruncateFlag::Auto),
+            Some(columns) => match columns.parse::<usize>() {
+                Ok(columns) if columns > 0 => Some(TruncateFlag::Width(columns)),
+                _ => {
+                    return Err(Error::with_description(
+                        "The argument '--truncate-names' requires auto or a valid positive number",
+                        ErrorKind::ValueValidation,
+                    ));
+                }
+            },
+            None => None,
+        };
+
+        let owner_width = match matches
+            .values_of("owner-width")
+            .and_then(|mut w| w.next_back())
+        {
+            Some(columns) => match columns.parse::<usize>() {
+                Ok(columns) if columns > 0 => Some(columns),
+                _ => {
+                    return Err(Error::with_description(
+                        "The argument '--owner-width' requires a valid positive number",
+                        ErrorKind::ValueValidation,
+                    ));
+                }
+            },
+            None => None,
         };
 
         Ok(Self {
@@ -121,39 +414,259 @@ impl Flags {
             layout,
             display_indicators: matches.is_present("indicators"),
             recursive,
+            flat_recursive,
             recursion_depth,
             sort_by,
+            sort_then_by,
             sort_order,
+            collation,
             size: SizeFlag::from(size_inputs[size_inputs.len() - 1]),
-            blocks: blocks_inputs.into_iter().map(|b| Block::from(b)).collect(),
+            size_unit: matches
+                .values_of("size-unit")
+                .and_then(|mut units| units.next_back())
+                .map(SizeUnitFlag::from),
+            permission,
+            total: TotalFlag::from(total_inputs[total_inputs.len() - 1]),
+            mime: MimeFlag::from(mime_inputs[mime_inputs.len() - 1]),
+            executable: ExecFlag::from(executable_inputs[executable_inputs.len() - 1]),
+            checksum,
+            checksum_max_size,
+            quoting: QuotingFlag::from(quoting_inputs[quoting_inputs.len() - 1]),
+            format: match matches.values_of("format").and_then(|mut f| f.next_back()) {
+                Some(format) => FormatFlag::from(format),
+                None => FormatFlag::Default,
+            },
+            blocks,
             ignore_globs,
+            ignore_patterns,
+            only_globs,
+            size_filters,
+            type_filters,
+            date_filters,
             // Take only the last value
             date: if classic_mode {
                 DateFlag::Date
             } else {
                 DateFlag::from(date_inputs[date_inputs.len() - 1])
             },
-            color: if classic_mode {
+            time: TimeFlag::from(time_inputs[time_inputs.len() - 1]),
+            color: if classic_mode || matches.is_present("no-color") {
                 WhenFlag::Never
+            } else if preview_pane && matches.occurrences_of("color") == 0 {
+                // The previewers show the colors, though they read a pipe.
+                WhenFlag::Always
             } else {
                 WhenFlag::from(color_inputs[color_inputs.len() - 1])
             },
+            paging: WhenFlag::from(paging_inputs[paging_inputs.len() - 1]),
+            watch: matches.is_present("watch"),
             prefix_indent: matches.is_present("prefix-indent"),
-            icon: if classic_mode {
+            icon: if classic_mode || matches.is_present("ascii") {
                 WhenFlag::Never
+            } else if preview_pane && matches.occurrences_of("icon") == 0 {
+                WhenFlag::Always
             } else {
                 WhenFlag::from(icon_inputs[icon_inputs.len() - 1])
             },
             icon_theme: IconTheme::from(icon_theme_inputs[icon_theme_inputs.len() - 1]),
+            icon_fallback: WhenFlag::from(icon_fallback_inputs[icon_fallback_inputs.len() - 1]),
             directory_order: if classic_mode {
                 DirOrderFlag::None
             } else {
                 DirOrderFlag::from(dir_order_inputs[dir_order_inputs.len() - 1])
             },
+            dotfiles_order: DotFilesFlag::from(dotfiles_inputs[dotfiles_inputs.len() - 1]),
             no_symlink,
-            total_size,
+            symlink_target: SymlinkTargetFlag::from(
+                symlink_target_inputs[symlink_target_inputs.len() - 1],
+            ),
+            follow_chain,
+            dir_size,
+            one_file_system: matches.is_present("one-file-system"),
+            archives: matches.is_present("archives"),
+            dereference: matches.is_present("dereference"),
+            dereference_command_line: matches.is_present("dereference-command-line"),
+            quiet: matches.is_present("quiet"),
+            extended: matches.is_present("extended"),
+            preview,
+            header: matches.is_present("header"),
+            summary: matches.is_present("summary"),
+            width,
+            truncate_names,
+            owner_width,
+            date_locale: None,
+            date_width: None,
+            full_path: if matches.is_present("absolute") {
+                PathFlag::Absolute
+            } else if matches.is_present("full-path") || flat_recursive {
+                PathFlag::Relative
+            } else {
+                PathFlag::None
+            },
+            zero: matches.is_present("zero"),
+            preview_pane,
+            symbols: if matches.is_present("ascii") {
+                Symbols::ascii()
+            } else {
+                Symbols::default()
+            },
+            custom_blocks: Vec::new(),
+            custom_block_jobs: DEFAULT_CUSTOM_BLOCK_JOBS,
+            directory_config: false,
+            audit_rules: if matches.is_present("audit") {
+                AuditRule::all()
+            } else {
+                Vec::new()
+            },
+            display_rules: Vec::new(),
         })
     }
+
+    /// Fill the flags left to their default on the command line and in the
+    /// environment from the configuration file.
+    pub fn apply_config(&mut self, matches: &ArgMatches, config: &Config) {
+        if let Some(date) = config.date {
+            if matches.occurrences_of("date") == 0 && !matches.is_present("classic") {
+                self.date = date;
+            }
+        }
+        if config.date_locale.is_some() {
+            self.date_locale = config.date_locale.clone();
+        }
+        if config.date_width.is_some() {
+            self.date_width = config.date_width;
+        }
+        if let Some(dir_size) = config.dir_size {
+            if matches.occurrences_of("dir-size") == 0 && !matches.is_present("total-size") {
+                self.dir_size = dir_size;
+            }
+        }
+        if let Some(owner_width) = config.owner_width {
+            if matches.occurrences_of("owner-width") == 0 {
+                self.owner_width = Some(owner_width);
+            }
+        }
+        let recursing = match self.layout {
+            Layout::Tree { .. } => true,
+            _ => self.recursive,
+        };
+        // The classic mode lists everything, like `ls -R`.
+        if recursing && !matches.is_present("no-default-ignore") && !matches.is_present("classic") {
+            let patterns = match &config.default_ignores {
+                Some(patterns) => patterns.clone(),
+                None => DEFAULT_IGNORES.iter().map(|p| p.to_string()).collect(),
+            };
+            self.ignore_more(patterns);
+        }
+        if let Some(fallback) = config.icons.fallback {
+            if matches.occurrences_of("icon-fallback") == 0 {
+                self.icon_fallback = fallback;
+            }
+        }
+        if !matches.is_present("ascii") {
+            self.symbols = config.theme.symbols.clone();
+        }
+
+        if let Some(rules) = &config.theme.audit_rules {
+            if matches.is_present("audit") {
+                self.audit_rules = rules.clone();
+            }
+        }
+
+        self.directory_config = config.directory_config;
+        self.custom_blocks = config.custom_blocks.clone();
+        self.display_rules = config.rules.clone();
+        if let Some(jobs) = config.custom_block_jobs {
+            self.custom_block_jobs = jobs;
+        }
+        // The custom blocks show before the name, unless placed by `--blocks`.
+        if self.custom_blocks.is_empty() {
+            self.blocks.retain(|block| *block != Block::Custom);
+        } else if matches.occurrences_of("blocks") == 0 {
+            let position = self
+                .blocks
+                .iter()
+                .position(|block| *block == Block::Name)
+                .unwrap_or(self.blocks.len());
+            self.blocks.insert(position, Block::Custom);
+        }
+    }
+
+    /// The flags of the listing of a directory, with the overrides of its
+    /// `.lsd.yaml` file.
+    pub fn with_directory_config(&self, config: &DirectoryConfig) -> Self {
+        let mut flags = self.clone();
+
+        if let Some(sort) = config.sort {
+            flags.sort_by = sort;
+            flags.sort_then_by.clear();
+        }
+        if let Some(reverse) = config.reverse {
+            flags.sort_order = if reverse {
+                SortOrder::Reverse
+            } else {
+                SortOrder::Default
+            };
+        }
+        if let Some(blocks) = &config.blocks {
+            flags.blocks = blocks.clone();
+        }
+
+        if !config.ignore_globs.is_empty() {
+            flags.ignore_more(config.ignore_globs.clone());
+        }
+
+        flags
+    }
+
+    /// Ignore the entries matching `patterns` on top of the ones ignored
+    /// already.
+    fn ignore_more(&mut self, patterns: Vec<String>) {
+        self.ignore_patterns.extend(patterns);
+        // The patterns were checked when the config was read.
+        let patterns = self.ignore_patterns.iter().map(String::as_str).collect();
+        if let Ok(ignore_globs) = build_glob_set(patterns) {
+            self.ignore_globs = ignore_globs;
+        }
+    }
+
+    /// Whether the size sorted tree of the total sizes is asked for, like with
+    /// `du`. It then shows the share of each entry in its parent size.
+    pub fn is_disk_usage(&self) -> bool {
+        if let Layout::Tree { .. } = self.layout {
+            self.dir_size == DirSizeFlag::Total && self.sort_by == SortFlag::Size
+        } else {
+            false
+        }
+    }
+}
+
+/// The entries `--tree` and `--recursive` ignore, unless the config file
+/// names others or `--no-default-ignore` is given.
+pub const DEFAULT_IGNORES: [&str; 3] = [".git", "node_modules", "target"];
+
+fn build_glob_set(patterns: Vec<&str>) -> Result<GlobSet, Error> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob = match Glob::new(pattern) {
+            Ok(g) => g,
+            Err(e) => {
+                return Err(Error::with_description(
+                    &e.to_string(),
+                    ErrorKind::ValueValidation,
+                ));
+            }
+        };
+        builder.add(glob);
+    }
+
+    match builder.build() {
+        Ok(globs) => Ok(globs),
+        Err(e) => Err(Error::with_description(
+            &e.to_string(),
+            ErrorKind::ValueValidation,
+        )),
+    }
 }
 
 impl Default for Flags {
@@ -163,16 +676,33 @@ impl Default for Flags {
             layout: Layout::Grid,
             display_indicators: false,
             recursive: false,
-            recursion_depth: usize::max_value(),
+            flat_recursive: false,
+            recursion_depth: usize::MAX,
             sort_by: SortFlag::Name,
+            sort_then_by: Vec::new(),
             sort_order: SortOrder::Default,
+            collation: CollationFlag::Default,
             directory_order: DirOrderFlag::None,
+            dotfiles_order: DotFilesFlag::Mixed,
             size: SizeFlag::Default,
+            size_unit: None,
+            permission: PermissionFlag::Rwx,
+            total: TotalFlag::None,
+            mime: MimeFlag::Extension,
+            executable: ExecFlag::Access,
+            checksum: ChecksumFlag::Sha256,
+            checksum_max_size: 100 * 1024 * 1024,
+            format: FormatFlag::Default,
+            quoting: QuotingFlag::Literal,
             date: DateFlag::Date,
+            time: TimeFlag::Modified,
             color: WhenFlag::Auto,
+            paging: WhenFlag::Never,
+            watch: false,
             prefix_indent: false,
             icon: WhenFlag::Auto,
             icon_theme: IconTheme::Fancy,
+            icon_fallback: WhenFlag::Auto,
             blocks: vec![
                 Block::Permission,
                 Block::User,
@@ -182,19 +712,65 @@ impl Default for Flags {
                 Block::Name,
             ],
             no_symlink: false,
-            total_size: false,
+            symlink_target: SymlinkTargetFlag::Stored,
+            follow_chain: None,
+            dir_size: DirSizeFlag::Inode,
+            one_file_system: false,
+            archives: false,
+            dereference: false,
+            dereference_command_line: false,
+            quiet: false,
             ignore_globs: GlobSet::empty(),
+            ignore_patterns: Vec::new(),
+            only_globs: GlobSet::empty(),
+            size_filters: Vec::new(),
+            type_filters: Vec::new(),
+            date_filters: Vec::new(),
+            extended: false,
+            preview: None,
+            header: false,
+            summary: false,
+            full_path: PathFlag::None,
+            width: None,
+            truncate_names: None,
+            owner_width: None,
+            date_locale: None,
+            date_width: None,
+            zero: false,
+            preview_pane: false,
+            symbols: Symbols::default(),
+            custom_blocks: Vec::new(),
+            custom_block_jobs: DEFAULT_CUSTOM_BLOCK_JOBS,
+            directory_config: false,
+            audit_rules: Vec::new(),
+            display_rules: Vec::new(),
         }
     }
 }
 
+// The commands mostly wait on the disk or on other processes, like `git`.
+const DEFAULT_CUSTOM_BLOCK_JOBS: usize = 4;
+
+// Enough for the links of `alternatives`, far below the 40 of the kernel.
+const DEFAULT_CHAIN_DEPTH: usize = 8;
+
 #[derive(Clone, Debug, Copy, PartialEq, Eq)]
 pub enum Block {
     // FileType,
     Permission,
     User,
     Group,
+    Context,
+    Device,
+    /// The type and the device of the filesystem.
+    Filesystem,
+    Flags,
     Size,
+    Count,
+    Mime,
+    Checksum,
+    /// The blocks of the config file, in their order.
+    Custom,
     Date,
     Name,
 }
@@ -205,7 +781,15 @@ impl<'a> From<&'a str> for Block {
             "permission" => Block::Permission,
             "user" => Block::User,
             "group" => Block::Group,
+            "context" => Block::Context,
+            "device" => Block::Device,
+            "filesystem" => Block::Filesystem,
+            "flags" => Block::Flags,
             "size" => Block::Size,
+            "count" => Block::Count,
+            "mime" => Block::Mime,
+            "checksum" => Block::Checksum,
+            "custom" => Block::Custom,
             "date" => Block::Date,
             "name" => Block::Name,
             _ => panic!("invalid \"time\" flag: {}", block),
@@ -240,117 +824,1446 @@ impl<'a> From<&'a str> for SizeFlag {
 }
 
 #[derive(Clone, Debug, Copy, PartialEq, Eq)]
-pub enum DateFlag {
-    Date,
-    Relative,
+pub enum SizeUnitFlag {
+    Byte,
+    Kilo,
+    Mega,
+    Giga,
 }
 
-impl<'a> From<&'a str> for DateFlag {
-    fn from(time: &'a str) -> Self {
-        match time {
-            "date" => DateFlag::Date,
-            "relative" => DateFlag::Relative,
-            _ => panic!("invalid \"time\" flag: {}", time),
+impl<'a> From<&'a str> for SizeUnitFlag {
+    fn from(unit: &'a str) -> Self {
+        match unit {
+            "B" => SizeUnitFlag::Byte,
+            "K" => SizeUnitFlag::Kilo,
+            "M" => SizeUnitFlag::Mega,
+            "G" => SizeUnitFlag::Giga,
+            _ => panic!("invalid \"size-unit\" flag: {}", unit),
         }
     }
 }
 
 #[derive(Clone, Debug, Copy, PartialEq, Eq)]
-pub enum WhenFlag {
-    Always,
-    Auto,
-    Never,
+pub enum PermissionFlag {
+    Rwx,
+    Octal,
+    Disable,
 }
-impl<'a> From<&'a str> for WhenFlag {
-    fn from(when: &'a str) -> Self {
-        match when {
-            "always" => WhenFlag::Always,
-            "auto" => WhenFlag::Auto,
-            "never" => WhenFlag::Never,
-            _ => panic!("invalid \"when\" flag: {}", when),
+
+impl<'a> From<&'a str> for PermissionFlag {
+    fn from(permission: &'a str) -> Self {
+        match permission {
+            "rwx" => PermissionFlag::Rwx,
+            "octal" => PermissionFlag::Octal,
+            "disable" => PermissionFlag::Disable,
+            _ => panic!("invalid \"permission\" flag: {}", permission),
         }
     }
 }
 
+/// How the names are compared when sorting.
 #[derive(Clone, Debug, Copy, PartialEq, Eq)]
-pub enum SortFlag {
-    Name,
-    Time,
+pub enum CollationFlag {
+    /// Regardless of the case, the names differing only by it being left in
+    /// the order of the directory.
+    Default,
+    /// Regardless of the case, the capitalized names first among the ones
+    /// differing only by it.
+    IgnoreCase,
+    /// Following the `LC_COLLATE` rules of the locale.
+    Locale,
+}
+
+#[derive(Clone, Debug, Copy, PartialEq, Eq)]
+pub enum TotalFlag {
+    None,
+    /// The disk usage in kilobytes, like `ls -l`.
+    Blocks,
+    /// The cumulative size of the entries.
     Size,
 }
 
+impl<'a> From<&'a str> for TotalFlag {
+    fn from(total: &'a str) -> Self {
+        match total {
+            "none" => TotalFlag::None,
+            "blocks" => TotalFlag::Blocks,
+            "size" => TotalFlag::Size,
+            _ => panic!("invalid \"total\" flag: {}", total),
+        }
+    }
+}
+
+/// What the size block shows for the directories.
 #[derive(Clone, Debug, Copy, PartialEq, Eq)]
-pub enum SortOrder {
-    Default,
-    Reverse,
+pub enum DirSizeFlag {
+    /// The size of the directory itself, like `ls`.
+    Inode,
+    /// The number of entries inside, like `42 items`.
+    Count,
+    /// The total size of the content, like `du`.
+    Total,
+}
+
+impl<'a> From<&'a str> for DirSizeFlag {
+    fn from(dir_size: &'a str) -> Self {
+        match dir_size {
+            "inode" => DirSizeFlag::Inode,
+            "count" => DirSizeFlag::Count,
+            "total" => DirSizeFlag::Total,
+            _ => panic!("invalid \"dir-size\" flag: {}", dir_size),
+        }
+    }
 }
 
+/// How the content types of the `mime` block are inferred.
 #[derive(Clone, Debug, Copy, PartialEq, Eq)]
-pub enum DirOrderFlag {
+pub enum MimeFlag {
+    /// From the file extensions only.
+    Extension,
+    /// From the first bytes of the files, then from the extensions.
+    Deep,
+}
+
+impl<'a> From<&'a str> for MimeFlag {
+    fn from(mime: &'a str) -> Self {
+        match mime {
+            "extension" => MimeFlag::Extension,
+            "deep" => MimeFlag::Deep,
+            _ => panic!("invalid \"mime\" flag: {}", mime),
+        }
+    }
+}
+
+/// What makes a file colored as executable.
+#[derive(Clone, Debug, Copy, PartialEq, Eq)]
+pub enum ExecFlag {
+    /// The current user may run it, according to the owners and the ACL.
+    Access,
+    /// Any of its execute bits is set.
+    Mode,
+}
+
+impl<'a> From<&'a str> for ExecFlag {
+    fn from(executable: &'a str) -> Self {
+        match executable {
+            "access" => ExecFlag::Access,
+            "mode" => ExecFlag::Mode,
+            _ => panic!("invalid \"executable\" flag: {}", executable),
+        }
+    }
+}
+
+/// How much of the path of the entries is displayed in place of their names.
+#[derive(Clone, Debug, Copy, PartialEq, Eq)]
+pub enum PathFlag {
+    /// The names only.
     None,
-    First,
-    Last,
+    /// The paths from the listed operands.
+    Relative,
+    /// The absolute paths.
+    Absolute,
 }
 
-impl<'a> From<&'a str> for DirOrderFlag {
-    fn from(when: &'a str) -> Self {
-        match when {
-            "none" => DirOrderFlag::None,
-            "first" => DirOrderFlag::First,
-            "last" => DirOrderFlag::Last,
-            _ => panic!("invalid \"when\" flag: {}", when),
+/// The hash algorithm of the `checksum` block.
+#[derive(Clone, Debug, Copy, PartialEq, Eq)]
+pub enum ChecksumFlag {
+    Md5,
+    Sha256,
+}
+
+impl<'a> From<&'a str> for ChecksumFlag {
+    fn from(checksum: &'a str) -> Self {
+        match checksum {
+            "md5" => ChecksumFlag::Md5,
+            "sha256" => ChecksumFlag::Sha256,
+            _ => panic!("invalid \"checksum\" flag: {}", checksum),
         }
     }
 }
 
 #[derive(Clone, Debug, Copy, PartialEq, Eq)]
-pub enum IconTheme {
-    Unicode,
-    Fancy,
+pub enum FormatFlag {
+    /// The listing for humans, following the layout.
+    Default,
+    Csv,
+    Tsv,
+    /// The fields of the long view in fixed widths, which never change.
+    Porcelain,
 }
 
-impl<'a> From<&'a str> for IconTheme {
-    fn from(theme: &'a str) -> Self {
-        match theme {
-            "fancy" => IconTheme::Fancy,
-            "unicode" => IconTheme::Unicode,
-            _ => panic!("invalid \"icon-theme\" flag: {}", theme),
+impl<'a> From<&'a str> for FormatFlag {
+    fn from(format: &'a str) -> Self {
+        match format {
+            "csv" => FormatFlag::Csv,
+            "tsv" => FormatFlag::Tsv,
+            "porcelain" => FormatFlag::Porcelain,
+            _ => panic!("invalid \"format\" flag: {}", format),
         }
     }
 }
 
 #[derive(Clone, Debug, Copy, PartialEq, Eq)]
-pub enum Layout {
-    Grid,
-    Tree { long: bool },
-    OneLine { long: bool },
+pub enum QuotingFlag {
+    /// The names as they are.
+    Literal,
+    /// The names quoted so that they can be pasted in a shell.
+    Shell,
+    /// The names as C string literals.
+    C,
 }
 
-#[cfg(test)]
-mod test {
-    use super::Flags;
-    use crate::app;
-    use clap::ErrorKind;
+impl<'a> From<&'a str> for QuotingFlag {
+    fn from(quoting: &'a str) -> Self {
+        match quoting {
+            "literal" => QuotingFlag::Literal,
+            "shell" => QuotingFlag::Shell,
+            "c" => QuotingFlag::C,
+            _ => panic!("invalid \"quoting-style\" flag: {}", quoting),
+        }
+    }
+}
 
-    #[test]
-    fn test_validate_depth_value() {
-        let matches = app::build()
-            .get_matches_from_safe(vec!["lsd", "--tree", "--depth", "xx"])
-            .unwrap();
-        let res = Flags::from_matches(&matches);
+/// A threshold on the size of the entries, like `+10M` or `-4K`.
+#[derive(Clone, Debug, Copy, PartialEq, Eq)]
+pub enum SizeFilter {
+    Above(u64),
+    Below(u64),
+}
 
-        assert!(res.is_err());
-        assert_eq!(res.unwrap_err().kind, ErrorKind::ValueValidation);
+impl SizeFilter {
+    pub fn parse(filter: &str) -> Result<Self, Error> {
+        let invalid = || {
+            Error::with_description(
+                &format!(
+                    "invalid size filter '{}', expected something like +10M or -4K",
+                    filter
+                ),
+                ErrorKind::ValueValidation,
+            )
+        };
+
+        let (is_above, threshold) = match filter.chars().next() {
+            Some('+') => (true, &filter[1..]),
+            Some('-') => (false, &filter[1..]),
+            _ => return Err(invalid()),
+        };
+
+        let bytes = Size::parse(threshold).ok_or_else(invalid)?.get_bytes();
+
+        if is_above {
+            Ok(SizeFilter::Above(bytes))
+        } else {
+            Ok(SizeFilter::Below(bytes))
+        }
     }
 
-    #[test]
-    fn test_useless_depth() {
-        let matches = app::build()
-            .get_matches_from_safe(vec!["lsd", "--depth", "10"])
-            .unwrap();
-        let res = Flags::from_matches(&matches);
+    pub fn matches(&self, bytes: u64) -> bool {
+        match self {
+            SizeFilter::Above(threshold) => bytes > *threshold,
+            SizeFilter::Below(threshold) => bytes < *threshold,
+        }
+    }
+}
 
-        assert!(res.is_err());
-        assert_eq!(res.unwrap_err().kind, ErrorKind::MissingRequiredArgument);
+/// A file type of the entries to display, as named by `find -type`.
+#[derive(Clone, Debug, Copy, PartialEq, Eq)]
+pub enum TypeFilter {
+    File,
+    Directory,
+    SymLink,
+    Socket,
+    Pipe,
+    BlockDevice,
+    CharDevice,
+}
+
+impl TypeFilter {
+    pub fn matches(self, file_type: FileType) -> bool {
+        let filter = match file_type {
+            FileType::File { .. } => TypeFilter::File,
+            FileType::Directory { .. } => TypeFilter::Directory,
+            FileType::SymLink { .. } => TypeFilter::SymLink,
+            FileType::Socket => TypeFilter::Socket,
+            FileType::Pipe => TypeFilter::Pipe,
+            FileType::BlockDevice => TypeFilter::BlockDevice,
+            FileType::CharDevice => TypeFilter::CharDevice,
+            FileType::Special => return false,
+        };
+
+        self == filter
+    }
+}
+
+impl<'a> From<&'a str> for TypeFilter {
+    fn from(file_type: &'a str) -> Self {
+        match file_type {
+            "f" => TypeFilter::File,
+            "d" => TypeFilter::Directory,
+            "l" => TypeFilter::SymLink,
+            "socket" => TypeFilter::Socket,
+            "pipe" => TypeFilter::Pipe,
+            "block" => TypeFilter::BlockDevice,
+            "char" => TypeFilter::CharDevice,
+            _ => panic!("invalid \"type\" flag: {}", file_type),
+        }
+    }
+}
+
+/// A limit on the modification date of the entries.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DateFilter {
+    NewerThan(Date),
+    OlderThan(Date),
+}
+
+impl DateFilter {
+    pub fn matches(&self, date: &Date) -> bool {
+        // The entries without the date can't be told to be older or newer.
+        if date.epoch().is_none() {
+            return false;
+        }
+
+        match self {
+            DateFilter::NewerThan(limit) => date > limit,
+            DateFilter::OlderThan(limit) => date < limit,
+        }
+    }
+}
+
+/// Parse either a duration before now, like `30m` or `2d`, or an absolute
+/// local date, like `2019-10-23` or `2019-10-23 08:46`.
+fn parse_date_limit(limit: &str) -> Result<Date, Error> {
+    let now = time::now();
+
+    for format in &["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M", "%Y-%m-%d"] {
+        if let Ok(mut date) = time::strptime(limit, format) {
+            date.tm_utcoff = now.tm_utcoff;
+            return Ok(Date::new(date));
+        }
+    }
+
+    let invalid = || {
+        Error::with_description(
+            &format!(
+                "invalid date '{}', expected a duration like 2d or a date like 2019-10-23",
+                limit
+            ),
+            ErrorKind::ValueValidation,
+        )
+    };
+
+    let duration = Date::parse_duration(limit).ok_or_else(invalid)?;
+
+    Ok(Date::new(now - duration))
+}
+
+/// The width the names are cut to in the grid.
+#[derive(Clone, Debug, Copy, PartialEq, Eq)]
+pub enum TruncateFlag {
+    Width(usize),
+    /// A third of the width of the grid, so that three columns always fit.
+    Auto,
+}
+
+impl TruncateFlag {
+    /// The width of the names in a grid `grid_width` columns wide, if known.
+    pub fn name_width(self, grid_width: Option<usize>) -> Option<usize> {
+        match self {
+            TruncateFlag::Width(width) => Some(width),
+            TruncateFlag::Auto => grid_width.map(|width| (width / 3).max(1)),
+        }
+    }
+}
+
+/// How the symlink targets are displayed.
+#[derive(Clone, Debug, Copy, PartialEq, Eq)]
+pub enum SymlinkTargetFlag {
+    /// As read from the link, usually relative to its directory.
+    Stored,
+    Absolute,
+    /// Relative to the current directory.
+    Relative,
+}
+
+impl<'a> From<&'a str> for SymlinkTargetFlag {
+    fn from(symlink_target: &'a str) -> Self {
+        match symlink_target {
+            "stored" => SymlinkTargetFlag::Stored,
+            "absolute" => SymlinkTargetFlag::Absolute,
+            "relative" => SymlinkTargetFlag::Relative,
+            _ => panic!("invalid \"symlink-target\" flag: {}", symlink_target),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Copy, PartialEq, Eq)]
+pub enum DateFlag {
+    Date,
+    Relative,
+    /// The date in UTC rather than in the local timezone.
+    Utc,
+}
+
+impl<'a> From<&'a str> for DateFlag {
+    fn from(time: &'a str) -> Self {
+        match time {
+            "date" => DateFlag::Date,
+            "relative" => DateFlag::Relative,
+            "utc" => DateFlag::Utc,
+            _ => panic!("invalid \"time\" flag: {}", time),
+        }
+    }
+}
+
+/// Which of the times of the entries is their date.
+#[derive(Clone, Debug, Copy, PartialEq, Eq)]
+pub enum TimeFlag {
+    Modified,
+    /// The birth time, missing where the system doesn't record it.
+    Created,
+}
+
+impl<'a> From<&'a str> for TimeFlag {
+    fn from(time: &'a str) -> Self {
+        match time {
+            "modified" => TimeFlag::Modified,
+            "created" => TimeFlag::Created,
+            _ => panic!("invalid \"time\" flag: {}", time),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Copy, PartialEq, Eq)]
+pub enum WhenFlag {
+    Always,
+    Auto,
+    Never,
+}
+impl<'a> From<&'a str> for WhenFlag {
+    fn from(when: &'a str) -> Self {
+        match when {
+            "always" => WhenFlag::Always,
+            "auto" => WhenFlag::Auto,
+            "never" => WhenFlag::Never,
+            _ => panic!("invalid \"when\" flag: {}", when),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Copy, PartialEq, Eq)]
+pub enum SortFlag {
+    Name,
+    Time,
+    Size,
+    Extension,
+    Inode,
+    /// The number of hard links.
+    Links,
+    /// The order in which the entries are read.
+    None,
+}
+
+impl SortFlag {
+    /// The key named `key` in `--sort` and in the config files.
+    pub fn from_key(key: &str) -> Option<Self> {
+        match key {
+            "name" => Some(SortFlag::Name),
+            "time" => Some(SortFlag::Time),
+            "size" => Some(SortFlag::Size),
+            "extension" => Some(SortFlag::Extension),
+            "inode" => Some(SortFlag::Inode),
+            "links" => Some(SortFlag::Links),
+            "none" => Some(SortFlag::None),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Copy, PartialEq, Eq)]
+pub enum SortOrder {
+    Default,
+    Reverse,
+}
+
+#[derive(Clone, Debug, Copy, PartialEq, Eq)]
+pub enum DirOrderFlag {
+    None,
+    First,
+    Last,
+}
+
+impl<'a> From<&'a str> for DirOrderFlag {
+    fn from(when: &'a str) -> Self {
+        match when {
+            "none" => DirOrderFlag::None,
+            "first" => DirOrderFlag::First,
+            "last" => DirOrderFlag::Last,
+            _ => panic!("invalid \"when\" flag: {}", when),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Copy, PartialEq, Eq)]
+pub enum DotFilesFlag {
+    First,
+    Last,
+    /// Sorted with the other entries.
+    Mixed,
+}
+
+impl<'a> From<&'a str> for DotFilesFlag {
+    fn from(dotfiles: &'a str) -> Self {
+        match dotfiles {
+            "first" => DotFilesFlag::First,
+            "last" => DotFilesFlag::Last,
+            "mixed" => DotFilesFlag::Mixed,
+            _ => panic!("invalid \"dotfiles\" flag: {}", dotfiles),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Copy, PartialEq, Eq)]
+pub enum IconTheme {
+    Unicode,
+    Fancy,
+}
+
+impl<'a> From<&'a str> for IconTheme {
+    fn from(theme: &'a str) -> Self {
+        match theme {
+            "fancy" => IconTheme::Fancy,
+            "unicode" => IconTheme::Unicode,
+            _ => panic!("invalid \"icon-theme\" flag: {}", theme),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Copy, PartialEq, Eq)]
+pub enum Layout {
+    Grid,
+    Tree { long: bool },
+    OneLine { long: bool },
+}
+
+/// The strings drawing the arrows of the symlinks and the branches of the
+/// tree, which the fonts without these glyphs can replace.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Symbols {
+    pub symlink_arrow: String,
+    /// The branch of the entries followed by another one, `├──`.
+    pub tree_edge: String,
+    /// The line going down to the next entries, `│`.
+    pub tree_line: String,
+    /// The branch of the last entry, `└──`.
+    pub tree_corner: String,
+    /// The end of the truncated names, `…`.
+    pub ellipsis: String,
+    /// The end of the symlink chains looping back, `⟲`.
+    pub symlink_loop: String,
+}
+
+impl Default for Symbols {
+    fn default() -> Self {
+        Self {
+            symlink_arrow: String::from("\u{21d2}"),               // ⇒
+            tree_edge: String::from("\u{251c}\u{2500}\u{2500}"),   // ├──
+            tree_line: String::from("\u{2502}  "),                 // │
+            tree_corner: String::from("\u{2514}\u{2500}\u{2500}"), // └──
+            ellipsis: String::from("\u{2026}"),                    // …
+            symlink_loop: String::from("\u{27f2}"),                // ⟲
+        }
+    }
+}
+
+impl Symbols {
+    /// The symbols of `--ascii`, for the terminals and logs knowing no more.
+    pub fn ascii() -> Self {
+        Self {
+            symlink_arrow: String::from("->"),
+            tree_edge: String::from("|--"),
+            tree_line: String::from("|  "),
+            tree_corner: String::from("`--"),
+            ellipsis: String::from("~"),
+            symlink_loop: String::from("(loop)"),
+        }
+    }
+}
+
+/// A block of the long view showing the output of a command run on every
+/// entry, like `git log -1 --format=%ar -- {}`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CustomBlock {
+    /// The name in the header.
+    pub name: String,
+    /// The program and its arguments, where `{}` stands for the path.
+    pub command: Vec<String>,
+    /// How long the command may run before being killed.
+    pub timeout: Duration,
+}
+
+/// A rule of `--audit`, flagging the entries whose permissions deserve a look.
+#[derive(Clone, Debug, Copy, PartialEq, Eq)]
+pub enum AuditRule {
+    /// The entries anyone may write, but the sticky directories.
+    WorldWritable,
+    /// The setuid files.
+    Setuid,
+    /// The entries whose owner isn't the one of their directory.
+    ForeignOwner,
+}
+
+impl AuditRule {
+    pub fn all() -> Vec<Self> {
+        vec![
+            AuditRule::WorldWritable,
+            AuditRule::Setuid,
+            AuditRule::ForeignOwner,
+        ]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            AuditRule::WorldWritable => "world-writable",
+            AuditRule::Setuid => "setuid",
+            AuditRule::ForeignOwner => "foreign-owner",
+        }
+    }
+}
+
+impl<'a> From<&'a str> for AuditRule {
+    fn from(rule: &'a str) -> Self {
+        match rule {
+            "world-writable" => AuditRule::WorldWritable,
+            "setuid" => AuditRule::Setuid,
+            "foreign-owner" => AuditRule::ForeignOwner,
+            _ => panic!("invalid \"audit\" rule: {}", rule),
+        }
+    }
+}
+
+/// A rule of the config file, changing how the entries meeting all its
+/// conditions show. Only the first rule an entry meets applies.
+#[derive(Clone, Debug, Default)]
+pub struct DisplayRule {
+    pub name: Option<GlobMatcher>,
+    pub file_type: Option<TypeFilter>,
+    pub size: Option<SizeFilter>,
+    pub dates: Vec<DateFilter>,
+    /// The style of the name, instead of the one of its type.
+    pub style: Option<ElemStyle>,
+    pub icon: Option<String>,
+    pub hide: bool,
+}
+
+impl DisplayRule {
+    /// Whether `meta` meets the conditions of the rule. Like with `--size`,
+    /// the directories only have a size with `total_size`.
+    pub fn matches(&self, meta: &Meta, total_size: bool) -> bool {
+        if let Some(ref name) = self.name {
+            if !name.is_match(&meta.name.name) {
+                return false;
+            }
+        }
+
+        if let Some(file_type) = self.file_type {
+            if !file_type.matches(meta.file_type) {
+                return false;
+            }
+        }
+
+        if let Some(size) = self.size {
+            if let (FileType::Directory { .. }, false) = (meta.file_type, total_size) {
+                return false;
+            }
+            if !size.matches(meta.size.get_bytes()) {
+                return false;
+            }
+        }
+
+        self.dates.iter().all(|date| date.matches(&meta.date))
+    }
+
+    /// The index of the first of `rules` which `meta` meets.
+    pub fn first_match(rules: &[Self], meta: &Meta, total_size: bool) -> Option<usize> {
+        rules.iter().position(|rule| rule.matches(meta, total_size))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        AuditRule, Block, ChecksumFlag, DateFilter, DateFlag, Display, Flags, Layout, MimeFlag,
+        PathFlag, PermissionFlag, SizeFilter, SortFlag, TimeFlag, TotalFlag, TypeFilter, WhenFlag,
+        DEFAULT_CHAIN_DEPTH,
+    };
+    use crate::app;
+    use crate::meta::{Date, FileType};
+    use clap::ErrorKind;
+
+    #[test]
+    fn test_validate_depth_value() {
+        let matches = app::build()
+            .get_matches_from_safe(vec!["lsd", "--tree", "--depth", "xx"])
+            .unwrap();
+        let res = Flags::from_matches(&matches);
+
+        assert!(res.is_err());
+        assert_eq!(res.unwrap_err().kind, ErrorKind::ValueValidation);
+    }
+
+    #[test]
+    fn test_no_user_removes_the_user_block() {
+        let matches = app::build()
+            .get_matches_from_safe(vec!["lsd", "--no-user"])
+            .unwrap();
+        let res = Flags::from_matches(&matches).unwrap();
+
+        assert_eq!(
+            res.blocks,
+            vec![
+                Block::Permission,
+                Block::Group,
+                Block::Size,
+                Block::Date,
+                Block::Name
+            ]
+        );
+    }
+
+    #[test]
+    fn test_no_group_removes_the_group_block() {
+        let matches = app::build()
+            .get_matches_from_safe(vec!["lsd", "--no-group"])
+            .unwrap();
+        let res = Flags::from_matches(&matches).unwrap();
+
+        assert_eq!(
+            res.blocks,
+            vec![
+                Block::Permission,
+                Block::User,
+                Block::Size,
+                Block::Date,
+                Block::Name
+            ]
+        );
+    }
+
+    #[test]
+    fn test_no_user_and_no_group_apply_to_custom_blocks() {
+        let matches = app::build()
+            .get_matches_from_safe(vec![
+                "lsd",
+                "--blocks",
+                "name,user,group",
+                "--no-user",
+                "--no-group",
+            ])
+            .unwrap();
+        let res = Flags::from_matches(&matches).unwrap();
+
+        assert_eq!(res.blocks, vec![Block::Name]);
+    }
+
+    #[test]
+    fn test_context_is_displayed_after_the_owner() {
+        let matches = app::build()
+            .get_matches_from_safe(vec!["lsd", "-Z"])
+            .unwrap();
+        let res = Flags::from_matches(&matches).unwrap();
+
+        assert_eq!(
+            res.blocks,
+            vec![
+                Block::Permission,
+                Block::User,
+                Block::Group,
+                Block::Context,
+                Block::Size,
+                Block::Date,
+                Block::Name
+            ]
+        );
+    }
+
+    #[test]
+    fn test_context_without_owner_is_displayed_before_the_name() {
+        let matches = app::build()
+            .get_matches_from_safe(vec!["lsd", "--blocks", "size,name", "--context"])
+            .unwrap();
+        let res = Flags::from_matches(&matches).unwrap();
+
+        assert_eq!(res.blocks, vec![Block::Size, Block::Context, Block::Name]);
+    }
+
+    #[test]
+    fn test_context_is_not_duplicated() {
+        let matches = app::build()
+            .get_matches_from_safe(vec!["lsd", "--blocks", "context,name", "-Z"])
+            .unwrap();
+        let res = Flags::from_matches(&matches).unwrap();
+
+        assert_eq!(res.blocks, vec![Block::Context, Block::Name]);
+    }
+
+    #[test]
+    fn test_permission_octal() {
+        let matches = app::build()
+            .get_matches_from_safe(vec!["lsd", "--permission", "octal"])
+            .unwrap();
+        let res = Flags::from_matches(&matches).unwrap();
+
+        assert_eq!(res.permission, PermissionFlag::Octal);
+        assert!(res.blocks.contains(&Block::Permission));
+    }
+
+    #[test]
+    fn test_permission_disable_removes_the_permission_block() {
+        let matches = app::build()
+            .get_matches_from_safe(vec!["lsd", "--permission", "disable"])
+            .unwrap();
+        let res = Flags::from_matches(&matches).unwrap();
+
+        assert!(!res.blocks.contains(&Block::Permission));
+    }
+
+    #[test]
+    fn test_total_blocks() {
+        let matches = app::build()
+            .get_matches_from_safe(vec!["lsd", "--total", "size", "--total", "blocks"])
+            .unwrap();
+        let res = Flags::from_matches(&matches).unwrap();
+
+        assert_eq!(res.total, TotalFlag::Blocks);
+    }
+
+    #[test]
+    fn test_absolute_implies_the_full_path() {
+        let matches = app::build()
+            .get_matches_from_safe(vec!["lsd", "--full-path", "--absolute"])
+            .unwrap();
+        let res = Flags::from_matches(&matches).unwrap();
+
+        assert_eq!(res.full_path, PathFlag::Absolute);
+    }
+
+    #[test]
+    fn test_time_created() {
+        let matches = app::build()
+            .get_matches_from_safe(vec!["lsd", "--time", "created", "-t"])
+            .unwrap();
+        let res = Flags::from_matches(&matches).unwrap();
+
+        assert_eq!(res.time, TimeFlag::Created);
+        assert_eq!(res.sort_by, SortFlag::Time);
+        assert!(!DateFilter::OlderThan(Date::new(time::now())).matches(&Date::default()));
+    }
+
+    #[test]
+    fn test_date_of_the_config() {
+        use crate::config_file::Config;
+
+        let config = Config::from_yaml("date: utc").unwrap();
+
+        let matches = app::build().get_matches_from_safe(vec!["lsd"]).unwrap();
+        let mut res = Flags::from_matches(&matches).unwrap();
+        res.apply_config(&matches, &config);
+        assert_eq!(res.date, DateFlag::Utc);
+
+        let matches = app::build()
+            .get_matches_from_safe(vec!["lsd", "--date", "relative"])
+            .unwrap();
+        let mut res = Flags::from_matches(&matches).unwrap();
+        res.apply_config(&matches, &config);
+        assert_eq!(res.date, DateFlag::Relative);
+    }
+
+    #[test]
+    fn test_audit_rules_of_the_config() {
+        use crate::config_file::Config;
+
+        let config = Config::from_yaml("theme:\n  audit-rules: [setuid]\n").unwrap();
+
+        let matches = app::build().get_matches_from_safe(vec!["lsd"]).unwrap();
+        let mut res = Flags::from_matches(&matches).unwrap();
+        res.apply_config(&matches, &config);
+        assert!(res.audit_rules.is_empty());
+
+        let matches = app::build()
+            .get_matches_from_safe(vec!["lsd", "--audit"])
+            .unwrap();
+        let mut res = Flags::from_matches(&matches).unwrap();
+        assert_eq!(AuditRule::all(), res.audit_rules);
+        res.apply_config(&matches, &config);
+        assert_eq!(vec![AuditRule::Setuid], res.audit_rules);
+    }
+
+    #[test]
+    fn test_dir_size_of_the_config() {
+        use super::DirSizeFlag;
+        use crate::config_file::Config;
+
+        let config = Config::from_yaml("dir-size: count").unwrap();
+
+        let matches = app::build().get_matches_from_safe(vec!["lsd"]).unwrap();
+        let mut res = Flags::from_matches(&matches).unwrap();
+        res.apply_config(&matches, &config);
+        assert_eq!(res.dir_size, DirSizeFlag::Count);
+
+        let matches = app::build()
+            .get_matches_from_safe(vec!["lsd", "--total-size"])
+            .unwrap();
+        let mut res = Flags::from_matches(&matches).unwrap();
+        res.apply_config(&matches, &config);
+        assert_eq!(res.dir_size, DirSizeFlag::Total);
+    }
+
+    #[test]
+    fn test_default_ignores_of_the_config() {
+        use crate::config_file::Config;
+
+        let config = Config::from_yaml("default-ignores: [vendor]").unwrap();
+        let flags_for = |args: Vec<&str>, config: &Config| {
+            let matches = app::build().get_matches_from_safe(args).unwrap();
+            let mut res = Flags::from_matches(&matches).unwrap();
+            res.apply_config(&matches, config);
+            res
+        };
+
+        let res = flags_for(vec!["lsd", "--tree"], &Config::default());
+        assert!(res.ignore_globs.is_match("node_modules"));
+
+        let res = flags_for(vec!["lsd", "--recursive"], &config);
+        assert!(res.ignore_globs.is_match("vendor"));
+        assert!(!res.ignore_globs.is_match("node_modules"));
+
+        let res = flags_for(vec!["lsd", "--tree", "--no-default-ignore"], &config);
+        assert!(!res.ignore_globs.is_match("vendor"));
+
+        let res = flags_for(vec!["lsd"], &config);
+        assert!(!res.ignore_globs.is_match("vendor"));
+
+        let res = flags_for(vec!["lsd", "--tree", "--classic"], &Config::default());
+        assert!(!res.ignore_globs.is_match("node_modules"));
+    }
+
+    #[test]
+    fn test_icon_fallback_of_the_config() {
+        use crate::config_file::Config;
+
+        let config = Config::from_yaml("icons:\n  fallback: always").unwrap();
+
+        let matches = app::build().get_matches_from_safe(vec!["lsd"]).unwrap();
+        let mut res = Flags::from_matches(&matches).unwrap();
+        res.apply_config(&matches, &config);
+        assert_eq!(res.icon_fallback, WhenFlag::Always);
+
+        let matches = app::build()
+            .get_matches_from_safe(vec!["lsd", "--icon-fallback", "never"])
+            .unwrap();
+        let mut res = Flags::from_matches(&matches).unwrap();
+        res.apply_config(&matches, &config);
+        assert_eq!(res.icon_fallback, WhenFlag::Never);
+    }
+
+    #[test]
+    fn test_custom_blocks_of_the_config() {
+        use crate::config_file::Config;
+
+        let config =
+            Config::from_yaml("custom-blocks:\n  - name: Age\n    command: [date]\n").unwrap();
+
+        let matches = app::build()
+            .get_matches_from_safe(vec!["lsd", "-l"])
+            .unwrap();
+        let mut res = Flags::from_matches(&matches).unwrap();
+        res.apply_config(&matches, &config);
+        assert_eq!(
+            vec![Block::Date, Block::Custom, Block::Name],
+            res.blocks[res.blocks.len() - 3..].to_vec()
+        );
+
+        let matches = app::build()
+            .get_matches_from_safe(vec!["lsd", "--blocks", "custom,name"])
+            .unwrap();
+        let mut res = Flags::from_matches(&matches).unwrap();
+        res.apply_config(&matches, &Config::default());
+        assert_eq!(vec![Block::Name], res.blocks);
+    }
+
+    #[test]
+    fn test_with_directory_config() {
+        use super::SortOrder;
+        use crate::config_file::DirectoryConfig;
+
+        let config = DirectoryConfig::from_yaml(
+            "sort: time\nreverse: true\nblocks: [name]\nignore-globs: [\"*.o\"]\n",
+        )
+        .unwrap();
+
+        let matches = app::build()
+            .get_matches_from_safe(vec!["lsd", "-I", "target"])
+            .unwrap();
+        let res = Flags::from_matches(&matches)
+            .unwrap()
+            .with_directory_config(&config);
+
+        assert_eq!(SortFlag::Time, res.sort_by);
+        assert_eq!(SortOrder::Reverse, res.sort_order);
+        assert_eq!(vec![Block::Name], res.blocks);
+        assert!(res.ignore_globs.is_match("target"));
+        assert!(res.ignore_globs.is_match("main.o"));
+        assert!(!res.ignore_globs.is_match("main.c"));
+    }
+
+    #[test]
+    fn test_ascii_wins_over_the_config_symbols() {
+        use super::{Symbols, WhenFlag};
+        use crate::config_file::Config;
+
+        let config = Config::from_yaml("theme:\n  symbols:\n    symlink-arrow: \"~>\"\n").unwrap();
+
+        let matches = app::build().get_matches_from_safe(vec!["lsd"]).unwrap();
+        let mut res = Flags::from_matches(&matches).unwrap();
+        res.apply_config(&matches, &config);
+        assert_eq!("~>", res.symbols.symlink_arrow);
+
+        let matches = app::build()
+            .get_matches_from_safe(vec!["lsd", "--ascii", "--icon", "always"])
+            .unwrap();
+        let mut res = Flags::from_matches(&matches).unwrap();
+        res.apply_config(&matches, &config);
+        assert_eq!(Symbols::ascii(), res.symbols);
+        assert_eq!(WhenFlag::Never, res.icon);
+    }
+
+    #[test]
+    fn test_flat_recursive() {
+        let matches = app::build()
+            .get_matches_from_safe(vec!["lsd", "--flat-recursive", "--depth", "2"])
+            .unwrap();
+        let res = Flags::from_matches(&matches).unwrap();
+
+        assert!(res.recursive);
+        assert_eq!(2, res.recursion_depth);
+        assert_eq!(PathFlag::Relative, res.full_path);
+    }
+
+    #[test]
+    fn test_truncate_names() {
+        use super::TruncateFlag;
+
+        let truncate = |value| {
+            let matches = app::build()
+                .get_matches_from_safe(vec!["lsd", "--truncate-names", value])
+                .unwrap();
+            Flags::from_matches(&matches).map(|flags| flags.truncate_names)
+        };
+
+        assert_eq!(Some(TruncateFlag::Width(20)), truncate("20").unwrap());
+        assert_eq!(Some(TruncateFlag::Auto), truncate("auto").unwrap());
+        assert!(truncate("0").is_err());
+        assert!(truncate("wide").is_err());
+
+        assert_eq!(Some(20), TruncateFlag::Auto.name_width(Some(60)));
+        assert_eq!(None, TruncateFlag::Auto.name_width(None));
+    }
+
+    #[test]
+    fn test_owner_width() {
+        let matches = app::build()
+            .get_matches_from_safe(vec!["lsd", "--owner-width", "12"])
+            .unwrap();
+        assert_eq!(Some(12), Flags::from_matches(&matches).unwrap().owner_width);
+
+        let matches = app::build()
+            .get_matches_from_safe(vec!["lsd", "--owner-width", "0"])
+            .unwrap();
+        assert_eq!(
+            ErrorKind::ValueValidation,
+            Flags::from_matches(&matches).unwrap_err().kind
+        );
+    }
+
+    #[test]
+    fn test_size_unit() {
+        use super::SizeUnitFlag;
+
+        let matches = app::build().get_matches_from_safe(vec!["lsd"]).unwrap();
+        assert_eq!(None, Flags::from_matches(&matches).unwrap().size_unit);
+
+        let matches = app::build()
+            .get_matches_from_safe(vec!["lsd", "--size-unit", "K", "--size-unit", "M"])
+            .unwrap();
+        assert_eq!(
+            Some(SizeUnitFlag::Mega),
+            Flags::from_matches(&matches).unwrap().size_unit
+        );
+
+        let res = app::build().get_matches_from_safe(vec!["lsd", "--size-unit", "X"]);
+        assert_eq!(ErrorKind::InvalidValue, res.unwrap_err().kind);
+    }
+
+    #[test]
+    fn test_width() {
+        let matches = app::build()
+            .get_matches_from_safe(vec!["lsd", "--width", "40", "-w", "100"])
+            .unwrap();
+        assert_eq!(Some(100), Flags::from_matches(&matches).unwrap().width);
+
+        let matches = app::build()
+            .get_matches_from_safe(vec!["lsd", "--width", "0"])
+            .unwrap();
+        assert!(Flags::from_matches(&matches).is_err());
+    }
+
+    #[test]
+    fn test_disk_usage() {
+        let matches = app::build()
+            .get_matches_from_safe(vec!["lsd", "--tree", "--total-size", "-S"])
+            .unwrap();
+        let res = Flags::from_matches(&matches).unwrap();
+
+        assert!(res.is_disk_usage());
+        assert!(!Flags::default().is_disk_usage());
+    }
+
+    #[test]
+    fn test_mime_deep() {
+        let matches = app::build()
+            .get_matches_from_safe(vec!["lsd", "--blocks", "mime,name", "--mime", "deep"])
+            .unwrap();
+        let res = Flags::from_matches(&matches).unwrap();
+
+        assert_eq!(res.blocks, vec![Block::Mime, Block::Name]);
+        assert_eq!(res.mime, MimeFlag::Deep);
+    }
+
+    #[test]
+    fn test_checksum_is_displayed_before_the_name() {
+        let matches = app::build()
+            .get_matches_from_safe(vec![
+                "lsd",
+                "--checksum",
+                "md5",
+                "--checksum-max-size",
+                "1K",
+            ])
+            .unwrap();
+        let res = Flags::from_matches(&matches).unwrap();
+
+        assert_eq!(
+            res.blocks,
+            vec![
+                Block::Permission,
+                Block::User,
+                Block::Group,
+                Block::Size,
+                Block::Date,
+                Block::Checksum,
+                Block::Name
+            ]
+        );
+        assert_eq!(res.checksum, ChecksumFlag::Md5);
+        assert_eq!(res.checksum_max_size, 1024);
+    }
+
+    #[test]
+    fn test_invalid_checksum_max_size() {
+        let matches = app::build()
+            .get_matches_from_safe(vec!["lsd", "--checksum-max-size", "big"])
+            .unwrap();
+
+        assert!(Flags::from_matches(&matches).is_err());
+    }
+
+    #[test]
+    fn test_size_filter() {
+        assert_eq!(
+            SizeFilter::Above(10 * 1024 * 1024),
+            SizeFilter::parse("+10M").unwrap()
+        );
+        assert_eq!(SizeFilter::Below(4096), SizeFilter::parse("-4k").unwrap());
+        assert_eq!(
+            SizeFilter::Above(1536),
+            SizeFilter::parse("+1.5KB").unwrap()
+        );
+        assert_eq!(SizeFilter::Below(42), SizeFilter::parse("-42").unwrap());
+
+        assert!(SizeFilter::parse("10M").is_err());
+        assert!(SizeFilter::parse("+M").is_err());
+        assert!(SizeFilter::parse("+10X").is_err());
+    }
+
+    #[test]
+    fn test_display_rules() {
+        use super::DisplayRule;
+        use crate::meta::Meta;
+        use globset::Glob;
+        use std::fs;
+        use tempfile::tempdir;
+
+        let tmp_dir = tempdir().expect("failed to create temp dir");
+        let path = tmp_dir.path().join("big.log");
+        fs::write(&path, "0123456789").expect("failed to create file");
+        let file = Meta::from_path(&path).expect("failed to get meta");
+        let dir = Meta::from_path(&tmp_dir.path().to_path_buf()).expect("failed to get meta");
+
+        let rule = |pattern: &str, size| DisplayRule {
+            name: Some(Glob::new(pattern).unwrap().compile_matcher()),
+            size: Some(size),
+            ..DisplayRule::default()
+        };
+        let rules = vec![
+            rule("*.txt", SizeFilter::Above(5)),
+            rule("*.log", SizeFilter::Above(20)),
+            rule("*.log", SizeFilter::Above(5)),
+            DisplayRule {
+                size: Some(SizeFilter::Above(0)),
+                ..DisplayRule::default()
+            },
+        ];
+
+        assert_eq!(Some(2), DisplayRule::first_match(&rules, &file, false));
+        assert_eq!(None, DisplayRule::first_match(&rules, &dir, false));
+        assert_eq!(Some(3), DisplayRule::first_match(&rules, &dir, true));
+        assert!(DisplayRule::default().matches(&dir, false));
+    }
+
+    #[test]
+    fn test_size_filter_matches() {
+        assert!(SizeFilter::Above(1024).matches(1025));
+        assert!(!SizeFilter::Above(1024).matches(1024));
+        assert!(SizeFilter::Below(1024).matches(1023));
+        assert!(!SizeFilter::Below(1024).matches(2048));
+    }
+
+    #[test]
+    fn test_date_filter_duration() {
+        let matches = app::build()
+            .get_matches_from_safe(vec!["lsd", "--newer-than", "2d"])
+            .unwrap();
+        let res = Flags::from_matches(&matches).unwrap();
+
+        let hour_ago = Date::new(time::now() - time::Duration::hours(1));
+        let week_ago = Date::new(time::now() - time::Duration::weeks(1));
+        assert_eq!(1, res.date_filters.len());
+        assert!(res.date_filters[0].matches(&hour_ago));
+        assert!(!res.date_filters[0].matches(&week_ago));
+    }
+
+    #[test]
+    fn test_date_filter_absolute_date() {
+        let matches = app::build()
+            .get_matches_from_safe(vec!["lsd", "--older-than", "2019-10-23"])
+            .unwrap();
+        let res = Flags::from_matches(&matches).unwrap();
+
+        let old = Date::new(time::strptime("2019-10-01", "%Y-%m-%d").unwrap());
+        assert!(res.date_filters[0].matches(&old));
+        assert!(!res.date_filters[0].matches(&Date::new(time::now())));
+    }
+
+    #[test]
+    fn test_type_filters() {
+        let matches = app::build()
+            .get_matches_from_safe(vec!["lsd", "--type", "d", "--type", "socket"])
+            .unwrap();
+        let flags = Flags::from_matches(&matches).unwrap();
+        assert_eq!(
+            vec![TypeFilter::Directory, TypeFilter::Socket],
+            flags.type_filters
+        );
+
+        assert!(app::build()
+            .get_matches_from_safe(vec!["lsd", "--type", "x"])
+            .is_err());
+    }
+
+    #[test]
+    fn test_type_filter_matches() {
+        assert!(TypeFilter::File.matches(FileType::File {
+            uid: false,
+            exec: true
+        }));
+        assert!(TypeFilter::SymLink.matches(FileType::SymLink { is_dir: true }));
+        assert!(!TypeFilter::Directory.matches(FileType::SymLink { is_dir: true }));
+        assert!(!TypeFilter::Pipe.matches(FileType::Special));
+    }
+
+    #[test]
+    fn test_invalid_date_filter() {
+        for invalid in &["2", "d", "2x", "2019-13-45"] {
+            let matches = app::build()
+                .get_matches_from_safe(vec!["lsd", "--newer-than", invalid])
+                .unwrap();
+
+            assert!(Flags::from_matches(&matches).is_err());
+        }
+    }
+
+    #[test]
+    fn test_follow_chain() {
+        let flags = |args: Vec<&str>| {
+            let matches = app::build().get_matches_from_safe(args).unwrap();
+            Flags::from_matches(&matches)
+        };
+
+        assert_eq!(None, flags(vec!["lsd"]).unwrap().follow_chain);
+        assert_eq!(
+            Some(DEFAULT_CHAIN_DEPTH),
+            flags(vec!["lsd", "--follow-chain"]).unwrap().follow_chain
+        );
+        assert_eq!(
+            Some(3),
+            flags(vec!["lsd", "--follow-chain", "--chain-depth", "3"])
+                .unwrap()
+                .follow_chain
+        );
+        assert_eq!(
+            ErrorKind::MissingRequiredArgument,
+            flags(vec!["lsd", "--chain-depth", "3"]).unwrap_err().kind
+        );
+        assert_eq!(
+            ErrorKind::ValueValidation,
+            flags(vec!["lsd", "--follow-chain", "--chain-depth", "0"])
+                .unwrap_err()
+                .kind
+        );
+    }
+
+    #[test]
+    fn test_useless_depth() {
+        let matches = app::build()
+            .get_matches_from_safe(vec!["lsd", "--depth", "10"])
+            .unwrap();
+        let res = Flags::from_matches(&matches);
+
+        assert!(res.is_err());
+        assert_eq!(res.unwrap_err().kind, ErrorKind::MissingRequiredArgument);
+    }
+
+    #[test]
+    fn test_later_layout_flag_wins() {
+        let matches = app::build()
+            .get_matches_from_safe(vec!["lsd", "-l", "-1"])
+            .unwrap();
+        let flags = Flags::from_matches(&matches).unwrap();
+        assert_eq!(Layout::OneLine { long: false }, flags.layout);
+
+        let matches = app::build()
+            .get_matches_from_safe(vec!["lsd", "-1", "-l"])
+            .unwrap();
+        let flags = Flags::from_matches(&matches).unwrap();
+        assert_eq!(Layout::OneLine { long: true }, flags.layout);
+    }
+
+    #[test]
+    fn test_later_color_flag_wins() {
+        let color = |args: Vec<&str>| {
+            let matches = app::build().get_matches_from_safe(args).unwrap();
+            Flags::from_matches(&matches).unwrap().color
+        };
+
+        assert_eq!(WhenFlag::Never, color(vec!["lsd", "--no-color"]));
+        assert_eq!(
+            WhenFlag::Never,
+            color(vec!["lsd", "--color", "always", "--no-color"])
+        );
+        assert_eq!(
+            WhenFlag::Always,
+            color(vec!["lsd", "--no-color", "--color", "always"])
+        );
+    }
+
+    #[test]
+    fn test_preview_pane() {
+        let flags = |args: Vec<&str>| {
+            let matches = app::build().get_matches_from_safe(args).unwrap();
+            Flags::from_matches(&matches).unwrap()
+        };
+
+        let pane = flags(vec!["lsd", "--preview-pane", "-l"]);
+        assert!(pane.preview_pane);
+        assert_eq!(Layout::OneLine { long: false }, pane.layout);
+        assert_eq!(WhenFlag::Always, pane.color);
+        assert_eq!(WhenFlag::Always, pane.icon);
+
+        let pane = flags(vec!["lsd", "--preview-pane", "--color", "never", "--ascii"]);
+        assert_eq!(WhenFlag::Never, pane.color);
+        assert_eq!(WhenFlag::Never, pane.icon);
+    }
+
+    #[test]
+    fn test_later_sort_flag_wins() {
+        let matches = app::build()
+            .get_matches_from_safe(vec!["lsd", "-t", "-S"])
+            .unwrap();
+        let flags = Flags::from_matches(&matches).unwrap();
+        assert_eq!(SortFlag::Size, flags.sort_by);
+
+        let matches = app::build()
+            .get_matches_from_safe(vec!["lsd", "-S", "-t"])
+            .unwrap();
+        let flags = Flags::from_matches(&matches).unwrap();
+        assert_eq!(SortFlag::Time, flags.sort_by);
+
+        let matches = app::build()
+            .get_matches_from_safe(vec!["lsd", "-t", "-U"])
+            .unwrap();
+        let flags = Flags::from_matches(&matches).unwrap();
+        assert_eq!(SortFlag::None, flags.sort_by);
+
+        let matches = app::build()
+            .get_matches_from_safe(vec!["lsd", "--sort", "size", "-t"])
+            .unwrap();
+        let flags = Flags::from_matches(&matches).unwrap();
+        assert_eq!(SortFlag::Time, flags.sort_by);
+    }
+
+    #[test]
+    fn test_sort_with_secondary_keys() {
+        let matches = app::build()
+            .get_matches_from_safe(vec!["lsd", "--sort", "size,time,name"])
+            .unwrap();
+        let flags = Flags::from_matches(&matches).unwrap();
+
+        assert_eq!(SortFlag::Size, flags.sort_by);
+        assert_eq!(vec![SortFlag::Time, SortFlag::Name], flags.sort_then_by);
+    }
+
+    #[test]
+    fn test_sort_by_the_other_fields() {
+        let matches = app::build()
+            .get_matches_from_safe(vec!["lsd", "--sort", "extension,links,inode"])
+            .unwrap();
+        let flags = Flags::from_matches(&matches).unwrap();
+
+        assert_eq!(SortFlag::Extension, flags.sort_by);
+        assert_eq!(vec![SortFlag::Links, SortFlag::Inode], flags.sort_then_by);
+    }
+
+    #[test]
+    fn test_invalid_sort_key() {
+        for invalid in &["size,", "ext", "name,date"] {
+            let matches = app::build()
+                .get_matches_from_safe(vec!["lsd", "--sort", invalid])
+                .unwrap();
+            let res = Flags::from_matches(&matches);
+
+            assert!(res.is_err());
+            assert_eq!(res.unwrap_err().kind, ErrorKind::ValueValidation);
+        }
+    }
+
+    #[test]
+    fn test_ls_flags_can_be_combined_and_repeated() {
+        let matches = app::build()
+            .get_matches_from_safe(vec!["lsd", "-lahhd", "-R", "-d"])
+            .unwrap();
+        let flags = Flags::from_matches(&matches).unwrap();
+
+        assert_eq!(Display::DisplayDirectoryItself, flags.display);
     }
 }
@@ -1,21 +1,92 @@
+use crate::config_file::IconsConfig;
+use crate::flags::WhenFlag;
 use crate::meta::{FileType, Name};
+use globset::GlobMatcher;
 use std::collections::HashMap;
 
 pub struct Icons {
-    display_icons: bool,
-    icons_by_name: HashMap<&'static str, &'static str>,
-    icons_by_extension: HashMap<&'static str, &'static str>,
+    theme: Theme,
+    matchers: Vec<IconMatcher>,
     default_folder_icon: &'static str,
     default_file_icon: &'static str,
+    separator: String,
+}
+
+// The matchers are tried in turn, the first one knowing the entry gives its
+// icon.
+enum IconMatcher {
+    /// The whole name of the files, matched exactly first then regardless of
+    /// its case, so `Dockerfile` and `dockerfile` share the same icon.
+    Name(HashMap<String, String>),
+    /// The whole name of the directories, matched like the one of the files.
+    DirName(HashMap<String, String>),
+    /// The extension, for the files only.
+    Extension(HashMap<String, String>),
+    /// The glob patterns on the name, in the order of the config file.
+    Glob(Vec<(GlobMatcher, String)>),
+}
+
+impl IconMatcher {
+    fn icon_for(&self, name: &Name, is_dir: bool) -> Option<&str> {
+        match self {
+            IconMatcher::Name(_) | IconMatcher::Extension(_) if is_dir => None,
+            IconMatcher::DirName(_) if !is_dir => None,
+            IconMatcher::Name(icons) | IconMatcher::DirName(icons) => {
+                let name = name.name();
+                icons
+                    .get(&name)
+                    .or_else(|| icons.get(&name.to_lowercase()))
+                    .map(String::as_str)
+            }
+            IconMatcher::Extension(icons) => name
+                .extension()
+                .and_then(|extension| icons.get(extension))
+                .map(String::as_str),
+            IconMatcher::Glob(globs) => {
+                let name = name.name();
+                globs
+                    .iter()
+                    .find(|(glob, _)| glob.is_match(&name))
+                    .map(|(_, icon)| icon.as_str())
+            }
+        }
+    }
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum Theme {
     NoIcon,
+    /// The glyphs of the Nerd Fonts.
     Fancy,
+    /// The symbols of the Unicode standard, which the stock fonts have.
     Unicode,
 }
 
+impl Theme {
+    /// The theme to use instead of `self` when the terminal named `term`, from
+    /// `TERM`, can't show the Nerd Fonts glyphs, with `fallback` telling
+    /// whether to fall back.
+    pub fn with_fallback(self, fallback: WhenFlag, term: &str) -> Self {
+        let fall_back = match fallback {
+            WhenFlag::Always => true,
+            WhenFlag::Auto => !supports_nerd_fonts(term),
+            WhenFlag::Never => false,
+        };
+
+        match self {
+            Theme::Fancy if fall_back => Theme::Unicode,
+            theme => theme,
+        }
+    }
+}
+
+/// Whether the terminal named `term` might show the Nerd Fonts glyphs. The
+/// consoles of the kernels and the hardware terminals only have their own
+/// fonts, all the others may be set up with one.
+fn supports_nerd_fonts(term: &str) -> bool {
+    !(term == "linux" || term == "dumb" || term.starts_with("vt") || term.starts_with("cons"))
+}
+
 const ICON_SPACE: &str = "  ";
 
 // In order to add a new icon, write the unicode value like "\ue5fb" then
@@ -24,114 +95,158 @@ const ICON_SPACE: &str = "  ";
 // s#\\u[0-9a-f]*#\=eval('"'.submatch(0).'"')#
 impl Icons {
     pub fn new(theme: Theme) -> Self {
-        let display_icons = theme == Theme::Fancy || theme == Theme::Unicode;
-        let (icons_by_name, icons_by_extension, default_file_icon, default_folder_icon) =
-            if theme == Theme::Fancy {
-                (
-                    Self::get_default_icons_by_name(),
-                    Self::get_default_icons_by_extension(),
-                    "\u{f016}", // 
-                    "\u{f115}", // 
-                )
-            } else {
-                (
-                    HashMap::new(),
-                    HashMap::new(),
-                    "\u{1f5cb}", // 🗋
-                    "\u{1f5c1}", // 🗁
-                )
-            };
-
-        Self {
-            display_icons,
+        let (
             icons_by_name,
+            icons_by_dir_name,
             icons_by_extension,
             default_file_icon,
             default_folder_icon,
+        ) = if theme == Theme::Fancy {
+            (
+                Self::get_default_icons_by_name(),
+                Self::get_default_icons_by_dir_name(),
+                Self::get_default_icons_by_extension(),
+                "\u{f016}", // 
+                "\u{f115}", // 
+            )
+        } else {
+            (
+                HashMap::new(),
+                HashMap::new(),
+                Self::get_unicode_icons_by_extension(),
+                "\u{1f5cb}", // 🗋
+                "\u{1f5c1}", // 🗁
+            )
+        };
+
+        Self {
+            theme,
+            matchers: vec![
+                IconMatcher::Name(Self::to_owned_map(icons_by_name)),
+                IconMatcher::DirName(Self::to_owned_map(icons_by_dir_name)),
+                IconMatcher::Extension(Self::to_owned_map(icons_by_extension)),
+                IconMatcher::Glob(Vec::new()),
+            ],
+            default_file_icon,
+            default_folder_icon,
+            separator: String::from(ICON_SPACE),
+        }
+    }
+
+    /// Add the icons of the configuration file, overriding the default ones.
+    pub fn with_config(mut self, config: &IconsConfig) -> Self {
+        for matcher in &mut self.matchers {
+            match matcher {
+                IconMatcher::Name(icons) => icons.extend(config.by_name.clone()),
+                IconMatcher::DirName(icons) => icons.extend(config.by_dir_name.clone()),
+                IconMatcher::Extension(icons) => icons.extend(config.by_extension.clone()),
+                IconMatcher::Glob(globs) => globs.extend(config.by_glob.iter().cloned()),
+            }
+        }
+        if let Some(separator) = &config.separator {
+            self.separator = separator.clone();
+        }
+
+        self
+    }
+
+    fn to_owned_map(map: HashMap<&'static str, &'static str>) -> HashMap<String, String> {
+        map.into_iter()
+            .map(|(key, icon)| (key.to_string(), icon.to_string()))
+            .collect()
+    }
+
+    fn find_icon(&self, name: &Name, is_dir: bool) -> Option<&str> {
+        self.matchers
+            .iter()
+            .find_map(|matcher| matcher.icon_for(name, is_dir))
+    }
+
+    /// Of the `fancy` and `unicode` icons, the one of the theme.
+    fn pick(&self, fancy: &'static str, unicode: &'static str) -> &'static str {
+        match self.theme {
+            Theme::Fancy => fancy,
+            _ => unicode,
         }
     }
 
     pub fn get(&self, name: &Name) -> String {
-        if !self.display_icons {
+        if self.theme == Theme::NoIcon {
             return String::new();
         }
 
-        let mut res = String::with_capacity(4 + ICON_SPACE.len()); // 4 == max icon size
+        if let Some(icon) = name.icon() {
+            return format!("{}{}", icon, self.separator);
+        }
+
+        let mut res = String::with_capacity(4 + self.separator.len()); // 4 == max icon size
 
         // Check file types
         let file_type: FileType = name.file_type();
 
         if let FileType::Directory { .. } = file_type {
-            res += self.default_folder_icon;
-            res += ICON_SPACE;
+            // Check the known directory names, like `.git` or `node_modules`.
+            res += match self.find_icon(name, true) {
+                Some(icon) => icon,
+                None => self.default_folder_icon,
+            };
+            res += &self.separator;
+            return res;
+        } else if let FileType::SymLink { is_dir: true } = file_type {
+            res += self.pick("\u{f482}", "\u{1f517}"); // "", "🔗"
+            res += &self.separator;
             return res;
-        } else if let FileType::SymLink = file_type {
-            res += "\u{e27c}"; // ""
-            res += ICON_SPACE;
+        } else if let FileType::SymLink { is_dir: false } = file_type {
+            res += self.pick("\u{e27c}", "\u{1f517}"); // "", "🔗"
+            res += &self.separator;
             return res;
         } else if let FileType::Socket = file_type {
-            res += "\u{f6a7}"; // ""
-            res += ICON_SPACE;
+            res += self.pick("\u{f6a7}", "\u{1f50c}"); // "", "🔌"
+            res += &self.separator;
             return res;
         } else if let FileType::Pipe = file_type {
-            res += "\u{f731}"; // ""
-            res += ICON_SPACE;
+            res += self.pick("\u{f731}", "\u{2016}"); // "", "‖"
+            res += &self.separator;
             return res;
         } else if let FileType::CharDevice = file_type {
-            res += "\u{e601}"; // ""
-            res += ICON_SPACE;
+            res += self.pick("\u{e601}", "\u{2328}"); // "", "⌨"
+            res += &self.separator;
             return res;
         } else if let FileType::BlockDevice = file_type {
-            res += "\u{fc29}"; // "ﰩ"
-            res += ICON_SPACE;
+            res += self.pick("\u{fc29}", "\u{1f5b4}"); // "ﰩ", "🖴"
+            res += &self.separator;
             return res;
         } else if let FileType::Special = file_type {
-            res += "\u{f2dc}"; // ""
-            res += ICON_SPACE;
+            res += self.pick("\u{f2dc}", "\u{2699}"); // "", "⚙"
+            res += &self.separator;
             return res;
         }
 
-        // Check the known names.
-        if let Some(icon) = self.icons_by_name.get(name.name().as_str()) {
+        // Check the known names, extensions and patterns.
+        if let Some(icon) = self.find_icon(name, false) {
             res += icon;
-            res += ICON_SPACE;
+            res += &self.separator;
             return res;
         }
 
-        // Check the known extensions.
-        if let Some(extension) = name.extension() {
-            if let Some(icon) = self.icons_by_extension.get(extension.as_str()) {
-                res += icon;
-                res += ICON_SPACE;
-                return res;
-            }
-        }
-
         // Use the default icons.
         res += self.default_file_icon;
-        res += ICON_SPACE;
+        res += &self.separator;
         res
     }
 
     fn get_default_icons_by_name() -> HashMap<&'static str, &'static str> {
         let mut m = HashMap::new();
 
-        m.insert(".Trash", "\u{f1f8}"); // ""
-        m.insert(".atom", "\u{e764}"); // ""
         m.insert(".bashprofile", "\u{e615}"); // ""
         m.insert(".bashrc", "\u{f489}"); // ""
         m.insert(".git", "\u{f1d3}"); // ""
         m.insert(".gitattributes", "\u{f1d3}"); // ""
         m.insert(".gitconfig", "\u{f1d3}"); // ""
-        m.insert(".github", "\u{f408}"); // ""
         m.insert(".gitignore", "\u{f1d3}"); // ""
         m.insert(".gitmodules", "\u{f1d3}"); // ""
-        m.insert(".rvm", "\u{e21e}"); // ""
         m.insert(".vimrc", "\u{e62b}"); // ""
-        m.insert(".vscode", "\u{e70c}"); // ""
         m.insert(".zshrc", "\u{f489}"); // ""
-        m.insert("bin", "\u{e5fc}"); // ""
-        m.insert("config", "\u{e5fc}"); // ""
         m.insert("docker-compose.yml", "\u{f308}"); // ""
         m.insert("dockerfile", "\u{f308}"); // ""
         m.insert("ds_store", "\u{f179}"); // ""
@@ -144,14 +259,55 @@ impl Icons {
         m.insert("gulpfile.js", "\u{e610}"); // ""
         m.insert("gulpfile.ls", "\u{e610}"); // ""
         m.insert("hidden", "\u{f023}"); // ""
-        m.insert("include", "\u{e5fc}"); // ""
-        m.insert("lib", "\u{f121}"); // ""
         m.insert("localized", "\u{f179}"); // ""
-        m.insert("node_modules", "\u{e718}"); // ""
         m.insert("npmignore", "\u{e71e}"); // ""
         m.insert("rubydoc", "\u{e73b}"); // ""
         m.insert("yarn.lock", "\u{e718}"); // ""
         m.insert("Cargo.lock", "\u{e7a8}"); // ""
+        m.insert("cargo.toml", "\u{e7a8}"); // ""
+        m.insert("license", "\u{f48a}"); // ""
+        m.insert("makefile", "\u{f489}"); // ""
+
+        m
+    }
+
+    fn get_default_icons_by_dir_name() -> HashMap<&'static str, &'static str> {
+        let mut m = HashMap::new();
+
+        m.insert(".Trash", "\u{f1f8}"); // ""
+        m.insert(".atom", "\u{e764}"); // ""
+        m.insert(".git", "\u{f1d3}"); // ""
+        m.insert(".github", "\u{f408}"); // ""
+        m.insert(".rvm", "\u{e21e}"); // ""
+        m.insert(".vscode", "\u{e70c}"); // ""
+        m.insert("bin", "\u{e5fc}"); // ""
+        m.insert("config", "\u{e5fc}"); // ""
+        m.insert("include", "\u{e5fc}"); // ""
+        m.insert("lib", "\u{f121}"); // ""
+        m.insert("node_modules", "\u{e718}"); // ""
+
+        m
+    }
+
+    // The few kinds of files the Unicode symbols tell apart.
+    fn get_unicode_icons_by_extension() -> HashMap<&'static str, &'static str> {
+        let mut m = HashMap::new();
+
+        for extension in &["bmp", "gif", "ico", "jpeg", "jpg", "png", "svg", "webp"] {
+            m.insert(*extension, "\u{1f5bc}"); // 🖼
+        }
+        for extension in &["flac", "m4a", "mp3", "ogg", "opus", "wav"] {
+            m.insert(*extension, "\u{1f3b5}"); // 🎵
+        }
+        for extension in &["avi", "mkv", "mov", "mp4", "webm"] {
+            m.insert(*extension, "\u{1f39e}"); // 🎞
+        }
+        for extension in &["7z", "bz2", "gz", "rar", "tar", "tgz", "xz", "zip", "zst"] {
+            m.insert(*extension, "\u{1f4e6}"); // 📦
+        }
+        for extension in &["md", "pdf", "rst", "txt"] {
+            m.insert(*extension, "\u{1f5b9}"); // 🖹
+        }
 
         m
     }
@@ -337,8 +493,10 @@ impl Icons {
 #[cfg(test)]
 mod test {
     use super::{Icons, Theme, ICON_SPACE};
+    use crate::config_file::IconsConfig;
+    use crate::flags::WhenFlag;
     use crate::meta::Meta;
-    use std::fs::File;
+    use std::fs::{self, File};
     use tempfile::tempdir;
 
     #[test]
@@ -404,6 +562,37 @@ mod test {
         assert_eq!(icon, format!("{}{}", "\u{1f5c1}", ICON_SPACE));
     }
 
+    #[test]
+    fn get_icon_by_extension_unicode() {
+        let tmp_dir = tempdir().expect("failed to create temp dir");
+        let file_path = tmp_dir.path().join("photo.png");
+        File::create(&file_path).expect("failed to create file");
+        let meta = Meta::from_path(&file_path).unwrap();
+
+        let icon = Icons::new(Theme::Unicode);
+        let icon = icon.get(&meta.name);
+
+        assert_eq!(icon, format!("{}{}", "\u{1f5bc}", ICON_SPACE));
+    }
+
+    #[test]
+    fn test_theme_with_fallback() {
+        let fancy = Theme::Fancy;
+
+        assert_eq!(Theme::Unicode, fancy.with_fallback(WhenFlag::Always, ""));
+        assert_eq!(Theme::Fancy, fancy.with_fallback(WhenFlag::Never, "linux"));
+        assert_eq!(Theme::Unicode, fancy.with_fallback(WhenFlag::Auto, "linux"));
+        assert_eq!(Theme::Unicode, fancy.with_fallback(WhenFlag::Auto, "vt220"));
+        assert_eq!(
+            Theme::Fancy,
+            fancy.with_fallback(WhenFlag::Auto, "xterm-256color")
+        );
+        assert_eq!(
+            Theme::NoIcon,
+            Theme::NoIcon.with_fallback(WhenFlag::Always, "")
+        );
+    }
+
     #[test]
     fn get_directory_icon_with_ext() {
         let tmp_dir = tempdir().expect("failed to create temp dir");
@@ -447,4 +636,133 @@ mod test {
             assert_eq!(icon, format!("{}{}", file_icon, ICON_SPACE));
         }
     }
+
+    #[test]
+    fn get_icon_by_name_ignoring_case() {
+        let tmp_dir = tempdir().expect("failed to create temp dir");
+        let file_path = tmp_dir.path().join("Makefile");
+        File::create(&file_path).expect("failed to create file");
+        let meta = Meta::from_path(&file_path).unwrap();
+
+        let icon = Icons::new(Theme::Fancy);
+        let icon = icon.get(&meta.name);
+
+        assert_eq!(icon, format!("{}{}", "\u{f489}", ICON_SPACE));
+    }
+
+    #[test]
+    fn get_directory_icon_by_name() {
+        let tmp_dir = tempdir().expect("failed to create temp dir");
+        let dir_path = tmp_dir.path().join("node_modules");
+        fs::create_dir(&dir_path).expect("failed to create dir");
+        let meta = Meta::from_path(&dir_path).unwrap();
+
+        let icon = Icons::new(Theme::Fancy);
+        let icon = icon.get(&meta.name);
+
+        assert_eq!(icon, format!("{}{}", "\u{e718}", ICON_SPACE));
+    }
+
+    #[test]
+    fn get_icon_by_name_of_the_other_kind() {
+        let tmp_dir = tempdir().expect("failed to create temp dir");
+        let dir_path = tmp_dir.path().join("Makefile");
+        fs::create_dir(&dir_path).expect("failed to create dir");
+        let dir = Meta::from_path(&dir_path).unwrap();
+        let file_path = tmp_dir.path().join("node_modules");
+        File::create(&file_path).expect("failed to create file");
+        let file = Meta::from_path(&file_path).unwrap();
+
+        let icon = Icons::new(Theme::Fancy);
+
+        assert_eq!(icon.get(&dir.name), format!("{}{}", "\u{f115}", ICON_SPACE));
+        assert_eq!(
+            icon.get(&file.name),
+            format!("{}{}", "\u{f016}", ICON_SPACE)
+        );
+    }
+
+    #[test]
+    fn get_icon_from_config() {
+        let tmp_dir = tempdir().expect("failed to create temp dir");
+        let named_path = tmp_dir.path().join("Justfile");
+        File::create(&named_path).expect("failed to create file");
+        let named = Meta::from_path(&named_path).unwrap();
+        let ext_path = tmp_dir.path().join("main.rs");
+        File::create(&ext_path).expect("failed to create file");
+        let ext = Meta::from_path(&ext_path).unwrap();
+
+        let mut config = IconsConfig::default();
+        config
+            .by_name
+            .insert(String::from("justfile"), String::from("J"));
+        config
+            .by_extension
+            .insert(String::from("rs"), String::from("R"));
+        let icons = Icons::new(Theme::Fancy).with_config(&config);
+
+        assert_eq!(icons.get(&named.name), format!("J{}", ICON_SPACE));
+        assert_eq!(icons.get(&ext.name), format!("R{}", ICON_SPACE));
+    }
+
+    #[test]
+    fn get_icon_with_the_config_separator() {
+        let tmp_dir = tempdir().expect("failed to create temp dir");
+        let file_path = tmp_dir.path().join("file");
+        File::create(&file_path).expect("failed to create file");
+        let meta = Meta::from_path(&file_path).unwrap();
+
+        let config = IconsConfig {
+            separator: Some(String::from(" ")),
+            ..IconsConfig::default()
+        };
+        let icons = Icons::new(Theme::Fancy).with_config(&config);
+
+        assert_eq!(icons.get(&meta.name), "\u{f016} ");
+    }
+
+    #[test]
+    fn get_icon_from_glob_after_extension() {
+        use globset::Glob;
+
+        let tmp_dir = tempdir().expect("failed to create temp dir");
+        let unknown_path = tmp_dir.path().join("bundle.min.xyz");
+        File::create(&unknown_path).expect("failed to create file");
+        let unknown = Meta::from_path(&unknown_path).unwrap();
+        let known_path = tmp_dir.path().join("bundle.min.js");
+        File::create(&known_path).expect("failed to create file");
+        let known = Meta::from_path(&known_path).unwrap();
+
+        let mut config = IconsConfig::default();
+        let glob = Glob::new("*.min.*").unwrap().compile_matcher();
+        config.by_glob.push((glob, String::from("M")));
+        let icons = Icons::new(Theme::Fancy).with_config(&config);
+
+        assert_eq!(icons.get(&unknown.name), format!("M{}", ICON_SPACE));
+        assert_eq!(
+            icons.get(&known.name),
+            format!("{}{}", "\u{e74e}", ICON_SPACE)
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn get_symlink_to_dir_icon() {
+        use std::os::unix::fs::symlink;
+
+        let tmp_dir = tempdir().expect("failed to create temp dir");
+        let link_path = tmp_dir.path().join("link");
+        symlink(tmp_dir.path(), &link_path).expect("failed to create symlink");
+        let meta = Meta::from_path(&link_path).unwrap();
+
+        let icon = Icons::new(Theme::Fancy);
+        let icon = icon.get(&meta.name);
+
+        assert_eq!(icon, format!("{}{}", "\u{f482}", ICON_SPACE)); //
+
+        let icon = Icons::new(Theme::Unicode);
+        let icon = icon.get(&meta.name);
+
+        assert_eq!(icon, format!("{}{}", "\u{1f517}", ICON_SPACE)); // 🔗
+    }
 }
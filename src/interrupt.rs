@@ -0,0 +1,56 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Catch the first `Ctrl-C`, for the long scans to stop where they are and
+/// list what they found so far, rather than be killed amid the output. The
+/// next one kills the process, as usual.
+#[cfg(unix)]
+pub fn catch() {
+    extern "C" fn handler(_: libc::c_int) {
+        INTERRUPTED.store(true, Ordering::SeqCst);
+        unsafe { libc::signal(libc::SIGINT, libc::SIG_DFL) };
+    }
+
+    unsafe {
+        libc::signal(
+            libc::SIGINT,
+            handler as extern "C" fn(libc::c_int) as libc::sighandler_t,
+        )
+    };
+}
+
+#[cfg(windows)]
+pub fn catch() {
+    use winapi::shared::minwindef::{BOOL, DWORD, FALSE, TRUE};
+    use winapi::um::consoleapi::SetConsoleCtrlHandler;
+    use winapi::um::wincon::CTRL_C_EVENT;
+
+    unsafe extern "system" fn handler(ctrl_type: DWORD) -> BOOL {
+        // The default handler ends the process, on the next `Ctrl-C` too.
+        if ctrl_type != CTRL_C_EVENT || INTERRUPTED.swap(true, Ordering::SeqCst) {
+            return FALSE;
+        }
+        TRUE
+    }
+
+    unsafe { SetConsoleCtrlHandler(Some(handler), TRUE) };
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn catch() {}
+
+/// Whether `Ctrl-C` was pressed since `catch`.
+pub fn is_interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+#[cfg(test)]
+mod test {
+    use super::is_interrupted;
+
+    #[test]
+    fn test_not_interrupted_without_ctrl_c() {
+        assert!(!is_interrupted());
+    }
+}
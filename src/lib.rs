@@ -0,0 +1,86 @@
+//! The metadata gathering and formatting behind the `lsd` command, usable by
+//! the tools wanting to list files the same way without spawning the binary.
+//!
+//! ```
+//! use lsd::color::{Colors, Theme as ColorTheme};
+//! use lsd::flags::Flags;
+//! use lsd::icon::{Icons, Theme as IconTheme};
+//! use lsd::meta::Meta;
+//! use lsd::{display, sort};
+//! use std::path::PathBuf;
+//!
+//! let flags = Flags::default();
+//! let mut errors = Vec::new();
+//!
+//! let meta = Meta::from_path(&PathBuf::from(".")).unwrap();
+//! let mut content = meta
+//!     .recurse_into(1, &flags, &mut errors)
+//!     .unwrap_or_default();
+//! content.sort_by(|a, b| sort::by_meta(a, b, &flags));
+//!
+//! let colors = Colors::new(ColorTheme::NoColor);
+//! let icons = Icons::new(IconTheme::NoIcon);
+//! print!("{}", display::one_line(content, &flags, &colors, &icons));
+//! ```
+
+#![allow(
+    clippy::cast_precision_loss,
+    clippy::cast_sign_loss,
+    clippy::match_same_arms,
+    clippy::cast_possible_wrap
+)]
+
+#[macro_use]
+extern crate clap;
+extern crate ansi_term;
+extern crate chrono_humanize;
+extern crate libc;
+extern crate lscolors;
+extern crate serde;
+extern crate serde_yaml;
+#[cfg(test)]
+extern crate tempfile;
+extern crate term_grid;
+extern crate terminal_size;
+extern crate time;
+extern crate unicode_width;
+
+#[cfg(unix)]
+extern crate users;
+
+#[cfg(windows)]
+extern crate winapi;
+
+pub mod app;
+pub mod color;
+pub mod config_file;
+pub mod core;
+pub mod display;
+pub mod flags;
+pub mod icon;
+pub mod interrupt;
+pub mod meta;
+pub mod pager;
+pub mod progress;
+pub mod sort;
+pub mod watch;
+
+/// The exit codes, compatible with the ones of `ls`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ExitCode {
+    OK,
+    /// Minor problems, like a subdirectory that cannot be accessed.
+    MinorIssue,
+    /// Serious trouble, like an invalid flag or a missing file operand.
+    MajorIssue,
+    /// The listing was cut short by `Ctrl-C`, like the shells report it.
+    Interrupted = 130,
+}
+
+impl ExitCode {
+    pub fn set_if_greater(&mut self, code: ExitCode) {
+        if code > *self {
+            *self = code;
+        }
+    }
+}
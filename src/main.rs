@@ -1,54 +1,114 @@
-#![allow(
-    clippy::cast_precision_loss,
-    clippy::cast_sign_loss,
-    clippy::match_same_arms,
-    clippy::cast_possible_wrap
-)]
-
-#[macro_use]
 extern crate clap;
-extern crate ansi_term;
-extern crate chrono_humanize;
-extern crate libc;
-extern crate lscolors;
-#[cfg(test)]
-extern crate tempfile;
-extern crate term_grid;
-extern crate terminal_size;
-extern crate time;
-extern crate unicode_width;
+extern crate lsd;
 extern crate wild;
 
-#[cfg(unix)]
-extern crate users;
+use lsd::app;
+use lsd::config_file::Config;
+use lsd::core::Core;
+use lsd::flags::Flags;
+use lsd::interrupt;
+use lsd::ExitCode;
+use std::env;
+use std::ffi::OsString;
+use std::io::{self, BufRead};
+use std::path::{Path, PathBuf};
+use std::process;
 
-#[cfg(windows)]
-extern crate winapi;
+fn main() {
+    // The arguments of the environment come right after the program name.
+    let mut args = wild::args_os();
+    let program = args.next();
+    let env_args = app::env_args(|name| env::var(name).ok())
+        .into_iter()
+        .map(OsString::from);
+    let args = program.into_iter().chain(env_args).chain(args);
 
-mod app;
-mod color;
-mod core;
-mod display;
-mod flags;
-mod icon;
-mod meta;
-mod sort;
+    let matches = app::build()
+        .get_matches_from_safe(args)
+        .unwrap_or_else(|err| exit_with_clap_error(err));
 
-use crate::core::Core;
-use crate::flags::Flags;
-use std::path::PathBuf;
+    if let Some(shell) = matches.value_of("generate-completions") {
+        let shell = shell.parse().expect("invalid shell");
+        app::build().gen_completions_to("lsd", shell, &mut io::stdout());
+        return;
+    }
 
-fn main() {
-    let matches = app::build().get_matches_from(wild::args_os());
+    let mut inputs: Vec<PathBuf> = Vec::new();
+    if !matches.is_present("stdin") || matches.occurrences_of("FILE") > 0 {
+        inputs.extend(
+            matches
+                .values_of_os("FILE")
+                .expect("failed to retrieve cli value")
+                .map(PathBuf::from),
+        );
+    }
+    if matches.is_present("stdin") {
+        match read_paths(io::stdin().lock()) {
+            Ok(paths) => inputs.extend(paths),
+            Err(err) => {
+                eprintln!("cannot read the standard input: {}", err);
+                process::exit(ExitCode::MajorIssue as i32);
+            }
+        }
+    }
+
+    let mut flags = Flags::from_matches(&matches).unwrap_or_else(|err| exit_with_clap_error(err));
+    let config_path = matches.value_of_os("config-file").map(Path::new);
+    let config = Config::load(config_path).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        Config::default()
+    });
+    flags.apply_config(&matches, &config);
+
+    interrupt::catch();
+    let watch = flags.watch;
+    let core = Core::new(flags, &config);
+    if watch {
+        core.watch(inputs);
+    }
+
+    let exit_code = core.run(inputs);
+    process::exit(exit_code as i32);
+}
+
+/// Read one path per line, ignoring the empty lines.
+fn read_paths<R: BufRead>(reader: R) -> io::Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+
+    for line in reader.split(b'\n') {
+        let mut line = line?;
+        if line.last() == Some(&b'\r') {
+            line.pop();
+        }
+        if line.is_empty() {
+            continue;
+        }
 
-    let inputs = matches
-        .values_of("FILE")
-        .expect("failed to retrieve cli value")
-        .map(PathBuf::from)
-        .collect();
+        paths.push(path_from_bytes(line));
+    }
+
+    Ok(paths)
+}
+
+#[cfg(unix)]
+fn path_from_bytes(bytes: Vec<u8>) -> PathBuf {
+    use std::ffi::OsString;
+    use std::os::unix::ffi::OsStringExt;
+
+    PathBuf::from(OsString::from_vec(bytes))
+}
+
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: Vec<u8>) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(&bytes).into_owned())
+}
 
-    let flags = Flags::from_matches(&matches).unwrap_or_else(|err| err.exit());
-    let core = Core::new(flags);
+fn exit_with_clap_error(err: clap::Error) -> ! {
+    if err.use_stderr() {
+        eprintln!("{}", err.message);
+        process::exit(ExitCode::MajorIssue as i32);
+    }
 
-    core.run(inputs);
+    // The help and the version are not errors.
+    err.exit()
 }
@@ -0,0 +1,260 @@
+use crate::color::{ColoredString, Colors, Elem};
+use std::path::Path;
+
+// The POSIX ACLs are stored by Linux as extended attributes under these names.
+#[cfg(unix)]
+const ACL_XATTRS: &[&str] = &["system.posix_acl_access", "system.posix_acl_default"];
+
+// Security contexts are present on every entry of the systems using them, so
+// flagging them as extended attributes would only add noise.
+#[cfg(unix)]
+const CONTEXT_XATTRS: &[&str] = &["security.selinux", "security.SMACK64"];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Xattr {
+    pub name: String,
+    pub value: Vec<u8>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct AccessControl {
+    has_acl: bool,
+    xattrs: Vec<Xattr>,
+}
+
+impl AccessControl {
+    /// The ACL of the entry at `path`, and its extended attributes when
+    /// `with_xattrs`, which are only listed by `--extended`.
+    #[cfg(unix)]
+    pub fn for_path(path: &Path, with_xattrs: bool) -> Self {
+        let xattrs = if with_xattrs {
+            read_xattrs(path)
+        } else {
+            Vec::new()
+        };
+
+        Self {
+            has_acl: has_acl(path),
+            xattrs,
+        }
+    }
+
+    #[cfg(not(unix))]
+    pub fn for_path(_: &Path, _: bool) -> Self {
+        Self::default()
+    }
+
+    pub fn xattrs(&self) -> &[Xattr] {
+        &self.xattrs
+    }
+
+    /// The character appended to the permissions, like the `ls` of macOS:
+    /// `@` when there are extended attributes, `+` when there is only an ACL.
+    pub fn method_char(&self) -> &'static str {
+        if !self.xattrs.is_empty() {
+            "@"
+        } else if self.has_acl {
+            "+"
+        } else {
+            ""
+        }
+    }
+
+    pub fn render(&self, colors: &Colors, alignment: usize) -> ColoredString<'_> {
+        let mut res = String::from(self.method_char());
+
+        for _ in res.len()..alignment {
+            res.push(' ');
+        }
+
+        colors.colorize(res, &Elem::Acl)
+    }
+}
+
+/// The extended attributes of the entry, but its ACL and its security
+/// context.
+#[cfg(unix)]
+fn read_xattrs(path: &Path) -> Vec<Xattr> {
+    use crate::meta::xattr;
+
+    let names = match xattr::list(path) {
+        Ok(names) => names,
+        Err(_) => return Vec::new(),
+    };
+
+    names
+        .into_iter()
+        .filter_map(|os_name| {
+            let name = os_name.to_string_lossy().to_string();
+            if ACL_XATTRS.contains(&name.as_str()) || CONTEXT_XATTRS.contains(&name.as_str()) {
+                return None;
+            }
+
+            let value = xattr::get(path, &os_name).unwrap_or_default();
+            Some(Xattr { name, value })
+        })
+        .collect()
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn has_acl(path: &Path) -> bool {
+    use crate::meta::xattr;
+    use std::ffi::OsStr;
+
+    ACL_XATTRS.iter().any(|name| {
+        xattr::get(path, OsStr::new(name))
+            .map(|value| !value.is_empty())
+            .unwrap_or(false)
+    })
+}
+
+/// Whether the entry has an extended ACL, checked like the `ls` of macOS:
+/// the ACL may be set but empty.
+#[cfg(target_os = "macos")]
+fn has_acl(path: &Path) -> bool {
+    use std::ffi::CString;
+    use std::os::raw::{c_char, c_int, c_void};
+    use std::os::unix::ffi::OsStrExt;
+
+    const ACL_TYPE_EXTENDED: c_int = 0x0000_0100;
+    const ACL_FIRST_ENTRY: c_int = 0;
+
+    extern "C" {
+        fn acl_get_link_np(path: *const c_char, acl_type: c_int) -> *mut c_void;
+        fn acl_get_entry(acl: *mut c_void, entry_id: c_int, entry: *mut *mut c_void) -> c_int;
+        fn acl_free(obj: *mut c_void) -> c_int;
+    }
+
+    let c_path = match CString::new(path.as_os_str().as_bytes()) {
+        Ok(c_path) => c_path,
+        Err(_) => return false,
+    };
+
+    unsafe {
+        let acl = acl_get_link_np(c_path.as_ptr(), ACL_TYPE_EXTENDED);
+        if acl.is_null() {
+            return false;
+        }
+        let mut entry = std::ptr::null_mut();
+        let has_entry = acl_get_entry(acl, ACL_FIRST_ENTRY, &mut entry) == 0;
+        acl_free(acl);
+        has_entry
+    }
+}
+
+impl Xattr {
+    /// The value as text when it is printable, or as hexadecimal bytes
+    /// otherwise.
+    pub fn value_string(&self) -> String {
+        match std::str::from_utf8(&self.value) {
+            Ok(text) if !text.chars().any(char::is_control) => text.to_string(),
+            _ => self
+                .value
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect::<Vec<String>>()
+                .join(""),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{AccessControl, Xattr};
+    use crate::color::{Colors, Theme};
+
+    #[test]
+    fn test_no_method_char_without_xattrs() {
+        let access_control = AccessControl::default();
+
+        assert_eq!("", access_control.method_char());
+    }
+
+    #[test]
+    fn test_xattrs_take_precedence_over_acl() {
+        let access_control = AccessControl {
+            has_acl: true,
+            xattrs: vec![Xattr {
+                name: String::from("user.comment"),
+                value: b"hello".to_vec(),
+            }],
+        };
+
+        assert_eq!("@", access_control.method_char());
+
+        let acl_only = AccessControl {
+            has_acl: true,
+            xattrs: Vec::new(),
+        };
+        assert_eq!("+", acl_only.method_char());
+    }
+
+    #[test]
+    fn test_render_is_padded_to_the_alignment() {
+        let access_control = AccessControl::default();
+        let colors = Colors::new(Theme::NoColor);
+
+        assert_eq!(" ", access_control.render(&colors, 1).to_string().as_str());
+        assert_eq!("", access_control.render(&colors, 0).to_string().as_str());
+    }
+
+    #[test]
+    fn test_xattr_value_string() {
+        let text = Xattr {
+            name: String::from("user.comment"),
+            value: b"hello".to_vec(),
+        };
+        let binary = Xattr {
+            name: String::from("user.binary"),
+            value: vec![0x00, 0xff, 0x10],
+        };
+
+        assert_eq!("hello", text.value_string());
+        assert_eq!("00ff10", binary.value_string());
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_detect_user_xattr() {
+        use std::ffi::CString;
+        use std::fs::File;
+        use std::os::unix::ffi::OsStrExt;
+        use tempfile::tempdir;
+
+        let tmp_dir = tempdir().expect("failed to create temp dir");
+        let file_path = tmp_dir.path().join("file.txt");
+        File::create(&file_path).expect("failed to create file");
+
+        let c_path = CString::new(file_path.as_os_str().as_bytes()).unwrap();
+        let c_name = CString::new("user.comment").unwrap();
+        let value = b"hello";
+        let res = unsafe {
+            libc::lsetxattr(
+                c_path.as_ptr(),
+                c_name.as_ptr(),
+                value.as_ptr() as *const _,
+                value.len(),
+                0,
+            )
+        };
+        if res != 0 {
+            // The filesystem of the temporary directory doesn't support the
+            // user extended attributes.
+            return;
+        }
+
+        // The attributes are only read for `--extended`.
+        assert_eq!("", AccessControl::for_path(&file_path, false).method_char());
+
+        let access_control = AccessControl::for_path(&file_path, true);
+
+        assert_eq!("@", access_control.method_char());
+        assert_eq!(
+            &[Xattr {
+                name: String::from("user.comment"),
+                value: value.to_vec(),
+            }],
+            access_control.xattrs()
+        );
+    }
+}
@@ -0,0 +1,89 @@
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// An entry that could not be accessed while gathering the metadata.
+#[derive(Debug)]
+pub struct AccessError {
+    path: PathBuf,
+    error: io::Error,
+}
+
+impl AccessError {
+    pub fn new(path: &Path, error: io::Error) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            error,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Report `errors` at once: the error itself when there is only one, or
+    /// their count followed by one line per entry.
+    pub fn summary(errors: &[AccessError]) -> String {
+        match errors {
+            [] => String::new(),
+            [error] => format!("{}\n", error),
+            _ => {
+                let mut output = format!("cannot access {} entries:\n", errors.len());
+                for error in errors {
+                    output += &format!("    '{}': {}\n", error.path.display(), error.error);
+                }
+                output
+            }
+        }
+    }
+}
+
+impl fmt::Display for AccessError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "cannot access '{}': {}", self.path.display(), self.error)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::AccessError;
+    use std::io;
+    use std::path::Path;
+
+    #[test]
+    fn test_display_access_error() {
+        let error = AccessError::new(
+            Path::new("some/dir"),
+            io::Error::new(io::ErrorKind::PermissionDenied, "Permission denied"),
+        );
+
+        assert_eq!(
+            "cannot access 'some/dir': Permission denied",
+            error.to_string()
+        );
+    }
+
+    #[test]
+    fn test_summary_of_several_errors() {
+        let errors = vec![
+            AccessError::new(
+                Path::new("one"),
+                io::Error::new(io::ErrorKind::PermissionDenied, "Permission denied"),
+            ),
+            AccessError::new(
+                Path::new("two"),
+                io::Error::new(io::ErrorKind::NotFound, "No such file or directory"),
+            ),
+        ];
+
+        assert_eq!("", AccessError::summary(&[]));
+        assert_eq!(
+            "cannot access 'one': Permission denied\n",
+            AccessError::summary(&errors[..1])
+        );
+        assert_eq!(
+            "cannot access 2 entries:\n    'one': Permission denied\n    'two': No such file or directory\n",
+            AccessError::summary(&errors)
+        );
+    }
+}
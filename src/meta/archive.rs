@@ -0,0 +1,371 @@
+use flate2::read::MultiGzDecoder;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::Path;
+
+const S_IFMT: u32 = 0o170_000;
+const S_IFLNK: u32 = 0o120_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EntryKind {
+    File,
+    Directory,
+    SymLink,
+}
+
+/// An entry at the top of an archive, as recorded by it. The directories
+/// only implied by the paths of their content are listed too.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ArchiveEntry {
+    pub name: String,
+    pub kind: EntryKind,
+    pub size: u64,
+    pub mode: Option<u32>,
+    pub modified: Option<i64>,
+    /// The names of the user and group, only recorded by the tarballs.
+    pub owner: Option<(String, String)>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Format {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+// An entry of the archive with its whole path.
+#[derive(Debug)]
+struct Record {
+    path: String,
+    kind: EntryKind,
+    size: u64,
+    mode: Option<u32>,
+    modified: Option<i64>,
+    owner: Option<(String, String)>,
+}
+
+/// Whether the content of `path` can be listed, from its extension.
+pub fn is_archive(path: &Path) -> bool {
+    format_of(path).is_some()
+}
+
+/// Read the entries at the top of the zip or tar archive at `path`, without
+/// extracting them.
+pub fn read(path: &Path) -> io::Result<Vec<ArchiveEntry>> {
+    let records = match format_of(path) {
+        Some(Format::Zip) => zip_records(File::open(path)?)?,
+        Some(Format::Tar) => tar_records(BufReader::new(File::open(path)?))?,
+        Some(Format::TarGz) => tar_records(MultiGzDecoder::new(BufReader::new(File::open(path)?)))?,
+        None => Vec::new(),
+    };
+
+    Ok(top_level(records))
+}
+
+fn format_of(path: &Path) -> Option<Format> {
+    let name = path.file_name()?.to_str()?.to_lowercase();
+
+    if name.ends_with(".zip") {
+        Some(Format::Zip)
+    } else if name.ends_with(".tar") {
+        Some(Format::Tar)
+    } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        Some(Format::TarGz)
+    } else {
+        None
+    }
+}
+
+/// The entries at the top of the archive, the deeper records only telling
+/// which directories exist.
+fn top_level(records: Vec<Record>) -> Vec<ArchiveEntry> {
+    let mut entries = BTreeMap::new();
+
+    for record in records {
+        let mut components = record
+            .path
+            .split('/')
+            .filter(|component| !component.is_empty() && *component != ".");
+        let name = match components.next() {
+            Some(name) => name.to_string(),
+            None => continue,
+        };
+
+        if components.next().is_some() {
+            entries.entry(name.clone()).or_insert_with(|| ArchiveEntry {
+                name,
+                kind: EntryKind::Directory,
+                size: 0,
+                mode: None,
+                modified: None,
+                owner: None,
+            });
+            continue;
+        }
+
+        // An archive may record the same entry several times, the last wins.
+        let kind = match entries.get(&name) {
+            Some(entry) if entry.kind == EntryKind::Directory => EntryKind::Directory,
+            _ => record.kind,
+        };
+        entries.insert(
+            name.clone(),
+            ArchiveEntry {
+                name,
+                kind,
+                size: record.size,
+                mode: record.mode,
+                modified: record.modified,
+                owner: record.owner,
+            },
+        );
+    }
+
+    entries.into_values().collect()
+}
+
+fn tar_records<R: Read>(reader: R) -> io::Result<Vec<Record>> {
+    let mut archive = tar::Archive::new(reader);
+    let mut records = Vec::new();
+
+    for entry in archive.entries()? {
+        let entry = entry?;
+        let header = entry.header();
+        let kind = match header.entry_type() {
+            tar::EntryType::Directory => EntryKind::Directory,
+            tar::EntryType::Symlink => EntryKind::SymLink,
+            // The global pax headers apply to the whole archive.
+            tar::EntryType::XGlobalHeader => continue,
+            _ => EntryKind::File,
+        };
+
+        let name = |name: Result<Option<&str>, _>, id: io::Result<u64>| match name {
+            Ok(Some(name)) if !name.is_empty() => name.to_string(),
+            _ => id.unwrap_or(0).to_string(),
+        };
+        let owner = (
+            name(header.username(), header.uid()),
+            name(header.groupname(), header.gid()),
+        );
+
+        records.push(Record {
+            path: entry.path()?.to_string_lossy().into_owned(),
+            kind,
+            size: entry.size(),
+            mode: header.mode().ok().map(|mode| mode & 0o7777),
+            modified: header.mtime().ok().map(|time| time as i64),
+            owner: Some(owner),
+        });
+    }
+
+    Ok(records)
+}
+
+// The entries of a zip file are listed by its central directory, at its end.
+fn zip_records(file: File) -> io::Result<Vec<Record>> {
+    let mut archive = zip::ZipArchive::new(file).map_err(io::Error::from)?;
+    let mut records = Vec::with_capacity(archive.len());
+
+    for idx in 0..archive.len() {
+        // The raw entries are not decompressed.
+        let entry = archive.by_index_raw(idx).map_err(io::Error::from)?;
+        let mode = entry.unix_mode();
+        let modified = entry.last_modified();
+
+        records.push(Record {
+            path: entry.name().to_string(),
+            kind: if entry.is_dir() {
+                EntryKind::Directory
+            } else if mode.map(|mode| mode & S_IFMT == S_IFLNK) == Some(true) {
+                EntryKind::SymLink
+            } else {
+                EntryKind::File
+            },
+            size: entry.size(),
+            mode: mode.map(|mode| mode & 0o7777),
+            modified: dos_time(modified.datepart(), modified.timepart()),
+            owner: None,
+        });
+    }
+
+    Ok(records)
+}
+
+/// The MS-DOS date and time of the zip entries, written in the local time of
+/// the archiver, here taken as the one of the user.
+fn dos_time(date: u16, time: u16) -> Option<i64> {
+    let year = 1980 + i64::from(date >> 9);
+    let month = i64::from((date >> 5) & 0xf);
+    let day = i64::from(date & 0x1f);
+    match (month, day) {
+        (1..=12, 1..=31) => (),
+        _ => return None,
+    }
+
+    // The days since the epoch of the proleptic Gregorian calendar, counting
+    // the years from March so that the leap days come last.
+    let (year, month) = if month <= 2 {
+        (year - 1, month + 9)
+    } else {
+        (year, month - 3)
+    };
+    let era = year / 400;
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * month + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+
+    let seconds = i64::from(time >> 11) * 3600
+        + i64::from((time >> 5) & 0x3f) * 60
+        + i64::from(time & 0x1f) * 2;
+
+    Some(days * 86400 + seconds - i64::from(time::now().tm_utcoff))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{dos_time, read, top_level, ArchiveEntry, EntryKind, Record};
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::fs::{self, File};
+    use std::path::Path;
+    use tempfile::tempdir;
+
+    fn tar_header(type_flag: tar::EntryType, size: u64) -> tar::Header {
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(type_flag);
+        header.set_size(size);
+        header.set_mode(0o644);
+        header.set_uid(1000);
+        header.set_gid(1000);
+        header.set_mtime(1_476_395_008);
+        header
+    }
+
+    #[test]
+    fn test_tar_entries() {
+        let tmp_dir = tempdir().expect("failed to create temp dir");
+        let path = tmp_dir.path().join("archive.tar.gz");
+        let long_name = format!("{}.txt", "x".repeat(120));
+
+        let gz = GzEncoder::new(File::create(&path).unwrap(), Compression::default());
+        let mut builder = tar::Builder::new(gz);
+        let mut dir = tar_header(tar::EntryType::Directory, 0);
+        builder.append_data(&mut dir, "dir/", &[][..]).unwrap();
+        let mut file = tar_header(tar::EntryType::Regular, 5);
+        builder
+            .append_data(&mut file, "dir/file.txt", &b"hello"[..])
+            .unwrap();
+        let mut long = tar_header(tar::EntryType::Regular, 5);
+        builder
+            .append_data(&mut long, &long_name, &b"hello"[..])
+            .unwrap();
+        builder.into_inner().unwrap().finish().unwrap();
+
+        let entries = read(&path).expect("failed to read the archive");
+
+        let names: Vec<&str> = entries.iter().map(|entry| entry.name.as_str()).collect();
+        assert_eq!(vec!["dir", long_name.as_str()], names);
+        assert_eq!(EntryKind::Directory, entries[0].kind);
+        assert_eq!(5, entries[1].size);
+        assert_eq!(Some(0o644), entries[1].mode);
+        assert_eq!(Some(1_476_395_008), entries[1].modified);
+        assert_eq!(
+            Some((String::from("1000"), String::from("1000"))),
+            entries[1].owner
+        );
+
+        let invalid = tmp_dir.path().join("invalid.tar");
+        fs::write(&invalid, &[1; 512][..]).unwrap();
+        assert!(read(&invalid).is_err());
+    }
+
+    #[test]
+    fn test_only_the_top_level_entries() {
+        let record = |path: &str, kind| Record {
+            path: path.to_string(),
+            kind,
+            size: 1,
+            mode: None,
+            modified: None,
+            owner: None,
+        };
+        let entries = top_level(vec![
+            record("top.txt", EntryKind::File),
+            record("./implied/file.txt", EntryKind::File),
+            record("./implied/deeper/file.txt", EntryKind::File),
+        ]);
+
+        assert_eq!(
+            vec![
+                ArchiveEntry {
+                    name: String::from("implied"),
+                    kind: EntryKind::Directory,
+                    size: 0,
+                    mode: None,
+                    modified: None,
+                    owner: None,
+                },
+                ArchiveEntry {
+                    name: String::from("top.txt"),
+                    kind: EntryKind::File,
+                    size: 1,
+                    mode: None,
+                    modified: None,
+                    owner: None,
+                },
+            ],
+            entries
+        );
+    }
+
+    #[test]
+    fn test_zip_entries() {
+        // The entries `dir/` and `dir/file.txt`, holding `hello`, both dated
+        // 2020-01-02 03:04:06, as stored by `zip` on Unix.
+        let zip = [
+            0x50, 0x4b, 0x03, 0x04, 0x14, 0x00, 0x00, 0x00, 0x00, 0x00, 0x83, 0x18, 0x22, 0x50,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x04, 0x00,
+            0x00, 0x00, 0x64, 0x69, 0x72, 0x2f, 0x50, 0x4b, 0x03, 0x04, 0x14, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x83, 0x18, 0x22, 0x50, 0x86, 0xa6, 0x10, 0x36, 0x05, 0x00, 0x00, 0x00,
+            0x05, 0x00, 0x00, 0x00, 0x0c, 0x00, 0x00, 0x00, 0x64, 0x69, 0x72, 0x2f, 0x66, 0x69,
+            0x6c, 0x65, 0x2e, 0x74, 0x78, 0x74, 0x68, 0x65, 0x6c, 0x6c, 0x6f, 0x50, 0x4b, 0x01,
+            0x02, 0x14, 0x03, 0x14, 0x00, 0x00, 0x00, 0x00, 0x00, 0x83, 0x18, 0x22, 0x50, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0xed, 0x41, 0x00, 0x00, 0x00,
+            0x00, 0x64, 0x69, 0x72, 0x2f, 0x50, 0x4b, 0x01, 0x02, 0x14, 0x03, 0x14, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x83, 0x18, 0x22, 0x50, 0x86, 0xa6, 0x10, 0x36, 0x05, 0x00, 0x00,
+            0x00, 0x05, 0x00, 0x00, 0x00, 0x0c, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0xa4, 0x81, 0x22, 0x00, 0x00, 0x00, 0x64, 0x69, 0x72, 0x2f, 0x66,
+            0x69, 0x6c, 0x65, 0x2e, 0x74, 0x78, 0x74, 0x50, 0x4b, 0x05, 0x06, 0x00, 0x00, 0x00,
+            0x00, 0x02, 0x00, 0x02, 0x00, 0x6c, 0x00, 0x00, 0x00, 0x51, 0x00, 0x00, 0x00, 0x00,
+            0x00,
+        ];
+        let tmp_dir = tempdir().expect("failed to create temp dir");
+        let path = tmp_dir.path().join("archive.ZIP");
+        fs::write(&path, &zip[..]).expect("failed to write file");
+
+        let entries = read(&path).expect("failed to read the archive");
+
+        assert_eq!(1, entries.len());
+        assert_eq!("dir", entries[0].name);
+        assert_eq!(EntryKind::Directory, entries[0].kind);
+        assert_eq!(Some(0o755), entries[0].mode);
+        assert_eq!(dos_time(0x5022, 0x1883), entries[0].modified);
+        assert!(read(&tmp_dir.path().join("missing.zip")).is_err());
+        assert!(read(Path::new("Cargo.toml")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_dos_time() {
+        let utc_offset = i64::from(time::now().tm_utcoff);
+
+        assert_eq!(Some(315_532_800 - utc_offset), dos_time(0x21, 0));
+        assert_eq!(
+            Some(1_577_934_246 - utc_offset),
+            dos_time((40 << 9) | (1 << 5) | 2, (3 << 11) | (4 << 5) | 3)
+        );
+        assert_eq!(None, dos_time(0, 0));
+    }
+}
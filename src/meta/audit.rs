@@ -0,0 +1,232 @@
+use crate::color::{Colors, Elem};
+use crate::flags::AuditRule;
+use crate::meta::{FileType, Owner, Permissions};
+use std::path::Path;
+
+/// The risky permissions of an entry, as found by the rules of `--audit`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Audit {
+    findings: Vec<AuditRule>,
+}
+
+impl Audit {
+    /// Check the entry against `rules`, `parent_uid` being the owner of the
+    /// directory holding it.
+    pub fn new(
+        rules: &[AuditRule],
+        file_type: FileType,
+        permissions: &Permissions,
+        owner: &Owner,
+        parent_uid: Option<u32>,
+    ) -> Self {
+        let findings = rules
+            .iter()
+            .cloned()
+            .filter(|rule| match *rule {
+                // The symlinks are always writable, and the sticky directories
+                // like `/tmp` only let their owners delete the entries.
+                AuditRule::WorldWritable => match file_type {
+                    FileType::SymLink { .. } => false,
+                    FileType::Directory { .. } => permissions.other_write && !permissions.sticky,
+                    _ => permissions.other_write,
+                },
+                AuditRule::Setuid => match file_type {
+                    FileType::File { .. } => permissions.setuid,
+                    _ => false,
+                },
+                AuditRule::ForeignOwner => match (owner.uid(), parent_uid) {
+                    (Some(uid), Some(parent_uid)) => uid != parent_uid,
+                    _ => false,
+                },
+            })
+            .collect();
+
+        Self { findings }
+    }
+
+    /// The owner of the directory holding `path`.
+    #[cfg(unix)]
+    pub fn parent_uid(path: &Path) -> Option<u32> {
+        use std::os::unix::fs::MetadataExt;
+
+        let parent = match (path.file_name(), path.parent()) {
+            (Some(_), Some(parent)) if parent != Path::new("") => parent.to_path_buf(),
+            (Some(_), _) => Path::new(".").to_path_buf(),
+            // The paths like `.` or `/` are their own name.
+            (None, _) => path.join(".."),
+        };
+
+        parent.metadata().ok().map(|meta| meta.uid())
+    }
+
+    #[cfg(not(unix))]
+    pub fn parent_uid(_: &Path) -> Option<u32> {
+        None
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.findings.is_empty()
+    }
+
+    pub fn findings_string(&self) -> String {
+        let names: Vec<&str> = self.findings.iter().map(AuditRule::name).collect();
+        names.join(", ")
+    }
+
+    /// The line under the entry telling what the audit found, if anything.
+    pub fn render(&self, colors: &Colors, prefix: &str) -> String {
+        if self.is_empty() {
+            return String::new();
+        }
+
+        let line = format!("! {}", self.findings_string());
+        format!("{}    {}\n", prefix, colors.colorize(line, &Elem::Audit))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Audit;
+    use crate::color::{Colors, Theme};
+    use crate::flags::AuditRule;
+    use crate::meta::{FileType, Owner, Permissions};
+
+    const ALL_RULES: [AuditRule; 3] = [
+        AuditRule::WorldWritable,
+        AuditRule::Setuid,
+        AuditRule::ForeignOwner,
+    ];
+
+    fn permissions(other_write: bool, sticky: bool, setuid: bool) -> Permissions {
+        Permissions {
+            user_read: true,
+            user_write: true,
+            user_execute: true,
+            group_read: true,
+            group_write: false,
+            group_execute: true,
+            other_read: true,
+            other_write,
+            other_execute: true,
+            sticky,
+            setgid: false,
+            setuid,
+            attributes: None,
+        }
+    }
+
+    fn owner() -> Owner {
+        Owner::new(String::from("root"), String::from("root"))
+    }
+
+    #[test]
+    fn test_world_writable() {
+        let file = FileType::File {
+            uid: false,
+            exec: false,
+        };
+        let dir = FileType::Directory { uid: false };
+        let audit = |file_type, permissions| {
+            Audit::new(&ALL_RULES, file_type, &permissions, &owner(), None).findings_string()
+        };
+
+        assert_eq!(
+            "world-writable",
+            audit(file, permissions(true, false, false))
+        );
+        assert_eq!(
+            "world-writable",
+            audit(dir, permissions(true, false, false))
+        );
+        assert_eq!("", audit(dir, permissions(true, true, false)));
+        assert_eq!(
+            "",
+            audit(
+                FileType::SymLink { is_dir: false },
+                permissions(true, false, false)
+            )
+        );
+    }
+
+    #[test]
+    fn test_only_the_given_rules_apply() {
+        let file = FileType::File {
+            uid: true,
+            exec: true,
+        };
+        let permissions = permissions(true, false, true);
+
+        assert_eq!(
+            "world-writable, setuid",
+            Audit::new(&ALL_RULES, file, &permissions, &owner(), None).findings_string()
+        );
+        assert_eq!(
+            "setuid",
+            Audit::new(&[AuditRule::Setuid], file, &permissions, &owner(), None).findings_string()
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_foreign_owner() {
+        use std::os::unix::fs::MetadataExt;
+        use tempfile::tempdir;
+
+        let tmp_dir = tempdir().expect("failed to create temp dir");
+        let meta = tmp_dir.path().metadata().expect("failed to get metadata");
+        let owner = Owner::without_names(&meta);
+        let dir = FileType::Directory { uid: false };
+        let permissions = permissions(false, false, false);
+
+        let audit = |parent_uid| {
+            Audit::new(&ALL_RULES, dir, &permissions, &owner, parent_uid).findings_string()
+        };
+        assert_eq!("", audit(Some(meta.uid())));
+        assert_eq!("foreign-owner", audit(Some(meta.uid() + 1)));
+        assert_eq!("", audit(None));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_parent_uid() {
+        use std::fs::File;
+        use std::os::unix::fs::MetadataExt;
+        use tempfile::tempdir;
+
+        let tmp_dir = tempdir().expect("failed to create temp dir");
+        let path = tmp_dir.path().join("file");
+        File::create(&path).expect("failed to create file");
+        let uid = tmp_dir
+            .path()
+            .metadata()
+            .expect("failed to get metadata")
+            .uid();
+
+        assert_eq!(Some(uid), Audit::parent_uid(&path));
+    }
+
+    #[test]
+    fn test_render_nothing_found() {
+        let colors = Colors::new(Theme::NoColor);
+
+        assert_eq!("", Audit::default().render(&colors, ""));
+    }
+
+    #[test]
+    fn test_render_findings() {
+        let colors = Colors::new(Theme::NoColor);
+        let file = FileType::File {
+            uid: false,
+            exec: false,
+        };
+        let audit = Audit::new(
+            &ALL_RULES,
+            file,
+            &permissions(true, false, false),
+            &owner(),
+            None,
+        );
+
+        assert_eq!("│    ! world-writable\n", audit.render(&colors, "│"));
+    }
+}
@@ -0,0 +1,145 @@
+use crate::color::{ColoredString, Colors, Elem};
+use crate::flags::ChecksumFlag;
+use md5::{Digest, Md5};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+// The files are hashed by a few threads, the reads being the bottleneck.
+const HASHING_THREADS: usize = 4;
+
+/// The digest of the content of a regular file, in hexadecimal.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Checksum {
+    digest: Option<String>,
+}
+
+impl Checksum {
+    /// Hash the file at `path`. The checksum is missing when it cannot be read.
+    pub fn for_file(path: &Path, algorithm: ChecksumFlag) -> Self {
+        let digest = File::open(path).and_then(|file| match algorithm {
+            ChecksumFlag::Md5 => digest(file, Md5::new()),
+            ChecksumFlag::Sha256 => digest(file, Sha256::new()),
+        });
+
+        Self {
+            digest: digest.ok().map(|bytes| to_hex(&bytes)),
+        }
+    }
+
+    /// Hash the files at `paths` in parallel.
+    pub fn for_files(paths: Vec<PathBuf>, algorithm: ChecksumFlag) -> HashMap<PathBuf, Self> {
+        let queue = Arc::new(Mutex::new(paths.into_iter()));
+
+        let workers: Vec<_> = (0..HASHING_THREADS)
+            .map(|_| {
+                let queue = Arc::clone(&queue);
+                thread::spawn(move || {
+                    let mut checksums = Vec::new();
+                    // The lock is released by the closure, before the hashing.
+                    while let Some(path) = queue.lock().ok().and_then(|mut paths| paths.next()) {
+                        let checksum = Self::for_file(&path, algorithm);
+                        checksums.push((path, checksum));
+                    }
+                    checksums
+                })
+            })
+            .collect();
+
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().unwrap_or_default())
+            .collect()
+    }
+
+    pub fn value_string(&self) -> String {
+        match &self.digest {
+            Some(digest) => digest.clone(),
+            None => String::from("-"),
+        }
+    }
+
+    pub fn render(&self, colors: &Colors, alignment: usize) -> ColoredString<'static> {
+        let mut res = self.value_string();
+
+        for _ in res.len()..alignment {
+            res.push(' ');
+        }
+
+        colors.colorize(res, &Elem::Checksum)
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn digest<R: Read, D: Digest + Write>(mut reader: R, mut hasher: D) -> io::Result<Vec<u8>> {
+    io::copy(&mut reader, &mut hasher)?;
+    Ok(hasher.finalize().to_vec())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{digest, to_hex, Checksum};
+    use crate::flags::ChecksumFlag;
+    use md5::{Digest, Md5};
+    use sha2::Sha256;
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn md5(data: &[u8]) -> String {
+        to_hex(&digest(data, Md5::new()).unwrap())
+    }
+
+    fn sha256(data: &[u8]) -> String {
+        to_hex(&digest(data, Sha256::new()).unwrap())
+    }
+
+    #[test]
+    fn test_md5() {
+        assert_eq!("d41d8cd98f00b204e9800998ecf8427e", md5(b""));
+        assert_eq!(
+            "9e107d9d372bb6826bd81d3542a419d6",
+            md5(b"The quick brown fox jumps over the lazy dog")
+        );
+        assert_eq!("7707d6ae4e027c70eea2a935c2296f21", md5(&[b'a'; 1_000_000]));
+    }
+
+    #[test]
+    fn test_sha256() {
+        assert_eq!(
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+            sha256(b"")
+        );
+        assert_eq!(
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1",
+            sha256(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq")
+        );
+        assert_eq!(
+            "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0",
+            sha256(&[b'a'; 1_000_000])
+        );
+    }
+
+    #[test]
+    fn test_checksums_of_files() {
+        let tmp_dir = tempdir().expect("failed to create temp dir");
+        let one = tmp_dir.path().join("one");
+        let missing = tmp_dir.path().join("missing");
+        fs::write(&one, b"abc").expect("failed to write file");
+
+        let checksums =
+            Checksum::for_files(vec![one.clone(), missing.clone()], ChecksumFlag::Sha256);
+
+        assert_eq!(
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+            checksums[&one].value_string()
+        );
+        assert_eq!("-", checksums[&missing].value_string());
+    }
+}
@@ -0,0 +1,293 @@
+use crate::color::{ColoredString, Colors, Elem};
+use crate::flags::MimeFlag;
+use crate::meta::{FileType, Name};
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+// The longest signature checked, the one of WebP and WAV, is 12 bytes long.
+const MAGIC_BYTES: u64 = 16;
+
+const BY_EXTENSION: &[(&str, &str)] = &[
+    ("7z", "application/x-7z-compressed"),
+    ("avi", "video/x-msvideo"),
+    ("bmp", "image/bmp"),
+    ("bz2", "application/x-bzip2"),
+    ("c", "text/x-c"),
+    ("cpp", "text/x-c++"),
+    ("css", "text/css"),
+    ("csv", "text/csv"),
+    ("deb", "application/vnd.debian.binary-package"),
+    ("exe", "application/x-msdownload"),
+    ("flac", "audio/flac"),
+    ("gif", "image/gif"),
+    ("go", "text/x-go"),
+    ("gz", "application/gzip"),
+    ("h", "text/x-c"),
+    ("hpp", "text/x-c++"),
+    ("htm", "text/html"),
+    ("html", "text/html"),
+    ("ico", "image/vnd.microsoft.icon"),
+    ("iso", "application/x-iso9660-image"),
+    ("java", "text/x-java"),
+    ("jpeg", "image/jpeg"),
+    ("jpg", "image/jpeg"),
+    ("js", "text/javascript"),
+    ("json", "application/json"),
+    ("md", "text/markdown"),
+    ("mkv", "video/x-matroska"),
+    ("mp3", "audio/mpeg"),
+    ("mp4", "video/mp4"),
+    ("ogg", "audio/ogg"),
+    ("pdf", "application/pdf"),
+    ("png", "image/png"),
+    ("py", "text/x-python"),
+    ("rs", "text/x-rust"),
+    ("sh", "application/x-sh"),
+    ("svg", "image/svg+xml"),
+    ("tar", "application/x-tar"),
+    ("toml", "application/toml"),
+    ("ts", "text/x-typescript"),
+    ("txt", "text/plain"),
+    ("wasm", "application/wasm"),
+    ("wav", "audio/wav"),
+    ("webm", "video/webm"),
+    ("webp", "image/webp"),
+    ("xml", "application/xml"),
+    ("xz", "application/x-xz"),
+    ("yaml", "application/x-yaml"),
+    ("yml", "application/x-yaml"),
+    ("zip", "application/zip"),
+];
+
+// The signatures starting the files, `None` standing for any byte.
+const BY_MAGIC: &[(&[Option<u8>], &str)] = &[
+    (&MAGIC_PNG, "image/png"),
+    (&[Some(0xff), Some(0xd8), Some(0xff)], "image/jpeg"),
+    (&MAGIC_GIF, "image/gif"),
+    (&MAGIC_PDF, "application/pdf"),
+    (
+        &[Some(b'P'), Some(b'K'), Some(3), Some(4)],
+        "application/zip",
+    ),
+    (&[Some(0x1f), Some(0x8b)], "application/gzip"),
+    (&[Some(b'B'), Some(b'Z'), Some(b'h')], "application/x-bzip2"),
+    (&MAGIC_XZ, "application/x-xz"),
+    (
+        &[Some(0x7f), Some(b'E'), Some(b'L'), Some(b'F')],
+        "application/x-executable",
+    ),
+    (
+        &[Some(0), Some(b'a'), Some(b's'), Some(b'm')],
+        "application/wasm",
+    ),
+    (&MAGIC_WEBP, "image/webp"),
+    (&MAGIC_WAV, "audio/wav"),
+    (
+        &[Some(b'O'), Some(b'g'), Some(b'g'), Some(b'S')],
+        "audio/ogg",
+    ),
+    (
+        &[Some(b'f'), Some(b'L'), Some(b'a'), Some(b'C')],
+        "audio/flac",
+    ),
+    (&[Some(b'I'), Some(b'D'), Some(b'3')], "audio/mpeg"),
+    (&[Some(b'#'), Some(b'!')], "text/x-shellscript"),
+];
+
+const MAGIC_PNG: [Option<u8>; 8] = [
+    Some(0x89),
+    Some(b'P'),
+    Some(b'N'),
+    Some(b'G'),
+    Some(b'\r'),
+    Some(b'\n'),
+    Some(0x1a),
+    Some(b'\n'),
+];
+const MAGIC_GIF: [Option<u8>; 4] = [Some(b'G'), Some(b'I'), Some(b'F'), Some(b'8')];
+const MAGIC_PDF: [Option<u8>; 5] = [Some(b'%'), Some(b'P'), Some(b'D'), Some(b'F'), Some(b'-')];
+const MAGIC_XZ: [Option<u8>; 6] = [
+    Some(0xfd),
+    Some(b'7'),
+    Some(b'z'),
+    Some(b'X'),
+    Some(b'Z'),
+    Some(0),
+];
+const MAGIC_WEBP: [Option<u8>; 12] = [
+    Some(b'R'),
+    Some(b'I'),
+    Some(b'F'),
+    Some(b'F'),
+    None,
+    None,
+    None,
+    None,
+    Some(b'W'),
+    Some(b'E'),
+    Some(b'B'),
+    Some(b'P'),
+];
+const MAGIC_WAV: [Option<u8>; 12] = [
+    Some(b'R'),
+    Some(b'I'),
+    Some(b'F'),
+    Some(b'F'),
+    None,
+    None,
+    None,
+    None,
+    Some(b'W'),
+    Some(b'A'),
+    Some(b'V'),
+    Some(b'E'),
+];
+
+/// The MIME type of an entry, like `text/x-rust` or `image/png`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ContentType {
+    mime: Option<&'static str>,
+}
+
+impl ContentType {
+    /// Infer the type of the entry from its extension, or with `MimeFlag::Deep`
+    /// from the first bytes of the files, falling back on the extension.
+    pub fn for_entry(path: &Path, name: &Name, file_type: FileType, mode: MimeFlag) -> Self {
+        let mime = match file_type {
+            FileType::Directory { .. } => Some("inode/directory"),
+            FileType::SymLink { .. } => Some("inode/symlink"),
+            FileType::Pipe => Some("inode/fifo"),
+            FileType::BlockDevice => Some("inode/blockdevice"),
+            FileType::CharDevice => Some("inode/chardevice"),
+            FileType::Socket => Some("inode/socket"),
+            FileType::Special => None,
+            FileType::File { .. } => match mode {
+                MimeFlag::Extension => by_extension(name),
+                MimeFlag::Deep => match read_magic(path) {
+                    Some(bytes) => by_magic(&bytes)
+                        .or_else(|| by_extension(name))
+                        .or_else(|| Some(by_encoding(&bytes))),
+                    None => by_extension(name),
+                },
+            },
+        };
+
+        Self { mime }
+    }
+
+    pub fn value_string(&self) -> String {
+        String::from(self.mime.unwrap_or("-"))
+    }
+
+    pub fn render(&self, colors: &Colors, alignment: usize) -> ColoredString<'static> {
+        let mut res = self.value_string();
+
+        for _ in res.len()..alignment {
+            res.push(' ');
+        }
+
+        colors.colorize(res, &Elem::ContentType)
+    }
+}
+
+fn by_extension(name: &Name) -> Option<&'static str> {
+    let extension = name.extension()?.to_lowercase();
+
+    BY_EXTENSION
+        .iter()
+        .find(|(known, _)| *known == extension)
+        .map(|(_, mime)| *mime)
+}
+
+fn by_magic(bytes: &[u8]) -> Option<&'static str> {
+    BY_MAGIC
+        .iter()
+        .find(|(magic, _)| {
+            magic.len() <= bytes.len()
+                && magic
+                    .iter()
+                    .zip(bytes)
+                    .all(|(expected, byte)| expected.map(|b| b == *byte) != Some(false))
+        })
+        .map(|(_, mime)| *mime)
+}
+
+// Like `file`, tell the text from the binary data with the NUL bytes.
+fn by_encoding(bytes: &[u8]) -> &'static str {
+    if bytes.is_empty() {
+        "inode/x-empty"
+    } else if bytes.contains(&0) {
+        "application/octet-stream"
+    } else {
+        "text/plain"
+    }
+}
+
+fn read_magic(path: &Path) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    File::open(path)
+        .ok()?
+        .take(MAGIC_BYTES)
+        .read_to_end(&mut bytes)
+        .ok()?;
+
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{by_encoding, by_magic, ContentType};
+    use crate::flags::MimeFlag;
+    use crate::meta::{FileType, Name};
+    use std::fs;
+    use std::path::Path;
+    use tempfile::tempdir;
+
+    fn file_type() -> FileType {
+        FileType::File {
+            uid: false,
+            exec: false,
+        }
+    }
+
+    #[test]
+    fn test_mime_by_extension() {
+        let path = Path::new("main.RS");
+        let name = Name::new(path, file_type());
+
+        assert_eq!(
+            "text/x-rust",
+            ContentType::for_entry(path, &name, file_type(), MimeFlag::Extension).value_string()
+        );
+    }
+
+    #[test]
+    fn test_mime_by_magic() {
+        assert_eq!(Some("image/png"), by_magic(b"\x89PNG\r\n\x1a\n...."));
+        assert_eq!(
+            Some("image/webp"),
+            by_magic(b"RIFF\x10\x00\x00\x00WEBPVP8 ")
+        );
+        assert_eq!(None, by_magic(b"RIFF"));
+        assert_eq!("text/plain", by_encoding(b"hello"));
+        assert_eq!("application/octet-stream", by_encoding(b"\x01\x00"));
+    }
+
+    #[test]
+    fn test_deep_mime_reads_the_content() {
+        let tmp_dir = tempdir().expect("failed to create temp dir");
+        let path = tmp_dir.path().join("image.txt");
+        fs::write(&path, b"GIF89a\x01\x00").expect("failed to write file");
+        let name = Name::new(&path, file_type());
+
+        assert_eq!(
+            "text/plain",
+            ContentType::for_entry(&path, &name, file_type(), MimeFlag::Extension).value_string()
+        );
+        assert_eq!(
+            "image/gif",
+            ContentType::for_entry(&path, &name, file_type(), MimeFlag::Deep).value_string()
+        );
+    }
+}
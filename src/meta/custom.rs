@@ -0,0 +1,267 @@
+use crate::color::{ColoredString, Colors, Elem};
+use crate::flags::CustomBlock;
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use unicode_width::UnicodeWidthStr;
+
+// How often a running command is checked for its exit.
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+/// The output of a custom block command for an entry.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CustomValue {
+    value: Option<String>,
+}
+
+impl CustomValue {
+    pub fn new(value: &str) -> Self {
+        Self {
+            value: Some(value.to_string()),
+        }
+    }
+
+    /// Run the command of `block` on the entry at `path`. The value is the
+    /// first line of the trimmed output, missing when the command fails or
+    /// outlives the timeout of the block, with the processes it started.
+    pub fn for_entry(path: &Path, block: &CustomBlock) -> Self {
+        let (program, args) = match block.command.split_first() {
+            Some(command) => command,
+            None => return Self::default(),
+        };
+
+        let mut command = Command::new(program);
+        command
+            .args(args.iter().map(|arg| substitute(arg, path)))
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null());
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::CommandExt;
+
+            // In a group of its own, the command is killed with its children.
+            unsafe {
+                command.pre_exec(|| {
+                    libc::setpgid(0, 0);
+                    Ok(())
+                })
+            };
+        }
+
+        let mut child = match command.spawn() {
+            Ok(child) => child,
+            Err(_) => return Self::default(),
+        };
+
+        // The output is read aside, as a full pipe would block the command.
+        // The children left may keep it open, so it is waited for until the
+        // deadline only.
+        let mut stdout = child.stdout.take();
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let mut output = Vec::new();
+            if let Some(stdout) = &mut stdout {
+                stdout.read_to_end(&mut output).ok();
+            }
+            sender.send(output).ok();
+        });
+
+        let deadline = Instant::now() + block.timeout;
+        let success = loop {
+            match child.try_wait() {
+                Ok(Some(status)) => break status.success(),
+                Ok(None) if Instant::now() < deadline => thread::sleep(POLL_INTERVAL),
+                _ => break false,
+            }
+        };
+
+        let output = if success {
+            receiver
+                .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                .ok()
+        } else {
+            None
+        };
+        let output = match output {
+            Some(output) => output,
+            None => {
+                kill(&mut child);
+                return Self::default();
+            }
+        };
+
+        let output = String::from_utf8_lossy(&output);
+        Self {
+            value: Some(output.trim().lines().next().unwrap_or("").to_string()),
+        }
+    }
+
+    /// Run the commands of `blocks` on the entries at `paths`, with `jobs`
+    /// commands at most running at once.
+    pub fn for_entries(
+        paths: Vec<PathBuf>,
+        blocks: &[CustomBlock],
+        jobs: usize,
+    ) -> HashMap<PathBuf, Vec<Self>> {
+        let queue = Arc::new(Mutex::new(paths.into_iter()));
+        let blocks = Arc::new(blocks.to_vec());
+
+        let workers: Vec<_> = (0..jobs.max(1))
+            .map(|_| {
+                let queue = Arc::clone(&queue);
+                let blocks = Arc::clone(&blocks);
+                thread::spawn(move || {
+                    let mut values = Vec::new();
+                    // The lock is released by the closure, before the commands run.
+                    while let Some(path) = queue.lock().ok().and_then(|mut paths| paths.next()) {
+                        let row = blocks
+                            .iter()
+                            .map(|block| Self::for_entry(&path, block))
+                            .collect();
+                        values.push((path, row));
+                    }
+                    values
+                })
+            })
+            .collect();
+
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().unwrap_or_default())
+            .collect()
+    }
+
+    pub fn value_string(&self) -> String {
+        match &self.value {
+            Some(value) => value.clone(),
+            None => String::from("-"),
+        }
+    }
+
+    pub fn render(&self, colors: &Colors, value_alignment: usize) -> ColoredString<'static> {
+        let mut res = self.value_string();
+
+        for _ in res.width()..value_alignment {
+            res.push(' ');
+        }
+
+        colors.colorize(res, &Elem::Custom)
+    }
+}
+
+/// Kill the command and, on Unix, the processes it started.
+fn kill(child: &mut Child) {
+    #[cfg(unix)]
+    unsafe {
+        libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL)
+    };
+    child.kill().ok();
+    child.wait().ok();
+}
+
+/// The argument `arg` with the `{}` in it replaced by `path`, which is passed
+/// as is when it is the whole argument, like with `find -exec`.
+fn substitute(arg: &str, path: &Path) -> OsString {
+    if arg == "{}" {
+        path.as_os_str().to_os_string()
+    } else {
+        OsString::from(arg.replace("{}", &path.to_string_lossy()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::CustomValue;
+    use crate::color::{Colors, Theme};
+    use crate::flags::CustomBlock;
+    use std::path::Path;
+    use std::time::Duration;
+
+    fn block(command: &[&str], timeout: Duration) -> CustomBlock {
+        CustomBlock {
+            name: String::from("Custom"),
+            command: command.iter().map(|arg| arg.to_string()).collect(),
+            timeout,
+        }
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_value_is_the_trimmed_output() {
+        let block = block(&["echo", "  one {}  "], Duration::from_secs(5));
+
+        assert_eq!(
+            CustomValue::new("one some/file"),
+            CustomValue::for_entry(Path::new("some/file"), &block)
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_failed_command_has_no_value() {
+        let block = block(&["false"], Duration::from_secs(5));
+
+        assert_eq!(
+            CustomValue::default(),
+            CustomValue::for_entry(Path::new("file"), &block)
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_slow_command_is_killed() {
+        let block = block(&["sleep", "5"], Duration::from_millis(50));
+
+        assert_eq!(
+            CustomValue::default(),
+            CustomValue::for_entry(Path::new("file"), &block)
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_children_are_killed_too() {
+        use std::time::Instant;
+
+        let block = block(
+            &["sh", "-c", "sleep 5; echo one"],
+            Duration::from_millis(100),
+        );
+        let start = Instant::now();
+
+        assert_eq!(
+            CustomValue::default(),
+            CustomValue::for_entry(Path::new("file"), &block)
+        );
+        assert!(start.elapsed() < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn test_missing_program_has_no_value() {
+        let block = block(&["lsd-test-missing-program"], Duration::from_secs(5));
+
+        assert_eq!(
+            "-",
+            CustomValue::for_entry(Path::new("file"), &block).value_string()
+        );
+    }
+
+    #[test]
+    fn test_render_pads_the_value() {
+        let colors = Colors::new(Theme::NoColor);
+
+        assert_eq!(
+            "one  ",
+            CustomValue::new("one")
+                .render(&colors, 5)
+                .to_string()
+                .as_str()
+        );
+    }
+}
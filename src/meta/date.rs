@@ -1,60 +1,171 @@
 use crate::color::{ColoredString, Colors, Elem};
-use crate::flags::{DateFlag, Flags};
+use crate::flags::{DateFlag, Flags, TimeFlag};
 use chrono_humanize::HumanTime;
 use std::fs::Metadata;
-use std::time::UNIX_EPOCH;
+use std::time::{SystemTime, UNIX_EPOCH};
 use time::{Duration, Timespec};
+use unicode_width::UnicodeWidthStr;
 
-#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
-pub struct Date(time::Tm);
+// The conversion to the local time is left to the rendering, as the sorts and
+// the filters don't need it. The date is missing when the platform or the
+// filesystem doesn't record it, like the creation time on older Linux.
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Date(Option<Timespec>);
 
 impl<'a> From<&'a Metadata> for Date {
     fn from(meta: &'a Metadata) -> Self {
-        let modified_time = meta.modified().expect("failed to retrieve modified date");
+        Self::for_time(meta, TimeFlag::Modified)
+    }
+}
 
-        let modified_time_since_epoch =
-            modified_time.duration_since(UNIX_EPOCH).unwrap_or_default();
+impl Date {
+    pub fn new(time: time::Tm) -> Self {
+        Date(Some(time.to_timespec()))
+    }
 
-        let time = time::at(Timespec::new(
-            modified_time_since_epoch.as_secs() as i64,
-            modified_time_since_epoch.subsec_nanos() as i32,
-        ));
+    /// The time of the entry selected by `time`.
+    pub fn for_time(meta: &Metadata, time: TimeFlag) -> Self {
+        let system_time = match time {
+            TimeFlag::Modified => meta.modified(),
+            TimeFlag::Created => meta.created(),
+        };
 
-        Date(time)
+        Date(system_time.ok().map(to_timespec))
     }
-}
 
-impl Date {
-    pub fn render(&self, colors: &Colors, date_alignment: usize, flags: &Flags) -> ColoredString {
-        let mut content = String::with_capacity(date_alignment + 1);
-        let now = time::now();
+    /// The number of seconds since the Unix epoch.
+    pub fn epoch(&self) -> Option<i64> {
+        self.0.map(|time| time.sec)
+    }
 
-        let elem;
-        if self.0 > now - Duration::hours(1) {
-            elem = &Elem::HourOld;
-        } else if self.0 > now - Duration::days(1) {
-            elem = &Elem::DayOld;
-        } else {
-            elem = &Elem::Older;
-        }
+    /// The date in UTC as ISO 8601, like `2020-01-02T03:04:05Z`.
+    pub fn iso_string(&self) -> Option<String> {
+        self.0.map(|time| time::at_utc(time).rfc3339().to_string())
+    }
+
+    /// Parse a duration made of a number and a unit among `ms`, `s`, `m`, `h`,
+    /// `d`, `w` and `y`, like `30m` or `2d`. The durations beyond what a
+    /// `Duration` holds are rejected, as it would panic.
+    pub fn parse_duration(input: &str) -> Option<Duration> {
+        let unit_idx = input.find(|c: char| !c.is_ascii_digit())?;
+        let value: i64 = input[..unit_idx].parse().ok()?;
+
+        let unit_millis: i64 = match &input[unit_idx..] {
+            "ms" => 1,
+            "s" => 1000,
+            "m" => 60 * 1000,
+            "h" => 60 * 60 * 1000,
+            "d" => 24 * 60 * 60 * 1000,
+            "w" => 7 * 24 * 60 * 60 * 1000,
+            "y" => 365 * 24 * 60 * 60 * 1000,
+            _ => return None,
+        };
+
+        value.checked_mul(unit_millis).map(Duration::milliseconds)
+    }
+
+    pub fn render(
+        &self,
+        colors: &Colors,
+        date_alignment: usize,
+        flags: &Flags,
+    ) -> ColoredString<'_> {
+        let mut content = String::with_capacity(date_alignment + 1);
+        let elem = &match self.0 {
+            Some(time) => colors.date_elem(time::get_time() - time),
+            None => Elem::NonFile,
+        };
 
-        let date_string = &self.date_string(&flags);
+        let date_string = &self.date_string(flags);
         content += date_string;
 
-        for _ in 0..(date_alignment - date_string.len()) {
+        for _ in date_string.width()..date_alignment {
             content.push(' ');
         }
         colors.colorize(content, elem)
     }
 
     pub fn date_string(&self, flags: &Flags) -> String {
-        match flags.date {
-            DateFlag::Date => self.0.ctime().to_string(),
-            DateFlag::Relative => format!("{}", HumanTime::from(self.0 - time::now())),
+        match (self.0, flags.date) {
+            (None, _) => String::from("-"),
+            (Some(time), DateFlag::Date) | (Some(time), DateFlag::Utc)
+                if flags.date_locale.is_some() =>
+            {
+                let utc = flags.date == DateFlag::Utc;
+                match localized_ctime(time, utc) {
+                    Some(date) if utc => format!("{} UTC", date),
+                    Some(date) => date,
+                    None => time::at(time).ctime().to_string(),
+                }
+            }
+            (Some(time), DateFlag::Date) => time::at(time).ctime().to_string(),
+            (Some(time), DateFlag::Utc) => format!("{} UTC", time::at_utc(time).ctime()),
+            (Some(time), DateFlag::Relative) => {
+                format!("{}", HumanTime::from(time - time::get_time()))
+            }
         }
     }
 }
 
+// Missing from the bindings of the `libc` crate.
+#[cfg(unix)]
+extern "C" {
+    fn strftime(
+        s: *mut libc::c_char,
+        max: libc::size_t,
+        format: *const libc::c_char,
+        tm: *const libc::tm,
+    ) -> libc::size_t;
+}
+
+/// The date like `ctime`, with the names of the days and the months of the
+/// `LC_TIME` locale, as set when the locale of the dates is configured.
+#[cfg(unix)]
+fn localized_ctime(time: Timespec, utc: bool) -> Option<String> {
+    let seconds = time.sec as libc::time_t;
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    let res = unsafe {
+        if utc {
+            libc::gmtime_r(&seconds, &mut tm)
+        } else {
+            libc::localtime_r(&seconds, &mut tm)
+        }
+    };
+    if res.is_null() {
+        return None;
+    }
+
+    let mut buf = [0u8; 128];
+    let format = b"%a %b %e %H:%M:%S %Y\0";
+    let len = unsafe {
+        strftime(
+            buf.as_mut_ptr() as *mut libc::c_char,
+            buf.len(),
+            format.as_ptr() as *const libc::c_char,
+            &tm,
+        )
+    };
+    if len == 0 {
+        return None;
+    }
+
+    Some(String::from_utf8_lossy(&buf[..len]).to_string())
+}
+
+#[cfg(not(unix))]
+fn localized_ctime(_: Timespec, _: bool) -> Option<String> {
+    None
+}
+
+fn to_timespec(time: SystemTime) -> Timespec {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+
+    Timespec::new(
+        since_epoch.as_secs() as i64,
+        since_epoch.subsec_nanos() as i32,
+    )
+}
+
 #[cfg(test)]
 mod test {
     use super::Date;
@@ -72,7 +183,7 @@ mod test {
         Command::new("touch")
             .arg("-t")
             .arg(date.strftime("%Y%m%d%H%M.%S").unwrap().to_string())
-            .arg(&path)
+            .arg(path)
             .status()
     }
 
@@ -188,8 +299,10 @@ mod test {
         let colors = Colors::new(Theme::Default);
         let date = Date::from(&file_path.metadata().unwrap());
 
-        let mut flags = Flags::default();
-        flags.date = DateFlag::Relative;
+        let flags = Flags {
+            date: DateFlag::Relative,
+            ..Flags::default()
+        };
 
         assert_eq!(
             Colour::Fixed(36).paint("2 days ago  "),
@@ -219,13 +332,15 @@ mod test {
             .status()
             .unwrap()
             .success();
-        assert_eq!(true, success, "failed to exec touch");
+        assert!(success, "failed to exec touch");
 
         let colors = Colors::new(Theme::Default);
         let date = Date::from(&file_path.metadata().unwrap());
 
-        let mut flags = Flags::default();
-        flags.date = DateFlag::Relative;
+        let flags = Flags {
+            date: DateFlag::Relative,
+            ..Flags::default()
+        };
 
         assert_eq!(
             Colour::Fixed(40).paint("now  "),
@@ -234,4 +349,88 @@ mod test {
 
         fs::remove_file(file_path).unwrap();
     }
+
+    #[test]
+    fn test_utc_date() {
+        let date = Date::new(time::at_utc(time::Timespec::new(86400, 0)));
+        let flags = Flags {
+            date: DateFlag::Utc,
+            ..Flags::default()
+        };
+
+        assert_eq!("Fri Jan  2 00:00:00 1970 UTC", date.date_string(&flags));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_localized_date_in_the_c_locale() {
+        // The tests don't set the locale, so the names are the ones of `C`.
+        let date = Date::new(time::at_utc(time::Timespec::new(86400, 0)));
+        let flags = Flags {
+            date: DateFlag::Utc,
+            date_locale: Some(String::new()),
+            ..Flags::default()
+        };
+
+        assert_eq!("Fri Jan  2 00:00:00 1970 UTC", date.date_string(&flags));
+    }
+
+    #[test]
+    fn test_missing_date() {
+        let colors = Colors::new(Theme::Default);
+        let date = Date::default();
+
+        assert_eq!(None, date.epoch());
+        assert_eq!(
+            Colour::Fixed(245).paint("-  "),
+            date.render(&colors, 3, &Flags::default())
+        );
+    }
+
+    #[test]
+    fn test_date_color_follows_the_thresholds() {
+        use crate::color::DateThresholds;
+        use crate::config_file::ThemeConfig;
+
+        let config = ThemeConfig {
+            date_thresholds: DateThresholds {
+                hour_old: time::Duration::minutes(5),
+                day_old: time::Duration::weeks(1),
+            },
+            ..ThemeConfig::default()
+        };
+        let colors = Colors::new(Theme::Default).with_config(&config);
+        let flags = Flags::default();
+
+        let date = Date::new(time::now() - time::Duration::minutes(10));
+        let date_string = date.date_string(&flags);
+        assert_eq!(
+            Colour::Fixed(42).paint(date_string.clone()),
+            date.render(&colors, date_string.len(), &flags)
+        );
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(
+            Some(time::Duration::minutes(30)),
+            Date::parse_duration("30m")
+        );
+        assert_eq!(Some(time::Duration::days(14)), Date::parse_duration("2w"));
+        assert_eq!(
+            Some(time::Duration::milliseconds(100)),
+            Date::parse_duration("100ms")
+        );
+        assert_eq!(None, Date::parse_duration("2"));
+        assert_eq!(None, Date::parse_duration("d"));
+        assert_eq!(None, Date::parse_duration("3q"));
+        assert_eq!(
+            Some(time::Duration::days(365 * 3)),
+            Date::parse_duration("3y")
+        );
+        assert_eq!(None, Date::parse_duration("99999999999999999d"));
+        assert_eq!(None, Date::parse_duration("10000000000000000s"));
+        assert_eq!(None, Date::parse_duration("9999999999999y"));
+        assert_eq!(None, Date::parse_duration("99999999999999999999ms"));
+    }
 }
@@ -0,0 +1,96 @@
+use crate::color::{ColoredString, Colors, Elem};
+use std::fs::Metadata;
+
+/// The device of the filesystem an entry resides on.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Device {
+    id: Option<u64>,
+}
+
+impl From<&Metadata> for Device {
+    #[cfg(unix)]
+    fn from(meta: &Metadata) -> Self {
+        use std::os::unix::fs::MetadataExt;
+
+        Self {
+            id: Some(meta.dev()),
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn from(_: &Metadata) -> Self {
+        Self::default()
+    }
+}
+
+impl Device {
+    pub fn new(id: u64) -> Self {
+        Self { id: Some(id) }
+    }
+
+    /// The device number, missing where the platform doesn't expose it.
+    pub fn id(&self) -> Option<u64> {
+        self.id
+    }
+
+    /// The major and minor numbers of the device.
+    pub fn major_minor(&self) -> Option<(u64, u64)> {
+        self.id.map(major_minor)
+    }
+
+    /// The major and minor numbers of the device, like `8:1`.
+    pub fn device_string(&self) -> String {
+        match self.major_minor() {
+            Some((major, minor)) => format!("{}:{}", major, minor),
+            None => String::from("?"),
+        }
+    }
+
+    pub fn render(&self, colors: &Colors, device_alignment: usize) -> ColoredString<'static> {
+        let mut res = self.device_string();
+
+        for _ in res.len()..device_alignment {
+            res.push(' ');
+        }
+
+        colors.colorize(res, &Elem::Device)
+    }
+}
+
+// The encoding of the glibc, able to hold 32 bits major and minor numbers.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn major_minor(id: u64) -> (u64, u64) {
+    let major = ((id >> 8) & 0xfff) | ((id >> 32) & !0xfff);
+    let minor = (id & 0xff) | ((id >> 12) & !0xff);
+
+    (major, minor)
+}
+
+// The encoding of the BSDs, with the major number on the highest byte.
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn major_minor(id: u64) -> (u64, u64) {
+    ((id >> 24) & 0xff, id & 0xff_ffff)
+}
+
+#[cfg(test)]
+mod test {
+    use super::Device;
+    use crate::color::{Colors, Theme};
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_device_string() {
+        assert_eq!("8:1", Device::new(0x801).device_string());
+        assert_eq!("259:65536", Device::new(0x1001_0300).device_string());
+    }
+
+    #[test]
+    fn test_render_missing_device() {
+        let colors = Colors::new(Theme::NoColor);
+
+        assert_eq!(
+            "?   ",
+            Device::default().render(&colors, 4).to_string().as_str()
+        );
+    }
+}
@@ -0,0 +1,209 @@
+use super::{AccessError, Details, Meta};
+use crate::flags::{Display, Flags};
+use std::fs::ReadDir;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+
+/// Whether the entry is listed with `flags`, the one check every entry goes
+/// through, `.` and `..` included. The ignore globs win over `--all` and
+/// `--almost-all`, which only reveal the hidden entries.
+pub fn is_listed(meta: &Meta, flags: &Flags) -> bool {
+    if flags.ignore_globs.is_match(meta.name.raw_name()) {
+        return false;
+    }
+
+    match flags.display {
+        Display::DisplayOnlyVisible => !meta.is_hidden(),
+        _ => true,
+    }
+}
+
+/// The entries of a directory, read one at a time and without their content,
+/// skipping the ones hidden by `flags`.
+pub struct Entries<'a> {
+    dir: &'a Path,
+    flags: &'a Flags,
+    details: Details,
+    read_dir: ReadDir,
+}
+
+impl<'a> Entries<'a> {
+    pub fn new(dir: &'a Path, flags: &'a Flags) -> Result<Self, AccessError> {
+        match dir.read_dir() {
+            Ok(read_dir) => Ok(Self {
+                dir,
+                flags,
+                details: Details::for_flags(flags),
+                read_dir,
+            }),
+            Err(err) => Err(AccessError::new(dir, err)),
+        }
+    }
+}
+
+impl<'a> Iterator for Entries<'a> {
+    type Item = Result<Meta, AccessError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let entry = match self.read_dir.next()? {
+                Ok(entry) => entry,
+                Err(err) => return Some(Err(AccessError::new(self.dir, err))),
+            };
+            let path = entry.path();
+
+            let name = match path.file_name() {
+                Some(name) => name,
+                None => {
+                    let err = Error::new(ErrorKind::InvalidInput, "invalid file name");
+                    return Some(Err(AccessError::new(&path, err)));
+                }
+            };
+
+            // The ignored entries and the dot files hidden by the flags
+            // aren't even read.
+            if self.flags.ignore_globs.is_match(name) {
+                continue;
+            }
+            if self.flags.display == Display::DisplayOnlyVisible
+                && name.to_string_lossy().starts_with('.')
+            {
+                continue;
+            }
+
+            // The type recorded by the directory is enough when nothing else
+            // is displayed, but the symlinks still need their target. The
+            // colors only add the mode of the files and directories.
+            #[cfg(unix)]
+            {
+                if !self.details.metadata {
+                    match entry.file_type() {
+                        Ok(file_type) if !file_type.is_symlink() => {
+                            let meta = if self.details.mode
+                                && (file_type.is_file() || file_type.is_dir())
+                            {
+                                match Meta::from_mode(&path, file_type, self.details) {
+                                    Ok(meta) => meta,
+                                    Err(err) => return Some(Err(AccessError::new(&path, err))),
+                                }
+                            } else {
+                                Meta::from_file_type(&path, file_type)
+                            };
+                            if is_listed(&meta, self.flags) {
+                                return Some(Ok(meta));
+                            }
+                            continue;
+                        }
+                        _ => {}
+                    }
+                }
+            }
+
+            match Meta::from_path_with(&path, self.flags.dereference, self.details) {
+                Ok(meta) if is_listed(&meta, self.flags) => return Some(Ok(meta)),
+                Ok(_) => continue,
+                Err(err) => return Some(Err(AccessError::new(&path, err))),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::is_listed;
+    use crate::app;
+    use crate::flags::Flags;
+    use crate::meta::{FileFlags, Meta};
+    use std::fs::File;
+    use tempfile::tempdir;
+
+    fn flags(args: Vec<&str>) -> Flags {
+        let matches = app::build().get_matches_from_safe(args).unwrap();
+        Flags::from_matches(&matches).unwrap()
+    }
+
+    #[test]
+    fn test_all_reveals_the_hidden_entries() {
+        let tmp_dir = tempdir().expect("failed to create temp dir");
+        let meta = |name: &str| {
+            let path = tmp_dir.path().join(name);
+            File::create(&path).expect("failed to create file");
+            Meta::from_path(&path).expect("failed to get meta")
+        };
+        let file = meta("file");
+        let dotfile = meta(".file");
+        // Like a file hidden from the Finder of macOS.
+        let mut hidden = meta("hidden");
+        hidden.file_flags = FileFlags::new(0x8000);
+        let mut parent = meta("parent");
+        parent.name.rename("..");
+
+        let visible = flags(vec!["lsd"]);
+        assert!(is_listed(&file, &visible));
+        assert!(!is_listed(&dotfile, &visible));
+        assert!(!is_listed(&hidden, &visible));
+
+        let all = flags(vec!["lsd", "-a"]);
+        assert!(is_listed(&dotfile, &all));
+        assert!(is_listed(&parent, &all));
+        assert!(is_listed(&hidden, &all));
+
+        // The ignore globs win over `--all`.
+        let ignored = flags(vec!["lsd", "-a", "-I", ".."]);
+        assert!(!is_listed(&parent, &ignored));
+        assert!(is_listed(&dotfile, &ignored));
+    }
+
+    #[test]
+    fn test_dotfiles_are_skipped_by_name() {
+        use super::Entries;
+
+        let tmp_dir = tempdir().expect("failed to create temp dir");
+        File::create(tmp_dir.path().join("file")).expect("failed to create file");
+        File::create(tmp_dir.path().join(".file")).expect("failed to create file");
+
+        let names = |flags: &Flags| -> Vec<String> {
+            let mut names: Vec<String> = Entries::new(tmp_dir.path(), flags)
+                .expect("failed to read dir")
+                .map(|meta| meta.expect("failed to get meta").name.name())
+                .collect();
+            names.sort();
+            names
+        };
+
+        assert_eq!(vec!["file"], names(&flags(vec!["lsd"])));
+        assert_eq!(vec![".file", "file"], names(&flags(vec!["lsd", "-A"])));
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_colors_only_read_the_mode() {
+        use super::Entries;
+        use crate::meta::FileType;
+        use std::fs;
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp_dir = tempdir().expect("failed to create temp dir");
+        let path = tmp_dir.path().join("script");
+        fs::write(&path, "#!/bin/sh").expect("failed to create file");
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755))
+            .expect("failed to set permissions");
+
+        let flags = flags(vec!["lsd", "--color", "always", "--executable", "mode"]);
+        let metas: Vec<Meta> = Entries::new(tmp_dir.path(), &flags)
+            .expect("failed to read dir")
+            .map(|meta| meta.expect("failed to get meta"))
+            .collect();
+
+        assert_eq!(1, metas.len());
+        assert_eq!(
+            FileType::File {
+                uid: false,
+                exec: true
+            },
+            metas[0].file_type
+        );
+        // The rest of the metadata isn't read.
+        assert_eq!(0, metas[0].size.get_bytes());
+    }
+}
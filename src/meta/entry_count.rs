@@ -0,0 +1,165 @@
+use crate::color::{ColoredString, Colors, Elem};
+use crate::flags::{Flags, SizeFlag};
+use std::fs;
+use std::path::Path;
+
+/// The number of entries inside a directory.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EntryCount {
+    count: Option<usize>,
+}
+
+impl EntryCount {
+    pub fn new(count: usize) -> Self {
+        Self { count: Some(count) }
+    }
+
+    /// Count the entries of the directory at `path`, hidden ones included. The
+    /// count is missing when the directory cannot be read.
+    pub fn for_dir(path: &Path) -> Self {
+        match fs::read_dir(path) {
+            Ok(entries) => Self::new(entries.count()),
+            Err(_) => Self::default(),
+        }
+    }
+
+    pub fn value_string(&self) -> String {
+        match self.count {
+            Some(count) => count.to_string(),
+            None => String::from("-"),
+        }
+    }
+
+    pub fn render(&self, colors: &Colors, count_alignment: usize) -> ColoredString<'_> {
+        let value = self.value_string();
+
+        let mut res = String::with_capacity(count_alignment);
+        for _ in value.len()..count_alignment {
+            res.push(' ');
+        }
+        res += &value;
+
+        colors.colorize(res, &Elem::EntryCount)
+    }
+
+    /// The word after the count shown in the size block, like `42 items`.
+    fn unit_string(&self, flags: &Flags) -> String {
+        let unit = match self.count {
+            Some(1) => "item",
+            Some(_) => "items",
+            None => "",
+        };
+
+        // The bytes have no unit, and so no separator before it.
+        if flags.size == SizeFlag::Default || unit.is_empty() {
+            unit.to_string()
+        } else {
+            format!(" {}", unit)
+        }
+    }
+
+    /// The widths of the count and its unit, in the sub-columns of the sizes.
+    pub fn size_lengths(&self, flags: &Flags) -> (usize, usize, usize) {
+        (self.value_string().len(), 0, self.unit_string(flags).len())
+    }
+
+    /// Render the count in place of a size, aligned like the sizes.
+    pub fn render_as_size(
+        &self,
+        colors: &Colors,
+        alignment: (usize, usize, usize),
+        flags: &Flags,
+    ) -> ColoredString<'static> {
+        let (integer_alignment, fraction_alignment, unit_alignment) = alignment;
+        let value = self.value_string();
+        let unit = self.unit_string(flags);
+
+        let mut res =
+            String::with_capacity(integer_alignment + fraction_alignment + unit_alignment + 1);
+        for _ in value.len()..integer_alignment {
+            res.push(' ');
+        }
+        res += &value;
+        for _ in 0..fraction_alignment {
+            res.push(' ');
+        }
+        if flags.size == SizeFlag::Default {
+            res.push(' ');
+        }
+        res += &unit;
+        for _ in unit.len()..unit_alignment {
+            res.push(' ');
+        }
+
+        colors.colorize(res, &Elem::EntryCount)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::EntryCount;
+    use crate::color::{Colors, Theme};
+    use crate::flags::{Flags, SizeFlag};
+    use std::fs::File;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_count_dir_entries() {
+        let tmp_dir = tempdir().expect("failed to create temp dir");
+        File::create(tmp_dir.path().join("one")).expect("failed to create file");
+        File::create(tmp_dir.path().join(".hidden")).expect("failed to create file");
+
+        assert_eq!(EntryCount::new(2), EntryCount::for_dir(tmp_dir.path()));
+    }
+
+    #[test]
+    fn test_render_is_right_aligned() {
+        let colors = Colors::new(Theme::NoColor);
+
+        assert_eq!(
+            "  42",
+            EntryCount::new(42).render(&colors, 4).to_string().as_str()
+        );
+        assert_eq!(
+            "  -",
+            EntryCount::default()
+                .render(&colors, 3)
+                .to_string()
+                .as_str()
+        );
+    }
+
+    #[test]
+    fn test_render_as_size() {
+        let colors = Colors::new(Theme::NoColor);
+        let flags = Flags::default();
+
+        assert_eq!(
+            " 42   items",
+            EntryCount::new(42)
+                .render_as_size(&colors, (3, 2, 5), &flags)
+                .to_string()
+                .as_str()
+        );
+        assert_eq!(
+            "1 item ",
+            EntryCount::new(1)
+                .render_as_size(&colors, (1, 0, 5), &flags)
+                .to_string()
+                .as_str()
+        );
+
+        let flags = Flags {
+            size: SizeFlag::Bytes,
+            ..Flags::default()
+        };
+        assert_eq!((2, 0, 6), EntryCount::new(42).size_lengths(&flags));
+        assert_eq!(
+            "42 items",
+            EntryCount::new(42)
+                .render_as_size(&colors, (2, 0, 6), &flags)
+                .to_string()
+                .as_str()
+        );
+    }
+}
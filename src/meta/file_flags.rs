@@ -0,0 +1,117 @@
+use crate::color::{ColoredString, Colors, Elem};
+use std::fs::Metadata;
+
+// The flags of `chflags`, as named by `ls -lO`.
+const NAMES: [(u32, &str); 9] = [
+    (0x0000_0001, "nodump"),
+    (0x0000_0002, "uchg"),
+    (0x0000_0004, "uappnd"),
+    (0x0000_0008, "opaque"),
+    (HIDDEN, "hidden"),
+    (0x0001_0000, "arch"),
+    (0x0002_0000, "schg"),
+    (0x0004_0000, "sappnd"),
+    (0x0010_0000, "sunlnk"),
+];
+
+const HIDDEN: u32 = 0x0000_8000;
+
+/// The file flags of the BSDs and macOS, which `chflags` sets.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FileFlags {
+    bits: Option<u32>,
+}
+
+impl From<&Metadata> for FileFlags {
+    #[cfg(target_os = "macos")]
+    fn from(meta: &Metadata) -> Self {
+        use std::os::macos::fs::MetadataExt;
+
+        Self::new(meta.st_flags())
+    }
+
+    #[cfg(target_os = "freebsd")]
+    fn from(meta: &Metadata) -> Self {
+        use std::os::freebsd::fs::MetadataExt;
+
+        Self::new(meta.st_flags())
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "freebsd")))]
+    fn from(_: &Metadata) -> Self {
+        Self::default()
+    }
+}
+
+impl FileFlags {
+    pub fn new(bits: u32) -> Self {
+        Self { bits: Some(bits) }
+    }
+
+    /// Whether the Finder hides the entry, as the dot files.
+    pub fn is_hidden(&self) -> bool {
+        self.bits.map(|bits| bits & HIDDEN != 0) == Some(true)
+    }
+
+    /// The names of the flags joined by commas, `-` when none is set like
+    /// `ls -lO`.
+    pub fn flags_string(&self) -> String {
+        let bits = match self.bits {
+            Some(bits) => bits,
+            None => return String::from("?"),
+        };
+
+        let names: Vec<&str> = NAMES
+            .iter()
+            .filter(|(bit, _)| bits & bit != 0)
+            .map(|(_, name)| *name)
+            .collect();
+
+        if names.is_empty() {
+            String::from("-")
+        } else {
+            names.join(",")
+        }
+    }
+
+    pub fn render(&self, colors: &Colors, flags_alignment: usize) -> ColoredString<'static> {
+        let mut res = self.flags_string();
+
+        for _ in res.len()..flags_alignment {
+            res.push(' ');
+        }
+
+        colors.colorize(res, &Elem::FileFlags)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::FileFlags;
+    use crate::color::{Colors, Theme};
+
+    #[test]
+    fn test_flags_string() {
+        assert_eq!("-", FileFlags::new(0).flags_string());
+        assert_eq!("uchg", FileFlags::new(0x2).flags_string());
+        assert_eq!("hidden,schg", FileFlags::new(0x2_8000).flags_string());
+        assert_eq!("?", FileFlags::default().flags_string());
+    }
+
+    #[test]
+    fn test_is_hidden() {
+        assert!(FileFlags::new(0x8000).is_hidden());
+        assert!(!FileFlags::new(0x2).is_hidden());
+        assert!(!FileFlags::default().is_hidden());
+    }
+
+    #[test]
+    fn test_render_flags() {
+        let colors = Colors::new(Theme::NoColor);
+
+        assert_eq!(
+            "uchg  ",
+            FileFlags::new(0x2).render(&colors, 6).to_string().as_str()
+        );
+    }
+}
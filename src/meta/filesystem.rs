@@ -0,0 +1,364 @@
+use crate::color::{ColoredString, Colors, Elem};
+use crate::meta::Device;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::Path;
+
+// The filesystems made up by the kernel, with nothing stored on a disk. Their
+// entries are countless, ever changing, and some tell absurd sizes, like the
+// `/proc/kcore` as big as the address space.
+const VIRTUAL_TYPES: [&str; 19] = [
+    "autofs",
+    "binfmt_misc",
+    "bpf",
+    "cgroup",
+    "cgroup2",
+    "configfs",
+    "debugfs",
+    "devfs",
+    "devpts",
+    "efivarfs",
+    "fusectl",
+    "hugetlbfs",
+    "mqueue",
+    "proc",
+    "procfs",
+    "pstore",
+    "securityfs",
+    "selinuxfs",
+    "sysfs",
+];
+
+/// The filesystem an entry resides on: its type, like `ext4` or `tmpfs`, and
+/// the device it is mounted from.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Filesystem {
+    fs_type: Option<String>,
+    source: Option<String>,
+}
+
+impl Filesystem {
+    pub fn new(fs_type: &str, source: &str) -> Self {
+        Self {
+            fs_type: Some(fs_type.to_string()),
+            source: Some(source.to_string()),
+        }
+    }
+
+    /// The filesystem of the entry at `path`, on `device`, as found in the
+    /// mount table. It is read once, and each device looked up once too.
+    #[cfg(target_os = "linux")]
+    pub fn for_entry(path: &Path, device: Device) -> Self {
+        use std::fs;
+
+        thread_local! {
+            static MOUNTS: Vec<Mount> = fs::read_to_string("/proc/self/mountinfo")
+                .map(|content| parse_mountinfo(&content))
+                .unwrap_or_default();
+            static BY_DEVICE: RefCell<HashMap<u64, Filesystem>> = RefCell::new(HashMap::new());
+        }
+
+        let id = match device.id() {
+            Some(id) => id,
+            None => return Self::default(),
+        };
+        if let Some(filesystem) = BY_DEVICE.with(|by_device| by_device.borrow().get(&id).cloned()) {
+            return filesystem;
+        }
+
+        let filesystem = MOUNTS.with(|mounts| {
+            let (major, minor) = device.major_minor().unwrap_or((0, 0));
+            // The subvolumes of btrfs have devices of their own, unknown to the
+            // table, so their mount point is looked for instead.
+            let mount = mounts
+                .iter()
+                .find(|mount| mount.device == (major, minor))
+                .or_else(|| {
+                    let path = fs::canonicalize(path).ok()?;
+                    mounts
+                        .iter()
+                        .filter(|mount| path.starts_with(&mount.mount_point))
+                        .max_by_key(|mount| mount.mount_point.as_os_str().len())
+                });
+
+            match mount {
+                Some(mount) => Self::new(&mount.fs_type, &mount.source),
+                None => Self::default(),
+            }
+        });
+        BY_DEVICE.with(|by_device| by_device.borrow_mut().insert(id, filesystem.clone()));
+
+        filesystem
+    }
+
+    /// The filesystem of the entry at `path`, as told by `statfs`.
+    #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+    pub fn for_entry(path: &Path, _: Device) -> Self {
+        use std::ffi::{CStr, CString};
+        use std::os::unix::ffi::OsStrExt;
+
+        let path = match CString::new(path.as_os_str().as_bytes()) {
+            Ok(path) => path,
+            Err(_) => return Self::default(),
+        };
+
+        let mut stat: libc::statfs = unsafe { std::mem::zeroed() };
+        if unsafe { libc::statfs(path.as_ptr(), &mut stat) } != 0 {
+            return Self::default();
+        }
+
+        let field = |field: &[libc::c_char]| unsafe {
+            CStr::from_ptr(field.as_ptr()).to_string_lossy().to_string()
+        };
+        Self::new(&field(&stat.f_fstypename), &field(&stat.f_mntfromname))
+    }
+
+    /// The filesystem of the volume holding the entry at `path`, the source
+    /// being the root of the volume, like `C:\`.
+    #[cfg(windows)]
+    pub fn for_entry(path: &Path, _: Device) -> Self {
+        use std::iter;
+        use std::os::windows::ffi::OsStrExt;
+        use std::ptr;
+        use winapi::um::fileapi::{GetVolumeInformationW, GetVolumePathNameW};
+
+        // Beyond `MAX_PATH`, for the roots of the mounted folders.
+        const LEN: usize = 1024;
+
+        let path: Vec<u16> = path
+            .as_os_str()
+            .encode_wide()
+            .chain(iter::once(0))
+            .collect();
+        let mut root = vec![0u16; LEN];
+        let mut fs_type = vec![0u16; LEN];
+
+        let found = unsafe {
+            GetVolumePathNameW(path.as_ptr(), root.as_mut_ptr(), LEN as u32) != 0
+                && GetVolumeInformationW(
+                    root.as_ptr(),
+                    ptr::null_mut(),
+                    0,
+                    ptr::null_mut(),
+                    ptr::null_mut(),
+                    ptr::null_mut(),
+                    fs_type.as_mut_ptr(),
+                    LEN as u32,
+                ) != 0
+        };
+        if !found {
+            return Self::default();
+        }
+
+        let string = |wide: &[u16]| {
+            let end = wide.iter().position(|c| *c == 0).unwrap_or(wide.len());
+            String::from_utf16_lossy(&wide[..end])
+        };
+        Self::new(&string(&fs_type), &string(&root))
+    }
+
+    #[cfg(not(any(
+        target_os = "linux",
+        target_os = "macos",
+        target_os = "freebsd",
+        windows
+    )))]
+    pub fn for_entry(_: &Path, _: Device) -> Self {
+        Self::default()
+    }
+
+    /// Whether the entry at `path`, on `device`, is on a virtual filesystem
+    /// like `/proc` or `/sys`, looked up once for each device.
+    pub fn is_virtual(path: &Path, device: Device) -> bool {
+        thread_local! {
+            static BY_DEVICE: RefCell<HashMap<u64, bool>> = RefCell::new(HashMap::new());
+        }
+
+        let id = match device.id() {
+            Some(id) => id,
+            None => return false,
+        };
+
+        BY_DEVICE.with(|by_device| {
+            *by_device
+                .borrow_mut()
+                .entry(id)
+                .or_insert_with(|| Self::for_entry(path, device).is_virtual_type())
+        })
+    }
+
+    fn is_virtual_type(&self) -> bool {
+        match &self.fs_type {
+            Some(fs_type) => VIRTUAL_TYPES.contains(&fs_type.as_str()),
+            None => false,
+        }
+    }
+
+    /// The type and the device, like `ext4 /dev/sda1`, `?` when unknown.
+    pub fn filesystem_string(&self) -> String {
+        match (&self.fs_type, &self.source) {
+            (Some(fs_type), Some(source)) => format!("{} {}", fs_type, source),
+            _ => String::from("?"),
+        }
+    }
+
+    pub fn render(&self, colors: &Colors, filesystem_alignment: usize) -> ColoredString<'static> {
+        let mut res = self.filesystem_string();
+
+        for _ in res.chars().count()..filesystem_alignment {
+            res.push(' ');
+        }
+
+        colors.colorize(res, &Elem::Filesystem)
+    }
+}
+
+/// A line of `/proc/self/mountinfo`.
+#[cfg(target_os = "linux")]
+#[derive(Debug, PartialEq, Eq)]
+struct Mount {
+    device: (u64, u64),
+    mount_point: std::path::PathBuf,
+    fs_type: String,
+    source: String,
+}
+
+/// The mounts of `content`, formatted like the `mountinfo` files of the
+/// proc filesystem:
+///
+/// `36 35 98:0 /mnt1 /mnt2 rw,noatime master:1 - ext3 /dev/root rw`
+///
+/// The optional fields before the `-` vary in number.
+#[cfg(target_os = "linux")]
+fn parse_mountinfo(content: &str) -> Vec<Mount> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split(' ').collect();
+            let separator = fields.iter().position(|field| *field == "-")?;
+            let mut numbers = fields.get(2)?.split(':').map(str::parse);
+            let device = match (numbers.next(), numbers.next()) {
+                (Some(Ok(major)), Some(Ok(minor))) => (major, minor),
+                _ => return None,
+            };
+
+            Some(Mount {
+                device,
+                mount_point: unescape(fields.get(4)?).into(),
+                fs_type: unescape(fields.get(separator + 1)?),
+                source: unescape(fields.get(separator + 2)?),
+            })
+        })
+        .collect()
+}
+
+/// The field with the octal escapes of the kernel decoded, like `\040` for
+/// the spaces.
+#[cfg(target_os = "linux")]
+fn unescape(field: &str) -> String {
+    let bytes = field.as_bytes();
+    let mut res = Vec::with_capacity(bytes.len());
+
+    let mut idx = 0;
+    while idx < bytes.len() {
+        let escape = bytes.get(idx + 1..idx + 4).and_then(|digits| {
+            let digits = std::str::from_utf8(digits).ok()?;
+            u8::from_str_radix(digits, 8).ok()
+        });
+        match escape {
+            Some(byte) if bytes[idx] == b'\\' => {
+                res.push(byte);
+                idx += 4;
+            }
+            _ => {
+                res.push(bytes[idx]);
+                idx += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&res).to_string()
+}
+
+#[cfg(test)]
+mod test {
+    use super::Filesystem;
+    use crate::color::{Colors, Theme};
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_parse_mountinfo() {
+        use super::{parse_mountinfo, Mount};
+
+        let content = "\
+36 35 98:0 /mnt1 /mnt2 rw,noatime master:1 - ext3 /dev/root rw,errors=continue
+37 36 0:31 / /my\\040disk rw - tmpfs tmpfs rw
+broken line
+";
+
+        assert_eq!(
+            vec![
+                Mount {
+                    device: (98, 0),
+                    mount_point: "/mnt2".into(),
+                    fs_type: String::from("ext3"),
+                    source: String::from("/dev/root"),
+                },
+                Mount {
+                    device: (0, 31),
+                    mount_point: "/my disk".into(),
+                    fs_type: String::from("tmpfs"),
+                    source: String::from("tmpfs"),
+                },
+            ],
+            parse_mountinfo(content)
+        );
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_root_filesystem_is_found() {
+        use crate::meta::Device;
+        use std::path::Path;
+
+        let meta = Path::new("/").metadata().expect("failed to get metadata");
+        let filesystem = Filesystem::for_entry(Path::new("/"), Device::from(&meta));
+
+        assert_ne!("?", filesystem.filesystem_string());
+    }
+
+    #[test]
+    fn test_virtual_types() {
+        assert!(Filesystem::new("proc", "proc").is_virtual_type());
+        assert!(Filesystem::new("sysfs", "sysfs").is_virtual_type());
+        assert!(!Filesystem::new("ext4", "/dev/sda1").is_virtual_type());
+        assert!(!Filesystem::new("tmpfs", "tmpfs").is_virtual_type());
+        assert!(!Filesystem::default().is_virtual_type());
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_proc_is_virtual() {
+        use crate::meta::Device;
+        use std::path::Path;
+
+        let device = |path: &str| Device::from(&Path::new(path).metadata().unwrap());
+
+        assert!(Filesystem::is_virtual(Path::new("/proc"), device("/proc")));
+        assert!(!Filesystem::is_virtual(Path::new("/"), device("/")));
+    }
+
+    #[test]
+    fn test_render_filesystem() {
+        let colors = Colors::new(Theme::NoColor);
+
+        assert_eq!(
+            "ext4 /dev/sda1  ",
+            Filesystem::new("ext4", "/dev/sda1")
+                .render(&colors, 16)
+                .to_string()
+                .as_str()
+        );
+        assert_eq!("?", Filesystem::default().filesystem_string());
+    }
+}
@@ -1,6 +1,8 @@
 use crate::color::{ColoredString, Colors, Elem};
 use crate::meta::Permissions;
 use std::fs::Metadata;
+#[cfg(unix)]
+use std::{fs, path::Path};
 
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
 #[cfg_attr(windows, allow(dead_code))]
@@ -9,18 +11,33 @@ pub enum FileType {
     CharDevice,
     Directory { uid: bool },
     File { uid: bool, exec: bool },
-    SymLink,
+    SymLink { is_dir: bool },
     Pipe,
     Socket,
     Special,
 }
 
 impl FileType {
+    /// `symlink_meta` is the metadata of the target, when `meta` is the one of
+    /// a symlink.
     #[cfg(unix)]
-    pub fn new(meta: &Metadata, permissions: &Permissions) -> Self {
-        use std::os::unix::fs::FileTypeExt;
+    pub fn new(
+        meta: &Metadata,
+        symlink_meta: Option<&Metadata>,
+        permissions: &Permissions,
+    ) -> Self {
+        Self::from_file_type(meta.file_type(), symlink_meta, permissions)
+    }
 
-        let file_type = meta.file_type();
+    /// The type of `file_type`, like the one a directory records for its
+    /// entries.
+    #[cfg(unix)]
+    pub fn from_file_type(
+        file_type: fs::FileType,
+        symlink_meta: Option<&Metadata>,
+        permissions: &Permissions,
+    ) -> Self {
+        use std::os::unix::fs::FileTypeExt;
 
         if file_type.is_file() {
             FileType::File {
@@ -34,7 +51,9 @@ impl FileType {
         } else if file_type.is_fifo() {
             FileType::Pipe
         } else if file_type.is_symlink() {
-            FileType::SymLink
+            FileType::SymLink {
+                is_dir: symlink_meta.map(Metadata::is_dir) == Some(true),
+            }
         } else if file_type.is_char_device() {
             FileType::CharDevice
         } else if file_type.is_block_device() {
@@ -47,7 +66,11 @@ impl FileType {
     }
 
     #[cfg(windows)]
-    pub fn new(meta: &Metadata, permissions: &Permissions) -> Self {
+    pub fn new(
+        meta: &Metadata,
+        symlink_meta: Option<&Metadata>,
+        permissions: &Permissions,
+    ) -> Self {
         let file_type = meta.file_type();
 
         if file_type.is_file() {
@@ -60,7 +83,9 @@ impl FileType {
                 uid: permissions.setuid,
             }
         } else if file_type.is_symlink() {
-            FileType::SymLink
+            FileType::SymLink {
+                is_dir: symlink_meta.map(Metadata::is_dir) == Some(true),
+            }
         } else {
             FileType::Special
         }
@@ -68,7 +93,34 @@ impl FileType {
 }
 
 impl FileType {
-    pub fn render(self, colors: &Colors) -> ColoredString {
+    /// Keep the files executable only when the current user may run them,
+    /// which `access` tells according to the owners and the ACL.
+    #[cfg(unix)]
+    pub fn for_current_user(self, path: &Path) -> Self {
+        match self {
+            FileType::File { uid, exec: true } => FileType::File {
+                uid,
+                exec: can_execute(path),
+            },
+            other => other,
+        }
+    }
+
+    /// The name of the type in the machine-readable formats.
+    pub fn type_name(self) -> &'static str {
+        match self {
+            FileType::File { .. } => "file",
+            FileType::Directory { .. } => "directory",
+            FileType::SymLink { .. } => "symlink",
+            FileType::Pipe => "pipe",
+            FileType::BlockDevice => "block_device",
+            FileType::CharDevice => "char_device",
+            FileType::Socket => "socket",
+            FileType::Special => "special",
+        }
+    }
+
+    pub fn render(self, colors: &Colors) -> ColoredString<'_> {
         match self {
             FileType::File { exec, .. } => {
                 colors.colorize(String::from("."), &Elem::File { exec, uid: false })
@@ -77,7 +129,7 @@ impl FileType {
                 colors.colorize(String::from("d"), &Elem::Dir { uid: false })
             }
             FileType::Pipe => colors.colorize(String::from("|"), &Elem::Pipe),
-            FileType::SymLink => colors.colorize(String::from("l"), &Elem::SymLink),
+            FileType::SymLink { .. } => colors.colorize(String::from("l"), &Elem::SymLink),
             FileType::BlockDevice => colors.colorize(String::from("b"), &Elem::BlockDevice),
             FileType::CharDevice => colors.colorize(String::from("c"), &Elem::CharDevice),
             FileType::Socket => colors.colorize(String::from("s"), &Elem::Socket),
@@ -86,6 +138,17 @@ impl FileType {
     }
 }
 
+#[cfg(unix)]
+fn can_execute(path: &Path) -> bool {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    match CString::new(path.as_os_str().as_bytes()) {
+        Ok(c_path) => unsafe { libc::access(c_path.as_ptr(), libc::X_OK) == 0 },
+        Err(_) => false,
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::FileType;
@@ -115,11 +178,45 @@ mod test {
         let meta = file_path.metadata().expect("failed to get metas");
 
         let colors = Colors::new(Theme::NoLscolors);
-        let file_type = FileType::new(&meta, &Permissions::from(&meta));
+        let file_type = FileType::new(&meta, None, &Permissions::from(&meta));
 
         assert_eq!(Colour::Fixed(184).paint("."), file_type.render(&colors));
     }
 
+    #[test]
+    #[cfg(unix)]
+    fn test_executable_for_current_user() {
+        use std::fs;
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp_dir = tempdir().expect("failed to create temp dir");
+        let file_path = tmp_dir.path().join("script.sh");
+        File::create(&file_path).expect("failed to create file");
+        // Only the group, which the owner isn't part of here, may run it.
+        fs::set_permissions(&file_path, fs::Permissions::from_mode(0o070))
+            .expect("failed to set permissions");
+        let meta = file_path.metadata().expect("failed to get metas");
+
+        let file_type = FileType::new(&meta, None, &Permissions::from(&meta));
+        assert_eq!(
+            FileType::File {
+                uid: false,
+                exec: true
+            },
+            file_type
+        );
+
+        // The superuser may run any file with an execute bit.
+        let root = unsafe { libc::geteuid() } == 0;
+        assert_eq!(
+            FileType::File {
+                uid: false,
+                exec: root
+            },
+            file_type.for_current_user(&file_path)
+        );
+    }
+
     #[test]
     fn test_dir_type() {
         let tmp_dir = tempdir().expect("failed to create temp dir");
@@ -128,7 +225,7 @@ mod test {
         let metadata = tmp_dir.path().metadata().expect("failed to get metas");
 
         let colors = Colors::new(Theme::NoLscolors);
-        let file_type = FileType::new(&metadata, &meta.permissions);
+        let file_type = FileType::new(&metadata, None, &meta.permissions);
 
         assert_eq!(Colour::Fixed(33).paint("d"), file_type.render(&colors));
     }
@@ -150,11 +247,31 @@ mod test {
             .expect("failed to get metas");
 
         let colors = Colors::new(Theme::NoLscolors);
-        let file_type = FileType::new(&meta, &Permissions::from(&meta));
+        let file_type = FileType::new(&meta, None, &Permissions::from(&meta));
 
         assert_eq!(Colour::Fixed(44).paint("l"), file_type.render(&colors));
     }
 
+    #[test]
+    #[cfg(unix)] // Symlink support is *hard* on Windows
+    fn test_symlink_to_dir_type() {
+        let tmp_dir = tempdir().expect("failed to create temp dir");
+
+        // Create the symlinks
+        let file_path = tmp_dir.path().join("file.tmp");
+        File::create(&file_path).expect("failed to create file");
+        let file_link_path = tmp_dir.path().join("file-link.tmp");
+        symlink(&file_path, &file_link_path).expect("failed to create symlink");
+        let dir_link_path = tmp_dir.path().join("dir-link.tmp");
+        symlink(tmp_dir.path(), &dir_link_path).expect("failed to create symlink");
+
+        let file_link = Meta::from_path(&file_link_path).expect("failed to get metas");
+        let dir_link = Meta::from_path(&dir_link_path).expect("failed to get metas");
+
+        assert_eq!(FileType::SymLink { is_dir: false }, file_link.file_type);
+        assert_eq!(FileType::SymLink { is_dir: true }, dir_link.file_type);
+    }
+
     #[test]
     #[cfg(unix)] // Windows pipes aren't like Unix pipes
     fn test_pipe_type() {
@@ -167,11 +284,11 @@ mod test {
             .status()
             .expect("failed to exec mkfifo")
             .success();
-        assert_eq!(true, success, "failed to exec mkfifo");
+        assert!(success, "failed to exec mkfifo");
         let meta = pipe_path.metadata().expect("failed to get metas");
 
         let colors = Colors::new(Theme::NoLscolors);
-        let file_type = FileType::new(&meta, &Permissions::from(&meta));
+        let file_type = FileType::new(&meta, None, &Permissions::from(&meta));
 
         assert_eq!(Colour::Fixed(44).paint("|"), file_type.render(&colors));
     }
@@ -196,7 +313,7 @@ mod test {
         let meta = char_device_path.metadata().expect("failed to get metas");
 
         let colors = Colors::new(Theme::NoLscolors);
-        let file_type = FileType::new(&meta, &Permissions::from(&meta));
+        let file_type = FileType::new(&meta, None, &Permissions::from(&meta));
 
         assert_eq!(Colour::Fixed(44).paint("c"), file_type.render(&colors));
     }
@@ -212,7 +329,7 @@ mod test {
         let meta = socket_path.metadata().expect("failed to get metas");
 
         let colors = Colors::new(Theme::NoLscolors);
-        let file_type = FileType::new(&meta, &Permissions::from(&meta));
+        let file_type = FileType::new(&meta, None, &Permissions::from(&meta));
 
         assert_eq!(Colour::Fixed(44).paint("s"), file_type.render(&colors));
     }
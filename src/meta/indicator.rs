@@ -13,7 +13,7 @@ impl From<FileType> for Indicator {
             FileType::File { exec: true, .. } => "*",
             FileType::Pipe => "|",
             FileType::Socket => "=",
-            FileType::SymLink => "@",
+            FileType::SymLink { .. } => "@",
             _ => "",
         };
 
@@ -22,7 +22,7 @@ impl From<FileType> for Indicator {
 }
 
 impl Indicator {
-    pub fn render(&self, flags: &Flags) -> ColoredString {
+    pub fn render(&self, flags: &Flags) -> ColoredString<'_> {
         if flags.display_indicators {
             ANSIString::from(self.0)
         } else {
@@ -47,8 +47,10 @@ mod test {
 
     #[test]
     fn test_directory_indicator() {
-        let mut flags = Flags::default();
-        flags.display_indicators = true;
+        let flags = Flags {
+            display_indicators: true,
+            ..Flags::default()
+        };
 
         let file_type = Indicator::from(FileType::Directory { uid: false });
 
@@ -57,8 +59,10 @@ mod test {
 
     #[test]
     fn test_executable_file_indicator() {
-        let mut flags = Flags::default();
-        flags.display_indicators = true;
+        let flags = Flags {
+            display_indicators: true,
+            ..Flags::default()
+        };
 
         let file_type = Indicator::from(FileType::File {
             uid: false,
@@ -70,8 +74,10 @@ mod test {
 
     #[test]
     fn test_socket_indicator() {
-        let mut flags = Flags::default();
-        flags.display_indicators = true;
+        let flags = Flags {
+            display_indicators: true,
+            ..Flags::default()
+        };
 
         let file_type = Indicator::from(FileType::Socket);
 
@@ -80,18 +86,22 @@ mod test {
 
     #[test]
     fn test_symlink_indicator() {
-        let mut flags = Flags::default();
-        flags.display_indicators = true;
+        let flags = Flags {
+            display_indicators: true,
+            ..Flags::default()
+        };
 
-        let file_type = Indicator::from(FileType::SymLink);
+        let file_type = Indicator::from(FileType::SymLink { is_dir: false });
 
         assert_eq!("@", file_type.render(&flags).to_string().as_str());
     }
 
     #[test]
     fn test_not_represented_indicator() {
-        let mut flags = Flags::default();
-        flags.display_indicators = true;
+        let flags = Flags {
+            display_indicators: true,
+            ..Flags::default()
+        };
 
         // The File type doesn't have any indicator
         let file_type = Indicator::from(FileType::File {
@@ -0,0 +1,61 @@
+use std::fs::Metadata;
+
+/// The inode of an entry: its number and how many hard links lead to it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Inode {
+    index: Option<u64>,
+    links: Option<u64>,
+}
+
+impl From<&Metadata> for Inode {
+    #[cfg(unix)]
+    fn from(meta: &Metadata) -> Self {
+        use std::os::unix::fs::MetadataExt;
+
+        Self::new(meta.ino(), meta.nlink())
+    }
+
+    #[cfg(not(unix))]
+    fn from(_: &Metadata) -> Self {
+        Self::default()
+    }
+}
+
+impl Inode {
+    pub fn new(index: u64, links: u64) -> Self {
+        Self {
+            index: Some(index),
+            links: Some(links),
+        }
+    }
+
+    /// The inode number, missing where the platform doesn't expose it.
+    pub fn index(&self) -> Option<u64> {
+        self.index
+    }
+
+    /// The number of hard links, missing where the platform doesn't expose it.
+    pub fn links(&self) -> Option<u64> {
+        self.links
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    #[cfg(unix)]
+    fn test_hard_links_are_counted() {
+        use super::Inode;
+        use std::fs;
+        use tempfile::tempdir;
+
+        let tmp_dir = tempdir().expect("failed to create temp dir");
+        let path = tmp_dir.path().join("file");
+        fs::write(&path, "").expect("failed to create file");
+        fs::hard_link(&path, tmp_dir.path().join("link")).expect("failed to link");
+
+        let inode = Inode::from(&path.metadata().expect("failed to get metadata"));
+        assert_eq!(Some(2), inode.links());
+        assert!(inode.index().is_some());
+    }
+}
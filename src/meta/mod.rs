@@ -1,155 +1,600 @@
+mod access_control;
+mod access_error;
+mod archive;
+mod audit;
+mod checksum;
+mod content_type;
+mod custom;
 mod date;
+mod device;
+mod entries;
+mod entry_count;
+mod file_flags;
+mod filesystem;
 mod filetype;
 mod indicator;
+mod inode;
 mod name;
 mod owner;
 mod permissions;
+mod preview;
+mod security_context;
 mod size;
 mod symlink;
 
 #[cfg(windows)]
 mod windows_utils;
+#[cfg(unix)]
+mod xattr;
 
+pub use self::access_control::AccessControl;
+pub use self::access_error::AccessError;
+pub use self::audit::Audit;
+pub use self::checksum::Checksum;
+pub use self::content_type::ContentType;
+pub use self::custom::CustomValue;
 pub use self::date::Date;
+pub use self::device::Device;
+pub use self::entries::Entries;
+pub use self::entry_count::EntryCount;
+pub use self::file_flags::FileFlags;
+pub use self::filesystem::Filesystem;
 pub use self::filetype::FileType;
 pub use self::indicator::Indicator;
+pub use self::inode::Inode;
 pub use self::name::Name;
 pub use self::owner::Owner;
 pub use self::permissions::Permissions;
+pub use self::preview::Preview;
+pub use self::security_context::SecurityContext;
 pub use self::size::Size;
 pub use self::symlink::SymLink;
 pub use crate::flags::Display;
 pub use crate::icon::Icons;
 
+use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::fs::read_link;
+use std::fs::{read_link, Metadata};
 use std::io::{Error, ErrorKind};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use globset::GlobSet;
+use self::archive::{ArchiveEntry, EntryKind};
+use crate::color::Elem;
+use crate::config_file::DirectoryConfig;
+use crate::flags::{
+    AuditRule, Block, ChecksumFlag, CustomBlock, DirSizeFlag, DisplayRule, ExecFlag, Flags,
+    FormatFlag, Layout, MimeFlag, SortFlag, TimeFlag, WhenFlag,
+};
+use crate::interrupt;
+use crate::progress::Progress;
 
 #[derive(Clone, Debug)]
 pub struct Meta {
     pub name: Name,
     pub path: PathBuf,
     pub permissions: Permissions,
+    pub access_control: AccessControl,
+    pub security_context: SecurityContext,
     pub date: Date,
+    pub device: Device,
+    pub filesystem: Filesystem,
+    pub inode: Inode,
+    pub file_flags: FileFlags,
     pub owner: Owner,
     pub file_type: FileType,
     pub size: Size,
+    pub entry_count: EntryCount,
+    pub content_type: ContentType,
+    pub checksum: Checksum,
+    /// The values of the custom blocks, in their order.
+    pub custom: Vec<CustomValue>,
+    pub audit: Audit,
     pub symlink: SymLink,
     pub indicator: Indicator,
     pub content: Option<Vec<Meta>>,
+    /// The flags of the listing of the content, when the `.lsd.yaml` of the
+    /// directory overrides the ones of its parent.
+    pub directory_flags: Option<Box<Flags>>,
 }
 
 impl Meta {
+    /// Read the content of the directory, down to `depth` levels.
+    ///
+    /// The entries which cannot be accessed are skipped and reported through
+    /// `errors`, so the caller decides how to display them. The directories
+    /// already being read higher in the tree, reached again through a symlink
+    /// or a bind mount, are reported the same way instead of being read again.
     pub fn recurse_into(
         &self,
         depth: usize,
-        display: Display,
-        ignore_globs: &GlobSet,
-    ) -> Result<Option<Vec<Meta>>, std::io::Error> {
+        flags: &Flags,
+        errors: &mut Vec<AccessError>,
+    ) -> Option<Vec<Meta>> {
+        self.recurse_into_below(depth, flags, &mut Vec::new(), errors)
+    }
+
+    // `ancestors` are the identities of the directories being read.
+    fn recurse_into_below(
+        &self,
+        depth: usize,
+        flags: &Flags,
+        ancestors: &mut Vec<DirId>,
+        errors: &mut Vec<AccessError>,
+    ) -> Option<Vec<Meta>> {
+        let display = flags.display;
+
         if depth == 0 {
-            return Ok(None);
+            return None;
         }
 
         if display == Display::DisplayDirectoryItself {
-            return Ok(None);
+            return None;
         }
 
         match self.file_type {
             FileType::Directory { .. } => (),
-            _ => return Ok(None),
+            FileType::File { .. } if flags.archives && archive::is_archive(&self.path) => {
+                if let Layout::Tree { .. } = flags.layout {
+                    return self.archive_content(flags, errors);
+                }
+                return None;
+            }
+            _ => return None,
         }
 
-        let entries = match self.path.read_dir() {
+        let id = dir_id(&self.path);
+        if let Some(ref id) = id {
+            if ancestors.contains(id) {
+                let err = Error::new(
+                    ErrorKind::AlreadyExists,
+                    "not listing already-listed directory",
+                );
+                errors.push(AccessError::new(&self.path, err));
+                return None;
+            }
+        }
+        if enters_virtual_filesystem(&self.path, &id, ancestors) {
+            return None;
+        }
+
+        let entries = match Entries::new(&self.path, flags) {
             Ok(entries) => entries,
             Err(err) => {
-                eprintln!("cannot access '{}': {}", self.path.display(), err);
-                return Ok(None);
+                errors.push(err);
+                return None;
             }
         };
 
-        let mut content: Vec<Meta> = Vec::new();
+        let mut content = if let Display::DisplayAll = display {
+            match self.dot_entries(flags) {
+                Ok(dots) => dots,
+                Err(err) => {
+                    errors.push(err);
+                    return None;
+                }
+            }
+        } else {
+            Vec::new()
+        };
 
-        if let Display::DisplayAll = display {
-            let mut current_meta;
-            let mut parent_meta;
+        let nb_ancestors = ancestors.len();
+        ancestors.extend(id);
 
-            let absolute_path = fs::canonicalize(&self.path)?;
-            let parent_path = match absolute_path.parent() {
-                None => PathBuf::from("/"),
-                Some(path) => PathBuf::from(path),
+        for entry in entries {
+            if interrupt::is_interrupted() {
+                break;
+            }
+            let mut entry_meta = match entry {
+                Ok(meta) => meta,
+                Err(err) => {
+                    errors.push(err);
+                    continue;
+                }
             };
 
-            current_meta = self.clone();
-            current_meta.name.name = ".".to_string();
+            if !flags.one_file_system || entry_meta.device == self.device {
+                entry_meta.apply_directory_config(flags);
+                let entry_flags = entry_meta.content_flags(flags);
+                entry_meta.content =
+                    entry_meta.recurse_into_below(depth - 1, entry_flags, ancestors, errors);
+            }
 
-            parent_meta = Self::from_path(&parent_path)?;
-            parent_meta.name.name = "..".to_string();
+            content.push(entry_meta);
+        }
 
-            content.push(current_meta);
-            content.push(parent_meta);
+        ancestors.truncate(nb_ancestors);
+        Some(content)
+    }
+
+    /// Read the `.lsd.yaml` of the directory when the config file turns on
+    /// `directory-config`, its overrides applying to the listing of its
+    /// content and of the directories below it, on top of `flags`.
+    pub fn apply_directory_config(&mut self, flags: &Flags) {
+        if !flags.directory_config {
+            return;
+        }
+        if let FileType::Directory { .. } = self.file_type {
+            self.directory_flags = match DirectoryConfig::load(&self.path) {
+                Ok(Some(config)) => Some(Box::new(flags.with_directory_config(&config))),
+                Ok(None) => None,
+                Err(err) => {
+                    eprintln!("{}", err);
+                    None
+                }
+            };
         }
+    }
 
-        for entry in entries {
-            let path = entry?.path();
+    /// The flags of the listing of the content, the ones of the parent being
+    /// `flags`.
+    pub fn content_flags<'a>(&'a self, flags: &'a Flags) -> &'a Flags {
+        self.directory_flags.as_deref().unwrap_or(flags)
+    }
 
-            let name = path
-                    .file_name()
-                    .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "invalid file name"))?;
+    /// The entries at the top of the archive, as listed by it. Their paths, inside the
+    /// path of the archive, don't exist on the disk.
+    fn archive_content(&self, flags: &Flags, errors: &mut Vec<AccessError>) -> Option<Vec<Meta>> {
+        match archive::read(&self.path) {
+            Ok(entries) => Some(Self::from_archive_entries(&self.path, entries, flags)),
+            Err(err) => {
+                errors.push(AccessError::new(&self.path, err));
+                None
+            }
+        }
+    }
 
-            if ignore_globs.is_match(&name) {
-                continue;
+    fn from_archive_entries(parent: &Path, entries: Vec<ArchiveEntry>, flags: &Flags) -> Vec<Meta> {
+        entries
+            .into_iter()
+            .map(|entry| {
+                let path = parent.join(&entry.name);
+                let permissions = Permissions::from_mode(match (entry.mode, entry.kind) {
+                    (Some(mode), _) => mode,
+                    (None, EntryKind::Directory) => 0o755,
+                    (None, _) => 0o644,
+                });
+                let file_type = match entry.kind {
+                    EntryKind::File => FileType::File {
+                        uid: permissions.setuid,
+                        exec: permissions.is_executable(),
+                    },
+                    EntryKind::Directory => FileType::Directory {
+                        uid: permissions.setuid,
+                    },
+                    EntryKind::SymLink => FileType::SymLink { is_dir: false },
+                };
+                let (user, group) = entry
+                    .owner
+                    .unwrap_or_else(|| (String::from("-"), String::from("-")));
+
+                Self {
+                    name: Name::new(&path, file_type),
+                    path,
+                    permissions,
+                    access_control: AccessControl::default(),
+                    security_context: SecurityContext::None,
+                    date: match entry.modified {
+                        Some(seconds) => Date::new(time::at_utc(time::Timespec::new(seconds, 0))),
+                        None => Date::default(),
+                    },
+                    device: Device::default(),
+                    filesystem: Filesystem::default(),
+                    inode: Inode::default(),
+                    file_flags: FileFlags::default(),
+                    owner: Owner::new(user, group),
+                    file_type,
+                    size: Size::new(entry.size),
+                    entry_count: EntryCount::default(),
+                    content_type: ContentType::default(),
+                    checksum: Checksum::default(),
+                    custom: Vec::new(),
+                    audit: Audit::default(),
+                    symlink: SymLink::default(),
+                    indicator: Indicator::from(file_type),
+                    content: None,
+                    directory_flags: None,
+                }
+            })
+            .filter(|meta| entries::is_listed(meta, flags))
+            .collect()
+    }
+
+    /// Start reading the entries of the directory one at a time, without
+    /// their content nor `.` and `..`.
+    pub fn read_entries<'a>(&'a self, flags: &'a Flags) -> Result<Entries<'a>, AccessError> {
+        Entries::new(&self.path, flags)
+    }
+
+    /// The `.` and `..` entries of the directory, unless `flags` ignores them.
+    pub fn dot_entries(&self, flags: &Flags) -> Result<Vec<Meta>, AccessError> {
+        let absolute_path = match fs::canonicalize(&self.path) {
+            Ok(path) => path,
+            Err(err) => return Err(AccessError::new(&self.path, err)),
+        };
+        // The roots, like `/` or `C:\`, are their own parent.
+        let parent_path = match absolute_path.parent() {
+            None => absolute_path.clone(),
+            Some(path) => PathBuf::from(path),
+        };
+
+        let mut current_meta = self.clone();
+        current_meta.name.rename(".");
+
+        let mut parent_meta = match Self::from_path(&parent_path) {
+            Ok(meta) => meta,
+            Err(err) => return Err(AccessError::new(&parent_path, err)),
+        };
+        parent_meta.name.rename("..");
+
+        let mut dots = vec![current_meta, parent_meta];
+        dots.retain(|dot| entries::is_listed(dot, flags));
+
+        Ok(dots)
+    }
+
+    /// Whether the system hides the entry: the dot files, along with the
+    /// entries with the hidden attribute of Windows, or the `hidden` flag of
+    /// macOS. `.` and `..` aren't hidden.
+    pub fn is_hidden(&self) -> bool {
+        let name = &self.name.name;
+        if name == "." || name == ".." {
+            return false;
+        }
+
+        let has_hidden_attribute = match self.permissions.attributes {
+            Some(attributes) => attributes.hidden,
+            None => false,
+        };
+
+        name.starts_with('.') || has_hidden_attribute || self.file_flags.is_hidden()
+    }
+
+    /// Keep only the entries of the content for which `keep` returns true,
+    /// along with the directories leading to them.
+    pub fn retain_content(&mut self, keep: &dyn Fn(&Meta) -> bool) {
+        if let Some(content) = self.content.take() {
+            let content = content
+                .into_iter()
+                .filter_map(|mut meta| {
+                    meta.retain_content(keep);
+
+                    let has_matches = match meta.content {
+                        Some(ref content) => !content.is_empty(),
+                        None => false,
+                    };
+                    if has_matches || keep(&meta) {
+                        Some(meta)
+                    } else {
+                        None
+                    }
+                })
+                .collect();
+
+            self.content = Some(content);
+        }
+    }
+
+    /// Count the entries of the directories, down to the loaded content. This
+    /// needs a read of every directory and so is only done when displayed.
+    pub fn calculate_entry_count(&mut self) {
+        if let FileType::Directory { .. } = self.file_type {
+            self.entry_count = EntryCount::for_dir(&self.path);
+        }
+
+        if let Some(metas) = &mut self.content {
+            for meta in metas.iter_mut() {
+                meta.calculate_entry_count();
             }
+        }
+    }
 
-            if let Display::DisplayOnlyVisible = display {
-                if name.to_string_lossy().starts_with('.')
-                {
-                    continue;
+    /// Check the entries against the `rules` of `--audit`, down to the loaded
+    /// content, and highlight the names of the ones at risk. `parent_uid` is
+    /// the owner of the directory holding this entry.
+    pub fn calculate_audit(&mut self, rules: &[AuditRule], parent_uid: Option<u32>) {
+        self.audit = Audit::new(
+            rules,
+            self.file_type,
+            &self.permissions,
+            &self.owner,
+            parent_uid,
+        );
+        if !self.audit.is_empty() {
+            self.name = self.name.clone().with_highlight(Some(Elem::Audit));
+        }
+
+        let uid = self.owner.uid();
+        if let Some(metas) = &mut self.content {
+            for meta in metas.iter_mut() {
+                meta.calculate_audit(rules, uid);
+            }
+        }
+    }
+
+    /// Check the entries against the `rules` of the config file, down to the
+    /// loaded content, and give the ones meeting a rule its style and icon.
+    pub fn calculate_display_rules(&mut self, rules: &[DisplayRule], total_size: bool) {
+        if let Some(idx) = DisplayRule::first_match(rules, self, total_size) {
+            let rule = &rules[idx];
+            let mut name = self.name.clone();
+            if rule.style.is_some() {
+                name = name.with_highlight(Some(Elem::Rule(idx)));
+            }
+            if rule.icon.is_some() {
+                name = name.with_icon(rule.icon.clone());
+            }
+            self.name = name;
+        }
+
+        if let Some(metas) = &mut self.content {
+            for meta in metas.iter_mut() {
+                meta.calculate_display_rules(rules, total_size);
+            }
+        }
+    }
+
+    /// Infer the content types, down to the loaded content. With
+    /// `MimeFlag::Deep` the files are opened, so this is only done when
+    /// displayed.
+    pub fn calculate_content_type(&mut self, mode: MimeFlag) {
+        self.content_type = ContentType::for_entry(&self.path, &self.name, self.file_type, mode);
+
+        if let Some(metas) = &mut self.content {
+            for meta in metas.iter_mut() {
+                meta.calculate_content_type(mode);
+            }
+        }
+    }
+
+    /// Hash the files of `metas` up to `max_size` bytes, down to the loaded
+    /// content. The files are read in parallel.
+    pub fn calculate_checksums(metas: &mut [Meta], algorithm: ChecksumFlag, max_size: u64) {
+        fn files_to_hash(metas: &[Meta], max_size: u64, paths: &mut Vec<PathBuf>) {
+            for meta in metas {
+                if let FileType::File { .. } = meta.file_type {
+                    if meta.size.get_bytes() <= max_size {
+                        paths.push(meta.path.clone());
+                    }
+                }
+                if let Some(content) = &meta.content {
+                    files_to_hash(content, max_size, paths);
                 }
             }
+        }
 
-            let mut entry_meta = match Self::from_path(&path) {
-                Ok(res) => res,
-                Err(err) => {
-                    eprintln!("cannot access '{}': {}", path.display(), err);
-                    continue;
+        fn set_checksums(metas: &mut [Meta], checksums: &HashMap<PathBuf, Checksum>) {
+            for meta in metas {
+                if let Some(checksum) = checksums.get(&meta.path) {
+                    meta.checksum = checksum.clone();
                 }
-            };
+                if let Some(content) = &mut meta.content {
+                    set_checksums(content, checksums);
+                }
+            }
+        }
 
-            match entry_meta.recurse_into(depth - 1, display, ignore_globs) {
-                Ok(content) => entry_meta.content = content,
-                Err(err) => {
-                    eprintln!("cannot access '{}': {}", path.display(), err);
-                    continue;
+        let mut paths = Vec::new();
+        files_to_hash(metas, max_size, &mut paths);
+        let checksums = Checksum::for_files(paths, algorithm);
+        set_checksums(metas, &checksums);
+    }
+
+    /// Run the commands of the custom `blocks` on the entries of `metas`, down
+    /// to the loaded content, `jobs` at once.
+    pub fn calculate_custom_blocks(metas: &mut [Meta], blocks: &[CustomBlock], jobs: usize) {
+        fn entry_paths(metas: &[Meta], paths: &mut Vec<PathBuf>) {
+            for meta in metas {
+                paths.push(meta.path.clone());
+                if let Some(content) = &meta.content {
+                    entry_paths(content, paths);
                 }
-            };
+            }
+        }
 
-            content.push(entry_meta);
+        fn set_values(metas: &mut [Meta], values: &mut HashMap<PathBuf, Vec<CustomValue>>) {
+            for meta in metas {
+                if let Some(custom) = values.remove(&meta.path) {
+                    meta.custom = custom;
+                }
+                if let Some(content) = &mut meta.content {
+                    set_values(content, values);
+                }
+            }
         }
 
-        Ok(Some(content))
+        let mut paths = Vec::new();
+        entry_paths(metas, &mut paths);
+        let mut values = CustomValue::for_entries(paths, blocks, jobs);
+        set_values(metas, &mut values);
     }
 
-    pub fn calculate_total_size(&mut self) {
+    /// Replace the size of the directories by the total size of their content.
+    /// With `one_file_system`, the directories on other filesystems only count
+    /// for their own size. The entries of the virtual filesystems, like
+    /// `/proc`, count for nothing.
+    pub fn calculate_total_size(
+        &mut self,
+        one_file_system: bool,
+        errors: &mut Vec<AccessError>,
+        progress: &mut Progress,
+    ) {
+        if Filesystem::is_virtual(&self.path, self.device) {
+            self.size = Size::new(0);
+            return;
+        }
         if let FileType::Directory { uid: _ } = self.file_type {
+            let device = if one_file_system {
+                self.device.id()
+            } else {
+                None
+            };
             if let Some(metas) = &mut self.content {
                 let mut size_accumulated = self.size.get_bytes();
                 for x in &mut metas.iter_mut() {
-                    x.calculate_total_size();
+                    if !one_file_system || x.device == self.device {
+                        x.calculate_total_size(one_file_system, errors, progress);
+                    }
                     size_accumulated += x.size.get_bytes();
                 }
+                size_accumulated +=
+                    Meta::calculate_unlisted_size(&self.path, metas, device, errors, progress);
                 self.size = Size::new(size_accumulated);
             } else {
                 // possibility that 'depth' limited the recursion in 'recurse_into'
-                self.size = Size::new(Meta::calculate_total_file_size(&self.path));
+                self.size = Size::new(Meta::calculate_total_file_size(
+                    &self.path, device, errors, progress,
+                ));
+            }
+        }
+    }
+
+    /// The size of the entries of the directory at `path` left out of the
+    /// `listed` ones, like the ignored or hidden entries, which the totals
+    /// still count.
+    fn calculate_unlisted_size(
+        path: &Path,
+        listed: &[Meta],
+        device: Option<u64>,
+        errors: &mut Vec<AccessError>,
+        progress: &mut Progress,
+    ) -> u64 {
+        let entries = match path.read_dir() {
+            Ok(entries) => entries,
+            Err(err) => {
+                errors.push(AccessError::new(path, err));
+                return 0;
+            }
+        };
+        let listed: HashSet<&Path> = listed.iter().map(|meta| meta.path.as_path()).collect();
+
+        let mut size = 0;
+        for entry in entries {
+            if interrupt::is_interrupted() {
+                break;
+            }
+            match entry {
+                Ok(entry) => {
+                    let path = entry.path();
+                    if !listed.contains(path.as_path()) {
+                        size += Meta::calculate_total_file_size(&path, device, errors, progress);
+                    }
+                }
+                Err(err) => errors.push(AccessError::new(path, err)),
             }
         }
+        size
     }
 
-    fn calculate_total_file_size(path: &PathBuf) -> u64 {
-        let metadata = if read_link(&path).is_ok() {
+    /// The size of `path` and its content, without crossing into the
+    /// filesystems other than the one of `device` when given.
+    fn calculate_total_file_size(
+        path: &PathBuf,
+        device: Option<u64>,
+        errors: &mut Vec<AccessError>,
+        progress: &mut Progress,
+    ) -> u64 {
+        progress.tick(path);
+        let metadata = if read_link(path).is_ok() {
             // If the file is a link, retrieve the metadata without following
             // the link.
             path.symlink_metadata()
@@ -159,32 +604,42 @@ impl Meta {
         let metadata = match metadata {
             Ok(meta) => meta,
             Err(err) => {
-                eprintln!("cannot access '{}': {}", path.display(), err);
+                errors.push(AccessError::new(path, err));
                 return 0;
             }
         };
+        if Filesystem::is_virtual(path, Device::from(&metadata)) {
+            return 0;
+        }
         let file_type = metadata.file_type();
         if file_type.is_file() {
             metadata.len()
         } else if file_type.is_dir() {
             let mut size = metadata.len();
 
+            if device.is_some() && Device::from(&metadata).id() != device {
+                return size;
+            }
+
             let entries = match path.read_dir() {
                 Ok(entries) => entries,
                 Err(err) => {
-                    eprintln!("cannot access '{}': {}", path.display(), err);
+                    errors.push(AccessError::new(path, err));
                     return size;
                 }
             };
             for entry in entries {
+                if interrupt::is_interrupted() {
+                    break;
+                }
                 let path = match entry {
                     Ok(entry) => entry.path(),
                     Err(err) => {
-                        eprintln!("cannot access '{}': {}", path.display(), err);
+                        errors.push(AccessError::new(path, err));
                         continue;
                     }
                 };
-                size += Meta::calculate_total_file_size(&path);
+                size += Meta::calculate_total_file_size(&path, device, errors, progress);
             }
             size
         } else {
@@ -192,37 +647,722 @@ impl Meta {
         }
     }
 
+    /// Read the metadata of `path` itself, without following it when it is a
+    /// symlink.
     pub fn from_path(path: &PathBuf) -> Result<Self, std::io::Error> {
-        let metadata = if read_link(path).is_ok() {
-            // If the file is a link, retrieve the metadata without following
-            // the link.
-            path.symlink_metadata()?
-        } else {
-            path.metadata()?
+        Self::from_path_with(path, false, Details::all())
+    }
+
+    /// Read the metadata of the target of `path` when it is a symlink, like
+    /// `ls -L`. The broken symlinks are described themselves.
+    pub fn from_path_following(path: &PathBuf) -> Result<Self, std::io::Error> {
+        Self::from_path_with(path, true, Details::all())
+    }
+
+    /// Read the metadata of `path`, following it with `dereference`, but only
+    /// gather the `details` asked for. The others keep their default value.
+    ///
+    /// Like `ls`, a symlink is the size of its target when followed, and the
+    /// length of the path it holds otherwise, which is also the size of the
+    /// broken ones.
+    pub fn from_path_with(
+        path: &PathBuf,
+        dereference: bool,
+        details: Details,
+    ) -> Result<Self, std::io::Error> {
+        // A single call tells the links apart, but the junctions of Windows
+        // are only found by reading them.
+        let link_metadata = path.symlink_metadata()?;
+        let is_symlink =
+            link_metadata.file_type().is_symlink() || (cfg!(windows) && read_link(path).is_ok());
+        if !is_symlink {
+            return Self::from_metadata(path, link_metadata, None, false, details);
+        }
+
+        match (dereference, path.metadata()) {
+            (true, Ok(target_metadata)) => {
+                Self::from_metadata(path, target_metadata, None, false, details)
+            }
+            // The metadata of the target is kept aside, for its type.
+            (false, Ok(target_metadata)) => {
+                Self::from_metadata(path, link_metadata, Some(target_metadata), true, details)
+            }
+            (_, Err(_)) => Self::from_metadata(path, link_metadata, None, true, details),
+        }
+    }
+
+    /// The entry at `path` known only by the type its directory records,
+    /// with the default for everything the metadata would tell.
+    #[cfg(unix)]
+    pub fn from_file_type(path: &Path, file_type: fs::FileType) -> Self {
+        let permissions = Permissions::from_mode(0);
+        let file_type = FileType::from_file_type(file_type, None, &permissions);
+
+        Self {
+            name: Name::new(path, file_type),
+            path: path.to_path_buf(),
+            permissions,
+            access_control: AccessControl::default(),
+            security_context: SecurityContext::None,
+            date: Date::default(),
+            device: Device::default(),
+            filesystem: Filesystem::default(),
+            inode: Inode::default(),
+            file_flags: FileFlags::default(),
+            owner: Owner::new(String::from("-"), String::from("-")),
+            file_type,
+            size: Size::new(0),
+            entry_count: EntryCount::default(),
+            content_type: ContentType::default(),
+            checksum: Checksum::default(),
+            custom: Vec::new(),
+            audit: Audit::default(),
+            symlink: SymLink::default(),
+            indicator: Indicator::from(file_type),
+            content: None,
+            directory_flags: None,
+        }
+    }
+
+    /// The entry at `path` known by the type its directory records and by
+    /// its mode, enough to color its name.
+    #[cfg(unix)]
+    pub fn from_mode(
+        path: &Path,
+        file_type: fs::FileType,
+        details: Details,
+    ) -> Result<Self, std::io::Error> {
+        let permissions = Permissions::from_mode(read_mode(path)?);
+        let meta = Self::from_file_type(path, file_type);
+        let file_type = FileType::from_file_type(file_type, None, &permissions);
+        let name_type = match details.executable {
+            ExecFlag::Access => file_type.for_current_user(path),
+            ExecFlag::Mode => file_type,
+        };
+        let capabilities = match file_type {
+            FileType::File { .. } => details.capabilities && has_capabilities(path),
+            _ => false,
         };
+        let highlight = permissions.highlight(file_type, capabilities);
+
+        Ok(Self {
+            name: Name::new(path, name_type).with_highlight(highlight),
+            permissions,
+            file_type,
+            indicator: Indicator::from(file_type),
+            ..meta
+        })
+    }
 
+    fn from_metadata(
+        path: &Path,
+        metadata: Metadata,
+        symlink_meta: Option<Metadata>,
+        is_symlink: bool,
+        details: Details,
+    ) -> Result<Self, std::io::Error> {
         #[cfg(unix)]
-        let owner = Owner::from(&metadata);
+        let owner = if details.owner {
+            Owner::from(&metadata)
+        } else {
+            Owner::without_names(&metadata)
+        };
         #[cfg(unix)]
         let permissions = Permissions::from(&metadata);
 
         #[cfg(windows)]
-        let (owner, permissions) = windows_utils::get_file_data(&path)?;
+        let (owner, permissions) = windows_utils::get_file_data(path, &metadata)?;
 
-        let file_type = FileType::new(&metadata, &permissions);
-        let name = Name::new(&path, file_type);
+        let file_type = FileType::new(&metadata, symlink_meta.as_ref(), &permissions);
+        // Whether the current user may run the file only tells the color of
+        // its name, the type and its `*` indicator follow the mode.
+        #[cfg(unix)]
+        let name_type = match details.executable {
+            ExecFlag::Access => file_type.for_current_user(path),
+            ExecFlag::Mode => file_type,
+        };
+        #[cfg(not(unix))]
+        let name_type = file_type;
+        let capabilities = match file_type {
+            FileType::File { .. } => details.capabilities && has_capabilities(path),
+            _ => false,
+        };
+        let highlight = permissions.highlight(file_type, capabilities);
+        let name = Name::new(path, name_type).with_highlight(highlight);
 
         Ok(Self {
             path: path.to_path_buf(),
-            symlink: SymLink::from(path.as_path()),
+            symlink: if is_symlink && details.symlink {
+                match details.symlink_chain {
+                    Some(max_depth) => SymLink::with_chain(path, max_depth),
+                    None => SymLink::from(path),
+                }
+            } else {
+                SymLink::default()
+            },
+            access_control: if details.access_control {
+                AccessControl::for_path(path, details.xattrs)
+            } else {
+                AccessControl::default()
+            },
+            security_context: if details.security_context {
+                SecurityContext::from(path)
+            } else {
+                SecurityContext::None
+            },
             size: Size::from(&metadata),
-            date: Date::from(&metadata),
+            entry_count: EntryCount::default(),
+            content_type: ContentType::default(),
+            checksum: Checksum::default(),
+            custom: Vec::new(),
+            audit: Audit::default(),
+            date: Date::for_time(&metadata, details.time),
+            device: Device::from(&metadata),
+            filesystem: if details.filesystem {
+                Filesystem::for_entry(path, Device::from(&metadata))
+            } else {
+                Filesystem::default()
+            },
+            inode: Inode::from(&metadata),
+            file_flags: FileFlags::from(&metadata),
             indicator: Indicator::from(file_type),
             owner,
             permissions,
             name,
             file_type,
             content: None,
+            directory_flags: None,
         })
     }
 }
+
+/// The metadata costly to gather, which only some views display.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Details {
+    /// The names of the user and group, instead of their ids.
+    pub owner: bool,
+    pub symlink: bool,
+    /// How many targets of the chains of symlinks are followed, if any.
+    pub symlink_chain: Option<usize>,
+    pub access_control: bool,
+    /// The extended attributes, listed by `--extended`.
+    pub xattrs: bool,
+    pub security_context: bool,
+    /// The type and the device of the filesystem, looked up in the mounts.
+    pub filesystem: bool,
+    /// Which of the times is the date of the entries.
+    pub time: TimeFlag,
+    /// Whether the files have capabilities, only shown by their color.
+    pub capabilities: bool,
+    pub executable: ExecFlag,
+    /// Whether the entries need their metadata, rather than only the type
+    /// their directory records.
+    pub metadata: bool,
+    /// Whether the entries need their mode, for the colors of their names,
+    /// when they don't need the rest of their metadata.
+    pub mode: bool,
+}
+
+impl Details {
+    pub fn all() -> Self {
+        Self {
+            owner: true,
+            symlink: true,
+            symlink_chain: None,
+            access_control: true,
+            xattrs: true,
+            security_context: true,
+            filesystem: true,
+            time: TimeFlag::Modified,
+            capabilities: true,
+            executable: ExecFlag::Access,
+            metadata: true,
+            mode: true,
+        }
+    }
+
+    /// The details displayed with `flags`: the short views only need the
+    /// names and the file types.
+    pub fn for_flags(flags: &Flags) -> Self {
+        let long = match flags.layout {
+            Layout::OneLine { long } | Layout::Tree { long } => long,
+            Layout::Grid => false,
+        };
+        let delimited = flags.format != FormatFlag::Default;
+        let has_block = |block| long && flags.blocks.contains(&block);
+
+        Self {
+            owner: has_block(Block::User) || has_block(Block::Group),
+            symlink: (has_block(Block::Name) && !flags.no_symlink) || delimited,
+            symlink_chain: flags.follow_chain,
+            access_control: has_block(Block::Permission) || (long && flags.extended),
+            xattrs: long && flags.extended,
+            security_context: has_block(Block::Context),
+            filesystem: has_block(Block::Filesystem),
+            time: flags.time,
+            capabilities: flags.color != WhenFlag::Never,
+            executable: flags.executable,
+            metadata: !cfg!(target_os = "linux") || needs_metadata(flags),
+            mode: flags.color != WhenFlag::Never,
+        }
+    }
+}
+
+/// Whether the listing with `flags` shows, sorts, or filters the entries by
+/// something else than their names and types. The symlinks are always read.
+fn needs_metadata(flags: &Flags) -> bool {
+    let long = match flags.layout {
+        Layout::OneLine { long } | Layout::Tree { long } => long,
+        Layout::Grid => false,
+    };
+    let by_name = |sort: &SortFlag| match sort {
+        SortFlag::Name | SortFlag::Extension | SortFlag::None => true,
+        SortFlag::Time | SortFlag::Size | SortFlag::Inode | SortFlag::Links => false,
+    };
+
+    long || flags.display_indicators
+        || !by_name(&flags.sort_by)
+        || !flags.sort_then_by.iter().all(by_name)
+        || !flags.size_filters.is_empty()
+        || !flags.date_filters.is_empty()
+        || !flags.audit_rules.is_empty()
+        || !flags.display_rules.is_empty()
+        || flags.dir_size == DirSizeFlag::Total
+        || flags.one_file_system
+        || flags.dereference
+        || flags.format != FormatFlag::Default
+        || flags.summary
+}
+
+/// Whether the file is given capabilities, stored by Linux in an extended
+/// attribute.
+#[cfg(target_os = "linux")]
+fn has_capabilities(path: &Path) -> bool {
+    use std::ffi::OsStr;
+
+    xattr::get(path, OsStr::new("security.capability"))
+        .map(|value| !value.is_empty())
+        .unwrap_or(false)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn has_capabilities(_: &Path) -> bool {
+    false
+}
+
+/// The mode of the entry at `path`, without following the links. The mask
+/// of `statx` asks for the type and the mode only, so that the filesystems
+/// may skip the rest, and the network ones answer from their cache.
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+fn read_mode(path: &Path) -> Result<u32, std::io::Error> {
+    use std::ffi::CString;
+    use std::mem::MaybeUninit;
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = CString::new(path.as_os_str().as_bytes())?;
+    let mut buf = MaybeUninit::<libc::statx>::zeroed();
+    let result = unsafe {
+        libc::statx(
+            libc::AT_FDCWD,
+            c_path.as_ptr(),
+            libc::AT_SYMLINK_NOFOLLOW | libc::AT_STATX_DONT_SYNC,
+            libc::STATX_TYPE | libc::STATX_MODE,
+            buf.as_mut_ptr(),
+        )
+    };
+    if result != 0 {
+        let err = Error::last_os_error();
+        // The kernels older than 4.11 don't know `statx`.
+        if err.raw_os_error() == Some(libc::ENOSYS) {
+            return read_mode_from_metadata(path);
+        }
+        return Err(err);
+    }
+
+    let buf = unsafe { buf.assume_init() };
+    Ok(u32::from(buf.stx_mode))
+}
+
+#[cfg(all(unix, not(all(target_os = "linux", target_env = "gnu"))))]
+fn read_mode(path: &Path) -> Result<u32, std::io::Error> {
+    read_mode_from_metadata(path)
+}
+
+#[cfg(unix)]
+fn read_mode_from_metadata(path: &Path) -> Result<u32, std::io::Error> {
+    use std::os::unix::fs::PermissionsExt;
+
+    Ok(path.symlink_metadata()?.permissions().mode())
+}
+
+/// Whether the directory of `id` is the root of a virtual filesystem, like
+/// `/proc`, reached from a directory of another filesystem. The recursion
+/// leaves them out, but still reads them when they are the operands.
+#[cfg(unix)]
+fn enters_virtual_filesystem(path: &Path, id: &Option<DirId>, ancestors: &[DirId]) -> bool {
+    match (id, ancestors.last()) {
+        (Some((device, _)), Some((parent_device, _))) if device != parent_device => {
+            Filesystem::is_virtual(path, Device::new(*device))
+        }
+        _ => false,
+    }
+}
+
+#[cfg(not(unix))]
+fn enters_virtual_filesystem(_: &Path, _: &Option<DirId>, _: &[DirId]) -> bool {
+    false
+}
+
+// What tells the directories apart, even when reached through several paths.
+#[cfg(unix)]
+type DirId = (u64, u64);
+#[cfg(not(unix))]
+type DirId = PathBuf;
+
+#[cfg(unix)]
+fn dir_id(path: &Path) -> Option<DirId> {
+    use std::os::unix::fs::MetadataExt;
+
+    path.metadata().ok().map(|meta| (meta.dev(), meta.ino()))
+}
+
+#[cfg(not(unix))]
+fn dir_id(path: &Path) -> Option<DirId> {
+    fs::canonicalize(path).ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Device, Meta};
+    use crate::flags::Flags;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_is_hidden() {
+        use crate::meta::permissions::Attributes;
+
+        let tmp_dir = tempdir().expect("failed to create temp dir");
+        let meta = |name: &str| {
+            let path = tmp_dir.path().join(name);
+            fs::write(&path, "").expect("failed to create file");
+            Meta::from_path(&path).expect("failed to get meta")
+        };
+
+        assert!(meta(".file").is_hidden());
+        assert!(!meta("file").is_hidden());
+
+        let mut dot = meta("dot");
+        dot.name.rename(".");
+        assert!(!dot.is_hidden());
+
+        // Like the files of Windows with the hidden or the system attribute,
+        // only the first being hidden.
+        let mut hidden = meta("hidden");
+        hidden.permissions.attributes = Some(Attributes {
+            hidden: true,
+            ..Attributes::default()
+        });
+        assert!(hidden.is_hidden());
+        let mut system = meta("system");
+        system.permissions.attributes = Some(Attributes {
+            system: true,
+            ..Attributes::default()
+        });
+        assert!(!system.is_hidden());
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_virtual_filesystems_are_left_out() {
+        use super::{dir_id, enters_virtual_filesystem};
+        use crate::progress::Progress;
+        use std::path::Path;
+
+        let proc_dir = Path::new("/proc");
+        let root_id = dir_id(Path::new("/")).expect("failed to get dir id");
+
+        assert!(enters_virtual_filesystem(
+            proc_dir,
+            &dir_id(proc_dir),
+            &[root_id]
+        ));
+        // The operands are read anyway.
+        assert!(!enters_virtual_filesystem(proc_dir, &dir_id(proc_dir), &[]));
+
+        let mut meta = Meta::from_path(&proc_dir.to_path_buf()).expect("failed to get meta");
+        meta.calculate_total_size(false, &mut Vec::new(), &mut Progress::hidden());
+
+        assert_eq!(0, meta.size.get_bytes());
+    }
+
+    #[test]
+    fn test_root_is_its_own_parent() {
+        use crate::flags::Display;
+
+        // The root of the current directory, like `/` or `C:\`.
+        let current_dir = fs::canonicalize(".").expect("failed to canonicalize");
+        let root = current_dir
+            .ancestors()
+            .last()
+            .expect("failed to get root")
+            .to_path_buf();
+
+        let flags = Flags {
+            display: Display::DisplayAll,
+            ..Flags::default()
+        };
+        let meta = Meta::from_path(&root).expect("failed to get meta");
+        let dots = meta.dot_entries(&flags).expect("failed to get dots");
+
+        assert_eq!(2, dots.len());
+        assert_eq!(root, dots[1].path);
+    }
+
+    #[test]
+    fn test_one_file_system_stops_at_other_devices() {
+        let tmp_dir = tempdir().expect("failed to create temp dir");
+        let sub_dir = tmp_dir.path().join("sub");
+        fs::create_dir(&sub_dir).expect("failed to create dir");
+        fs::write(sub_dir.join("file.txt"), "data").expect("failed to create file");
+
+        let mut meta = Meta::from_path(&tmp_dir.path().to_path_buf()).expect("failed to get meta");
+        // Pretend the directory is a mount point of another filesystem.
+        meta.device = Device::new(meta.device.id().unwrap_or(0) + 1);
+
+        let mut errors = Vec::new();
+        let flags = Flags {
+            one_file_system: true,
+            ..Flags::default()
+        };
+        let content = meta
+            .recurse_into(2, &flags, &mut errors)
+            .expect("failed to read dir");
+
+        assert_eq!(1, content.len());
+        assert!(content[0].content.is_none());
+
+        let content = meta
+            .recurse_into(2, &Flags::default(), &mut errors)
+            .expect("failed to read dir");
+
+        assert_eq!(Some(1), content[0].content.as_ref().map(Vec::len));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_symlink_loop_is_not_followed() {
+        use std::os::unix::fs::symlink;
+
+        let tmp_dir = tempdir().expect("failed to create temp dir");
+        let sub_dir = tmp_dir.path().join("sub");
+        fs::create_dir(&sub_dir).expect("failed to create dir");
+        symlink(tmp_dir.path(), sub_dir.join("loop")).expect("failed to create symlink");
+
+        let meta = Meta::from_path(&tmp_dir.path().to_path_buf()).expect("failed to get meta");
+        let flags = Flags {
+            dereference: true,
+            ..Flags::default()
+        };
+        let mut errors = Vec::new();
+        let content = meta
+            .recurse_into(10, &flags, &mut errors)
+            .expect("failed to read dir");
+
+        let sub_content = content[0].content.as_ref().expect("failed to read sub");
+        assert_eq!("loop", sub_content[0].name.name);
+        assert!(sub_content[0].content.is_none());
+        assert_eq!(1, errors.len());
+        assert!(errors[0]
+            .to_string()
+            .ends_with("not listing already-listed directory"));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_symlink_size() {
+        use std::os::unix::fs::symlink;
+
+        let tmp_dir = tempdir().expect("failed to create temp dir");
+        let target = tmp_dir.path().join("target");
+        fs::write(&target, "0123456789abcdef").expect("failed to write file");
+        let link = tmp_dir.path().join("link");
+        symlink(&target, &link).expect("failed to create symlink");
+        let target_len = target.as_os_str().len() as u64;
+
+        let meta = Meta::from_path(&link).expect("failed to get meta");
+        assert_eq!(target_len, meta.size.get_bytes());
+
+        let meta = Meta::from_path_following(&link).expect("failed to get meta");
+        assert_eq!(16, meta.size.get_bytes());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_broken_symlink_size() {
+        use std::os::unix::fs::symlink;
+
+        let tmp_dir = tempdir().expect("failed to create temp dir");
+        let link = tmp_dir.path().join("link");
+        symlink("missing", &link).expect("failed to create symlink");
+
+        for meta in &[
+            Meta::from_path(&link).expect("failed to get meta"),
+            Meta::from_path_following(&link).expect("failed to get meta"),
+        ] {
+            assert_eq!(7, meta.size.get_bytes());
+            assert_eq!(super::FileType::SymLink { is_dir: false }, meta.file_type);
+        }
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_access_only_colors_the_name() {
+        use super::FileType;
+        use std::fs::File;
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp_dir = tempdir().expect("failed to create temp dir");
+        let path = tmp_dir.path().join("script.sh");
+        File::create(&path).expect("failed to create file");
+        // Only the group, which the owner isn't part of here, may run it.
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o070))
+            .expect("failed to set permissions");
+
+        let meta = Meta::from_path(&path).expect("failed to get meta");
+        let root = unsafe { libc::geteuid() } == 0;
+
+        assert_eq!(
+            FileType::File {
+                uid: false,
+                exec: true
+            },
+            meta.file_type
+        );
+        assert_eq!(
+            FileType::File {
+                uid: false,
+                exec: root
+            },
+            meta.name.file_type()
+        );
+    }
+
+    #[test]
+    fn test_details_for_flags() {
+        use super::Details;
+        use crate::flags::{ExecFlag, Layout, TimeFlag};
+
+        let none = Details {
+            owner: false,
+            symlink: false,
+            symlink_chain: None,
+            access_control: false,
+            xattrs: false,
+            security_context: false,
+            filesystem: false,
+            time: TimeFlag::Modified,
+            capabilities: true,
+            executable: ExecFlag::Access,
+            metadata: !cfg!(target_os = "linux"),
+            mode: true,
+        };
+        assert_eq!(none, Details::for_flags(&Flags::default()));
+
+        let flags = Flags {
+            layout: Layout::OneLine { long: true },
+            ..Flags::default()
+        };
+        assert_eq!(
+            Details {
+                xattrs: false,
+                security_context: false,
+                filesystem: false,
+                ..Details::all()
+            },
+            Details::for_flags(&flags)
+        );
+    }
+
+    #[test]
+    fn test_plain_names_skip_the_metadata() {
+        use super::Details;
+        use crate::flags::{Layout, SortFlag, WhenFlag};
+
+        let flags = Flags {
+            color: WhenFlag::Never,
+            ..Flags::default()
+        };
+        assert_eq!(
+            !cfg!(target_os = "linux"),
+            Details::for_flags(&flags).metadata
+        );
+
+        let sorted = Flags {
+            sort_by: SortFlag::Size,
+            ..flags.clone()
+        };
+        assert!(Details::for_flags(&sorted).metadata);
+
+        let long = Flags {
+            layout: Layout::OneLine { long: true },
+            ..flags
+        };
+        assert!(Details::for_flags(&long).metadata);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_from_file_type() {
+        use crate::meta::FileType;
+
+        let tmp_dir = tempdir().expect("failed to create temp dir");
+        fs::write(tmp_dir.path().join("file"), "content").expect("failed to create file");
+        fs::create_dir(tmp_dir.path().join("dir")).expect("failed to create dir");
+
+        let mut metas: Vec<Meta> = fs::read_dir(tmp_dir.path())
+            .expect("failed to read dir")
+            .map(|entry| {
+                let entry = entry.expect("failed to read entry");
+                let file_type = entry.file_type().expect("failed to get file type");
+                Meta::from_file_type(&entry.path(), file_type)
+            })
+            .collect();
+        metas.sort_by(|a, b| a.path.cmp(&b.path));
+
+        assert_eq!(FileType::Directory { uid: false }, metas[0].file_type);
+        assert_eq!(
+            FileType::File {
+                uid: false,
+                exec: false
+            },
+            metas[1].file_type
+        );
+        assert_eq!("file", metas[1].name.name());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_skipped_details_keep_their_default() {
+        use super::Details;
+        use std::os::unix::fs::symlink;
+
+        let tmp_dir = tempdir().expect("failed to create temp dir");
+        let link_path = tmp_dir.path().join("link");
+        symlink("target", &link_path).expect("failed to create symlink");
+
+        let none = Details::for_flags(&Flags::default());
+        let meta = Meta::from_path_with(&link_path, false, none).expect("failed to get meta");
+        assert_eq!(None, meta.symlink.symlink_string(&Flags::default()));
+        assert_eq!(
+            meta.owner.uid().map(|uid| uid.to_string()),
+            Some(meta.owner.user())
+        );
+
+        let meta = Meta::from_path(&link_path).expect("failed to get meta");
+        assert_eq!(
+            Some(String::from("target")),
+            meta.symlink.symlink_string(&Flags::default())
+        );
+    }
+}
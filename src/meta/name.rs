@@ -1,75 +1,188 @@
 use crate::color::{ColoredString, Colors, Elem};
+use crate::flags::{Flags, PathFlag, QuotingFlag, WhenFlag};
 use crate::icon::Icons;
 use crate::meta::filetype::FileType;
+use std::borrow::Cow;
 use std::cmp::{Ordering, PartialOrd};
+use std::ffi::{OsStr, OsString};
 use std::path::Path;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+// The characters having a meaning for the shells, that need to be quoted.
+const SHELL_SPECIAL_CHARS: &str = " \t\n!\"#$&'()*,;<=>?[\\]^`{|}~";
 
 #[derive(Clone, Debug, Eq)]
 pub struct Name {
+    /// The name to display, with the bytes which aren't valid UTF-8 replaced
+    /// by `\u{fffd}`.
     pub name: String,
-    path: String,
-    extension: Option<String>,
+    /// The name as stored by the filesystem, for the machine readable outputs,
+    /// only kept when it isn't valid UTF-8.
+    raw_name: Option<OsString>,
     file_type: FileType,
+    highlight: Option<Elem>,
+    /// The icon set by a rule of the config file, instead of the one of the type.
+    icon: Option<String>,
 }
 
 impl Name {
     pub fn new(path: &Path, file_type: FileType) -> Self {
-        let name = match path.file_name() {
-            Some(name) => name.to_string_lossy().to_string(),
-            None => path.to_string_lossy().to_string(),
+        let raw_name = match path.file_name() {
+            Some(name) => name,
+            None => path.as_os_str(),
+        };
+        let (name, raw_name) = match raw_name.to_str() {
+            Some(name) => (name.to_string(), None),
+            None => (
+                raw_name.to_string_lossy().to_string(),
+                Some(raw_name.to_os_string()),
+            ),
         };
-
-        let mut extension = None;
-        if let Some(res) = path.extension() {
-            extension = Some(
-                res.to_str()
-                    .expect("failed to encode file name")
-                    .to_string(),
-            );
-        }
-
-        let path_string = path.to_string_lossy().to_string();
 
         Self {
             name,
-            path: path_string,
-            extension,
+            raw_name,
             file_type,
+            highlight: None,
+            icon: None,
         }
     }
 
-    pub fn name_string(&self, icons: &Icons) -> String {
+    /// Color the name with `highlight` rather than by its type, for the entries
+    /// whose permissions deserve attention.
+    pub fn with_highlight(mut self, highlight: Option<Elem>) -> Self {
+        self.highlight = highlight;
+        self
+    }
+
+    /// Show the entry with `icon` rather than the one of its type.
+    pub fn with_icon(mut self, icon: Option<String>) -> Self {
+        self.icon = icon;
+        self
+    }
+
+    pub fn icon(&self) -> Option<&String> {
+        self.icon.as_ref()
+    }
+
+    /// Name the entry `name`, like `.` and `..` for the directories.
+    pub fn rename(&mut self, name: &str) {
+        self.name = name.to_string();
+        self.raw_name = None;
+    }
+
+    /// The name string of the entry at `path`.
+    pub fn name_string(&self, path: &Path, icons: &Icons, flags: &Flags) -> String {
+        self.name_string_within(path, icons, flags, None)
+    }
+
+    /// The name string, with the name wider than `max_width` cut in its
+    /// middle but for the extension, like `a_very…name.txt`.
+    pub fn name_string_within(
+        &self,
+        path: &Path,
+        icons: &Icons,
+        flags: &Flags,
+        max_width: Option<usize>,
+    ) -> String {
         let icon = icons.get(self);
-        let mut content = String::with_capacity(icon.len() + self.name.len() + 3 /* spaces */);
+        // The paths are already absolute with `PathFlag::Absolute`.
+        let name = match flags.full_path {
+            PathFlag::None => Cow::Borrowed(self.name.as_str()),
+            PathFlag::Relative | PathFlag::Absolute => path.to_string_lossy(),
+        };
+        // The control characters would mix with the escape sequences of the
+        // colors, so the colored names show them as `?` like `ls -q`.
+        let name = match (flags.color, flags.quoting) {
+            (WhenFlag::Auto, QuotingFlag::Literal) | (WhenFlag::Always, QuotingFlag::Literal)
+                if name.contains(char::is_control) =>
+            {
+                Cow::Owned(name.replace(char::is_control, "?"))
+            }
+            _ => name,
+        };
+        let name = match max_width {
+            Some(max_width) => quote(
+                &truncate_middle(&name, max_width, &flags.symbols.ellipsis),
+                flags.quoting,
+            ),
+            None => quote(&name, flags.quoting),
+        };
+        let mut content = String::with_capacity(icon.len() + name.len() + 3 /* spaces */);
 
         content += icon.as_str();
-        content += &self.name;
+        content += &name;
         content
     }
 
-    pub fn render(&self, colors: &Colors, icons: &Icons) -> ColoredString {
-        let content = self.name_string(&icons);
+    /// The name escaped according to the quoting style, so that the control
+    /// characters can neither break the layout nor corrupt the terminal.
+    pub fn quoted_name(&self, quoting: QuotingFlag) -> String {
+        quote(&self.name, quoting)
+    }
+
+    /// Render the name of the entry at `path`, which `LS_COLORS` may match.
+    pub fn render(
+        &self,
+        path: &Path,
+        colors: &Colors,
+        icons: &Icons,
+        flags: &Flags,
+    ) -> ColoredString<'_> {
+        self.render_within(path, colors, icons, flags, None)
+    }
+
+    /// Render the name cut to `max_width` like `name_string_within`.
+    pub fn render_within(
+        &self,
+        path: &Path,
+        colors: &Colors,
+        icons: &Icons,
+        flags: &Flags,
+        max_width: Option<usize>,
+    ) -> ColoredString<'_> {
+        let content = self.name_string_within(path, icons, flags, max_width);
+
+        // Like `ls`, the permissions win over the colors of the extensions.
+        if let Some(ref highlight) = self.highlight {
+            return colors.colorize(content, highlight);
+        }
 
-        let elem = match self.file_type {
+        let kind = match self.file_type {
             FileType::CharDevice => Elem::CharDevice,
             FileType::Directory { uid } => Elem::Dir { uid },
-            FileType::SymLink => Elem::SymLink,
+            FileType::SymLink { .. } => Elem::SymLink,
             FileType::File { uid, exec } => Elem::File { uid, exec },
-            _ => Elem::File {
+            FileType::Pipe => Elem::Pipe,
+            FileType::BlockDevice => Elem::BlockDevice,
+            FileType::Socket => Elem::Socket,
+            FileType::Special => Elem::Special,
+        };
+        // The themes color the names of the other types like the files.
+        let elem = match &kind {
+            Elem::Pipe | Elem::BlockDevice | Elem::Socket | Elem::Special => Elem::File {
                 exec: false,
                 uid: false,
             },
+            kind => kind.clone(),
         };
 
-        colors.colorize_using_path(content, &self.path, &elem)
+        colors.colorize_using_path(content, path, &kind, &elem)
     }
 
     pub fn name(&self) -> String {
         self.name.clone()
     }
 
-    pub fn extension(&self) -> Option<String> {
-        self.extension.clone()
+    pub fn raw_name(&self) -> &OsStr {
+        match &self.raw_name {
+            Some(raw_name) => raw_name,
+            None => OsStr::new(&self.name),
+        }
+    }
+
+    pub fn extension(&self) -> Option<&str> {
+        Path::new(&self.name).extension().and_then(OsStr::to_str)
     }
 
     pub fn file_type(&self) -> FileType {
@@ -77,6 +190,108 @@ impl Name {
     }
 }
 
+/// Cut `name` to `max_width` columns with `ellipsis` in its middle, keeping
+/// its extension unless it is too wide itself. The extension is the one of
+/// `name` as displayed, its control characters already replaced.
+fn truncate_middle(name: &str, max_width: usize, ellipsis: &str) -> String {
+    if name.width() <= max_width {
+        return name.to_string();
+    }
+
+    let extension = match Path::new(name).extension().and_then(OsStr::to_str) {
+        // Around the ellipsis, at least a character of the stem on each side.
+        Some(extension) if extension.width() + 1 + ellipsis.width() + 2 <= max_width => {
+            format!(".{}", extension)
+        }
+        _ => String::new(),
+    };
+    let stem = &name[..name.len() - extension.len()];
+
+    let available = max_width.saturating_sub(extension.width() + ellipsis.width());
+
+    let mut tail = Vec::new();
+    let mut tail_width = 0;
+    for c in stem.chars().rev() {
+        let width = c.width().unwrap_or(0);
+        if tail_width + width > available / 2 {
+            break;
+        }
+        tail_width += width;
+        tail.push(c);
+    }
+    let tail: String = tail.into_iter().rev().collect();
+
+    // The head takes what the tail left, as the wide characters may not fill
+    // its half.
+    let mut head = String::new();
+    let mut head_width = 0;
+    for c in stem.chars() {
+        let width = c.width().unwrap_or(0);
+        if head_width + width > available - tail_width {
+            break;
+        }
+        head_width += width;
+        head.push(c);
+    }
+
+    format!("{}{}{}{}", head, ellipsis, tail, extension)
+}
+
+fn quote(name: &str, quoting: QuotingFlag) -> String {
+    match quoting {
+        QuotingFlag::Literal => name.to_string(),
+        QuotingFlag::Shell => shell_quote(name),
+        QuotingFlag::C => format!("\"{}\"", c_escape(name, '"')),
+    }
+}
+
+/// Quote `name` for a POSIX shell when needed: with single quotes, or with the
+/// `$'...'` form of bash and zsh when it holds control characters.
+fn shell_quote(name: &str) -> String {
+    if name.chars().any(char::is_control) {
+        return format!("$'{}'", c_escape(name, '\''));
+    }
+
+    if name.is_empty() || name.contains(|c| SHELL_SPECIAL_CHARS.contains(c)) {
+        return format!("'{}'", name.replace('\'', "'\\''"));
+    }
+
+    name.to_string()
+}
+
+/// Escape the backslashes, the `quote` character and the control characters
+/// like the C string literals do.
+fn c_escape(name: &str, quote: char) -> String {
+    let mut res = String::with_capacity(name.len());
+
+    for c in name.chars() {
+        match c {
+            '\\' => res.push_str("\\\\"),
+            '\n' => res.push_str("\\n"),
+            '\t' => res.push_str("\\t"),
+            '\r' => res.push_str("\\r"),
+            '\u{7}' => res.push_str("\\a"),
+            '\u{8}' => res.push_str("\\b"),
+            '\u{b}' => res.push_str("\\v"),
+            '\u{c}' => res.push_str("\\f"),
+            c if c == quote => {
+                res.push('\\');
+                res.push(c);
+            }
+            c if c.is_control() => {
+                // The octal escapes are limited to the byte values.
+                let mut buf = [0; 4];
+                for byte in c.encode_utf8(&mut buf).bytes() {
+                    res.push_str(&format!("\\{:03o}", byte));
+                }
+            }
+            c => res.push(c),
+        }
+    }
+
+    res
+}
+
 impl Ord for Name {
     fn cmp(&self, other: &Self) -> Ordering {
         self.name.to_lowercase().cmp(&other.name.to_lowercase())
@@ -85,7 +300,7 @@ impl Ord for Name {
 
 impl PartialOrd for Name {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.name.to_lowercase().cmp(&other.name.to_lowercase()))
+        Some(self.cmp(other))
     }
 }
 
@@ -97,8 +312,9 @@ impl PartialEq for Name {
 
 #[cfg(test)]
 mod test {
-    use super::Name;
-    use crate::color::{self, Colors};
+    use super::{truncate_middle, Name};
+    use crate::color::{self, Colors, Elem};
+    use crate::flags::{Flags, PathFlag, QuotingFlag, WhenFlag};
     use crate::icon::{self, Icons};
     use crate::meta::FileType;
     use crate::meta::Meta;
@@ -114,6 +330,22 @@ mod test {
     use std::process::Command;
     use tempfile::tempdir;
 
+    #[test]
+    fn test_truncate_middle_keeps_the_extension() {
+        let name = "a_really_long_name.txt";
+
+        assert_eq!(name, truncate_middle(name, 30, "…"));
+        assert_eq!("a_re…ame.txt", truncate_middle(name, 12, "…"));
+        assert_eq!("a_r~ame.txt", truncate_middle(name, 11, "~"));
+        // The extension too wide to keep is cut like the rest.
+        assert_eq!("a_r…xt", truncate_middle(name, 6, "…"));
+    }
+
+    #[test]
+    fn test_truncate_middle_counts_the_columns() {
+        assert_eq!("日本…語", truncate_middle("日本語日本語", 7, "…"));
+    }
+
     #[test]
     #[cfg(unix)] // Windows uses different default permissions
     fn test_print_file_name() {
@@ -126,12 +358,12 @@ mod test {
         let meta = file_path.metadata().expect("failed to get metas");
 
         let colors = Colors::new(color::Theme::NoLscolors);
-        let file_type = FileType::new(&meta, &Permissions::from(&meta));
+        let file_type = FileType::new(&meta, None, &Permissions::from(&meta));
         let name = Name::new(&file_path, file_type);
 
         assert_eq!(
             Colour::Fixed(184).paint("  file.txt"),
-            name.render(&colors, &icons)
+            name.render(&file_path, &colors, &icons, &Flags::default())
         );
     }
 
@@ -149,7 +381,8 @@ mod test {
 
         assert_eq!(
             Colour::Fixed(33).paint("  directory"),
-            meta.name.render(&colors, &icons)
+            meta.name
+                .render(&meta.path, &colors, &icons, &Flags::default())
         );
     }
 
@@ -171,12 +404,12 @@ mod test {
             .expect("failed to get metas");
 
         let colors = Colors::new(color::Theme::NoLscolors);
-        let file_type = FileType::new(&meta, &Permissions::from(&meta));
+        let file_type = FileType::new(&meta, None, &Permissions::from(&meta));
         let name = Name::new(&symlink_path, file_type);
 
         assert_eq!(
             Colour::Fixed(44).paint("  target.tmp"),
-            name.render(&colors, &icons)
+            name.render(&symlink_path, &colors, &icons, &Flags::default())
         );
     }
 
@@ -193,16 +426,32 @@ mod test {
             .status()
             .expect("failed to exec mkfifo")
             .success();
-        assert_eq!(true, success, "failed to exec mkfifo");
+        assert!(success, "failed to exec mkfifo");
         let meta = pipe_path.metadata().expect("failed to get metas");
 
         let colors = Colors::new(color::Theme::NoLscolors);
-        let file_type = FileType::new(&meta, &Permissions::from(&meta));
+        let file_type = FileType::new(&meta, None, &Permissions::from(&meta));
         let name = Name::new(&pipe_path, file_type);
 
         assert_eq!(
             Colour::Fixed(184).paint("  pipe.tmp"),
-            name.render(&colors, &icons)
+            name.render(&pipe_path, &colors, &icons, &Flags::default())
+        );
+    }
+
+    #[test]
+    fn test_print_capabilities_name() {
+        let icons = Icons::new(icon::Theme::NoIcon);
+        let colors = Colors::new(color::Theme::NoLscolors);
+        let file_type = FileType::File {
+            uid: false,
+            exec: true,
+        };
+        let name = Name::new(Path::new("ping"), file_type).with_highlight(Some(Elem::Capabilities));
+
+        assert_eq!(
+            Colour::Black.on(Colour::Fixed(124)).paint("ping"),
+            name.render(Path::new("ping"), &colors, &icons, &Flags::default())
         );
     }
 
@@ -220,7 +469,10 @@ mod test {
 
         assert_eq!(
             "file.txt",
-            meta.name.render(&colors, &icons).to_string().as_str()
+            meta.name
+                .render(&meta.path, &colors, &icons, &Flags::default())
+                .to_string()
+                .as_str()
         );
     }
 
@@ -229,14 +481,34 @@ mod test {
         let path = Path::new("some-file.txt");
 
         let name = Name::new(
-            &path,
+            path,
             FileType::File {
                 uid: false,
                 exec: false,
             },
         );
 
-        assert_eq!(Some(String::from("txt")), name.extension());
+        assert_eq!(Some("txt"), name.extension());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_raw_name_keeps_the_invalid_bytes() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let file_type = FileType::File {
+            uid: false,
+            exec: false,
+        };
+        let raw_name = OsStr::from_bytes(b"caf\xe9.txt");
+        let name = Name::new(Path::new(raw_name), file_type);
+        assert_eq!("caf\u{fffd}.txt", name.name);
+        assert_eq!(raw_name, name.raw_name());
+        assert_eq!(Some("txt"), name.extension());
+
+        let name = Name::new(Path::new("dir/café"), file_type);
+        assert_eq!(OsStr::new("café"), name.raw_name());
     }
 
     #[test]
@@ -244,7 +516,7 @@ mod test {
         let path = Path::new(".gitignore");
 
         let name = Name::new(
-            &path,
+            path,
             FileType::File {
                 uid: false,
                 exec: false,
@@ -258,7 +530,7 @@ mod test {
     fn test_order_impl_is_case_insensitive() {
         let path_1 = Path::new("AAAA");
         let name_1 = Name::new(
-            &path_1,
+            path_1,
             FileType::File {
                 uid: false,
                 exec: false,
@@ -267,7 +539,7 @@ mod test {
 
         let path_2 = Path::new("aaaa");
         let name_2 = Name::new(
-            &path_2,
+            path_2,
             FileType::File {
                 uid: false,
                 exec: false,
@@ -281,7 +553,7 @@ mod test {
     fn test_partial_order_impl() {
         let path_a = Path::new("aaaa");
         let name_a = Name::new(
-            &path_a,
+            path_a,
             FileType::File {
                 uid: false,
                 exec: false,
@@ -290,21 +562,21 @@ mod test {
 
         let path_z = Path::new("zzzz");
         let name_z = Name::new(
-            &path_z,
+            path_z,
             FileType::File {
                 uid: false,
                 exec: false,
             },
         );
 
-        assert_eq!(true, name_a < name_z);
+        assert!(name_a < name_z);
     }
 
     #[test]
     fn test_partial_order_impl_is_case_insensitive() {
         let path_a = Path::new("aaaa");
         let name_a = Name::new(
-            &path_a,
+            path_a,
             FileType::File {
                 uid: false,
                 exec: false,
@@ -313,21 +585,21 @@ mod test {
 
         let path_z = Path::new("ZZZZ");
         let name_z = Name::new(
-            &path_z,
+            path_z,
             FileType::File {
                 uid: false,
                 exec: false,
             },
         );
 
-        assert_eq!(true, name_a < name_z);
+        assert!(name_a < name_z);
     }
 
     #[test]
     fn test_partial_eq_impl() {
         let path_1 = Path::new("aaaa");
         let name_1 = Name::new(
-            &path_1,
+            path_1,
             FileType::File {
                 uid: false,
                 exec: false,
@@ -336,21 +608,21 @@ mod test {
 
         let path_2 = Path::new("aaaa");
         let name_2 = Name::new(
-            &path_2,
+            path_2,
             FileType::File {
                 uid: false,
                 exec: false,
             },
         );
 
-        assert_eq!(true, name_1 == name_2);
+        assert!(name_1 == name_2);
     }
 
     #[test]
     fn test_partial_eq_impl_is_case_insensitive() {
         let path_1 = Path::new("AAAA");
         let name_1 = Name::new(
-            &path_1,
+            path_1,
             FileType::File {
                 uid: false,
                 exec: false,
@@ -359,13 +631,123 @@ mod test {
 
         let path_2 = Path::new("aaaa");
         let name_2 = Name::new(
-            &path_2,
+            path_2,
+            FileType::File {
+                uid: false,
+                exec: false,
+            },
+        );
+
+        assert!(name_1 == name_2);
+    }
+
+    #[test]
+    fn test_quoted_name() {
+        let name = |name: &str| {
+            Name::new(
+                Path::new(name),
+                FileType::File {
+                    uid: false,
+                    exec: false,
+                },
+            )
+        };
+
+        assert_eq!(
+            "plain.txt",
+            name("plain.txt").quoted_name(QuotingFlag::Shell)
+        );
+        assert_eq!("'a b'", name("a b").quoted_name(QuotingFlag::Shell));
+        assert_eq!("'it'\\''s'", name("it's").quoted_name(QuotingFlag::Shell));
+        assert_eq!("$'a\\nb'", name("a\nb").quoted_name(QuotingFlag::Shell));
+        assert_eq!("\"a\\tb\"", name("a\tb").quoted_name(QuotingFlag::C));
+        assert_eq!("\"\\033\"", name("\u{1b}").quoted_name(QuotingFlag::C));
+        assert_eq!("a\nb", name("a\nb").quoted_name(QuotingFlag::Literal));
+    }
+
+    #[test]
+    fn test_colored_names_hide_the_control_chars() {
+        let name = Name::new(
+            Path::new("a\u{1b}[31mb"),
+            FileType::File {
+                uid: false,
+                exec: false,
+            },
+        );
+        let icons = Icons::new(icon::Theme::NoIcon);
+        let path = Path::new("a\u{1b}[31mb");
+        let mut flags = Flags {
+            color: WhenFlag::Always,
+            ..Flags::default()
+        };
+
+        assert_eq!("a?[31mb", name.name_string(path, &icons, &flags));
+        flags.quoting = QuotingFlag::C;
+        assert_eq!("\"a\\033[31mb\"", name.name_string(path, &icons, &flags));
+        flags.quoting = QuotingFlag::Literal;
+        flags.color = WhenFlag::Never;
+        assert_eq!("a\u{1b}[31mb", name.name_string(path, &icons, &flags));
+    }
+
+    #[test]
+    fn test_truncated_names_hide_the_control_chars_of_the_extension() {
+        // The control character is wider than the `?` replacing it.
+        let path = Path::new("日本語日本語日本語日本語.x\u{85}");
+        let name = Name::new(
+            path,
+            FileType::File {
+                uid: false,
+                exec: false,
+            },
+        );
+        let icons = Icons::new(icon::Theme::NoIcon);
+        let flags = Flags {
+            color: WhenFlag::Always,
+            ..Flags::default()
+        };
+
+        assert_eq!(
+            "日本…語.x?",
+            name.name_string_within(path, &icons, &flags, Some(10))
+        );
+    }
+
+    #[test]
+    fn test_name_width_uses_the_quoted_name() {
+        let name = Name::new(
+            Path::new("a b"),
+            FileType::File {
+                uid: false,
+                exec: false,
+            },
+        );
+        let icons = Icons::new(icon::Theme::NoIcon);
+        let flags = Flags {
+            quoting: QuotingFlag::Shell,
+            ..Flags::default()
+        };
+
+        assert_eq!("'a b'", name.name_string(Path::new("a b"), &icons, &flags));
+    }
+
+    #[test]
+    fn test_name_string_with_the_full_path() {
+        let name = Name::new(
+            Path::new("dir/a b"),
             FileType::File {
                 uid: false,
                 exec: false,
             },
         );
+        let icons = Icons::new(icon::Theme::NoIcon);
+        let mut flags = Flags {
+            full_path: PathFlag::Relative,
+            ..Flags::default()
+        };
 
-        assert_eq!(true, name_1 == name_2);
+        let path = Path::new("dir/a b");
+        assert_eq!("dir/a b", name.name_string(path, &icons, &flags));
+        flags.quoting = QuotingFlag::Shell;
+        assert_eq!("'dir/a b'", name.name_string(path, &icons, &flags));
     }
 }
@@ -1,37 +1,115 @@
 use crate::color::{ColoredString, Colors, Elem};
+use crate::flags::Flags;
 #[cfg(unix)]
 use std::fs::Metadata;
+#[cfg(unix)]
+use std::sync::{Mutex, OnceLock};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+#[cfg(unix)]
+use users::UsersCache;
 
 #[derive(Clone, Debug)]
 pub struct Owner {
     user: String,
     group: String,
+    // The numeric ids, which only exist on Unix.
+    uid: Option<u32>,
+    gid: Option<u32>,
 }
 
 impl Owner {
-    #[cfg_attr(unix, allow(dead_code))]
     pub fn new(user: String, group: String) -> Self {
-        Self { user, group }
+        Self {
+            user,
+            group,
+            uid: None,
+            gid: None,
+        }
     }
 }
 
 #[cfg(unix)]
-impl<'a> From<&'a Metadata> for Owner {
-    fn from(meta: &Metadata) -> Self {
+impl Owner {
+    /// The owner with the ids in place of the names, saving their lookup.
+    pub fn without_names(meta: &Metadata) -> Self {
         use std::os::unix::fs::MetadataExt;
-        use users::{get_group_by_gid, get_user_by_uid};
 
-        let user = match get_user_by_uid(meta.uid()) {
-            Some(res) => res.name().to_string_lossy().to_string(),
-            None => meta.uid().to_string(),
-        };
+        Self {
+            user: meta.uid().to_string(),
+            group: meta.gid().to_string(),
+            uid: Some(meta.uid()),
+            gid: Some(meta.gid()),
+        }
+    }
+}
 
-        let group = match get_group_by_gid(meta.gid()) {
-            Some(res) => res.name().to_string_lossy().to_string(),
-            None => meta.gid().to_string(),
-        };
+/// Runs `lookup` with the names of the users and groups. Most of the entries
+/// share a few owners, and each lookup may query a remote directory like
+/// LDAP, so the names are resolved once for the whole process, whichever
+/// thread reads the entries.
+#[cfg(unix)]
+fn with_names<F, T>(lookup: F) -> T
+where
+    F: FnOnce(&UsersCache) -> T,
+{
+    static NAMES: OnceLock<Mutex<UsersCache>> = OnceLock::new();
+
+    let names = NAMES.get_or_init(|| Mutex::new(UsersCache::new()));
+    match names.lock() {
+        Ok(names) => lookup(&names),
+        Err(poisoned) => lookup(&poisoned.into_inner()),
+    }
+}
+
+// The user running the command, and the groups it belongs to.
+#[cfg(unix)]
+fn current_uid() -> u32 {
+    static CURRENT_UID: OnceLock<u32> = OnceLock::new();
+
+    *CURRENT_UID.get_or_init(users::get_effective_uid)
+}
 
-        Self { user, group }
+#[cfg(unix)]
+fn current_gids() -> &'static [u32] {
+    static CURRENT_GIDS: OnceLock<Vec<u32>> = OnceLock::new();
+
+    CURRENT_GIDS.get_or_init(read_current_gids)
+}
+
+#[cfg(unix)]
+fn read_current_gids() -> Vec<u32> {
+    let mut gids: Vec<u32> = users::group_access_list()
+        .map(|groups| groups.iter().map(users::Group::gid).collect())
+        .unwrap_or_default();
+    gids.push(users::get_effective_gid());
+    gids
+}
+
+#[cfg(unix)]
+impl From<&Metadata> for Owner {
+    fn from(meta: &Metadata) -> Self {
+        use std::os::unix::fs::MetadataExt;
+        use users::{Groups, Users};
+
+        // The ids without a name stand for themselves.
+        let (user, group) = with_names(|names| {
+            let user = names
+                .get_user_by_uid(meta.uid())
+                .map(|user| user.name().to_string_lossy().to_string())
+                .unwrap_or_else(|| meta.uid().to_string());
+            let group = names
+                .get_group_by_gid(meta.gid())
+                .map(|group| group.name().to_string_lossy().to_string())
+                .unwrap_or_else(|| meta.gid().to_string());
+            (user, group)
+        });
+
+        Self {
+            user,
+            group,
+            uid: Some(meta.uid()),
+            gid: Some(meta.gid()),
+        }
     }
 }
 
@@ -44,25 +122,175 @@ impl Owner {
         self.group.clone()
     }
 
-    pub fn render_user(&self, colors: &Colors, user_alignment: usize) -> ColoredString {
-        let mut res = String::with_capacity(user_alignment - self.user.len());
+    pub fn uid(&self) -> Option<u32> {
+        self.uid
+    }
+
+    pub fn gid(&self) -> Option<u32> {
+        self.gid
+    }
+
+    /// Whether the entry belongs to the user running the command.
+    #[cfg(unix)]
+    pub fn is_current_user(&self) -> bool {
+        self.uid == Some(current_uid())
+    }
 
-        for _ in 0..(user_alignment - self.user.len()) {
+    /// Whether the group of the entry is one of the current user.
+    #[cfg(unix)]
+    pub fn is_current_group(&self) -> bool {
+        match self.gid {
+            Some(gid) => current_gids().contains(&gid),
+            None => false,
+        }
+    }
+
+    #[cfg(not(unix))]
+    pub fn is_current_user(&self) -> bool {
+        false
+    }
+
+    #[cfg(not(unix))]
+    pub fn is_current_group(&self) -> bool {
+        false
+    }
+
+    /// The user name, truncated to the `--owner-width`.
+    pub fn user_string(&self, flags: &Flags) -> String {
+        truncate(&self.user, flags)
+    }
+
+    /// The group name, truncated to the `--owner-width`.
+    pub fn group_string(&self, flags: &Flags) -> String {
+        truncate(&self.group, flags)
+    }
+
+    pub fn render_user(
+        &self,
+        colors: &Colors,
+        user_alignment: usize,
+        flags: &Flags,
+    ) -> ColoredString<'_> {
+        let user = self.user_string(flags);
+        let mut res = String::with_capacity(user_alignment);
+
+        for _ in user.width()..user_alignment {
             res.push(' ');
         }
 
-        res += &self.user;
-        colors.colorize(res, &Elem::User)
+        res += &user;
+        if self.is_current_user() {
+            colors.colorize(res, &Elem::CurrentUser)
+        } else {
+            colors.colorize(res, &Elem::User)
+        }
     }
 
-    pub fn render_group(&self, colors: &Colors, group_alignment: usize) -> ColoredString {
-        let mut res = String::with_capacity(group_alignment - self.group.len());
+    pub fn render_group(
+        &self,
+        colors: &Colors,
+        group_alignment: usize,
+        flags: &Flags,
+    ) -> ColoredString<'_> {
+        let group = self.group_string(flags);
+        let mut res = String::with_capacity(group_alignment);
 
-        for _ in 0..(group_alignment - self.group.len()) {
+        for _ in group.width()..group_alignment {
             res.push(' ');
         }
 
-        res += &self.group;
-        colors.colorize(res, &Elem::Group)
+        res += &group;
+        if self.is_current_group() {
+            colors.colorize(res, &Elem::CurrentGroup)
+        } else {
+            colors.colorize(res, &Elem::Group)
+        }
+    }
+}
+
+/// Cut the `name` wider than the `--owner-width`, ending it with the
+/// ellipsis, like `DOMAIN\first…`.
+fn truncate(name: &str, flags: &Flags) -> String {
+    let max_width = match flags.owner_width {
+        Some(max_width) if name.width() > max_width => max_width,
+        _ => return name.to_string(),
+    };
+
+    let ellipsis = &flags.symbols.ellipsis;
+    let mut res = String::with_capacity(max_width + ellipsis.len());
+    let mut width = ellipsis.width();
+    for c in name.chars() {
+        width += c.width().unwrap_or(0);
+        if width > max_width {
+            break;
+        }
+        res.push(c);
+    }
+
+    res + ellipsis
+}
+
+#[cfg(test)]
+mod test {
+    use super::Owner;
+    use crate::flags::Flags;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_names_are_shared_by_the_threads() {
+        use super::with_names;
+        use std::thread;
+        use users::Users;
+
+        let name = |uid| with_names(|names| names.get_user_by_uid(uid).map(|user| user.uid()));
+        let other_thread = thread::spawn(move || name(0)).join().unwrap();
+
+        assert_eq!(other_thread, name(0));
+        // The ids without a user are cached too.
+        assert_eq!(None, name(4_000_000_000));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_current_user_is_highlighted() {
+        use crate::color::{Colors, Elem, Theme};
+        use tempfile::tempdir;
+
+        let tmp_dir = tempdir().expect("failed to create temp dir");
+        let meta = tmp_dir.path().metadata().expect("failed to get metadata");
+        let colors = Colors::new(Theme::Default);
+        let flags = Flags::default();
+
+        let owner = Owner::without_names(&meta);
+        assert!(owner.is_current_user());
+        assert!(owner.is_current_group());
+        assert_eq!(
+            colors.colorize(owner.user(), &Elem::CurrentUser),
+            owner.render_user(&colors, 0, &flags)
+        );
+        assert_eq!(
+            colors.colorize(owner.group(), &Elem::CurrentGroup),
+            owner.render_group(&colors, 0, &flags)
+        );
+
+        let other = Owner::new(String::from("other"), String::from("others"));
+        assert!(!other.is_current_user());
+        assert_eq!(
+            colors.colorize(String::from("other"), &Elem::User),
+            other.render_user(&colors, 0, &flags)
+        );
+    }
+
+    #[test]
+    fn test_owner_width_truncates_the_names() {
+        let owner = Owner::new(String::from("DOMAIN\\first.last"), String::from("users"));
+        let flags = Flags {
+            owner_width: Some(8),
+            ..Flags::default()
+        };
+
+        assert_eq!("DOMAIN\\\u{2026}", owner.user_string(&flags));
+        assert_eq!("users", owner.group_string(&flags));
+        assert_eq!("DOMAIN\\first.last", owner.user_string(&Flags::default()));
     }
 }
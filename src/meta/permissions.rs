@@ -1,4 +1,6 @@
 use crate::color::{ColoredString, Colors, Elem};
+use crate::flags::{Flags, PermissionFlag};
+use crate::meta::FileType;
 use ansi_term::ANSIStrings;
 use std::fs::Metadata;
 
@@ -19,15 +21,39 @@ pub struct Permissions {
     pub sticky: bool,
     pub setgid: bool,
     pub setuid: bool,
+
+    pub attributes: Option<Attributes>,
+}
+
+/// The Windows file attributes. On this platform they are more meaningful
+/// than the permissions emulated from the ACL.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Default)]
+pub struct Attributes {
+    pub archive: bool,
+    pub readonly: bool,
+    pub hidden: bool,
+    pub system: bool,
+    pub reparse_point: bool,
 }
 
-impl<'a> From<&'a Metadata> for Permissions {
+impl From<&Metadata> for Permissions {
     #[cfg(unix)]
     fn from(meta: &Metadata) -> Self {
         use std::os::unix::fs::PermissionsExt;
 
-        let bits = meta.permissions().mode();
-        let has_bit = |bit| bits & bit == bit;
+        Self::from_mode(meta.permissions().mode())
+    }
+
+    #[cfg(windows)]
+    fn from(_: &Metadata) -> Self {
+        panic!("Cannot get permissions from metadata on Windows")
+    }
+}
+
+impl Permissions {
+    /// The permissions of the bits of `mode`, like `0o755`.
+    pub fn from_mode(mode: u32) -> Self {
+        let has_bit = |bit| mode & bit == bit;
 
         Self {
             user_read: has_bit(modes::USER_READ),
@@ -45,17 +71,42 @@ impl<'a> From<&'a Metadata> for Permissions {
             sticky: has_bit(modes::STICKY),
             setgid: has_bit(modes::SETGID),
             setuid: has_bit(modes::SETUID),
+
+            attributes: None,
         }
     }
 
-    #[cfg(windows)]
-    fn from(_: &Metadata) -> Self {
-        panic!("Cannot get permissions from metadata on Windows")
+    pub fn render(&self, colors: &Colors, flags: &Flags) -> ColoredString<'_> {
+        match (flags.permission, &self.attributes) {
+            (PermissionFlag::Octal, _) => colors.colorize(self.octal(), &Elem::Octal),
+            (_, Some(attributes)) => attributes.render(colors),
+            (_, None) => self.render_rwx(colors),
+        }
     }
-}
 
-impl Permissions {
-    pub fn render(&self, colors: &Colors) -> ColoredString {
+    /// The width of the rendered permissions.
+    pub fn len(&self, flags: &Flags) -> usize {
+        match (flags.permission, &self.attributes) {
+            (PermissionFlag::Octal, _) => 4,
+            (_, Some(_)) => 5,
+            (_, None) => 9,
+        }
+    }
+
+    /// Format the permissions as four octal digits, like `0644` or `4755`.
+    pub fn octal(&self) -> String {
+        let digit = |a: bool, b: bool, c: bool| (a as u8) << 2 | (b as u8) << 1 | c as u8;
+
+        format!(
+            "{}{}{}{}",
+            digit(self.setuid, self.setgid, self.sticky),
+            digit(self.user_read, self.user_write, self.user_execute),
+            digit(self.group_read, self.group_write, self.group_execute),
+            digit(self.other_read, self.other_write, self.other_execute),
+        )
+    }
+
+    fn render_rwx(&self, colors: &Colors) -> ColoredString<'_> {
         let bit = |bit, chr: &'static str, elem: &Elem| {
             if bit {
                 colors.colorize(String::from(chr), elem)
@@ -77,7 +128,7 @@ impl Permissions {
             // Group permissions
             bit(self.group_read, "r", &Elem::Read),
             bit(self.group_write, "w", &Elem::Write),
-            match (self.group_execute, self.setuid) {
+            match (self.group_execute, self.setgid) {
                 (false, false) => colors.colorize(String::from("-"), &Elem::NoAccess),
                 (true, false) => colors.colorize(String::from("x"), &Elem::Exec),
                 (false, true) => colors.colorize(String::from("S"), &Elem::ExecSticky),
@@ -101,31 +152,230 @@ impl Permissions {
     pub fn is_executable(&self) -> bool {
         self.user_execute || self.group_execute || self.other_execute
     }
+
+    /// The element highlighting the name of the entry, like `ls` does for the
+    /// setuid and setgid files and the sticky or world-writable directories.
+    pub fn highlight(&self, file_type: FileType, capabilities: bool) -> Option<Elem> {
+        match file_type {
+            FileType::File { .. } if self.setuid => Some(Elem::Setuid),
+            FileType::File { .. } if self.setgid => Some(Elem::Setgid),
+            FileType::File { .. } if capabilities => Some(Elem::Capabilities),
+            FileType::Directory { .. } => match (self.sticky, self.other_write) {
+                (true, true) => Some(Elem::StickyOtherWritable),
+                (false, true) => Some(Elem::OtherWritable),
+                (true, false) => Some(Elem::Sticky),
+                (false, false) => None,
+            },
+            _ => None,
+        }
+    }
 }
 
-// More readable aliases for the permission bits exposed by libc.
-#[allow(trivial_numeric_casts)]
-#[cfg(unix)]
-mod modes {
-    use libc;
+impl Attributes {
+    /// Render the attributes like the PowerShell `Mode` column: `arhsl`.
+    pub fn render(&self, colors: &Colors) -> ColoredString<'_> {
+        let bit = |bit, chr: &'static str, elem: &Elem| {
+            if bit {
+                colors.colorize(String::from(chr), elem)
+            } else {
+                colors.colorize(String::from("-"), &Elem::NoAccess)
+            }
+        };
+
+        let strings: &[ColoredString] = &[
+            bit(self.archive, "a", &Elem::Read),
+            bit(self.readonly, "r", &Elem::Write),
+            bit(self.hidden, "h", &Elem::ExecSticky),
+            bit(self.system, "s", &Elem::ExecSticky),
+            bit(self.reparse_point, "l", &Elem::SymLink),
+        ];
+
+        let res = ANSIStrings(strings).to_string();
+        ColoredString::from(res)
+    }
+}
 
+// The permission bits of Unix, also recorded by the archives on the other
+// platforms.
+mod modes {
     pub type Mode = u32;
-    // The `libc::mode_t` type’s actual type varies, but the value returned
-    // from `metadata.permissions().mode()` is always `u32`.
 
-    pub const USER_READ: Mode = libc::S_IRUSR as Mode;
-    pub const USER_WRITE: Mode = libc::S_IWUSR as Mode;
-    pub const USER_EXECUTE: Mode = libc::S_IXUSR as Mode;
+    pub const USER_READ: Mode = 0o400;
+    pub const USER_WRITE: Mode = 0o200;
+    pub const USER_EXECUTE: Mode = 0o100;
+
+    pub const GROUP_READ: Mode = 0o040;
+    pub const GROUP_WRITE: Mode = 0o020;
+    pub const GROUP_EXECUTE: Mode = 0o010;
+
+    pub const OTHER_READ: Mode = 0o004;
+    pub const OTHER_WRITE: Mode = 0o002;
+    pub const OTHER_EXECUTE: Mode = 0o001;
+
+    pub const STICKY: Mode = 0o1000;
+    pub const SETGID: Mode = 0o2000;
+    pub const SETUID: Mode = 0o4000;
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Attributes, Permissions};
+    use crate::color::{Colors, Theme};
+    use crate::flags::Flags;
+    #[cfg(unix)]
+    use crate::flags::PermissionFlag;
+    #[cfg(unix)]
+    use std::fs::{self, File};
+    #[cfg(unix)]
+    use std::os::unix::fs::PermissionsExt;
+    #[cfg(unix)]
+    use tempfile::tempdir;
+
+    #[test]
+    #[cfg(unix)]
+    fn test_octal_permissions() {
+        let tmp_dir = tempdir().expect("failed to create temp dir");
+
+        let file_path = tmp_dir.path().join("file.txt");
+        File::create(&file_path).expect("failed to create file");
+        fs::set_permissions(&file_path, fs::Permissions::from_mode(0o644))
+            .expect("failed to set permissions");
+        let meta = file_path.metadata().expect("failed to get metas");
+
+        let colors = Colors::new(Theme::NoColor);
+        let flags = Flags {
+            permission: PermissionFlag::Octal,
+            ..Flags::default()
+        };
+
+        let permissions = Permissions::from(&meta);
+
+        assert_eq!("0644", permissions.octal());
+        assert_eq!(
+            "0644",
+            permissions.render(&colors, &flags).to_string().as_str()
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_octal_permissions_with_special_bits() {
+        let tmp_dir = tempdir().expect("failed to create temp dir");
+
+        let file_path = tmp_dir.path().join("file.txt");
+        File::create(&file_path).expect("failed to create file");
+        fs::set_permissions(&file_path, fs::Permissions::from_mode(0o4755))
+            .expect("failed to set permissions");
+        let meta = file_path.metadata().expect("failed to get metas");
+
+        assert_eq!("4755", Permissions::from(&meta).octal());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_rwx_permissions() {
+        let tmp_dir = tempdir().expect("failed to create temp dir");
+
+        let file_path = tmp_dir.path().join("file.txt");
+        File::create(&file_path).expect("failed to create file");
+        fs::set_permissions(&file_path, fs::Permissions::from_mode(0o640))
+            .expect("failed to set permissions");
+        let meta = file_path.metadata().expect("failed to get metas");
+
+        let colors = Colors::new(Theme::NoColor);
+        let flags = Flags::default();
+
+        assert_eq!(
+            "rw-r-----",
+            Permissions::from(&meta)
+                .render(&colors, &flags)
+                .to_string()
+                .as_str()
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_rwx_permissions_with_special_bits() {
+        let tmp_dir = tempdir().expect("failed to create temp dir");
+        let file_path = tmp_dir.path().join("file.txt");
+        File::create(&file_path).expect("failed to create file");
+
+        let colors = Colors::new(Theme::NoColor);
+        let flags = Flags::default();
+        let render = |mode| {
+            fs::set_permissions(&file_path, fs::Permissions::from_mode(mode))
+                .expect("failed to set permissions");
+            let meta = file_path.metadata().expect("failed to get metas");
+            Permissions::from(&meta).render(&colors, &flags).to_string()
+        };
 
-    pub const GROUP_READ: Mode = libc::S_IRGRP as Mode;
-    pub const GROUP_WRITE: Mode = libc::S_IWGRP as Mode;
-    pub const GROUP_EXECUTE: Mode = libc::S_IXGRP as Mode;
+        assert_eq!("rwsr-xr-x", render(0o4755));
+        assert_eq!("rwSr--r--", render(0o4644));
+        assert_eq!("rwxr-sr-x", render(0o2755));
+        assert_eq!("rw-r-Sr--", render(0o2644));
+        assert_eq!("rwxrwxrwt", render(0o1777));
+        assert_eq!("rwxr-xr-T", render(0o1754));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_highlight() {
+        use crate::color::Elem;
+        use crate::meta::FileType;
 
-    pub const OTHER_READ: Mode = libc::S_IROTH as Mode;
-    pub const OTHER_WRITE: Mode = libc::S_IWOTH as Mode;
-    pub const OTHER_EXECUTE: Mode = libc::S_IXOTH as Mode;
+        let tmp_dir = tempdir().expect("failed to create temp dir");
+        let highlight = |mode, file_type| {
+            fs::set_permissions(tmp_dir.path(), fs::Permissions::from_mode(mode))
+                .expect("failed to set permissions");
+            let meta = tmp_dir.path().metadata().expect("failed to get metas");
+            Permissions::from(&meta).highlight(file_type, false)
+        };
+        let file = FileType::File {
+            uid: false,
+            exec: true,
+        };
+        let dir = FileType::Directory { uid: false };
 
-    pub const STICKY: Mode = libc::S_ISVTX as Mode;
-    pub const SETGID: Mode = libc::S_ISGID as Mode;
-    pub const SETUID: Mode = libc::S_ISUID as Mode;
+        assert_eq!(Some(Elem::Setuid), highlight(0o4755, file));
+        assert_eq!(Some(Elem::Setgid), highlight(0o2755, file));
+        assert_eq!(None, highlight(0o755, file));
+        assert_eq!(Some(Elem::StickyOtherWritable), highlight(0o1777, dir));
+        assert_eq!(Some(Elem::OtherWritable), highlight(0o777, dir));
+        assert_eq!(Some(Elem::Sticky), highlight(0o1755, dir));
+        assert_eq!(None, highlight(0o755, dir));
+    }
+
+    #[test]
+    fn test_windows_attributes_replace_rwx() {
+        let permissions = Permissions {
+            user_read: true,
+            user_write: true,
+            user_execute: false,
+            group_read: true,
+            group_write: false,
+            group_execute: false,
+            other_read: true,
+            other_write: false,
+            other_execute: false,
+            sticky: false,
+            setgid: false,
+            setuid: false,
+            attributes: Some(Attributes {
+                archive: true,
+                readonly: true,
+                hidden: false,
+                system: false,
+                reparse_point: true,
+            }),
+        };
+
+        let colors = Colors::new(Theme::NoColor);
+        let flags = Flags::default();
+
+        assert_eq!(
+            "ar--l",
+            permissions.render(&colors, &flags).to_string().as_str()
+        );
+    }
 }
@@ -0,0 +1,106 @@
+use crate::color::{Colors, Elem};
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::str;
+
+// Only the beginning of the files is read, however big they are.
+const PREVIEW_BYTES: u64 = 4096;
+
+/// The first lines of a text file, displayed under its entry.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Preview {
+    lines: Vec<String>,
+}
+
+impl Preview {
+    /// The first `max_lines` lines of the file at `path`. There is no preview
+    /// for the empty or binary files, nor for the ones which cannot be read.
+    pub fn for_file(path: &Path, max_lines: usize) -> Option<Self> {
+        let mut bytes = Vec::new();
+        File::open(path)
+            .ok()?
+            .take(PREVIEW_BYTES)
+            .read_to_end(&mut bytes)
+            .ok()?;
+
+        Self::from_bytes(&bytes, max_lines)
+    }
+
+    fn from_bytes(bytes: &[u8], max_lines: usize) -> Option<Self> {
+        // Like `grep` and `diff`, a NUL byte is taken as the sign of a binary.
+        if bytes.contains(&0) {
+            return None;
+        }
+
+        let text = match str::from_utf8(bytes) {
+            Ok(text) => text,
+            // The read may have stopped in the middle of a character.
+            Err(err) if err.error_len().is_none() => {
+                str::from_utf8(&bytes[..err.valid_up_to()]).unwrap_or_default()
+            }
+            Err(_) => return None,
+        };
+
+        let lines: Vec<String> = text
+            .lines()
+            .take(max_lines)
+            .map(|line| {
+                // The control characters would mess with the terminal.
+                line.chars()
+                    .map(|c| if c == '\t' { ' ' } else { c })
+                    .filter(|c| !c.is_control())
+                    .collect()
+            })
+            .collect();
+
+        if lines.is_empty() {
+            None
+        } else {
+            Some(Self { lines })
+        }
+    }
+
+    /// Render one indented line per line of the preview, after `prefix`.
+    pub fn render(&self, colors: &Colors, prefix: &str) -> String {
+        let mut output = String::new();
+
+        for line in &self.lines {
+            output += prefix;
+            output += "    ";
+            output += &colors.colorize(line.clone(), &Elem::Preview).to_string();
+            output.push('\n');
+        }
+
+        output
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Preview;
+    use crate::color::{Colors, Theme};
+
+    #[test]
+    fn test_preview_first_lines() {
+        let preview = Preview::from_bytes(b"one\n\ttwo\nthree\n", 2).unwrap();
+        let colors = Colors::new(Theme::NoColor);
+
+        assert_eq!("|    one\n|     two\n", preview.render(&colors, "|"));
+    }
+
+    #[test]
+    fn test_no_preview_for_binary_or_empty_files() {
+        assert_eq!(None, Preview::from_bytes(b"\x7fELF\x00\x01", 3));
+        assert_eq!(None, Preview::from_bytes(b"\xff\xfe text", 3));
+        assert_eq!(None, Preview::from_bytes(b"", 3));
+    }
+
+    #[test]
+    fn test_preview_cut_in_a_character() {
+        let preview = Preview::from_bytes("caf\u{e9}".as_bytes()[..4].as_ref(), 1).unwrap();
+        let colors = Colors::new(Theme::NoColor);
+
+        assert_eq!("    caf\n", preview.render(&colors, ""));
+    }
+}
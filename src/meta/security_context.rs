@@ -0,0 +1,111 @@
+use crate::color::{ColoredString, Colors, Elem};
+use std::path::Path;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(windows, allow(dead_code))]
+pub enum SecurityContext {
+    SELinux(String),
+    Smack(String),
+    None,
+}
+
+impl From<&Path> for SecurityContext {
+    #[cfg(unix)]
+    fn from(path: &Path) -> Self {
+        use crate::meta::xattr;
+        use std::ffi::OsStr;
+
+        let read = |name: &str| {
+            xattr::get(path, OsStr::new(name))
+                .ok()
+                .map(|value| context_from_bytes(&value))
+                .filter(|context| !context.is_empty())
+        };
+
+        if let Some(context) = read("security.selinux") {
+            SecurityContext::SELinux(context)
+        } else if let Some(context) = read("security.SMACK64") {
+            SecurityContext::Smack(context)
+        } else {
+            SecurityContext::None
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn from(_: &Path) -> Self {
+        SecurityContext::None
+    }
+}
+
+// The kernel stores the contexts as NUL-terminated strings.
+#[cfg(unix)]
+fn context_from_bytes(value: &[u8]) -> String {
+    let value = match value.iter().position(|byte| *byte == 0) {
+        Some(end) => &value[..end],
+        None => value,
+    };
+
+    String::from_utf8_lossy(value).to_string()
+}
+
+impl SecurityContext {
+    pub fn context_string(&self) -> String {
+        match self {
+            SecurityContext::SELinux(context) | SecurityContext::Smack(context) => context.clone(),
+            // Same placeholder as `ls -Z`
+            SecurityContext::None => String::from("?"),
+        }
+    }
+
+    pub fn render(&self, colors: &Colors, context_alignment: usize) -> ColoredString<'_> {
+        let mut res = self.context_string();
+
+        for _ in res.len()..context_alignment {
+            res.push(' ');
+        }
+
+        colors.colorize(res, &Elem::Context)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::SecurityContext;
+    use crate::color::{Colors, Theme};
+
+    #[test]
+    #[cfg(unix)]
+    fn test_context_from_nul_terminated_bytes() {
+        use super::context_from_bytes;
+
+        assert_eq!(
+            "unconfined_u:object_r:user_home_t:s0",
+            context_from_bytes(b"unconfined_u:object_r:user_home_t:s0\0")
+        );
+        assert_eq!("_", context_from_bytes(b"_"));
+    }
+
+    #[test]
+    fn test_render_missing_context() {
+        let colors = Colors::new(Theme::NoColor);
+
+        assert_eq!(
+            "?    ",
+            SecurityContext::None
+                .render(&colors, 5)
+                .to_string()
+                .as_str()
+        );
+    }
+
+    #[test]
+    fn test_render_selinux_context() {
+        let colors = Colors::new(Theme::NoColor);
+        let context = SecurityContext::SELinux(String::from("system_u:object_r:etc_t:s0"));
+
+        assert_eq!(
+            "system_u:object_r:etc_t:s0",
+            context.render(&colors, 10).to_string().as_str()
+        );
+    }
+}
@@ -1,5 +1,5 @@
 use crate::color::{ColoredString, Colors, Elem};
-use crate::flags::{Flags, SizeFlag};
+use crate::flags::{Flags, SizeFlag, SizeUnitFlag};
 use std::fs::Metadata;
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -15,26 +15,72 @@ pub enum Unit {
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Size {
     bytes: u64,
+    // The disk usage, in 512-byte blocks.
+    blocks: u64,
 }
 
-impl<'a> From<&'a Metadata> for Size {
+impl From<&Metadata> for Size {
+    #[cfg(unix)]
     fn from(meta: &Metadata) -> Self {
-        let len = meta.len();
-        Self { bytes: len }
+        use std::os::unix::fs::MetadataExt;
+
+        Self {
+            bytes: meta.len(),
+            blocks: meta.blocks(),
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn from(meta: &Metadata) -> Self {
+        Self::new(meta.len())
     }
 }
 
 impl Size {
     pub fn new(bytes: u64) -> Self {
-        Self { bytes: bytes }
+        Self {
+            bytes,
+            blocks: bytes.div_ceil(512),
+        }
+    }
+
+    /// Parse a human readable size, like `42`, `4K`, `1.5MB` or `2g`.
+    pub fn parse(input: &str) -> Option<Self> {
+        let input = input.to_uppercase();
+        let input = input.trim_end_matches('B');
+        let (value, multiplier) = match input.chars().last() {
+            Some('K') => (&input[..input.len() - 1], 1024u64),
+            Some('M') => (&input[..input.len() - 1], 1024 * 1024),
+            Some('G') => (&input[..input.len() - 1], 1024 * 1024 * 1024),
+            Some('T') => (&input[..input.len() - 1], 1024 * 1024 * 1024 * 1024),
+            _ => (input, 1),
+        };
+
+        let value: f64 = value.parse().ok()?;
+        if value < 0.0 || !value.is_finite() {
+            return None;
+        }
+
+        Some(Self::new((value * multiplier as f64) as u64))
     }
 
     pub fn get_bytes(&self) -> u64 {
         self.bytes
     }
 
-    pub fn get_unit(&self, flags: &Flags) -> Unit
-    {
+    pub fn get_blocks(&self) -> u64 {
+        self.blocks
+    }
+
+    pub fn get_unit(&self, flags: &Flags) -> Unit {
+        match flags.size_unit {
+            Some(SizeUnitFlag::Byte) => return Unit::Byte,
+            Some(SizeUnitFlag::Kilo) => return Unit::Kilo,
+            Some(SizeUnitFlag::Mega) => return Unit::Mega,
+            Some(SizeUnitFlag::Giga) => return Unit::Giga,
+            None => (),
+        }
+
         if self.bytes < 1024 || flags.size == SizeFlag::Bytes {
             Unit::Byte
         } else if self.bytes < 1024 * 1024 {
@@ -48,57 +94,88 @@ impl Size {
         }
     }
 
+    /// The widths of the integer part of the value, of its fraction and of
+    /// the unit, the sub-columns the sizes are aligned in.
+    pub fn lengths(&self, flags: &Flags) -> (usize, usize, usize) {
+        let unit = self.get_unit(flags);
+        let value = self.render_value(&unit);
+        let integer = value.find('.').unwrap_or(value.len());
+
+        (
+            integer,
+            value.len() - integer,
+            Size::render_unit(&unit, flags).len(),
+        )
+    }
+
+    /// Render the size with the integer parts right-justified, the fractions
+    /// after them and the units in a sub-column of their own, like `  4.0 KB`
+    /// above `123   B`.
     pub fn render(
         &self,
         colors: &Colors,
-        value_alignment: usize,
-        unit_alignment: usize,
+        alignment: (usize, usize, usize),
         flags: &Flags,
-    ) -> ColoredString {
-        let mut content = String::with_capacity(value_alignment + unit_alignment + 1);
+    ) -> ColoredString<'_> {
+        let (integer_alignment, fraction_alignment, unit_alignment) = alignment;
+        let (integer, fraction, _) = self.lengths(flags);
+        let mut content =
+            String::with_capacity(integer_alignment + fraction_alignment + unit_alignment + 1);
 
         let unit = self.get_unit(flags);
+        let unit_str = Size::render_unit(&unit, flags);
 
-        let value_str = self.render_value(&unit);
-        let unit_str = Size::render_unit(&unit, &flags);
-
-        for _ in 0..(value_alignment - value_str.len()) {
+        for _ in integer..integer_alignment {
             content.push(' ');
         }
-
         content += &self.render_value(&unit);
+        for _ in fraction..fraction_alignment {
+            content.push(' ');
+        }
+
         if flags.size == SizeFlag::Default {
             content.push(' ');
         }
-        content += &Size::render_unit(&unit, &flags);
+        content += &unit_str;
 
-        for _ in 0..(unit_alignment - unit_str.len()) {
+        for _ in unit_str.len()..unit_alignment {
             content.push(' ');
         }
 
         self.paint(&unit, colors, content)
     }
 
-    fn paint(&self, unit: &Unit, colors: &Colors, content: String) -> ColoredString {
+    fn paint(&self, unit: &Unit, colors: &Colors, content: String) -> ColoredString<'_> {
         if unit == &Unit::None {
             colors.colorize(content, &Elem::NonFile)
-        } else if unit == &Unit::Byte || unit == &Unit::Kilo {
-            colors.colorize(content, &Elem::FileSmall)
-        } else if unit == &Unit::Mega {
-            colors.colorize(content, &Elem::FileMedium)
         } else {
-            colors.colorize(content, &Elem::FileLarge)
+            colors.colorize(content, &colors.size_elem(self.bytes))
         }
     }
 
+    /// Render the share of the size in the `parent` one, like ` 42%`.
+    pub fn render_share(&self, colors: &Colors, parent: u64) -> ColoredString<'static> {
+        let content = if parent == 0 {
+            String::from("   -")
+        } else {
+            let share = (self.bytes as f64 * 100.0 / parent as f64).round();
+            format!("{:>3}%", share)
+        };
+
+        colors.colorize(content, &colors.size_elem(self.bytes))
+    }
+
+    /// The value in the `unit`, with a single decimal above the bytes so that
+    /// the fractions line up.
     pub fn render_value(&self, unit: &Unit) -> String {
+        let bytes = self.bytes as f64;
         match unit {
             Unit::None => "".to_string(),
             Unit::Byte => self.bytes.to_string(),
-            Unit::Kilo => ((( self.bytes as f64 ) / 1024.0 * 10.0).round() / 10.0).to_string(),
-            Unit::Mega => ((( self.bytes as f64 ) / (1024.0 * 1024.0) * 10.0).round() / 10.0).to_string(),
-            Unit::Giga => ((( self.bytes as f64 ) / (1024.0 * 1024.0 * 1024.0) * 10.0).round() / 10.0).to_string(),
-            Unit::Tera => ((( self.bytes as f64 ) / (1024.0 * 1024.0 * 1024.0 * 1024.0) * 10.0).round() / 10.0).to_string(),
+            Unit::Kilo => format!("{:.1}", bytes / 1024.0),
+            Unit::Mega => format!("{:.1}", bytes / (1024.0 * 1024.0)),
+            Unit::Giga => format!("{:.1}", bytes / (1024.0 * 1024.0 * 1024.0)),
+            Unit::Tera => format!("{:.1}", bytes / (1024.0 * 1024.0 * 1024.0 * 1024.0)),
         }
     }
 
@@ -120,7 +197,7 @@ impl Size {
                 Unit::Giga => String::from("G"),
                 Unit::Tera => String::from("T"),
             },
-            SizeFlag::Bytes => String::from("")
+            SizeFlag::Bytes => String::from(""),
         }
     }
 }
@@ -128,7 +205,8 @@ impl Size {
 #[cfg(test)]
 mod test {
     use super::Size;
-    use crate::flags::{Flags, SizeFlag};
+    use crate::color::{Colors, Theme};
+    use crate::flags::{Flags, SizeFlag, SizeUnitFlag};
 
     #[test]
     fn render_byte() {
@@ -151,7 +229,7 @@ mod test {
         let mut flags = Flags::default();
         let unit = size.get_unit(&flags);
 
-        assert_eq!(size.render_value(&unit).as_str(), "42");
+        assert_eq!(size.render_value(&unit).as_str(), "42.0");
         assert_eq!(Size::render_unit(&unit, &flags).as_str(), "KB");
         flags.size = SizeFlag::Short;
         assert_eq!(Size::render_unit(&unit, &flags).as_str(), "K");
@@ -163,7 +241,7 @@ mod test {
         let mut flags = Flags::default();
         let unit = size.get_unit(&flags);
 
-        assert_eq!(size.render_value(&unit).as_str(), "42");
+        assert_eq!(size.render_value(&unit).as_str(), "42.0");
         assert_eq!(Size::render_unit(&unit, &flags).as_str(), "MB");
         flags.size = SizeFlag::Short;
         assert_eq!(Size::render_unit(&unit, &flags).as_str(), "M");
@@ -175,19 +253,28 @@ mod test {
         let mut flags = Flags::default();
         let unit = size.get_unit(&flags);
 
-        assert_eq!(size.render_value(&unit).as_str(), "42");
+        assert_eq!(size.render_value(&unit).as_str(), "42.0");
         assert_eq!(Size::render_unit(&unit, &flags).as_str(), "GB");
         flags.size = SizeFlag::Short;
         assert_eq!(Size::render_unit(&unit, &flags).as_str(), "G");
     }
 
+    #[test]
+    fn render_share() {
+        let colors = Colors::new(Theme::NoColor);
+
+        assert_eq!(" 33%", Size::new(1).render_share(&colors, 3).to_string());
+        assert_eq!("100%", Size::new(3).render_share(&colors, 3).to_string());
+        assert_eq!("   -", Size::new(0).render_share(&colors, 0).to_string());
+    }
+
     #[test]
     fn render_terabyte() {
         let size = Size::new(42 * 1024 * 1024 * 1024 * 1024); // 42 terabytes
         let mut flags = Flags::default();
         let unit = size.get_unit(&flags);
 
-        assert_eq!(size.render_value(&unit).as_str(), "42");
+        assert_eq!(size.render_value(&unit).as_str(), "42.0");
         assert_eq!(Size::render_unit(&unit, &flags).as_str(), "TB");
         flags.size = SizeFlag::Short;
         assert_eq!(Size::render_unit(&unit, &flags).as_str(), "T");
@@ -209,7 +296,75 @@ mod test {
         let flags = Flags::default();
         let unit = size.get_unit(&flags);
 
-        assert_eq!(size.render_value(&unit).as_str(), "42");
+        assert_eq!(size.render_value(&unit).as_str(), "42.0");
         assert_eq!(Size::render_unit(&unit, &flags).as_str(), "KB");
     }
+
+    #[test]
+    fn render_in_the_size_unit() {
+        let size = Size::new(512 * 1024);
+        let flags = Flags {
+            size_unit: Some(SizeUnitFlag::Mega),
+            ..Flags::default()
+        };
+        let unit = size.get_unit(&flags);
+
+        assert_eq!(size.render_value(&unit).as_str(), "0.5");
+        assert_eq!(Size::render_unit(&unit, &flags).as_str(), "MB");
+
+        let flags = Flags {
+            size_unit: Some(SizeUnitFlag::Byte),
+            ..Flags::default()
+        };
+        let unit = size.get_unit(&flags);
+        assert_eq!(size.render_value(&unit).as_str(), "524288");
+    }
+
+    #[test]
+    fn render_aligned_in_sub_columns() {
+        let colors = Colors::new(Theme::NoColor);
+        let flags = Flags::default();
+        let kilo = Size::new(4 * 1024);
+        let byte = Size::new(123);
+        let alignment = (3, 2, 2);
+
+        assert_eq!((1, 2, 2), kilo.lengths(&flags));
+        assert_eq!((3, 0, 1), byte.lengths(&flags));
+        assert_eq!(
+            "  4.0 KB",
+            kilo.render(&colors, alignment, &flags).to_string()
+        );
+        assert_eq!(
+            "123   B ",
+            byte.render(&colors, alignment, &flags).to_string()
+        );
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(Some(Size::new(42)), Size::parse("42"));
+        assert_eq!(Some(Size::new(4096)), Size::parse("4K"));
+        assert_eq!(Some(Size::new(1536 * 1024)), Size::parse("1.5mb"));
+        assert_eq!(None, Size::parse("M"));
+        assert_eq!(None, Size::parse("12X"));
+    }
+
+    #[test]
+    fn test_size_color_follows_the_thresholds() {
+        use crate::color::{Colors, Elem, SizeThresholds, Theme};
+        use crate::config_file::ThemeConfig;
+
+        let config = ThemeConfig {
+            size_thresholds: SizeThresholds {
+                medium: 100,
+                large: 1000,
+            },
+            ..ThemeConfig::default()
+        };
+        let colors = Colors::new(Theme::NoLscolors).with_config(&config);
+
+        assert_eq!(Elem::FileSmall, colors.size_elem(99));
+        assert_eq!(Elem::FileMedium, colors.size_elem(100));
+        assert_eq!(Elem::FileLarge, colors.size_elem(4096));
+    }
 }
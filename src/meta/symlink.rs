@@ -1,73 +1,381 @@
 use crate::color::{ColoredString, Colors, Elem};
+use crate::flags::{Flags, SymlinkTargetFlag};
 use ansi_term::{ANSIString, ANSIStrings};
-use std::fs::read_link;
-use std::path::Path;
+use std::env;
+use std::fs::{self, read_link};
+use std::iter;
+use std::path::{Component, Path, PathBuf};
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct SymLink {
-    target: Option<String>,
+    target: Option<PathBuf>,
+    // The target resolved against the directory of the link.
+    resolved: Option<PathBuf>,
     valid: bool,
+    // Whether the target is a directory, to color it as such.
+    is_dir: bool,
+    // The links the target leads through, with `--follow-chain`.
+    chain: Vec<SymLink>,
+    // Whether the chain goes on beyond the targets followed.
+    truncated: bool,
+    // Whether the chain loops back to one of its links.
+    looping: bool,
 }
 
 impl<'a> From<&'a Path> for SymLink {
     fn from(path: &'a Path) -> Self {
         if let Ok(target) = read_link(path) {
-            if target.is_absolute() || path.parent() == None {
-                return Self {
-                    valid: target.exists(),
-                    target: Some(
-                        target
-                            .to_str()
-                            .expect("failed to convert symlink to str")
-                            .to_string(),
-                    ),
-                };
-            }
+            // Unlike the path itself, `is_dir` follows the link.
+            let is_dir = path.is_dir();
+
+            #[cfg(windows)]
+            let target = strip_namespace_prefix(&target);
+
+            let resolved = match path.parent() {
+                Some(parent) if !target.is_absolute() => parent.join(&target),
+                _ => target.clone(),
+            };
 
             return Self {
-                target: Some(
-                    target
-                        .to_str()
-                        .expect("failed to convert symlink to str")
-                        .to_string(),
-                ),
-                valid: path.parent().unwrap().join(target).exists(),
+                valid: resolved.exists(),
+                target: Some(target),
+                resolved: Some(resolved),
+                is_dir,
+                ..Self::default()
             };
         }
 
-        Self {
-            target: None,
-            valid: false,
+        Self::default()
+    }
+}
+
+/// Remove the NT namespace prefix (`\??\`) or the verbatim prefix (`\\?\`)
+/// from a link target. Junctions store their target this way, but it is
+/// neither what the user created nor something the other tools accept.
+#[cfg(windows)]
+fn strip_namespace_prefix(target: &Path) -> PathBuf {
+    let target_string = target.to_string_lossy();
+
+    for (prefix, replacement) in &[
+        (r"\??\UNC\", r"\\"),
+        (r"\\?\UNC\", r"\\"),
+        (r"\??\", ""),
+        (r"\\?\", ""),
+    ] {
+        if let Some(rest) = target_string.strip_prefix(prefix) {
+            return PathBuf::from(format!("{}{}", replacement, rest));
         }
     }
+
+    target.to_path_buf()
 }
 
 impl SymLink {
-    pub fn symlink_string(&self) -> Option<String> {
-        if let Some(ref target) = self.target {
-            Some(target.to_string())
-        } else {
-            None
+    /// The target as read from the link, for the machine readable outputs.
+    pub fn target(&self) -> Option<&Path> {
+        self.target.as_ref().map(AsRef::as_ref)
+    }
+
+    /// The link at `path` followed through the links it points to, until a
+    /// target is not a link or is one of the links gone through, with
+    /// `max_depth` targets at most.
+    pub fn with_chain(path: &Path, max_depth: usize) -> Self {
+        let mut res = Self::from(path);
+        let mut seen = vec![location(path)];
+
+        let mut next = res.resolved.clone();
+        while let Some(link) = next {
+            if !is_symlink(&link) {
+                break;
+            }
+            // The looping chain ends with the link it loops back to.
+            let location = location(&link);
+            if seen.contains(&location) {
+                res.looping = true;
+                break;
+            }
+            if res.chain.len() + 1 >= max_depth {
+                res.truncated = true;
+                break;
+            }
+            seen.push(location);
+
+            let hop = Self::from(link.as_path());
+            next = hop.resolved.clone();
+            res.chain.push(hop);
         }
+
+        res
     }
 
-    pub fn render(&self, colors: &Colors) -> ColoredString {
-        if let Some(target_string) = self.symlink_string() {
-            let elem = if self.valid {
-                &Elem::SymLink
-            } else {
-                &Elem::BrokenSymLink
-            };
+    /// The target as shown by `--symlink-target`, followed by the targets of
+    /// the chain joined by the arrows.
+    pub fn symlink_string(&self, flags: &Flags) -> Option<String> {
+        let mut res = self.target_string(flags)?;
+        for link in &self.chain {
+            if let Some(target) = link.target_string(flags) {
+                res.push_str(&format!(" {} {}", flags.symbols.symlink_arrow, target));
+            }
+        }
+        if self.truncated {
+            res.push_str(&format!(
+                " {} {}",
+                flags.symbols.symlink_arrow, flags.symbols.ellipsis
+            ));
+        }
+        if self.looping {
+            res.push_str(&format!(" {}", flags.symbols.symlink_loop));
+        }
+
+        Some(res)
+    }
+
+    /// The target as stored in the link, as an absolute path, or relative to
+    /// the current directory.
+    fn target_string(&self, flags: &Flags) -> Option<String> {
+        let target = match (&self.target, &self.resolved, flags.symlink_target) {
+            (Some(target), _, SymlinkTargetFlag::Stored) => target.clone(),
+            (_, Some(resolved), SymlinkTargetFlag::Absolute) => absolute(resolved),
+            (_, Some(resolved), SymlinkTargetFlag::Relative) => match env::current_dir() {
+                Ok(cwd) => relative_to(&absolute(resolved), &absolute(&cwd)),
+                Err(_) => absolute(resolved),
+            },
+            _ => return None,
+        };
 
-            let strings: &[ColoredString] = &[
-                ColoredString::from(" \u{21d2} "), // ⇒
-                colors.colorize(target_string, elem),
-            ];
+        Some(target.to_string_lossy().to_string())
+    }
+
+    pub fn render(&self, colors: &Colors, flags: &Flags) -> ColoredString<'_> {
+        if self.target.is_none() {
+            return ANSIString::from("");
+        }
+
+        let arrow = ColoredString::from(format!(" {} ", flags.symbols.symlink_arrow));
+        let last = self.chain.len();
+        let mut strings: Vec<ColoredString> = Vec::new();
+        for (idx, link) in iter::once(self).chain(&self.chain).enumerate() {
+            if let Some(target_string) = link.target_string(flags) {
+                // Only the last target may not be a link.
+                let elem = if !link.valid {
+                    &Elem::BrokenSymLink
+                } else if link.is_dir && idx == last {
+                    &Elem::Dir { uid: false }
+                } else {
+                    &Elem::SymLink
+                };
 
-            let res = ANSIStrings(strings).to_string();
-            ColoredString::from(res)
-        } else {
-            ANSIString::from("")
+                strings.push(arrow.clone());
+                strings.push(colors.colorize(target_string, elem));
+            }
+        }
+        if self.truncated {
+            strings.push(arrow);
+            strings.push(ColoredString::from(flags.symbols.ellipsis.clone()));
+        }
+        if self.looping {
+            strings.push(ColoredString::from(format!(
+                " {}",
+                flags.symbols.symlink_loop
+            )));
         }
+
+        let res = ANSIStrings(&strings).to_string();
+        ColoredString::from(res)
+    }
+}
+
+fn is_symlink(path: &Path) -> bool {
+    fs::symlink_metadata(path)
+        .map(|meta| meta.file_type().is_symlink())
+        .unwrap_or(false)
+}
+
+/// The absolute path of the link at `path`, its directory resolved but not
+/// the link itself, which `canonicalize` would follow.
+fn location(path: &Path) -> PathBuf {
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) if parent != Path::new("") => absolute(parent).join(name),
+        (_, Some(name)) => absolute(Path::new(".")).join(name),
+        _ => absolute(path),
+    }
+}
+
+/// The canonical path of `path`, or for the broken links the absolute one
+/// with the `.` and `..` components resolved as written.
+fn absolute(path: &Path) -> PathBuf {
+    if let Ok(path) = fs::canonicalize(path) {
+        return path;
+    }
+
+    let path = match env::current_dir() {
+        Ok(cwd) => cwd.join(path),
+        Err(_) => path.to_path_buf(),
+    };
+
+    let mut res = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                res.pop();
+            }
+            component => res.push(component.as_os_str()),
+        }
+    }
+
+    res
+}
+
+/// The absolute `path` relative to the absolute `base`, going up with `..`
+/// from `base` to the part they share.
+fn relative_to(path: &Path, base: &Path) -> PathBuf {
+    let mut path_components = path.components().peekable();
+    let mut base_components = base.components().peekable();
+    while let (Some(a), Some(b)) = (path_components.peek(), base_components.peek()) {
+        if a != b {
+            break;
+        }
+        path_components.next();
+        base_components.next();
+    }
+
+    let mut res: PathBuf = base_components.map(|_| Component::ParentDir).collect();
+    res.extend(path_components);
+    if res.as_os_str().is_empty() {
+        res.push(".");
+    }
+
+    res
+}
+
+#[cfg(test)]
+mod test {
+    use super::relative_to;
+    use std::path::Path;
+
+    #[test]
+    fn test_relative_to() {
+        let relative = |path, base| relative_to(Path::new(path), Path::new(base));
+
+        assert_eq!(Path::new("lib/a.so"), relative("/usr/lib/a.so", "/usr"));
+        assert_eq!(
+            Path::new("../lib/a.so"),
+            relative("/usr/lib/a.so", "/usr/bin")
+        );
+        assert_eq!(Path::new("../../etc"), relative("/etc", "/usr/bin"));
+        assert_eq!(Path::new("."), relative("/usr", "/usr"));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_symlink_target_modes() {
+        use super::SymLink;
+        use crate::flags::{Flags, SymlinkTargetFlag};
+        use std::fs;
+        use std::os::unix::fs::symlink;
+        use tempfile::tempdir;
+
+        let tmp_dir = tempdir().expect("failed to create temp dir");
+        let dir = fs::canonicalize(tmp_dir.path()).expect("failed to canonicalize");
+        fs::create_dir(dir.join("sub")).expect("failed to create dir");
+        fs::write(dir.join("target"), "").expect("failed to create file");
+        let link_path = dir.join("sub/link");
+        symlink("../target", &link_path).expect("failed to create symlink");
+
+        let link = SymLink::from(link_path.as_path());
+        let string = |symlink_target| {
+            let flags = Flags {
+                symlink_target,
+                ..Flags::default()
+            };
+            link.symlink_string(&flags).unwrap()
+        };
+
+        assert_eq!("../target", string(SymlinkTargetFlag::Stored));
+        assert_eq!(
+            dir.join("target").to_string_lossy(),
+            string(SymlinkTargetFlag::Absolute)
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_follow_chain() {
+        use super::SymLink;
+        use crate::color::{Colors, Theme};
+        use crate::flags::Flags;
+        use std::fs;
+        use std::os::unix::fs::symlink;
+        use tempfile::tempdir;
+
+        let tmp_dir = tempdir().expect("failed to create temp dir");
+        let dir = tmp_dir.path();
+        fs::write(dir.join("c"), "").expect("failed to create file");
+        symlink("c", dir.join("b")).expect("failed to create symlink");
+        symlink("b", dir.join("a")).expect("failed to create symlink");
+        symlink("y", dir.join("x")).expect("failed to create symlink");
+        symlink("x", dir.join("y")).expect("failed to create symlink");
+
+        let flags = Flags::default();
+        let chain = |name, max_depth| {
+            SymLink::with_chain(&dir.join(name), max_depth)
+                .symlink_string(&flags)
+                .unwrap()
+        };
+
+        assert_eq!(
+            "b",
+            SymLink::from(dir.join("a").as_path())
+                .symlink_string(&flags)
+                .unwrap()
+        );
+        assert_eq!("b \u{21d2} c", chain("a", 8));
+        assert_eq!("c", chain("b", 8));
+        assert_eq!("b \u{21d2} \u{2026}", chain("a", 1));
+        assert_eq!("y \u{21d2} x \u{27f2}", chain("x", 8));
+
+        let colors = Colors::new(Theme::NoColor);
+        assert_eq!(
+            " \u{21d2} y \u{21d2} x \u{27f2}",
+            SymLink::with_chain(&dir.join("x"), 8)
+                .render(&colors, &flags)
+                .to_string()
+        );
+    }
+}
+
+#[cfg(test)]
+#[cfg(windows)]
+mod windows_test {
+    use super::strip_namespace_prefix;
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn test_strip_junction_namespace_prefix() {
+        assert_eq!(
+            PathBuf::from(r"C:\Users\lsd\target"),
+            strip_namespace_prefix(Path::new(r"\??\C:\Users\lsd\target"))
+        );
+        assert_eq!(
+            PathBuf::from(r"C:\Users\lsd\target"),
+            strip_namespace_prefix(Path::new(r"\\?\C:\Users\lsd\target"))
+        );
+    }
+
+    #[test]
+    fn test_strip_unc_namespace_prefix() {
+        assert_eq!(
+            PathBuf::from(r"\\server\share\dir"),
+            strip_namespace_prefix(Path::new(r"\??\UNC\server\share\dir"))
+        );
+    }
+
+    #[test]
+    fn test_keep_relative_target() {
+        assert_eq!(
+            PathBuf::from(r"..\target"),
+            strip_namespace_prefix(Path::new(r"..\target"))
+        );
     }
 }
@@ -1,19 +1,24 @@
 use std::ffi::{OsStr, OsString};
+use std::fs::{Metadata, OpenOptions};
 use std::io;
 use std::os::windows::ffi::{OsStrExt, OsStringExt};
-use std::path::PathBuf;
-use std::ptr::null_mut;
+use std::os::windows::fs::{MetadataExt, OpenOptionsExt};
+use std::os::windows::io::AsRawHandle;
+use std::path::{Component, Path, PathBuf, Prefix};
+use std::ptr::{null, null_mut};
 
 use winapi::ctypes::c_void;
+use winapi::shared::minwindef::MAX_PATH;
 use winapi::shared::winerror;
 use winapi::um::accctrl::TRUSTEE_W;
-use winapi::um::winnt;
+use winapi::um::{winbase, winnt};
 
+use super::permissions::Attributes;
 use super::{Owner, Permissions};
 
 const BUF_SIZE: u32 = 256;
 
-pub fn get_file_data(path: &PathBuf) -> Result<(Owner, Permissions), io::Error> {
+pub fn get_file_data(path: &Path, metadata: &Metadata) -> Result<(Owner, Permissions), io::Error> {
     // Overall design:
     // This function allocates some data with GetNamedSecurityInfoW,
     // manipulates it only through WinAPI calls (treating the pointers as
@@ -25,9 +30,13 @@ pub fn get_file_data(path: &PathBuf) -> Result<(Owner, Permissions), io::Error>
     // - LocalFree must be called before returning
     // - No pointer is valid after the call to LocalFree
 
+    let path = security_path(path);
     let windows_path = buf_from_os(path.as_os_str());
+    // The accounts local to the server of a share are only known to it.
+    let server = server_name(&path).map(buf_from_os);
 
-    // These pointers will be populated by GetNamedSecurityInfoW
+    // These pointers will be populated by GetNamedSecurityInfoW (or
+    // GetSecurityInfo for the links)
     // sd_ptr points at a new buffer that must be freed
     // The others point at (opaque) things inside that buffer
     let mut owner_sid_ptr = null_mut();
@@ -35,26 +44,61 @@ pub fn get_file_data(path: &PathBuf) -> Result<(Owner, Permissions), io::Error>
     let mut dacl_ptr = null_mut();
     let mut sd_ptr = null_mut();
 
-    // Assumptions:
-    // - windows_path is a null-terminated WTF-16-encoded string
-    // - The return value is checked against ERROR_SUCCESS before pointers are used
-    // - All pointers are opaque and should only be used with WinAPI calls
-    // - Pointers are only valid if their corresponding X_SECURITY_INFORMATION
-    //   flags are set
-    // - sd_ptr must be freed with LocalFree
-    let error_code = unsafe {
-        winapi::um::aclapi::GetNamedSecurityInfoW(
-            windows_path.as_ptr(),
-            winapi::um::accctrl::SE_FILE_OBJECT,
-            winnt::OWNER_SECURITY_INFORMATION
-                | winnt::GROUP_SECURITY_INFORMATION
-                | winnt::DACL_SECURITY_INFORMATION,
-            &mut owner_sid_ptr,
-            &mut group_sid_ptr,
-            &mut dacl_ptr,
-            null_mut(),
-            &mut sd_ptr,
-        )
+    let security_information = winnt::OWNER_SECURITY_INFORMATION
+        | winnt::GROUP_SECURITY_INFORMATION
+        | winnt::DACL_SECURITY_INFORMATION;
+
+    let error_code = if metadata.file_type().is_symlink() {
+        // GetNamedSecurityInfoW follows the symlinks and junctions, which
+        // fails for the dangling ones. Like lstat on Unix, the security
+        // information of the link itself are read through a handle opened on
+        // the reparse point.
+        let link = OpenOptions::new()
+            .access_mode(winnt::READ_CONTROL)
+            .custom_flags(
+                winbase::FILE_FLAG_OPEN_REPARSE_POINT | winbase::FILE_FLAG_BACKUP_SEMANTICS,
+            )
+            .open(&path)?;
+
+        // Assumptions:
+        // - link is an open handle, valid until the end of this block
+        // - The return value is checked against ERROR_SUCCESS before pointers are used
+        // - All pointers are opaque and should only be used with WinAPI calls
+        // - Pointers are only valid if their corresponding X_SECURITY_INFORMATION
+        //   flags are set
+        // - sd_ptr must be freed with LocalFree
+        unsafe {
+            winapi::um::aclapi::GetSecurityInfo(
+                link.as_raw_handle() as *mut _,
+                winapi::um::accctrl::SE_FILE_OBJECT,
+                security_information,
+                &mut owner_sid_ptr,
+                &mut group_sid_ptr,
+                &mut dacl_ptr,
+                null_mut(),
+                &mut sd_ptr,
+            )
+        }
+    } else {
+        // Assumptions:
+        // - windows_path is a null-terminated WTF-16-encoded string
+        // - The return value is checked against ERROR_SUCCESS before pointers are used
+        // - All pointers are opaque and should only be used with WinAPI calls
+        // - Pointers are only valid if their corresponding X_SECURITY_INFORMATION
+        //   flags are set
+        // - sd_ptr must be freed with LocalFree
+        unsafe {
+            winapi::um::aclapi::GetNamedSecurityInfoW(
+                windows_path.as_ptr(),
+                winapi::um::accctrl::SE_FILE_OBJECT,
+                security_information,
+                &mut owner_sid_ptr,
+                &mut group_sid_ptr,
+                &mut dacl_ptr,
+                null_mut(),
+                &mut sd_ptr,
+            )
+        }
     };
 
     if error_code != winerror::ERROR_SUCCESS {
@@ -65,22 +109,8 @@ pub fn get_file_data(path: &PathBuf) -> Result<(Owner, Permissions), io::Error>
     // - owner_sid_ptr is valid
     // - group_sid_ptr is valid
     // (both OK because GetNamedSecurityInfoW returned success)
-    let (owner_name, owner_domain) = unsafe { lookup_account_sid(owner_sid_ptr) }?;
-    let (group_name, group_domain) = unsafe { lookup_account_sid(group_sid_ptr) }?;
-
-    let owner_name = os_from_buf(&owner_name);
-    let owner_domain = os_from_buf(&owner_domain);
-    let group_name = os_from_buf(&group_name);
-    let group_domain = os_from_buf(&group_domain);
-
-    // Format into domain\name format
-    let mut owner = owner_domain.to_string_lossy().into_owned();
-    owner.push('\\');
-    owner.push_str(&owner_name.to_string_lossy());
-
-    let mut group = group_domain.to_string_lossy().into_owned();
-    group.push('\\');
-    group.push_str(&group_name.to_string_lossy());
+    let owner = unsafe { account_name_from_sid(owner_sid_ptr, server.as_ref()) };
+    let group = unsafe { account_name_from_sid(group_sid_ptr, server.as_ref()) };
 
     // This structure will be returned
     let owner = Owner::new(owner, group);
@@ -156,6 +186,8 @@ pub fn get_file_data(path: &PathBuf) -> Result<(Owner, Permissions), io::Error>
         sticky: false,
         setuid: false,
         setgid: false,
+
+        attributes: Some(get_attributes(metadata)),
     };
 
     // Assumptions:
@@ -171,6 +203,131 @@ pub fn get_file_data(path: &PathBuf) -> Result<(Owner, Permissions), io::Error>
     Ok((owner, permissions))
 }
 
+/// `path` in the extended-length form, like `\\?\C:\dir` or
+/// `\\?\UNC\server\share\dir`, when it is too long for the security
+/// functions, which fail on it otherwise. The form takes the path as is, so
+/// the paths with `..` are left alone.
+fn security_path(path: &Path) -> PathBuf {
+    if path.as_os_str().encode_wide().count() < MAX_PATH
+        || path
+            .components()
+            .any(|component| component == Component::ParentDir)
+    {
+        return path.to_path_buf();
+    }
+
+    let mut components = path.components();
+    let mut res = match components.next() {
+        Some(Component::Prefix(prefix)) => match prefix.kind() {
+            Prefix::Disk(letter) => PathBuf::from(format!(r"\\?\{}:\", letter as char)),
+            Prefix::UNC(server, share) => {
+                let mut res = OsString::from(r"\\?\UNC\");
+                res.push(server);
+                res.push(r"\");
+                res.push(share);
+                res.push(r"\");
+                PathBuf::from(res)
+            }
+            // Already verbatim, or a device.
+            _ => return path.to_path_buf(),
+        },
+        _ => return path.to_path_buf(),
+    };
+    // Like `C:dir`, relative to the current directory of the drive.
+    if components.next() != Some(Component::RootDir) {
+        return path.to_path_buf();
+    }
+
+    for component in components {
+        res.push(component);
+    }
+    res
+}
+
+/// The server of the share holding `path`, like `server` for
+/// `\\server\share\dir`.
+fn server_name(path: &Path) -> Option<&OsStr> {
+    match path.components().next() {
+        Some(Component::Prefix(prefix)) => match prefix.kind() {
+            Prefix::UNC(server, _) | Prefix::VerbatimUNC(server, _) => Some(server),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn get_attributes(metadata: &Metadata) -> Attributes {
+    let bits = metadata.file_attributes();
+    let has_bit = |bit: u32| bits & bit != 0;
+
+    Attributes {
+        archive: has_bit(winnt::FILE_ATTRIBUTE_ARCHIVE),
+        readonly: has_bit(winnt::FILE_ATTRIBUTE_READONLY),
+        hidden: has_bit(winnt::FILE_ATTRIBUTE_HIDDEN),
+        system: has_bit(winnt::FILE_ATTRIBUTE_SYSTEM),
+        reparse_point: has_bit(winnt::FILE_ATTRIBUTE_REPARSE_POINT),
+    }
+}
+
+/// Get a displayable `domain\name` account name from a SID
+///
+/// The SIDs of deleted accounts, or of accounts from an unreachable domain,
+/// can't be resolved. In that case the SID itself is displayed, the same way
+/// the Windows Explorer does.
+///
+/// The account is looked up on `server`, a NUL-terminated name, or on the
+/// local computer without it.
+///
+/// Assumption: sid is a valid pointer that remains valid through the entire
+/// function execution
+unsafe fn account_name_from_sid(sid: *mut c_void, server: Option<&Vec<u16>>) -> String {
+    match lookup_account_sid(sid, server) {
+        Ok((name, domain)) => {
+            let name = os_from_buf(&name);
+            let domain = os_from_buf(&domain);
+
+            // Format into domain\name format
+            let mut res = domain.to_string_lossy().into_owned();
+            res.push('\\');
+            res.push_str(&name.to_string_lossy());
+            res
+        }
+        Err(_) => sid_to_string(sid).unwrap_or_else(|_| String::from("?")),
+    }
+}
+
+/// Get the string representation of a SID, like `S-1-5-32-544`
+///
+/// Assumption: sid is a valid pointer that remains valid through the entire
+/// function execution
+unsafe fn sid_to_string(sid: *mut c_void) -> Result<String, io::Error> {
+    let mut string_ptr = null_mut();
+
+    // Assumptions:
+    // - sid is a valid pointer to a SID data structure
+    // - string_ptr is only valid if the call returns a non-zero value and
+    //   must be freed with LocalFree
+    let result = winapi::shared::sddl::ConvertSidToStringSidW(sid, &mut string_ptr);
+
+    if result == 0 {
+        return Err(io::Error::from_raw_os_error(
+            winapi::um::errhandlingapi::GetLastError() as i32,
+        ));
+    }
+
+    let mut len = 0;
+    while *string_ptr.add(len) != 0 {
+        len += 1;
+    }
+    let res = OsString::from_wide(std::slice::from_raw_parts(string_ptr, len))
+        .to_string_lossy()
+        .into_owned();
+
+    winapi::um::winbase::LocalFree(string_ptr as *mut _);
+
+    Ok(res)
+}
+
 /// Evaluate an ACL for a particular trustee and get its access rights
 ///
 /// Assumptions:
@@ -221,7 +378,12 @@ unsafe fn trustee_from_sid(sid_ptr: *mut c_void) -> TRUSTEE_W {
 /// function execution
 ///
 /// Returns null-terminated Vec's, one for the name and one for the domain.
-unsafe fn lookup_account_sid(sid: *mut c_void) -> Result<(Vec<u16>, Vec<u16>), std::io::Error> {
+unsafe fn lookup_account_sid(
+    sid: *mut c_void,
+    server: Option<&Vec<u16>>,
+) -> Result<(Vec<u16>, Vec<u16>), std::io::Error> {
+    let server = server.map_or(null(), |server| server.as_ptr());
+
     let mut name_size: u32 = BUF_SIZE;
     let mut domain_size: u32 = BUF_SIZE;
 
@@ -238,7 +400,7 @@ unsafe fn lookup_account_sid(sid: *mut c_void) -> Result<(Vec<u16>, Vec<u16>), s
         // - sid is a valid pointer to a SID data structure
         // - name_size and domain_size accurately reflect the sizes
         let result = winapi::um::winbase::LookupAccountSidW(
-            null_mut(),
+            server,
             sid,
             name.as_mut_ptr(),
             &mut name_size,
@@ -309,6 +471,41 @@ mod test {
         assert_eq!(os_from_buf(&unicode_buf_nuls), unicode_os);
     }
 
+    #[test]
+    fn test_security_path() {
+        let long_name = "a".repeat(MAX_PATH);
+
+        assert_eq!(
+            PathBuf::from(r"C:\dir"),
+            security_path(Path::new(r"C:\dir"))
+        );
+        assert_eq!(
+            Path::new(r"\\?\C:\dir").join(&long_name),
+            security_path(&Path::new(r"C:\dir").join(&long_name))
+        );
+        assert_eq!(
+            Path::new(r"\\?\UNC\server\share\dir").join(&long_name),
+            security_path(&Path::new(r"\\server\share\dir").join(&long_name))
+        );
+
+        let relative = Path::new(r"C:\dir\..").join(&long_name);
+        assert_eq!(relative, security_path(&relative));
+    }
+
+    #[test]
+    fn test_server_name() {
+        assert_eq!(
+            Some(OsStr::new("server")),
+            server_name(Path::new(r"\\server\share\dir"))
+        );
+        assert_eq!(
+            Some(OsStr::new("server")),
+            server_name(Path::new(r"\\?\UNC\server\share"))
+        );
+        assert_eq!(None, server_name(Path::new(r"C:\dir")));
+        assert_eq!(None, server_name(Path::new("dir")));
+    }
+
     #[test]
     fn every_wtf16_codepair_roundtrip() {
         for lsb in 0..256u16 {
@@ -0,0 +1,113 @@
+use std::ffi::{CString, OsStr, OsString};
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+// Thin wrappers around the extended attributes syscalls. Symlinks are never
+// followed: the attributes returned are the ones of the entry itself, which
+// is what the listing displays.
+
+/// List the names of the extended attributes set on `path`.
+///
+/// Filesystems (and platforms) without extended attributes support simply
+/// return an empty list.
+pub fn list(path: &Path) -> io::Result<Vec<OsString>> {
+    let c_path = CString::new(path.as_os_str().as_bytes())?;
+
+    let buf = match read_into_buffer(|buf, size| unsafe { sys::list(&c_path, buf, size) }) {
+        Ok(buf) => buf,
+        Err(ref err) if err.raw_os_error() == Some(libc::ENOTSUP) => return Ok(Vec::new()),
+        Err(err) => return Err(err),
+    };
+
+    Ok(buf
+        .split(|byte| *byte == 0)
+        .filter(|name| !name.is_empty())
+        .map(|name| OsStr::from_bytes(name).to_os_string())
+        .collect())
+}
+
+/// Read the value of the extended attribute `name` on `path`.
+pub fn get(path: &Path, name: &OsStr) -> io::Result<Vec<u8>> {
+    let c_path = CString::new(path.as_os_str().as_bytes())?;
+    let c_name = CString::new(name.as_bytes())?;
+
+    read_into_buffer(|buf, size| unsafe { sys::get(&c_path, &c_name, buf, size) })
+}
+
+/// Call `fetch` a first time to retrieve the required size, then a second
+/// time to fill the buffer. The operation is retried if the attributes grew
+/// in the meantime.
+fn read_into_buffer<F>(fetch: F) -> io::Result<Vec<u8>>
+where
+    F: Fn(*mut u8, usize) -> isize,
+{
+    loop {
+        let size = fetch(std::ptr::null_mut(), 0);
+        if size < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if size == 0 {
+            return Ok(Vec::new());
+        }
+
+        let mut buf = vec![0u8; size as usize];
+        let size = fetch(buf.as_mut_ptr(), buf.len());
+        if size < 0 {
+            let err = io::Error::last_os_error();
+            if err.raw_os_error() == Some(libc::ERANGE) {
+                continue;
+            }
+            return Err(err);
+        }
+
+        buf.truncate(size as usize);
+        return Ok(buf);
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod sys {
+    use std::ffi::CStr;
+
+    pub unsafe fn list(path: &CStr, buf: *mut u8, size: usize) -> isize {
+        libc::llistxattr(path.as_ptr(), buf as *mut _, size)
+    }
+
+    pub unsafe fn get(path: &CStr, name: &CStr, buf: *mut u8, size: usize) -> isize {
+        libc::lgetxattr(path.as_ptr(), name.as_ptr(), buf as *mut _, size)
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod sys {
+    use std::ffi::CStr;
+
+    pub unsafe fn list(path: &CStr, buf: *mut u8, size: usize) -> isize {
+        libc::listxattr(path.as_ptr(), buf as *mut _, size, libc::XATTR_NOFOLLOW)
+    }
+
+    pub unsafe fn get(path: &CStr, name: &CStr, buf: *mut u8, size: usize) -> isize {
+        libc::getxattr(
+            path.as_ptr(),
+            name.as_ptr(),
+            buf as *mut _,
+            size,
+            0,
+            libc::XATTR_NOFOLLOW,
+        )
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "macos")))]
+mod sys {
+    use std::ffi::CStr;
+
+    pub unsafe fn list(_: &CStr, _: *mut u8, _: usize) -> isize {
+        0
+    }
+
+    pub unsafe fn get(_: &CStr, _: &CStr, _: *mut u8, _: usize) -> isize {
+        0
+    }
+}
@@ -0,0 +1,106 @@
+use std::env;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+
+/// Where the listing is written: the standard output, or a pager reading it.
+pub enum Output {
+    Stdout(io::Stdout),
+    Pager(Child),
+}
+
+impl Output {
+    pub fn stdout() -> Self {
+        Output::Stdout(io::stdout())
+    }
+
+    /// Spawn the pager of `$PAGER`, `less -R` by default, falling back on the
+    /// standard output when it cannot be started.
+    pub fn pager() -> Self {
+        let (program, args) = pager_command(&env::var("PAGER").unwrap_or_default());
+
+        let child = Command::new(program)
+            .args(&args)
+            .stdin(Stdio::piped())
+            .spawn();
+
+        match child {
+            Ok(child) => Output::Pager(child),
+            Err(_) => Self::stdout(),
+        }
+    }
+
+    /// Flush the listing, waiting for the user to quit the pager.
+    pub fn finish(self) {
+        match self {
+            Output::Stdout(mut stdout) => {
+                stdout.flush().ok();
+            }
+            Output::Pager(mut child) => {
+                // Closing the input tells the pager there is nothing more.
+                drop(child.stdin.take());
+                child.wait().ok();
+            }
+        }
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Output::Stdout(stdout) => stdout.write(buf),
+            Output::Pager(child) => match child.stdin.as_mut() {
+                Some(stdin) => stdin.write(buf),
+                None => Err(io::Error::from(io::ErrorKind::BrokenPipe)),
+            },
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Output::Stdout(stdout) => stdout.flush(),
+            Output::Pager(child) => match child.stdin.as_mut() {
+                Some(stdin) => stdin.flush(),
+                None => Ok(()),
+            },
+        }
+    }
+}
+
+/// The program and arguments of the pager. Like `bat`, a bare `less` is given
+/// `-R` so that the colors are kept.
+fn pager_command(pager: &str) -> (String, Vec<String>) {
+    let mut words = pager.split_whitespace().map(String::from);
+
+    match words.next() {
+        Some(program) => {
+            let mut args: Vec<String> = words.collect();
+            if args.is_empty() && Path::new(&program).file_name() == Some("less".as_ref()) {
+                args.push(String::from("-R"));
+            }
+            (program, args)
+        }
+        None => (String::from("less"), vec![String::from("-R")]),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::pager_command;
+
+    #[test]
+    fn test_pager_command() {
+        let less = (String::from("less"), vec![String::from("-R")]);
+
+        assert_eq!(less, pager_command(""));
+        assert_eq!(less, pager_command("  "));
+        assert_eq!(
+            (String::from("/usr/bin/less"), vec![String::from("-R")]),
+            pager_command("/usr/bin/less")
+        );
+        assert_eq!(
+            (String::from("most"), vec![String::from("-s")]),
+            pager_command("most -s")
+        );
+    }
+}
@@ -0,0 +1,127 @@
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+use terminal_size::{terminal_size, Width};
+use unicode_width::UnicodeWidthChar;
+
+// How often the progress line is redrawn, as the terminal would be slower
+// than the scan otherwise.
+const DRAW_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A transient line on the standard error telling how many entries a long
+/// scan went through, like the one of `--total-size`, erased once it is done.
+/// Nothing is drawn when the standard error is not a terminal.
+pub struct Progress {
+    enabled: bool,
+    scanned: u64,
+    // The scans over within the first interval draw nothing.
+    last_draw: Instant,
+    drawn: bool,
+}
+
+impl Progress {
+    /// The progress on the standard error, when it is a terminal.
+    pub fn on_stderr() -> Self {
+        #[cfg(unix)]
+        let enabled = unsafe { libc::isatty(libc::STDERR_FILENO) == 1 };
+        #[cfg(not(unix))]
+        let enabled = false;
+
+        Self {
+            enabled,
+            ..Self::hidden()
+        }
+    }
+
+    /// The progress never drawn, for the scans nobody waits on.
+    pub fn hidden() -> Self {
+        Self {
+            enabled: false,
+            scanned: 0,
+            last_draw: Instant::now(),
+            drawn: false,
+        }
+    }
+
+    pub fn scanned(&self) -> u64 {
+        self.scanned
+    }
+
+    /// Count the scan of `path`, showing it when the line is due a redraw.
+    pub fn tick(&mut self, path: &Path) {
+        self.scanned += 1;
+        if !self.enabled {
+            return;
+        }
+
+        let now = Instant::now();
+        if now.duration_since(self.last_draw) < DRAW_INTERVAL {
+            return;
+        }
+        self.last_draw = now;
+        self.drawn = true;
+
+        let width = match terminal_size() {
+            Some((Width(width), _)) => width as usize,
+            None => 80,
+        };
+        let line = progress_line(self.scanned, path, width);
+        eprint!("\r\x1b[K{}", line);
+        io::stderr().flush().ok();
+    }
+
+    /// Erase the progress line, if it was ever drawn.
+    pub fn finish(&mut self) {
+        if self.drawn {
+            eprint!("\r\x1b[K");
+            io::stderr().flush().ok();
+        }
+        self.drawn = false;
+    }
+}
+
+/// The `scanned` count and the current `path`, cut to fit in `width` columns
+/// so that the line never wraps.
+fn progress_line(scanned: u64, path: &Path, width: usize) -> String {
+    let line = format!("{} entries scanned: {}", scanned, path.display());
+
+    let mut res = String::with_capacity(line.len());
+    let mut line_width = 0;
+    for c in line.chars() {
+        line_width += c.width().unwrap_or(0);
+        // The last column is left empty, as some terminals wrap on it.
+        if line_width >= width {
+            break;
+        }
+        res.push(c);
+    }
+
+    res
+}
+
+#[cfg(test)]
+mod test {
+    use super::{progress_line, Progress};
+    use std::path::Path;
+
+    #[test]
+    fn test_progress_line_fits_the_width() {
+        let path = Path::new("some/deep/path");
+
+        assert_eq!(
+            "42 entries scanned: some/deep/path",
+            progress_line(42, path, 80)
+        );
+        assert_eq!("42 entries scanned: some", progress_line(42, path, 25));
+    }
+
+    #[test]
+    fn test_hidden_progress_still_counts() {
+        let mut progress = Progress::hidden();
+        progress.tick(Path::new("one"));
+        progress.tick(Path::new("two"));
+        progress.finish();
+
+        assert_eq!(2, progress.scanned());
+    }
+}
@@ -1,110 +1,132 @@
-use crate::flags::{DirOrderFlag, Flags, SortFlag, SortOrder};
+use crate::flags::{CollationFlag, DirOrderFlag, DotFilesFlag, Flags, SortFlag, SortOrder};
 use crate::meta::{FileType, Meta};
 use std::cmp::Ordering;
+use std::iter;
 
+/// Compare two entries according to the sort flags, for use with `sort_by`.
 pub fn by_meta(a: &Meta, b: &Meta, flags: &Flags) -> Ordering {
-    match flags.sort_by {
-        SortFlag::Name => match flags.directory_order {
-            DirOrderFlag::First => by_name_with_dirs_first(a, b, &flags),
-            DirOrderFlag::None => by_name(a, b, &flags),
-            DirOrderFlag::Last => by_name_with_files_first(a, b, &flags),
-        },
-        SortFlag::Size => match flags.directory_order {
-            DirOrderFlag::First => by_size_with_dirs_first(a, b, &flags),
-            DirOrderFlag::None => by_size(a, b, &flags),
-            DirOrderFlag::Last => by_size_with_files_first(a, b, &flags),
-        },
-        SortFlag::Time => match flags.directory_order {
-            DirOrderFlag::First => by_date_with_dirs_first(a, b, &flags),
-            DirOrderFlag::None => by_date(a, b, &flags),
-            DirOrderFlag::Last => by_date_with_files_first(a, b, &flags),
-        },
-    }
+    by_dotfiles(a, b, flags)
+        .then_with(|| by_directory_order(a, b, flags))
+        .then_with(|| by_sort_keys(a, b, flags))
 }
 
-fn by_size(a: &Meta, b: &Meta, flags: &Flags) -> Ordering {
-    if flags.sort_order == SortOrder::Default {
-        b.size.get_bytes().cmp(&a.size.get_bytes())
-    } else {
-        a.size.get_bytes().cmp(&b.size.get_bytes())
+/// Group the dotfiles apart, whatever the sort key and order.
+fn by_dotfiles(a: &Meta, b: &Meta, flags: &Flags) -> Ordering {
+    // `.` and `..` aren't hidden files, they stay at their usual place.
+    match flags.dotfiles_order {
+        DotFilesFlag::Mixed => Ordering::Equal,
+        DotFilesFlag::First => b.is_hidden().cmp(&a.is_hidden()),
+        DotFilesFlag::Last => a.is_hidden().cmp(&b.is_hidden()),
     }
 }
 
-fn by_size_with_dirs_first(a: &Meta, b: &Meta, flags: &Flags) -> Ordering {
-    match (a.file_type, b.file_type) {
-        (FileType::Directory { .. }, FileType::Directory { .. }) => by_size(a, b, &flags),
-        (FileType::Directory { .. }, _) => Ordering::Less,
-        (_, FileType::Directory { .. }) => Ordering::Greater,
-        _ => by_size(a, b, &flags),
+/// Group the directories apart, whatever the sort key and order.
+fn by_directory_order(a: &Meta, b: &Meta, flags: &Flags) -> Ordering {
+    match flags.directory_order {
+        DirOrderFlag::First => by_dirs_first(a, b),
+        DirOrderFlag::None => Ordering::Equal,
+        DirOrderFlag::Last => by_dirs_first(a, b).reverse(),
     }
 }
 
-fn by_size_with_files_first(a: &Meta, b: &Meta, flags: &Flags) -> Ordering {
-    match (a.file_type, b.file_type) {
-        (FileType::Directory { .. }, FileType::Directory { .. }) => by_size(a, b, &flags),
-        (FileType::Directory { .. }, _) => Ordering::Greater,
-        (_, FileType::Directory { .. }) => Ordering::Less,
-        _ => by_size(a, b, &flags),
+/// Compare by `sort_by`, then by the keys of `sort_then_by` for the entries
+/// it finds equal. The entries equal by all the keys are ordered by name and
+/// then by path, so that the listing is the same from a run to the other.
+///
+/// The reverse order inverts the whole comparison, within the groups.
+fn by_sort_keys(a: &Meta, b: &Meta, flags: &Flags) -> Ordering {
+    let ordering = by_keys(a, b, flags);
+
+    match flags.sort_order {
+        SortOrder::Default => ordering,
+        SortOrder::Reverse => ordering.reverse(),
     }
 }
 
-fn by_name(a: &Meta, b: &Meta, flags: &Flags) -> Ordering {
-    if flags.sort_order == SortOrder::Default {
-        a.name.cmp(&b.name)
-    } else {
-        b.name.cmp(&a.name)
+fn by_keys(a: &Meta, b: &Meta, flags: &Flags) -> Ordering {
+    let keys = iter::once(&flags.sort_by).chain(&flags.sort_then_by);
+
+    for key in keys {
+        let ordering = match key {
+            SortFlag::Name => cmp_names(a, b, flags),
+            SortFlag::Size => by_size(a, b),
+            SortFlag::Time => by_date(a, b),
+            SortFlag::Extension => cmp_extensions(a, b, flags),
+            SortFlag::Inode => a.inode.index().cmp(&b.inode.index()),
+            // The most linked first, like the biggest with the size.
+            SortFlag::Links => b.inode.links().cmp(&a.inode.links()),
+            // The rest keeps the reading order, reversed or not, like `ls -U`.
+            SortFlag::None => return Ordering::Equal,
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
     }
+
+    cmp_names(a, b, flags).then_with(|| a.path.cmp(&b.path))
 }
 
-fn by_name_with_dirs_first(a: &Meta, b: &Meta, flags: &Flags) -> Ordering {
+fn by_dirs_first(a: &Meta, b: &Meta) -> Ordering {
     match (a.file_type, b.file_type) {
-        (FileType::Directory { .. }, FileType::Directory { .. }) => by_name(a, b, &flags),
+        (FileType::Directory { .. }, FileType::Directory { .. }) => Ordering::Equal,
         (FileType::Directory { .. }, _) => Ordering::Less,
         (_, FileType::Directory { .. }) => Ordering::Greater,
-        _ => by_name(a, b, &flags),
+        _ => Ordering::Equal,
     }
 }
 
-fn by_name_with_files_first(a: &Meta, b: &Meta, flags: &Flags) -> Ordering {
-    match (a.file_type, b.file_type) {
-        (FileType::Directory { .. }, FileType::Directory { .. }) => by_name(a, b, &flags),
-        (FileType::Directory { .. }, _) => Ordering::Greater,
-        (_, FileType::Directory { .. }) => Ordering::Less,
-        _ => by_name(a, b, &flags),
-    }
+/// The largest first.
+fn by_size(a: &Meta, b: &Meta) -> Ordering {
+    b.size.get_bytes().cmp(&a.size.get_bytes())
 }
 
-fn by_date(a: &Meta, b: &Meta, flags: &Flags) -> Ordering {
-    if flags.sort_order == SortOrder::Default {
-        b.date.cmp(&a.date).then(a.name.cmp(&b.name))
-    } else {
-        a.date.cmp(&b.date).then(b.name.cmp(&a.name))
+fn cmp_names(a: &Meta, b: &Meta, flags: &Flags) -> Ordering {
+    match flags.collation {
+        CollationFlag::Default => a.name.cmp(&b.name),
+        CollationFlag::IgnoreCase => a.name.cmp(&b.name).then(a.name.name.cmp(&b.name.name)),
+        CollationFlag::Locale => cmp_with_locale(&a.name.name, &b.name.name),
     }
 }
 
-fn by_date_with_dirs_first(a: &Meta, b: &Meta, flags: &Flags) -> Ordering {
-    match (a.file_type, b.file_type) {
-        (FileType::Directory { .. }, FileType::Directory { .. }) => by_date(a, b, &flags),
-        (FileType::Directory { .. }, _) => Ordering::Less,
-        (_, FileType::Directory { .. }) => Ordering::Greater,
-        _ => by_date(a, b, &flags),
+/// The names without an extension come first, like `ls -X`, and the
+/// extensions are compared like the names.
+fn cmp_extensions(a: &Meta, b: &Meta, flags: &Flags) -> Ordering {
+    match (a.name.extension(), b.name.extension()) {
+        (Some(a), Some(b)) => match flags.collation {
+            CollationFlag::Default => a.to_lowercase().cmp(&b.to_lowercase()),
+            CollationFlag::IgnoreCase => a.to_lowercase().cmp(&b.to_lowercase()).then(a.cmp(b)),
+            CollationFlag::Locale => cmp_with_locale(a, b),
+        },
+        (a, b) => a.cmp(&b),
     }
 }
 
-fn by_date_with_files_first(a: &Meta, b: &Meta, flags: &Flags) -> Ordering {
-    match (a.file_type, b.file_type) {
-        (FileType::Directory { .. }, FileType::Directory { .. }) => by_date(a, b, &flags),
-        (FileType::Directory { .. }, _) => Ordering::Greater,
-        (_, FileType::Directory { .. }) => Ordering::Less,
-        _ => by_date(a, b, &flags),
+/// Compare with `strcoll`, expecting the `LC_COLLATE` locale to be set.
+#[cfg(unix)]
+fn cmp_with_locale(a: &str, b: &str) -> Ordering {
+    use std::ffi::CString;
+
+    match (CString::new(a), CString::new(b)) {
+        (Ok(a), Ok(b)) => unsafe { libc::strcoll(a.as_ptr(), b.as_ptr()) }.cmp(&0),
+        _ => a.cmp(b),
     }
 }
 
+#[cfg(not(unix))]
+fn cmp_with_locale(a: &str, b: &str) -> Ordering {
+    a.to_lowercase().cmp(&b.to_lowercase()).then(a.cmp(b))
+}
+
+/// The newest first.
+fn by_date(a: &Meta, b: &Meta) -> Ordering {
+    b.date.cmp(&a.date)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::flags::Flags;
-    use std::fs::{create_dir, File};
+    use std::fs::{self, create_dir, File};
     use std::process::Command;
     use tempfile::tempdir;
 
@@ -122,8 +144,10 @@ mod tests {
         create_dir(&path_z).expect("failed to create dir");
         let meta_z = Meta::from_path(&path_z).expect("failed to get meta");
 
-        let mut flags = Flags::default();
-        flags.directory_order = DirOrderFlag::First;
+        let mut flags = Flags {
+            directory_order: DirOrderFlag::First,
+            ..Flags::default()
+        };
 
         //  Sort with the dirs first
         assert_eq!(by_meta(&meta_a, &meta_z, &flags), Ordering::Greater);
@@ -147,8 +171,10 @@ mod tests {
         create_dir(&path_z).expect("failed to create dir");
         let meta_z = Meta::from_path(&path_z).expect("failed to get meta");
 
-        let mut flags = Flags::default();
-        flags.directory_order = DirOrderFlag::Last;
+        let flags = Flags {
+            directory_order: DirOrderFlag::Last,
+            ..Flags::default()
+        };
 
         // Sort with file first
         assert_eq!(by_meta(&meta_a, &meta_z, &flags), Ordering::Less);
@@ -171,8 +197,10 @@ mod tests {
         create_dir(&path_z).expect("failed to create dir");
         let meta_z = Meta::from_path(&path_z).expect("failed to get meta");
 
-        let mut flags = Flags::default();
-        flags.directory_order = DirOrderFlag::None;
+        let mut flags = Flags {
+            directory_order: DirOrderFlag::None,
+            ..Flags::default()
+        };
 
         // Sort by name unordered
         assert_eq!(by_meta(&meta_a, &meta_z, &flags), Ordering::Less);
@@ -196,8 +224,10 @@ mod tests {
         create_dir(&path_z).expect("failed to create dir");
         let meta_z = Meta::from_path(&path_z).expect("failed to get meta");
 
-        let mut flags = Flags::default();
-        flags.directory_order = DirOrderFlag::None;
+        let mut flags = Flags {
+            directory_order: DirOrderFlag::None,
+            ..Flags::default()
+        };
 
         // Sort by name unordered
         assert_eq!(by_meta(&meta_a, &meta_z, &flags), Ordering::Greater);
@@ -239,11 +269,13 @@ mod tests {
             .unwrap()
             .success();
 
-        assert_eq!(true, success, "failed to change file timestamp");
+        assert!(success, "failed to change file timestamp");
         let meta_z = Meta::from_path(&path_z).expect("failed to get meta");
 
-        let mut flags = Flags::default();
-        flags.sort_by = SortFlag::Time;
+        let mut flags = Flags {
+            sort_by: SortFlag::Time,
+            ..Flags::default()
+        };
 
         // Sort by time
         assert_eq!(by_meta(&meta_a, &meta_z, &flags), Ordering::Less);
@@ -252,4 +284,247 @@ mod tests {
         flags.sort_order = SortOrder::Reverse;
         assert_eq!(by_meta(&meta_a, &meta_z, &flags), Ordering::Greater);
     }
+
+    #[test]
+    fn test_sort_by_meta_ignoring_case() {
+        let tmp_dir = tempdir().expect("failed to create temp dir");
+
+        // Create the files;
+        let path_lower = tmp_dir.path().join("apple");
+        File::create(&path_lower).expect("failed to create file");
+        let meta_lower = Meta::from_path(&path_lower).expect("failed to get meta");
+
+        let path_upper = tmp_dir.path().join("Apple");
+        File::create(&path_upper).expect("failed to create file");
+        let meta_upper = Meta::from_path(&path_upper).expect("failed to get meta");
+
+        let path_b = tmp_dir.path().join("Banana");
+        File::create(&path_b).expect("failed to create file");
+        let meta_b = Meta::from_path(&path_b).expect("failed to get meta");
+
+        // The names equal regardless of their case are ordered by path.
+        let mut flags = Flags::default();
+        assert_eq!(by_meta(&meta_lower, &meta_upper, &flags), Ordering::Greater);
+
+        flags.collation = CollationFlag::IgnoreCase;
+        assert_eq!(by_meta(&meta_lower, &meta_upper, &flags), Ordering::Greater);
+        assert_eq!(by_meta(&meta_lower, &meta_b, &flags), Ordering::Less);
+    }
+
+    #[test]
+    fn test_sort_by_meta_with_secondary_keys() {
+        let tmp_dir = tempdir().expect("failed to create temp dir");
+
+        // Create the files, two of the same size;
+        let path_a = tmp_dir.path().join("aaa");
+        fs::write(&path_a, "12").expect("failed to write file");
+        let meta_a = Meta::from_path(&path_a).expect("failed to get meta");
+
+        let path_b = tmp_dir.path().join("bbb");
+        fs::write(&path_b, "1").expect("failed to write file");
+        let meta_b = Meta::from_path(&path_b).expect("failed to get meta");
+
+        let path_c = tmp_dir.path().join("ccc");
+        fs::write(&path_c, "12").expect("failed to write file");
+        let meta_c = Meta::from_path(&path_c).expect("failed to get meta");
+
+        let flags = Flags {
+            sort_by: SortFlag::Size,
+            sort_then_by: vec![SortFlag::Name],
+            ..Flags::default()
+        };
+        assert_eq!(by_meta(&meta_a, &meta_b, &flags), Ordering::Less);
+        assert_eq!(by_meta(&meta_a, &meta_c, &flags), Ordering::Less);
+
+        // The reverse order applies to all the keys.
+        let flags = Flags {
+            sort_order: SortOrder::Reverse,
+            ..flags
+        };
+        assert_eq!(by_meta(&meta_b, &meta_a, &flags), Ordering::Less);
+        assert_eq!(by_meta(&meta_c, &meta_a, &flags), Ordering::Less);
+    }
+
+    #[test]
+    fn test_sort_by_meta_extension() {
+        let tmp_dir = tempdir().expect("failed to create temp dir");
+
+        let path_rs = tmp_dir.path().join("aaa.rs");
+        File::create(&path_rs).expect("failed to create file");
+        let meta_rs = Meta::from_path(&path_rs).expect("failed to get meta");
+
+        let path_md = tmp_dir.path().join("zzz.md");
+        File::create(&path_md).expect("failed to create file");
+        let meta_md = Meta::from_path(&path_md).expect("failed to get meta");
+
+        let path_bare = tmp_dir.path().join("zzz");
+        File::create(&path_bare).expect("failed to create file");
+        let meta_bare = Meta::from_path(&path_bare).expect("failed to get meta");
+
+        let flags = Flags {
+            sort_by: SortFlag::Extension,
+            ..Flags::default()
+        };
+        assert_eq!(by_meta(&meta_md, &meta_rs, &flags), Ordering::Less);
+        assert_eq!(by_meta(&meta_bare, &meta_md, &flags), Ordering::Less);
+
+        // The extensions are compared regardless of their case.
+        let path_upper = tmp_dir.path().join("aaa.TXT");
+        File::create(&path_upper).expect("failed to create file");
+        let meta_upper = Meta::from_path(&path_upper).expect("failed to get meta");
+        let path_lower = tmp_dir.path().join("zzz.txt");
+        File::create(&path_lower).expect("failed to create file");
+        let meta_lower = Meta::from_path(&path_lower).expect("failed to get meta");
+
+        assert_eq!(by_meta(&meta_md, &meta_upper, &flags), Ordering::Less);
+        let ignore_case = Flags {
+            collation: CollationFlag::IgnoreCase,
+            ..flags
+        };
+        assert_eq!(by_meta(&meta_md, &meta_upper, &ignore_case), Ordering::Less);
+        assert_eq!(
+            by_meta(&meta_upper, &meta_lower, &ignore_case),
+            Ordering::Less
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_sort_by_meta_links() {
+        let tmp_dir = tempdir().expect("failed to create temp dir");
+
+        let path_a = tmp_dir.path().join("aaa");
+        File::create(&path_a).expect("failed to create file");
+        let meta_a = Meta::from_path(&path_a).expect("failed to get meta");
+
+        let path_z = tmp_dir.path().join("zzz");
+        File::create(&path_z).expect("failed to create file");
+        fs::hard_link(&path_z, tmp_dir.path().join("link")).expect("failed to link");
+        let meta_z = Meta::from_path(&path_z).expect("failed to get meta");
+
+        let flags = Flags {
+            sort_by: SortFlag::Links,
+            ..Flags::default()
+        };
+        assert_eq!(by_meta(&meta_z, &meta_a, &flags), Ordering::Less);
+
+        // Created one after the other, the files get increasing inodes on
+        // the usual filesystems, the first one coming last by its name.
+        let path_first = tmp_dir.path().join("yyy");
+        File::create(&path_first).expect("failed to create file");
+        let path_second = tmp_dir.path().join("bbb");
+        File::create(&path_second).expect("failed to create file");
+        let meta_first = Meta::from_path(&path_first).expect("failed to get meta");
+        let meta_second = Meta::from_path(&path_second).expect("failed to get meta");
+        if meta_first.inode.index() > meta_second.inode.index() {
+            return;
+        }
+
+        let flags = Flags {
+            sort_by: SortFlag::Inode,
+            ..Flags::default()
+        };
+        assert_eq!(by_meta(&meta_first, &meta_second, &flags), Ordering::Less);
+        assert_eq!(
+            by_meta(&meta_second, &meta_first, &flags),
+            Ordering::Greater
+        );
+    }
+
+    #[test]
+    fn test_reverse_keeps_the_groups() {
+        let tmp_dir = tempdir().expect("failed to create temp dir");
+
+        let path_dir = tmp_dir.path().join("aaa");
+        create_dir(&path_dir).expect("failed to create dir");
+        let meta_dir = Meta::from_path(&path_dir).expect("failed to get meta");
+
+        let path_hidden = tmp_dir.path().join(".zzz");
+        File::create(&path_hidden).expect("failed to create file");
+        let meta_hidden = Meta::from_path(&path_hidden).expect("failed to get meta");
+
+        let path_a = tmp_dir.path().join("apple");
+        File::create(&path_a).expect("failed to create file");
+        let meta_a = Meta::from_path(&path_a).expect("failed to get meta");
+
+        let path_upper = tmp_dir.path().join("Apple");
+        File::create(&path_upper).expect("failed to create file");
+        let meta_upper = Meta::from_path(&path_upper).expect("failed to get meta");
+
+        for sort_by in &[SortFlag::Name, SortFlag::Size, SortFlag::Time] {
+            let flags = Flags {
+                sort_by: *sort_by,
+                sort_order: SortOrder::Reverse,
+                directory_order: DirOrderFlag::First,
+                dotfiles_order: DotFilesFlag::Last,
+                ..Flags::default()
+            };
+
+            assert_eq!(by_meta(&meta_dir, &meta_a, &flags), Ordering::Less);
+            assert_eq!(by_meta(&meta_a, &meta_hidden, &flags), Ordering::Less);
+            // Even the tiebreak is reversed.
+            assert_eq!(by_meta(&meta_a, &meta_upper, &flags), Ordering::Less);
+        }
+    }
+
+    #[test]
+    fn test_sort_by_meta_none_keeps_the_reading_order() {
+        let tmp_dir = tempdir().expect("failed to create temp dir");
+
+        let path_a = tmp_dir.path().join("aaa");
+        File::create(&path_a).expect("failed to create file");
+        let meta_a = Meta::from_path(&path_a).expect("failed to get meta");
+
+        let path_z = tmp_dir.path().join("zzz");
+        File::create(&path_z).expect("failed to create file");
+        let meta_z = Meta::from_path(&path_z).expect("failed to get meta");
+
+        let flags = Flags {
+            sort_by: SortFlag::Size,
+            sort_then_by: vec![SortFlag::None, SortFlag::Name],
+            ..Flags::default()
+        };
+        assert_eq!(by_meta(&meta_z, &meta_a, &flags), Ordering::Equal);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_cmp_with_the_c_locale() {
+        // The tests don't set the locale, so `strcoll` compares the bytes.
+        assert_eq!(cmp_with_locale("Banana", "apple"), Ordering::Less);
+        assert_eq!(cmp_with_locale("apple", "apple"), Ordering::Equal);
+    }
+
+    #[test]
+    fn test_sort_by_meta_with_dotfiles_last() {
+        let tmp_dir = tempdir().expect("failed to create temp dir");
+
+        // Create the files;
+        let path_hidden = tmp_dir.path().join(".aaa");
+        File::create(&path_hidden).expect("failed to create file");
+        let meta_hidden = Meta::from_path(&path_hidden).expect("failed to get meta");
+
+        let path_visible = tmp_dir.path().join("zzz");
+        File::create(&path_visible).expect("failed to create file");
+        let meta_visible = Meta::from_path(&path_visible).expect("failed to get meta");
+
+        let mut flags = Flags::default();
+        assert_eq!(by_meta(&meta_hidden, &meta_visible, &flags), Ordering::Less);
+
+        flags.dotfiles_order = DotFilesFlag::Last;
+        assert_eq!(
+            by_meta(&meta_hidden, &meta_visible, &flags),
+            Ordering::Greater
+        );
+
+        // The dotfiles stay last in the reverse order
+        flags.sort_order = SortOrder::Reverse;
+        assert_eq!(
+            by_meta(&meta_hidden, &meta_visible, &flags),
+            Ordering::Greater
+        );
+
+        flags.dotfiles_order = DotFilesFlag::First;
+        assert_eq!(by_meta(&meta_hidden, &meta_visible, &flags), Ordering::Less);
+    }
 }
@@ -0,0 +1,230 @@
+use crate::interrupt;
+use notify::{DebouncedEvent, RecommendedWatcher, RecursiveMode, Watcher};
+use std::fs::{self, Metadata, Permissions};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+// How often `Ctrl-C` is looked for, and the snapshots taken when polling.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+// The bursts of events, like the ones of a checkout, make a single change.
+const NOTIFY_DELAY: Duration = Duration::from_millis(100);
+
+/// The changes of the entries listed by `--watch`, notified by the system,
+/// like inotify or FSEvents. Where the notifications are unavailable, or their
+/// limits reached, the entries are polled instead.
+pub enum Changes {
+    Notified {
+        // The notifications stop once the watcher is dropped.
+        _watcher: RecommendedWatcher,
+        events: Receiver<DebouncedEvent>,
+        paths: Vec<PathBuf>,
+        depth: usize,
+    },
+    Polled {
+        paths: Vec<PathBuf>,
+        depth: usize,
+        snapshot: Snapshot,
+    },
+}
+
+impl Changes {
+    /// Watch `paths` and their content down to `depth`.
+    pub fn watch(paths: &[PathBuf], depth: usize) -> Self {
+        let mode = if depth > 1 {
+            RecursiveMode::Recursive
+        } else {
+            RecursiveMode::NonRecursive
+        };
+        let (sender, events) = mpsc::channel();
+
+        let watcher = notify::watcher(sender, NOTIFY_DELAY).and_then(|mut watcher| {
+            for path in paths {
+                watcher.watch(path, mode)?;
+            }
+            Ok(watcher)
+        });
+
+        match watcher {
+            Ok(watcher) => Changes::Notified {
+                _watcher: watcher,
+                events,
+                paths: paths.to_vec(),
+                depth,
+            },
+            Err(_) => Self::poll(paths, depth),
+        }
+    }
+
+    fn poll(paths: &[PathBuf], depth: usize) -> Self {
+        Changes::Polled {
+            paths: paths.to_vec(),
+            depth,
+            snapshot: Snapshot::take(paths, depth),
+        }
+    }
+
+    /// Wait for the next change, false when interrupted before.
+    pub fn wait(&mut self) -> bool {
+        match self {
+            Changes::Notified { events, .. } => loop {
+                if interrupt::is_interrupted() {
+                    return false;
+                }
+                match events.recv_timeout(POLL_INTERVAL) {
+                    // The notices come ahead of the events they tell about.
+                    Ok(DebouncedEvent::NoticeWrite(_)) | Ok(DebouncedEvent::NoticeRemove(_)) => {}
+                    Ok(_) => {
+                        // The rest of the burst is shown by the same listing.
+                        while events.try_recv().is_ok() {}
+                        return true;
+                    }
+                    Err(RecvTimeoutError::Timeout) => {}
+                    Err(RecvTimeoutError::Disconnected) => break,
+                }
+            },
+            Changes::Polled {
+                paths,
+                depth,
+                snapshot,
+            } => loop {
+                if interrupt::is_interrupted() {
+                    return false;
+                }
+                let current = Snapshot::take(paths, *depth);
+                if current != *snapshot {
+                    *snapshot = current;
+                    return true;
+                }
+                thread::sleep(POLL_INTERVAL);
+            },
+        }
+
+        // The watcher is gone, the entries are polled from now on.
+        if let Changes::Notified { paths, depth, .. } = self {
+            *self = Self::poll(paths, *depth);
+        }
+        true
+    }
+}
+
+/// What `--watch` compares to tell that a listing is out of date: the paths
+/// and the metadata of the listed entries, down to `depth`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Snapshot {
+    entries: Vec<(PathBuf, Option<State>)>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct State {
+    modified: Option<SystemTime>,
+    len: u64,
+    permissions: Permissions,
+    #[cfg(unix)]
+    changed: (i64, i64),
+}
+
+impl State {
+    fn from(metadata: &Metadata) -> Self {
+        Self {
+            modified: metadata.modified().ok(),
+            len: metadata.len(),
+            permissions: metadata.permissions(),
+            // The status change time also covers the new owners.
+            #[cfg(unix)]
+            changed: {
+                use std::os::unix::fs::MetadataExt;
+                (metadata.ctime(), metadata.ctime_nsec())
+            },
+        }
+    }
+}
+
+impl Snapshot {
+    pub fn take(paths: &[PathBuf], depth: usize) -> Self {
+        let mut entries = Vec::new();
+        for path in paths {
+            Self::add(path, depth, &mut entries);
+        }
+
+        Self { entries }
+    }
+
+    fn add(path: &Path, depth: usize, entries: &mut Vec<(PathBuf, Option<State>)>) {
+        let metadata = fs::symlink_metadata(path).ok();
+        let is_dir = metadata.as_ref().map(Metadata::is_dir) == Some(true);
+        entries.push((path.to_path_buf(), metadata.as_ref().map(State::from)));
+
+        if !is_dir || depth == 0 {
+            return;
+        }
+
+        if let Ok(dir) = fs::read_dir(path) {
+            // The order of `read_dir` may change with the content left alone.
+            let mut children: Vec<PathBuf> = dir.filter_map(|e| e.ok()).map(|e| e.path()).collect();
+            children.sort();
+
+            for child in children {
+                Self::add(&child, depth - 1, entries);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Changes, Snapshot};
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_changes_are_notified() {
+        let tmp_dir = tempdir().expect("failed to create temp dir");
+        let paths = vec![tmp_dir.path().to_path_buf()];
+
+        let mut changes = Changes::watch(&paths, 1);
+        match changes {
+            Changes::Notified { .. } => (),
+            Changes::Polled { .. } => panic!("the changes are polled"),
+        }
+
+        fs::write(tmp_dir.path().join("one"), "1").expect("failed to write file");
+        assert!(changes.wait());
+    }
+
+    #[test]
+    fn test_changes_are_polled() {
+        let tmp_dir = tempdir().expect("failed to create temp dir");
+        let paths = vec![tmp_dir.path().to_path_buf()];
+
+        let mut changes = Changes::poll(&paths, 1);
+        fs::write(tmp_dir.path().join("one"), "1").expect("failed to write file");
+        assert!(changes.wait());
+    }
+
+    #[test]
+    fn test_snapshot_changes_with_the_content() {
+        let tmp_dir = tempdir().expect("failed to create temp dir");
+        let paths = vec![tmp_dir.path().to_path_buf()];
+        fs::write(tmp_dir.path().join("one"), "1").expect("failed to write file");
+
+        let before = Snapshot::take(&paths, 1);
+        assert_eq!(before, Snapshot::take(&paths, 1));
+
+        fs::write(tmp_dir.path().join("one"), "12").expect("failed to write file");
+        assert_ne!(before, Snapshot::take(&paths, 1));
+    }
+
+    #[test]
+    fn test_snapshot_stops_at_the_depth() {
+        let tmp_dir = tempdir().expect("failed to create temp dir");
+        let paths = vec![tmp_dir.path().to_path_buf()];
+        fs::create_dir(tmp_dir.path().join("dir")).expect("failed to create dir");
+
+        fs::write(tmp_dir.path().join("dir").join("deep"), "").expect("failed to write file");
+
+        assert_eq!(2, Snapshot::take(&paths, 1).entries.len());
+        assert_eq!(3, Snapshot::take(&paths, 2).entries.len());
+    }
+}
@@ -0,0 +1,122 @@
+//! The outputs of the renderers, compared with the ones stored in `golden/`.
+
+extern crate assert_cmd;
+extern crate predicates;
+
+mod support;
+
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use support::Fixture;
+
+fn names() -> Fixture {
+    Fixture::new()
+        .file("root/b.txt", "0123456789")
+        .file("root/A.md", "abc")
+        .file("root/with space", "")
+        .file("root/ünïcödé", "a")
+        .file("root/dir/inner.rs", "12345")
+        .file("root/dir/deeper/last", "")
+        .file("root/.hidden", "")
+}
+
+#[test]
+fn test_golden_tree() {
+    let fixture = names();
+
+    fixture
+        .cmd()
+        .arg("--tree")
+        .arg("root")
+        .assert()
+        .success()
+        .stdout(predicate::eq(include_str!("golden/tree.txt")));
+}
+
+#[test]
+fn test_golden_sort_by_size() {
+    let fixture = names();
+
+    fixture
+        .cmd()
+        .arg("-1")
+        .arg("--sort")
+        .arg("size,name")
+        .arg("--group-dirs")
+        .arg("last")
+        .arg("root/dir")
+        .arg("root")
+        .assert()
+        .success()
+        .stdout(predicate::eq(include_str!("golden/sort_by_size.txt")));
+}
+
+#[test]
+fn test_golden_reversed_grid() {
+    let fixture = names();
+
+    fixture
+        .cmd()
+        .arg("--reverse")
+        .arg("--group-dirs")
+        .arg("first")
+        .arg("--width")
+        .arg("30")
+        .arg("root")
+        .assert()
+        .success()
+        .stdout(predicate::eq(include_str!("golden/reversed_grid.txt")));
+}
+
+#[test]
+#[cfg(target_os = "linux")] // The other systems give other modes to the symlinks
+fn test_golden_long() {
+    let fixture = Fixture::new()
+        .file("root/script", "#!/bin/sh\n")
+        .mode("root/script", 0o755)
+        .file("root/secret", "42")
+        .mode("root/secret", 0o600)
+        .file("root/shared", "")
+        .mode("root/shared", 0o666)
+        .fifo("root/pipe")
+        .mode("root/pipe", 0o644)
+        .symlink("root/link", "script")
+        .dir("root/sub")
+        .file("root/sub/one", "1")
+        .mode("root/sub", 0o1777);
+
+    fixture
+        .cmd()
+        .arg("--long")
+        .arg("--blocks")
+        .arg("permission,size,name")
+        .arg("--size")
+        .arg("bytes")
+        .arg("--dir-size")
+        .arg("count")
+        .arg("root")
+        .assert()
+        .success()
+        .stdout(predicate::eq(include_str!("golden/long.txt")));
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_golden_long_tree() {
+    let fixture = names();
+
+    fixture
+        .cmd()
+        .arg("--tree")
+        .arg("--long")
+        .arg("--blocks")
+        .arg("name,size")
+        .arg("--size")
+        .arg("bytes")
+        .arg("--dir-size")
+        .arg("count")
+        .arg("root")
+        .assert()
+        .success()
+        .stdout(predicate::eq(include_str!("golden/long_tree.txt")));
+}
@@ -1,10 +1,12 @@
 extern crate assert_cmd;
 extern crate predicates;
 
+mod support;
+
 use assert_cmd::prelude::*;
 use assert_fs::prelude::*;
 use predicates::prelude::*;
-use std::process::Command;
+use support::cmd;
 
 #[test]
 fn test_runs_okay() {
@@ -51,8 +53,1190 @@ fn test_list_all_populated_directory() {
         .stdout(predicate::eq(".\n..\none\ntwo\n"));
 }
 
-fn cmd() -> Command {
-    Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap()
+#[test]
+fn test_ignore_globs_filter_the_dot_entries_of_all() {
+    let dir = tempdir();
+    dir.child("one").touch().unwrap();
+    dir.child(".hidden").touch().unwrap();
+
+    cmd()
+        .arg("--all")
+        .arg("--oneline")
+        .arg("--ignore-glob")
+        .arg("..")
+        .arg("--icon")
+        .arg("never")
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout(predicate::eq(".\n.hidden\none\n"));
+}
+
+#[test]
+fn test_list_multiple_operands_files_first() {
+    let dir = tempdir();
+    dir.child("one").touch().unwrap();
+    dir.child("sub/two").touch().unwrap();
+    dir.child("other").create_dir_all().unwrap();
+
+    let sub = dir.path().join("sub");
+    let other = dir.path().join("other");
+    cmd()
+        .arg(&sub)
+        .arg(dir.path().join("one"))
+        .arg(&other)
+        .assert()
+        .stdout(
+            predicate::str::similar(format!(
+                "one\n\n{}:\n\n{}:\ntwo\n",
+                other.display(),
+                sub.display()
+            ))
+            .from_utf8(),
+        );
+}
+
+#[test]
+fn test_recursion_names_the_operand() {
+    let dir = tempdir();
+    dir.child("one").touch().unwrap();
+    dir.child("sub/two").touch().unwrap();
+
+    cmd().arg("--recursive").arg(dir.path()).assert().stdout(
+        predicate::str::similar(format!(
+            "{}:\none\nsub\n\n{}:\ntwo\n",
+            dir.path().display(),
+            dir.path().join("sub").display()
+        ))
+        .from_utf8(),
+    );
+}
+
+#[test]
+fn test_list_operands_from_stdin() {
+    let dir = tempdir();
+    dir.child("one").touch().unwrap();
+    dir.child("two").touch().unwrap();
+    dir.child("three").touch().unwrap();
+
+    let input = format!(
+        "{}\n\n{}\n",
+        dir.path().join("one").display(),
+        dir.path().join("two").display()
+    );
+    cmd()
+        .arg("--stdin")
+        .with_stdin()
+        .buffer(input)
+        .assert()
+        .success()
+        .stdout(predicate::eq("one\ntwo\n"));
+}
+
+#[test]
+fn test_nonexistent_operand_from_stdin() {
+    let dir = tempdir();
+    dir.child("one").touch().unwrap();
+
+    let input = format!(
+        "{}\n{}\n",
+        dir.path().join("nonexistent").display(),
+        dir.path().join("one").display()
+    );
+    cmd()
+        .arg("--stdin")
+        .with_stdin()
+        .buffer(input)
+        .assert()
+        .code(2)
+        .stdout(predicate::eq("one\n"))
+        .stderr(predicate::str::contains("cannot access"));
+}
+
+#[test]
+fn test_list_only_matching_entries() {
+    let dir = tempdir();
+    dir.child("one.rs").touch().unwrap();
+    dir.child("two.md").touch().unwrap();
+    dir.child("empty").create_dir_all().unwrap();
+    dir.child("sub/three.rs").touch().unwrap();
+    dir.child("sub/four.md").touch().unwrap();
+
+    cmd()
+        .arg("--only")
+        .arg("*.rs")
+        .arg("--tree")
+        .arg("--icon")
+        .arg("never")
+        .arg(dir.path())
+        .assert()
+        .stdout(predicate::str::contains("one.rs"))
+        .stdout(predicate::str::contains("sub"))
+        .stdout(predicate::str::contains("three.rs"))
+        .stdout(predicate::str::contains("empty").not())
+        .stdout(predicate::str::contains(".md").not());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_flat_recursive_lists_the_matching_paths() {
+    let dir = tempdir();
+    dir.child("one.rs").touch().unwrap();
+    dir.child("sub/two.rs").touch().unwrap();
+    dir.child("sub/deep/three.rs").touch().unwrap();
+    dir.child("sub/readme.md").touch().unwrap();
+
+    cmd()
+        .current_dir(dir.path())
+        .arg("--flat-recursive")
+        .arg("--oneline")
+        .arg("--only")
+        .arg("*.rs")
+        .arg("--icon")
+        .arg("never")
+        .assert()
+        .success()
+        .stdout(predicate::eq(
+            "./one.rs\n./sub/deep/three.rs\n./sub/two.rs\n",
+        ));
+}
+
+#[test]
+fn test_total_size_sorts_the_directories_by_their_content() {
+    let dir = tempdir();
+    dir.child("big/content")
+        .write_binary(&vec![0u8; 100_000])
+        .unwrap();
+    dir.child("small").create_dir_all().unwrap();
+    dir.child("medium")
+        .write_binary(&vec![0u8; 50_000])
+        .unwrap();
+
+    cmd()
+        .arg("--oneline")
+        .arg("--sizesort")
+        .arg("--total-size")
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout(predicate::eq("big\nmedium\nsmall\n"));
+}
+
+#[test]
+fn test_total_size_counts_the_ignored_entries() {
+    let dir = tempdir();
+    dir.child("node_modules/big")
+        .write_binary(&vec![0u8; 100_000])
+        .unwrap();
+    dir.child("file").write_str("data").unwrap();
+
+    let size_of_dir = |args: &[&str]| {
+        let output = cmd()
+            .args(args)
+            .arg("-l")
+            .arg("--total-size")
+            .arg("--size")
+            .arg("bytes")
+            .arg("--blocks")
+            .arg("size,name")
+            .arg("--icon")
+            .arg("never")
+            .arg(dir.path())
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let first_line = stdout.lines().next().unwrap().to_string();
+        first_line
+            .split_whitespace()
+            .find_map(|field| field.parse::<u64>().ok())
+            .unwrap()
+    };
+
+    let tree = size_of_dir(&["--tree"]);
+    assert_eq!(size_of_dir(&["-d"]), tree);
+    assert!(tree > 100_000);
+}
+
+#[test]
+fn test_dir_size_count_shows_the_entries() {
+    let dir = tempdir();
+    dir.child("file").write_str("data").unwrap();
+    dir.child("sub/one").touch().unwrap();
+    dir.child("sub/two").touch().unwrap();
+
+    cmd()
+        .arg("-l")
+        .arg("--blocks")
+        .arg("size,name")
+        .arg("--dir-size")
+        .arg("count")
+        .arg("--icon")
+        .arg("never")
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match(r"^4 B     file\s*\n2 items sub\s*\n$").unwrap());
+}
+
+#[test]
+fn test_nonexistent_operand_is_a_major_issue() {
+    let dir = tempdir();
+    cmd()
+        .arg(dir.path().join("nonexistent"))
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("cannot access"));
+}
+
+#[test]
+fn test_stream_unsorted_entries() {
+    let dir = tempdir();
+    dir.child("one").touch().unwrap();
+    dir.child("two").touch().unwrap();
+    dir.child("three.md").touch().unwrap();
+
+    cmd()
+        .arg("-1")
+        .arg("--no-sort")
+        .arg("--ignore-glob")
+        .arg("*.md")
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout(predicate::function(|output: &str| {
+            let mut names: Vec<&str> = output.lines().collect();
+            names.sort();
+            names == ["one", "two"]
+        }));
+}
+
+#[test]
+fn test_sort_by_size_then_by_name() {
+    let dir = tempdir();
+    dir.child("ccc").write_str("12").unwrap();
+    dir.child("bbb").write_str("1").unwrap();
+    dir.child("aaa").write_str("12").unwrap();
+
+    cmd()
+        .arg("-1")
+        .arg("--sort")
+        .arg("size,name")
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match("^aaa\\s*\nccc\\s*\nbbb\\s*\n$").unwrap());
+}
+
+#[test]
+fn test_reverse_keeps_the_directories_first() {
+    let dir = tempdir();
+    dir.child("aaa").touch().unwrap();
+    dir.child("bbb").create_dir_all().unwrap();
+    dir.child("ccc").touch().unwrap();
+    dir.child("ddd").create_dir_all().unwrap();
+
+    cmd()
+        .arg("-1")
+        .arg("-r")
+        .arg("--group-dirs")
+        .arg("first")
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match("^ddd\\s*\nbbb\\s*\nccc\\s*\naaa\\s*\n$").unwrap());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_type_filter_applies_at_every_level() {
+    let dir = tempdir();
+    dir.child("one").touch().unwrap();
+    dir.child("sub/two").touch().unwrap();
+    dir.child("sub/deep").create_dir_all().unwrap();
+
+    cmd()
+        .current_dir(dir.path())
+        .arg("--flat-recursive")
+        .arg("--oneline")
+        .arg("--type")
+        .arg("d")
+        .arg("--icon")
+        .arg("never")
+        .assert()
+        .success()
+        .stdout(predicate::eq("./sub\n./sub/deep\n"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_audit_flags_the_risky_entries() {
+    use std::fs::{set_permissions, Permissions};
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = tempdir();
+    dir.child("safe").touch().unwrap();
+    dir.child("shared").touch().unwrap();
+    set_permissions(dir.path().join("shared"), Permissions::from_mode(0o666)).unwrap();
+
+    cmd()
+        .arg("-l")
+        .arg("--audit")
+        .arg("--blocks")
+        .arg("name")
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::is_match("^safe\\s*\nshared\\s*\n    ! world-writable\n$").unwrap(),
+        );
+}
+
+#[test]
+fn test_archives_list_their_top_level_entries() {
+    let dir = tempdir();
+    let file = std::fs::File::create(dir.path().join("archive.tar")).unwrap();
+    let mut builder = tar::Builder::new(file);
+    let mut header = tar::Header::new_gnu();
+    header.set_size(0);
+    header.set_mode(0o644);
+    builder
+        .append_data(&mut header, "top/inner.txt", &[][..])
+        .unwrap();
+    builder.finish().unwrap();
+
+    cmd()
+        .arg("--tree")
+        .arg("--archives")
+        .arg("--icon")
+        .arg("never")
+        .arg(dir.path().join("archive.tar"))
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match(r"archive\.tar\n\S+ top\n$").unwrap());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_symlink_target_relative_to_the_current_directory() {
+    use std::os::unix::fs::symlink;
+
+    let dir = tempdir();
+    dir.child("target").touch().unwrap();
+    dir.child("sub").create_dir_all().unwrap();
+    symlink("../target", dir.path().join("sub/link")).unwrap();
+
+    cmd()
+        .current_dir(dir.path())
+        .arg("-l")
+        .arg("--blocks")
+        .arg("name")
+        .arg("--icon")
+        .arg("never")
+        .arg("--symlink-target")
+        .arg("relative")
+        .arg("sub")
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match(r"^link \S+ target\s*\n$").unwrap());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_follow_chain() {
+    use std::os::unix::fs::symlink;
+
+    let dir = tempdir();
+    dir.child("target").touch().unwrap();
+    symlink("target", dir.path().join("middle")).unwrap();
+    symlink("middle", dir.path().join("link")).unwrap();
+
+    cmd()
+        .arg("-l")
+        .arg("--blocks")
+        .arg("name")
+        .arg("--icon")
+        .arg("never")
+        .arg("--follow-chain")
+        .arg(dir.path().join("link"))
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match(r"link \S+ middle \S+ target\s*\n$").unwrap());
+}
+
+#[test]
+fn test_truncate_names_in_the_grid() {
+    let dir = tempdir();
+    dir.child("a_really_really_long_file_name.txt")
+        .touch()
+        .unwrap();
+    dir.child("short").touch().unwrap();
+
+    cmd()
+        .arg("--width")
+        .arg("40")
+        .arg("--truncate-names")
+        .arg("16")
+        .arg("--icon")
+        .arg("never")
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout("a_real…_name.txt  short\n");
+}
+
+#[test]
+#[cfg(unix)]
+fn test_forced_colors_are_written_to_files() {
+    let dir = tempdir();
+    dir.child("dir").create_dir_all().unwrap();
+    dir.child("a\u{1b}[31mb").touch().unwrap();
+
+    cmd()
+        .env_remove("LS_COLORS")
+        .arg("--color")
+        .arg("always")
+        .arg("--icon")
+        .arg("never")
+        .arg(dir.path())
+        .assert()
+        .stdout(predicate::str::contains("a?[31mb"))
+        .stdout(predicate::str::contains("\u{1b}[1;34mdir\u{1b}[0m"));
+
+    cmd()
+        .arg("--color")
+        .arg("always")
+        .arg("--no-color")
+        .arg(dir.path())
+        .assert()
+        .stdout("a\u{1b}[31mb\ndir\n");
+}
+
+#[test]
+fn test_preview_pane_cuts_the_names_to_the_width() {
+    let dir = tempdir();
+    dir.child("a_very_long_name.txt").touch().unwrap();
+    dir.child("short").touch().unwrap();
+
+    cmd()
+        .arg("--preview-pane")
+        .arg("--no-color")
+        .arg("--icon")
+        .arg("never")
+        .arg("--width")
+        .arg("14")
+        .arg(dir.path())
+        .assert()
+        .stdout("a_ver…name.txt\nshort\n");
+}
+
+#[test]
+fn test_long_tree_aligns_the_blocks_right_of_the_names() {
+    let dir = tempdir();
+    dir.child("a").write_str("abc").unwrap();
+    dir.child("sub/longer_name")
+        .write_str("0123456789")
+        .unwrap();
+
+    cmd()
+        .arg("--tree")
+        .arg("--long")
+        .arg("--blocks")
+        .arg("name,size")
+        .arg("--size")
+        .arg("bytes")
+        .arg("--icon")
+        .arg("never")
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\n├── a                 3\n"))
+        .stdout(predicate::str::contains("\n   └── longer_name   10\n"));
+}
+
+#[test]
+fn test_tree_ignores_the_vendored_directories() {
+    let dir = tempdir();
+    dir.child("node_modules/left-pad/index.js").touch().unwrap();
+    dir.child("src/main.rs").touch().unwrap();
+
+    cmd()
+        .arg("--tree")
+        .arg("--icon")
+        .arg("never")
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("main.rs"))
+        .stdout(predicate::str::contains("node_modules").not());
+
+    cmd()
+        .arg("--tree")
+        .arg("--no-default-ignore")
+        .arg("--icon")
+        .arg("never")
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("index.js"));
+
+    // The listing of the directory alone ignores nothing.
+    cmd()
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("node_modules"));
+}
+
+#[test]
+#[cfg(unix)]
+fn test_interrupted_listing_is_still_shown() {
+    let dir = tempdir();
+    dir.child("one").touch().unwrap();
+    // The parent of the shell is lsd itself.
+    dir.child("config.yaml")
+        .write_str("custom-blocks:\n  - name: Kill\n    command: [sh, -c, \"kill -INT $PPID\"]\n")
+        .unwrap();
+
+    cmd()
+        .arg("--config-file")
+        .arg(dir.path().join("config.yaml"))
+        .arg("-l")
+        .arg("--blocks")
+        .arg("custom,name")
+        .arg("--icon")
+        .arg("never")
+        .arg(dir.path().join("one"))
+        .assert()
+        .code(130)
+        .stdout(predicate::str::is_match(r"^\s*\S*one\s*\n$").unwrap())
+        .stderr(predicate::eq("interrupted, the listing is incomplete\n"));
+}
+
+#[test]
+fn test_rules_of_the_config() {
+    let dir = tempdir();
+    dir.child("one.txt").touch().unwrap();
+    dir.child("two.pyc").touch().unwrap();
+    dir.child("three").touch().unwrap();
+    dir.child("config.yaml")
+        .write_str(
+            "rules:\n  - name: \"*.pyc\"\n    hide: true\n  - name: \"*.txt\"\n    icon: T\n",
+        )
+        .unwrap();
+
+    cmd()
+        .arg("--config-file")
+        .arg(dir.path().join("config.yaml"))
+        .arg("--icon")
+        .arg("always")
+        .arg("--icon-theme")
+        .arg("unicode")
+        .arg("--ignore-glob")
+        .arg("config.yaml")
+        .arg(dir.path())
+        .assert()
+        .stdout(predicate::str::is_match(r"^T +one\.txt\n\S+ +three\n$").unwrap());
+}
+
+#[test]
+fn test_preview_text_files() {
+    let dir = tempdir();
+    dir.child("notes.txt")
+        .write_str("first\nsecond\nthird\n")
+        .unwrap();
+    dir.child("data.bin").write_binary(b"\x00\x01\x02").unwrap();
+
+    cmd()
+        .arg("--tree")
+        .arg("--preview")
+        .arg("2")
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "notes.txt\n       first\n       second\n",
+        ))
+        .stdout(predicate::str::contains("third").not())
+        .stdout(predicate::str::contains("data.bin\n\u{2514}"));
+}
+
+#[test]
+fn test_tree_draws_the_config_symbols() {
+    let dir = tempdir();
+    dir.child("sub/inner").touch().unwrap();
+    dir.child("sub/other").touch().unwrap();
+    dir.child("config.yaml")
+        .write_str("theme:\n  symbols:\n    tree-edge: \"|--\"\n    tree-line: \"|  \"\n    tree-corner: \"`--\"\n")
+        .unwrap();
+
+    cmd()
+        .arg("--config-file")
+        .arg(dir.path().join("config.yaml"))
+        .arg("--tree")
+        .arg("--icon")
+        .arg("never")
+        .arg(dir.path().join("sub"))
+        .assert()
+        .success()
+        .stdout(predicate::eq("sub\n|-- inner\n`-- other\n"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_ascii_draws_the_tree_and_the_arrows() {
+    use std::os::unix::fs::symlink;
+
+    let dir = tempdir();
+    dir.child("sub/inner").touch().unwrap();
+    symlink("inner", dir.path().join("sub/link")).unwrap();
+
+    cmd()
+        .arg("--ascii")
+        .arg("--tree")
+        .arg("--long")
+        .arg("--blocks")
+        .arg("name")
+        .arg(dir.path().join("sub"))
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::is_match("^sub\\s*\n\\|-- inner\\s*\n`-- link -> inner\\s*\n$")
+                .unwrap(),
+        );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_quiet_hides_access_errors() {
+    let dir = tempdir();
+    dir.child("sub").create_dir_all().unwrap();
+    std::os::unix::fs::symlink("..", dir.path().join("sub").join("loop")).unwrap();
+
+    cmd()
+        .arg("--quiet")
+        .arg("--recursive")
+        .arg("--dereference")
+        .arg(dir.path())
+        .assert()
+        .code(1)
+        .stderr(predicate::eq(""));
+}
+
+#[test]
+fn test_quiet_still_reports_the_operands() {
+    let dir = tempdir();
+    cmd()
+        .arg("--quiet")
+        .arg(dir.path().join("nonexistent"))
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("nonexistent"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_paging_always_pipes_through_the_pager() {
+    let dir = tempdir();
+    dir.child("one").touch().unwrap();
+    cmd()
+        .env("PAGER", "sed s/^/paged:/")
+        .arg("--paging")
+        .arg("always")
+        .arg(dir.path())
+        .assert()
+        .stdout(predicate::eq("paged:one\n"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_dereference_command_line_lists_the_linked_directory() {
+    use std::os::unix::fs::symlink;
+
+    let dir = tempdir();
+    dir.child("target").create_dir_all().unwrap();
+    dir.child("target/inner").touch().unwrap();
+    symlink("inner", dir.path().join("target/inner-link")).unwrap();
+    symlink("target", dir.path().join("link")).unwrap();
+
+    cmd()
+        .arg("-H")
+        .arg("--blocks")
+        .arg("name")
+        .arg("-l")
+        .arg(dir.path().join("link"))
+        .assert()
+        .stdout(predicate::str::is_match("^inner\\s*\ninner-link ⇒ inner\\s*\n$").unwrap());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_trailing_separator_lists_the_linked_directory() {
+    use std::os::unix::fs::symlink;
+
+    let dir = tempdir();
+    dir.child("target/inner").touch().unwrap();
+    symlink("target", dir.path().join("link")).unwrap();
+    let link = dir.path().join("link");
+
+    cmd().arg(&link).assert().stdout(predicate::eq("link\n"));
+
+    cmd()
+        .arg(link.join(""))
+        .assert()
+        .stdout(predicate::eq("inner\n"));
+
+    cmd()
+        .arg("--absolute")
+        .arg(link.join(""))
+        .assert()
+        .stdout(predicate::str::ends_with("/link/inner\n"));
+}
+
+#[test]
+fn test_directory_only_lists_the_operands_themselves() {
+    let dir = tempdir();
+    dir.child("sub/inner").touch().unwrap();
+    dir.child("file").touch().unwrap();
+
+    cmd()
+        .arg("-ld")
+        .arg("--blocks")
+        .arg("permission,name")
+        .arg(dir.path().join("sub"))
+        .arg(dir.path().join("file"))
+        .assert()
+        .stdout(predicate::str::is_match("^\\.\\S+ file\\s*\nd\\S+ sub\\s*\n$").unwrap());
+}
+
+#[test]
+fn test_directory_only_wins_over_the_tree() {
+    let dir = tempdir();
+    dir.child("sub/inner").touch().unwrap();
+
+    cmd()
+        .arg("-d")
+        .arg("--tree")
+        .arg(dir.path().join("sub"))
+        .assert()
+        .success()
+        .stdout(predicate::eq("sub\n"));
+}
+
+#[test]
+fn test_grid_fits_the_columns_variable() {
+    let dir = tempdir();
+    dir.child("one").touch().unwrap();
+    dir.child("two").touch().unwrap();
+
+    cmd()
+        .env("COLUMNS", "80")
+        .arg(dir.path())
+        .assert()
+        .stdout(predicate::eq("one  two\n"));
+    cmd()
+        .env("COLUMNS", "80")
+        .arg("--width")
+        .arg("5")
+        .arg(dir.path())
+        .assert()
+        .stdout(predicate::eq("one\ntwo\n"));
+}
+
+#[test]
+fn test_invalid_flag_is_a_major_issue() {
+    cmd().arg("--invalid-flag").assert().code(2);
+}
+
+#[test]
+fn test_help_is_not_an_error() {
+    cmd().arg("--help").assert().success();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_unreadable_subdirectory_is_a_minor_issue() {
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    // The permissions don't restrict the superuser.
+    if unsafe { libc::geteuid() } == 0 {
+        return;
+    }
+
+    let dir = tempdir();
+    dir.child("one").touch().unwrap();
+    dir.child("locked").create_dir_all().unwrap();
+    let locked = dir.path().join("locked");
+    fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+
+    let assert = cmd().arg("--recursive").arg(dir.path()).assert();
+
+    fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+    assert.code(1).stdout(predicate::str::contains("one"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_unreadable_operand_is_a_major_issue() {
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    // The permissions don't restrict the superuser.
+    if unsafe { libc::geteuid() } == 0 {
+        return;
+    }
+
+    let dir = tempdir();
+    dir.child("locked").create_dir_all().unwrap();
+    let locked = dir.path().join("locked");
+    fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+
+    let assert = cmd().arg(&locked).assert();
+    // The listing without sort is streamed.
+    let streamed = cmd()
+        .arg("--sort")
+        .arg("none")
+        .arg("-1")
+        .arg(&locked)
+        .assert();
+
+    fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+    assert.code(2);
+    streamed.code(2);
+}
+
+#[test]
+fn test_generate_completions() {
+    cmd()
+        .arg("--generate-completions")
+        .arg("bash")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("--color"));
+}
+
+#[test]
+fn test_generate_completions_for_unknown_shell() {
+    cmd()
+        .arg("--generate-completions")
+        .arg("tcsh")
+        .assert()
+        .code(2);
+}
+
+#[test]
+#[cfg(unix)]
+fn test_custom_block_shows_the_command_output() {
+    let dir = tempdir();
+    dir.child("one").write_str("abcd").unwrap();
+    dir.child("two").write_str("xyz").unwrap();
+    dir.child("config.yaml")
+        .write_str("custom-blocks:\n  - name: Head\n    command: [head, -c, \"2\", \"{}\"]\n")
+        .unwrap();
+
+    cmd()
+        .arg("--config-file")
+        .arg(dir.path().join("config.yaml"))
+        .arg("-l")
+        .arg("--header")
+        .arg("--blocks")
+        .arg("custom,name")
+        .arg("--icon")
+        .arg("never")
+        .arg(dir.path().join("one"))
+        .arg(dir.path().join("two"))
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::is_match(r"^Head Name\nab   \S*one\s*\nxy   \S*two\s*\n$").unwrap(),
+        );
+}
+
+#[test]
+fn test_date_width_of_the_config() {
+    let dir = tempdir();
+    dir.child("one").touch().unwrap();
+    dir.child("config.yaml")
+        .write_str("date-locale: auto\ndate-width: 30\n")
+        .unwrap();
+
+    cmd()
+        .arg("--config-file")
+        .arg(dir.path().join("config.yaml"))
+        .arg("-l")
+        .arg("--blocks")
+        .arg("date,name")
+        .arg("--icon")
+        .arg("never")
+        .arg(dir.path().join("one"))
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match(r"^[^\n]{24} {6} \S*one\s*\n$").unwrap());
+}
+
+#[test]
+fn test_directory_config_overrides_the_flags() {
+    let dir = tempdir();
+    dir.child("list/a").write_str("1").unwrap();
+    dir.child("list/b").write_str("1234567890").unwrap();
+    dir.child("list/target/debug").touch().unwrap();
+    dir.child("list/.lsd.yaml")
+        .write_str("sort: size\nignore-globs: [target]\n")
+        .unwrap();
+    dir.child("config.yaml")
+        .write_str("directory-config: true\n")
+        .unwrap();
+
+    cmd()
+        .arg("--config-file")
+        .arg(dir.path().join("config.yaml"))
+        .arg("--oneline")
+        .arg("--icon")
+        .arg("never")
+        .arg(dir.path().join("list"))
+        .assert()
+        .success()
+        .stdout(predicate::eq("b\na\n"));
+
+    // The `.lsd.yaml` files are left alone unless the config opts in.
+    cmd()
+        .arg("--oneline")
+        .arg("--icon")
+        .arg("never")
+        .arg(dir.path().join("list"))
+        .assert()
+        .success()
+        .stdout(predicate::eq("a\nb\ntarget\n"));
+}
+
+#[test]
+fn test_directory_config_of_the_subdirectories() {
+    let dir = tempdir();
+    dir.child("root/one").touch().unwrap();
+    dir.child("root/sub/a").write_str("1").unwrap();
+    dir.child("root/sub/b").write_str("1234567890").unwrap();
+    dir.child("root/sub/target/debug").touch().unwrap();
+    dir.child("root/sub/.lsd.yaml")
+        .write_str("sort: size\nignore-globs: [target]\nblocks: [size, name]\n")
+        .unwrap();
+    dir.child("config.yaml")
+        .write_str("directory-config: true\n")
+        .unwrap();
+
+    cmd()
+        .arg("--config-file")
+        .arg(dir.path().join("config.yaml"))
+        .arg("--recursive")
+        .arg("--oneline")
+        .arg("--icon")
+        .arg("never")
+        .arg(dir.path().join("root"))
+        .assert()
+        .success()
+        .stdout(predicate::str::ends_with(":\nb\na\n"));
+
+    // The blocks of the long tree are the ones of the directory of the entries.
+    cmd()
+        .arg("--config-file")
+        .arg(dir.path().join("config.yaml"))
+        .arg("--tree")
+        .arg("--long")
+        .arg("--icon")
+        .arg("never")
+        .arg(dir.path().join("root"))
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match(r"one +\S+ +.* 0 +B").unwrap())
+        .stdout(predicate::str::is_match(r"b +10 +B *\n.*a +1 +B *\n$").unwrap())
+        .stdout(predicate::str::contains("target").not());
+}
+
+#[test]
+fn test_invalid_config_file_is_reported() {
+    let dir = tempdir();
+    dir.child("one").touch().unwrap();
+    dir.child("config.yaml")
+        .write_str("theme:\n  colors:\n    user: rainbow\n")
+        .unwrap();
+
+    cmd()
+        .arg("--config-file")
+        .arg(dir.path().join("config.yaml"))
+        .arg(dir.path().join("one"))
+        .assert()
+        .success()
+        .stdout(predicate::eq("one\n"))
+        .stderr(predicate::str::contains("invalid config file"));
+}
+
+#[test]
+fn test_csv_format() {
+    let dir = tempdir();
+    dir.child("one,two").write_str("data").unwrap();
+
+    let row = format!("\"{}\",file,4,", dir.path().join("one,two").display());
+    cmd()
+        .arg("--format")
+        .arg("csv")
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with(
+            "path,type,size_bytes,mtime_epoch,uid,gid,mode_octal,symlink_target\n",
+        ))
+        .stdout(predicate::str::contains(row));
+}
+
+#[test]
+fn test_csv_header_names_the_time() {
+    let dir = tempdir();
+    dir.child("one").touch().unwrap();
+
+    cmd()
+        .arg("--format")
+        .arg("csv")
+        .arg("--time")
+        .arg("created")
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with(
+            "path,type,size_bytes,btime_epoch,uid,gid,mode_octal,symlink_target\n",
+        ));
+}
+
+#[test]
+fn test_porcelain_format() {
+    let dir = tempdir();
+    dir.child("two words").write_str("data").unwrap();
+
+    let output = cmd()
+        .arg("--format")
+        .arg("porcelain")
+        .arg(dir.path())
+        .output()
+        .unwrap();
+    let output = String::from_utf8(output.stdout).unwrap();
+
+    let row = output.lines().next().unwrap();
+    assert!(row.starts_with("file "));
+    assert_eq!(
+        format!("{:>20} ", 4),
+        &row[61..82],
+        "the size ends at a fixed column"
+    );
+    assert!(row.ends_with(&format!(" {}", dir.path().join("two words").display())));
+}
+
+#[test]
+#[cfg(unix)] // Windows forbids the newlines in the file names
+fn test_zero_terminated_entries() {
+    let dir = tempdir();
+    dir.child("one").touch().unwrap();
+    dir.child("two\nlines").touch().unwrap();
+
+    cmd()
+        .arg("--zero")
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout(predicate::eq("one\0two\nlines\0"));
+
+    dir.child("sub/three").touch().unwrap();
+    cmd()
+        .arg("--zero")
+        .arg("--recursive")
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::similar(format!(
+            "{}:\0one\0sub\0two\nlines\0\0{}:\0three\0",
+            dir.path().display(),
+            dir.path().join("sub").display()
+        )));
+}
+
+#[test]
+#[cfg(target_os = "linux")] // The other systems may refuse the names which aren't UTF-8
+fn test_raw_names_are_kept_in_the_machine_readable_outputs() {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    let dir = tempdir();
+    dir.child(OsStr::from_bytes(b"caf\xe9")).touch().unwrap();
+
+    let output = cmd().arg("--zero").arg(dir.path()).output().unwrap();
+    assert_eq!(b"caf\xe9\0".to_vec(), output.stdout);
+
+    let output = cmd()
+        .arg("--zero")
+        .arg("--long")
+        .arg("--blocks")
+        .arg("name")
+        .arg(dir.path())
+        .output()
+        .unwrap();
+    assert_eq!(b"caf\xe9\0".to_vec(), output.stdout);
+
+    let output = cmd()
+        .arg("--format")
+        .arg("tsv")
+        .arg(dir.path())
+        .output()
+        .unwrap();
+    let row = [
+        dir.path()
+            .join(OsStr::from_bytes(b"caf\xe9"))
+            .as_os_str()
+            .as_bytes(),
+        b"\tfile",
+    ]
+    .concat();
+    assert!(output
+        .stdout
+        .windows(row.len())
+        .any(|window| window == &row[..]));
+
+    cmd()
+        .arg("--oneline")
+        .arg("--icon")
+        .arg("never")
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout(predicate::eq("caf\u{fffd}\n"));
+}
+
+#[test]
+#[cfg(unix)]
+fn test_device_block() {
+    let dir = tempdir();
+    dir.child("one").touch().unwrap();
+
+    cmd()
+        .arg("-l")
+        .arg("--blocks")
+        .arg("device,name")
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match(r"^\d+:\d+ one\n$").unwrap());
+}
+
+#[test]
+#[cfg(target_os = "linux")] // Only the BSDs and macOS have file flags
+fn test_flags_block_without_file_flags() {
+    let dir = tempdir();
+    dir.child("one").touch().unwrap();
+
+    cmd()
+        .arg("-l")
+        .arg("--blocks")
+        .arg("flags,name")
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout(predicate::eq("? one\n"));
 }
 
 fn tempdir() -> assert_fs::TempDir {
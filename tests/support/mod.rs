@@ -0,0 +1,103 @@
+//! The fixtures of the integration tests: temporary directories holding the
+//! entries to list, and the commands listing them away from the environment
+//! of the user.
+
+// Each test crate only uses a part of the support.
+#![allow(dead_code)]
+
+use assert_cmd::prelude::*;
+use assert_fs::TempDir;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// The `lsd` command, with the grid fitting `--width` or `$COLUMNS` only.
+pub fn cmd() -> Command {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    // The grid fits `$COLUMNS` when the output is not a terminal.
+    cmd.env_remove("COLUMNS");
+    cmd
+}
+
+/// A temporary directory filled by its builder methods, which take the paths
+/// relative to it and create the missing parent directories.
+pub struct Fixture {
+    dir: TempDir,
+}
+
+impl Fixture {
+    pub fn new() -> Self {
+        Self {
+            dir: TempDir::new().unwrap(),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        self.dir.path()
+    }
+
+    pub fn join(&self, path: &str) -> PathBuf {
+        self.dir.path().join(path)
+    }
+
+    pub fn file(self, path: &str, content: &str) -> Self {
+        let path = self.parent_of(path);
+        fs::write(path, content).unwrap();
+        self
+    }
+
+    pub fn dir(self, path: &str) -> Self {
+        fs::create_dir_all(self.join(path)).unwrap();
+        self
+    }
+
+    #[cfg(unix)]
+    pub fn symlink(self, path: &str, target: &str) -> Self {
+        let path = self.parent_of(path);
+        std::os::unix::fs::symlink(target, path).unwrap();
+        self
+    }
+
+    #[cfg(unix)]
+    pub fn fifo(self, path: &str) -> Self {
+        use std::ffi::CString;
+        use std::os::unix::ffi::OsStrExt;
+
+        let path = CString::new(self.parent_of(path).as_os_str().as_bytes()).unwrap();
+        assert_eq!(0, unsafe { libc::mkfifo(path.as_ptr(), 0o644) });
+        self
+    }
+
+    /// Set the mode of the entry at `path`, whatever the umask gave it.
+    #[cfg(unix)]
+    pub fn mode(self, path: &str, mode: u32) -> Self {
+        use std::os::unix::fs::PermissionsExt;
+
+        fs::set_permissions(self.join(path), fs::Permissions::from_mode(mode)).unwrap();
+        self
+    }
+
+    /// The `lsd` command run in the fixture, without the config file, the
+    /// colors, and the variables of the user.
+    pub fn cmd(&self) -> Command {
+        let mut cmd = cmd();
+        cmd.current_dir(self.path())
+            .env("XDG_CONFIG_HOME", self.path())
+            .env_remove("LS_COLORS")
+            .env_remove("LSD_OPTIONS")
+            .env_remove("LSD_COLOR")
+            .env_remove("LSD_ICONS")
+            .env_remove("LSD_DATE_FORMAT")
+            .arg("--icon")
+            .arg("never");
+        cmd
+    }
+
+    fn parent_of(&self, path: &str) -> PathBuf {
+        let path = self.join(path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).unwrap();
+        }
+        path
+    }
+}